@@ -6,6 +6,7 @@ use crate::types::{
 use crate::types::{WheelCoreMetaDataError, WheelCoreMetadata};
 use crate::utils::ReadAndSeek;
 use flate2::read::GzDecoder;
+use rattler_digest::{HashingReader, Sha256, Sha256Hash};
 
 use fs_err as fs;
 use miette::IntoDiagnostic;
@@ -120,15 +121,23 @@ impl SDist {
 
     /// Checks if this artifact implements PEP 643
     /// and returns the metadata if it does
+    ///
+    /// Returns `None` if one of the fields we rely on is marked as `Dynamic`, in that case the
+    /// values in the PKG-INFO are not reliable and the sdist has to be built to get its metadata.
+    /// See [`PackageInfo::into_sdist_metadata`].
     pub fn pep643_metadata(&self) -> Result<Option<(Vec<u8>, WheelCoreMetadata)>, SDistError> {
         // Assume we have a PKG-INFO
-        let (bytes, metadata) = self.read_package_info()?;
-        let metadata =
-            WheelCoreMetadata::try_from(metadata).map_err(SDistError::WheelCoreMetaDataError)?;
-        if metadata.metadata_version.implements_pep643() {
-            Ok(Some((bytes, metadata)))
-        } else {
-            Ok(None)
+        let (bytes, package_info) = self.read_package_info()?;
+        match package_info.into_sdist_metadata() {
+            Ok(metadata) => Ok(Some((bytes, metadata))),
+            Err(
+                err @ (WheelCoreMetaDataError::DynamicField(_)
+                | WheelCoreMetaDataError::PredatesPep643(_)),
+            ) => {
+                tracing::debug!("cannot use the PKG-INFO of {}: {err}", self.name);
+                Ok(None)
+            }
+            Err(err) => Err(SDistError::WheelCoreMetaDataError(err)),
         }
     }
 
@@ -164,6 +173,16 @@ impl ArtifactFromSource for SDist {
         Ok(vec)
     }
 
+    /// Hashes the archive while streaming it from the underlying reader.
+    fn try_get_hash(&self) -> Result<Sha256Hash, std::io::Error> {
+        let mut inner = self.lock_data();
+        inner.rewind()?;
+        let mut reader = HashingReader::<_, Sha256>::new(&mut *inner);
+        std::io::copy(&mut reader, &mut std::io::sink())?;
+        let (_, hash) = reader.finalize();
+        Ok(hash)
+    }
+
     fn distribution_name(&self) -> String {
         self.name().distribution.as_source_str().to_owned()
     }
@@ -273,7 +292,7 @@ mod tests {
     use crate::types::{ArtifactFromSource, PackageName};
     use crate::types::{
         ArtifactInfo, ArtifactName, DistInfoMetadata, Extra, NormalizedPackageName, STreeFilename,
-        WheelFilename, Yanked,
+        WheelCoreMetadata, WheelFilename, Yanked,
     };
     use crate::types::{SDistFilename, SDistFormat};
     use crate::utils::{get_package_db, setup};
@@ -287,6 +306,44 @@ mod tests {
     use tempfile::tempdir;
     use url::Url;
 
+    /// Sets an environment variable of the test process and removes it again when dropped.
+    struct EnvVarGuard(&'static str);
+
+    impl EnvVarGuard {
+        fn set(name: &'static str, value: &str) -> Self {
+            std::env::set_var(name, value);
+            Self(name)
+        }
+    }
+
+    impl Drop for EnvVarGuard {
+        fn drop(&mut self) {
+            std::env::remove_var(self.0);
+        }
+    }
+
+    /// Clears the informational fields (summary, README, authors, classifiers, ...) of metadata
+    /// that is built from a remote source. These follow whatever upstream publishes and are not
+    /// what the tests that build remote sources are about.
+    fn without_informational_fields(metadata: WheelCoreMetadata) -> WheelCoreMetadata {
+        WheelCoreMetadata {
+            summary: None,
+            description: None,
+            description_content_type: None,
+            keywords: Vec::new(),
+            home_page: None,
+            download_url: None,
+            author: None,
+            author_email: None,
+            maintainer: None,
+            maintainer_email: None,
+            classifiers: Vec::new(),
+            platforms: Vec::new(),
+            project_urls: Default::default(),
+            ..metadata
+        }
+    }
+
     #[tokio::test]
     pub async fn correct_metadata_fake_flask() {
         let path = Path::new(env!("CARGO_MANIFEST_DIR"))
@@ -299,6 +356,23 @@ mod tests {
         sdist.pep643_metadata().unwrap().unwrap();
     }
 
+    #[test]
+    pub fn dynamic_requires_dist_is_not_trusted() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/sdists/dynamic_requires_dist-1.0.0.tar.gz");
+
+        let sdist = SDist::from_path(&path, &"dynamic_requires_dist".parse().unwrap()).unwrap();
+
+        // The PKG-INFO is a 2.2 metadata file but `Requires-Dist` is marked as dynamic, so the
+        // listed requirements must not be used.
+        let (_, package_info) = sdist.read_package_info().unwrap();
+        assert_eq!(
+            package_info.dynamic_fields().collect::<Vec<_>>(),
+            ["Requires-Dist"]
+        );
+        assert!(sdist.pep643_metadata().unwrap().is_none());
+    }
+
     #[test]
     pub fn read_rich_build_info() {
         // Read path
@@ -411,6 +485,32 @@ mod tests {
         assert_debug_snapshot!(metadata);
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn build_wheel_with_excluded_build_requirement() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/sdists/env_package-0.1.tar.gz");
+
+        let sdist = SDist::from_path(&path, &"env_package".parse().unwrap()).unwrap();
+
+        let package_db = get_package_db();
+        let env_markers = Arc::new(Pep508EnvMakers::from_env().await.unwrap().0);
+
+        // The sdist is built with the default setuptools backend, excluding setuptools from the
+        // resolution must not remove it from the build environment
+        let options = ResolveOptions::default()
+            .with_env_variables(HashMap::from([(
+                "MY_ENV_VAR".to_string(),
+                "SOME_VALUE".to_string(),
+            )]))
+            .with_exclude(HashSet::from(["setuptools".parse().unwrap()]));
+
+        let wheel_builder = WheelBuilder::new(package_db.0, env_markers, None, options).unwrap();
+
+        let wheel = wheel_builder.build_wheel(&sdist).await.unwrap();
+        let (_, metadata) = wheel.metadata().unwrap();
+        assert_eq!(metadata.name.as_str(), "env-package");
+    }
+
     // On windows these tests will fail because python interpreter
     // should have SYSTEMROOT
     // https://github.com/pyinstaller/pyinstaller/issues/6878
@@ -474,6 +574,33 @@ mod tests {
         assert!(err_string.contains("MY_ENV_VAR should be set in order to build wheel"));
     }
 
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn build_wheel_with_clean_env_and_allowlist() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/sdists/allowlist_env_package-0.1.tar.gz");
+
+        let sdist = SDist::from_path(&path, &"allowlist_env_package".parse().unwrap()).unwrap();
+
+        let package_db = get_package_db();
+        let env_markers = Arc::new(Pep508EnvMakers::from_env().await.unwrap().0);
+
+        // The variable is only set in this process, it should be passed through because it is
+        // part of the allowlist. The name is only used by this test and the variable is removed
+        // again when the guard is dropped.
+        let _guard = EnvVarGuard::set("ALLOWLIST_ENV_PACKAGE_VAR", "SOME_VALUE");
+        let options = ResolveOptions {
+            clean_env: true,
+            ..Default::default()
+        }
+        .with_clean_env_allowlist(vec![String::from("ALLOWLIST_ENV_PACKAGE_VAR")]);
+
+        let wheel_builder = WheelBuilder::new(package_db.0, env_markers, None, options).unwrap();
+
+        // Build the wheel
+        wheel_builder.build_wheel(&sdist).await.unwrap();
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     pub async fn read_zip_metadata() {
         let path =
@@ -702,7 +829,7 @@ mod tests {
             .unwrap();
 
         // assert_debug_snapshot!(wheel_metadata.0);
-        assert_debug_snapshot!(wheel_metadata.1);
+        assert_debug_snapshot!(without_informational_fields(wheel_metadata.1));
 
         // assert_debug_snapshot!(artifact_info[0].filename);
     }
@@ -774,7 +901,7 @@ mod tests {
             .unwrap()
             .unwrap();
 
-        assert_debug_snapshot!(wheel_metadata.1);
+        assert_debug_snapshot!(without_informational_fields(wheel_metadata.1));
     }
 
     #[tokio::test(flavor = "multi_thread")]
@@ -803,7 +930,7 @@ mod tests {
 
         let artifact_info = vec![ArtifactInfo {
             filename: ArtifactName::STree(stree_file_name),
-            url: url,
+            url,
             is_direct_url: true,
             hashes: None,
             requires_python: None,
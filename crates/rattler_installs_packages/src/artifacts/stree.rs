@@ -3,10 +3,40 @@ use crate::types::ArtifactFromSource;
 use crate::types::ReadPyProjectError;
 use crate::types::{HasArtifactName, STreeFilename, SourceArtifactName};
 use fs_err as fs;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use once_cell::sync::OnceCell;
+use rattler_digest::Sha256Hash;
+use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
 
+/// The names of entries that are skipped anywhere in a source tree when hashing its contents. They
+/// contain version control data, caches, virtual environments or generated files that do not
+/// influence the build. The `.git` entry of a git worktree is a file, which is skipped as well.
+const IGNORED_ENTRIES: &[&str] = &[
+    ".git",
+    ".hg",
+    ".svn",
+    "__pycache__",
+    ".mypy_cache",
+    ".pytest_cache",
+    ".ruff_cache",
+    ".tox",
+    ".nox",
+    ".venv",
+    "node_modules",
+];
+
+/// The names of entries that are only skipped in the root of a source tree. These are commonly
+/// used for build outputs and virtual environments, but deeper in the tree they might just as well
+/// be part of the sources, e.g. a `build` package.
+const IGNORED_ROOT_ENTRIES: &[&str] = &["build", "dist", "venv"];
+
+/// Returns true if the entry with the given name is skipped when hashing a source tree.
+fn is_ignored_entry(name: &str, is_root: bool) -> bool {
+    IGNORED_ENTRIES.contains(&name)
+        || (is_root && IGNORED_ROOT_ENTRIES.contains(&name))
+        || name.ends_with(".egg-info")
+}
+
 /// Represents a source tree which can be a simple directory on filesystem
 /// or something cloned from git
 pub struct STree {
@@ -15,9 +45,21 @@ pub struct STree {
 
     /// Source tree location
     pub location: parking_lot::Mutex<PathBuf>,
+
+    /// The hash of the contents of the tree, computed once on first use
+    content_hash: OnceCell<Sha256Hash>,
 }
 
 impl STree {
+    /// Constructs a source tree with the given name that is located at `location`.
+    pub fn new(name: STreeFilename, location: PathBuf) -> Self {
+        Self {
+            name,
+            location: parking_lot::Mutex::new(location),
+            content_hash: OnceCell::new(),
+        }
+    }
+
     /// Get a lock on the inner data
     pub fn lock_data(&self) -> parking_lot::MutexGuard<PathBuf> {
         self.location.lock()
@@ -37,6 +79,65 @@ impl STree {
         }
         Ok(())
     }
+
+    /// Returns the hash of the contents of the source tree, see [`STree::hash_directory`]. The tree
+    /// is only hashed the first time this is called, later edits to the tree are not picked up.
+    pub fn content_hash(&self) -> std::io::Result<Sha256Hash> {
+        self.content_hash
+            .get_or_try_init(|| Self::hash_directory(self.lock_data().as_path()))
+            .copied()
+    }
+
+    /// Computes a hash over the relative paths and the contents of all files in the directory.
+    /// The hash changes whenever a file is added, removed or edited, but is the same for an
+    /// unchanged tree regardless of where it is located or when its files were touched. Version
+    /// control data, caches and build outputs are skipped.
+    fn hash_directory(root: &Path) -> std::io::Result<Sha256Hash> {
+        let mut files = Vec::new();
+        Self::collect_files(root, String::new(), &mut files)?;
+        files.sort();
+
+        let mut hasher = Sha256::new();
+        for (relative_path, path, is_symlink) in files {
+            // Symlinks are hashed by their target, this also avoids following cycles
+            let contents = if is_symlink {
+                fs::read_link(&path)?
+                    .to_string_lossy()
+                    .into_owned()
+                    .into_bytes()
+            } else {
+                fs::read(&path)?
+            };
+            hasher.update(relative_path.as_bytes());
+            hasher.update([0]);
+            hasher.update((contents.len() as u64).to_le_bytes());
+            hasher.update(&contents);
+        }
+        Ok(hasher.finalize())
+    }
+
+    /// Recursively collects the files in `dir` with their path relative to the root of the tree.
+    fn collect_files(
+        dir: &Path,
+        prefix: String,
+        files: &mut Vec<(String, PathBuf, bool)>,
+    ) -> std::io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if is_ignored_entry(&name, prefix.is_empty()) {
+                continue;
+            }
+            let relative_path = format!("{prefix}{name}");
+            let ty = entry.file_type()?;
+            if ty.is_dir() {
+                Self::collect_files(&entry.path(), format!("{relative_path}/"), files)?;
+            } else {
+                files.push((relative_path, entry.path(), ty.is_symlink()));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl HasArtifactName for STree {
@@ -48,21 +149,11 @@ impl HasArtifactName for STree {
 }
 
 impl ArtifactFromSource for STree {
+    /// Returns a hash of the contents of the source tree, see [`STree::content_hash`]. This is
+    /// used as the cache key of the wheel that is built from the tree, so editing the tree
+    /// triggers a rebuild while an unchanged tree reuses the cached wheel.
     fn try_get_bytes(&self) -> Result<Vec<u8>, std::io::Error> {
-        let vec = vec![];
-        let inner = self.lock_data();
-        let mut dir_entry = fs::read_dir(inner.as_path())?;
-
-        let next_entry = dir_entry.next();
-        if let Some(Ok(root_folder)) = next_entry {
-            let modified = root_folder.metadata()?.modified()?;
-            let mut hasher = DefaultHasher::new();
-            modified.hash(&mut hasher);
-            let hash = hasher.finish().to_be_bytes().as_slice().to_owned();
-            return Ok(hash);
-        }
-
-        Ok(vec)
+        Ok(self.content_hash()?.to_vec())
     }
 
     fn distribution_name(&self) -> String {
@@ -104,3 +195,91 @@ impl ArtifactFromSource for STree {
         Self::copy_dir_all(src.as_path(), work_dir)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn stree(path: &Path) -> STree {
+        STree::new(
+            STreeFilename {
+                distribution: "foo".parse().unwrap(),
+                version: pep440_rs::Version::from_str("0.0.0").unwrap(),
+                url: url::Url::from_directory_path(path).unwrap(),
+            },
+            path.to_path_buf(),
+        )
+    }
+
+    #[test]
+    fn test_pep621_metadata() {
+        let static_pyproject =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/stree/static_pyproject");
+        let (_, metadata) = stree(&static_pyproject).pep621_metadata().unwrap().unwrap();
+        assert_eq!(metadata.name.as_str(), "static-pyproject");
+
+        // Poetry projects don't have a `[project]` table
+        let poetry = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/stree/dev_folder_with_rich");
+        assert!(stree(&poetry).pep621_metadata().unwrap().is_none());
+
+        // Without a pyproject.toml the metadata is never static
+        let empty = tempfile::tempdir().unwrap();
+        assert!(stree(empty.path()).pep621_metadata().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("foo")).unwrap();
+        fs::write(
+            dir.path().join("pyproject.toml"),
+            "[project]\nname = \"foo\"\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("foo/__init__.py"), "").unwrap();
+        let hash = stree(dir.path()).try_get_bytes().unwrap();
+
+        // Rewriting the same contents or touching ignored entries does not change the hash
+        fs::write(dir.path().join("foo/__init__.py"), "").unwrap();
+        fs::create_dir_all(dir.path().join("foo/__pycache__")).unwrap();
+        fs::write(dir.path().join("foo/__pycache__/__init__.pyc"), "cache").unwrap();
+        fs::write(dir.path().join(".git"), "gitdir: ../.git/worktrees/foo").unwrap();
+        for ignored in [".venv/bin", "build/lib", "foo.egg-info", "foo/node_modules"] {
+            fs::create_dir_all(dir.path().join(ignored)).unwrap();
+            fs::write(dir.path().join(ignored).join("file"), "ignored").unwrap();
+        }
+        assert_eq!(stree(dir.path()).try_get_bytes().unwrap(), hash);
+
+        // Build outputs are only ignored in the root of the tree
+        fs::create_dir_all(dir.path().join("foo/build")).unwrap();
+        fs::write(dir.path().join("foo/build/__init__.py"), "").unwrap();
+        assert_ne!(stree(dir.path()).try_get_bytes().unwrap(), hash);
+        fs::remove_dir_all(dir.path().join("foo/build")).unwrap();
+
+        // The hash is only computed once per source tree
+        let tree = stree(dir.path());
+        assert_eq!(tree.try_get_bytes().unwrap(), hash);
+        fs::write(dir.path().join("foo/bar.py"), "").unwrap();
+        assert_eq!(tree.try_get_bytes().unwrap(), hash);
+        fs::remove_file(dir.path().join("foo/bar.py")).unwrap();
+
+        // An unchanged copy of the tree at another location has the same hash
+        let copy = tempfile::tempdir().unwrap();
+        STree::copy_dir_all(dir.path(), copy.path()).unwrap();
+        assert_eq!(stree(copy.path()).try_get_bytes().unwrap(), hash);
+
+        // Editing, adding or renaming a file changes the hash
+        fs::write(dir.path().join("foo/__init__.py"), "print('hello')").unwrap();
+        let edited = stree(dir.path()).try_get_bytes().unwrap();
+        assert_ne!(edited, hash);
+
+        fs::write(dir.path().join("foo/bar.py"), "").unwrap();
+        let added = stree(dir.path()).try_get_bytes().unwrap();
+        assert_ne!(added, edited);
+
+        fs::rename(dir.path().join("foo/bar.py"), dir.path().join("foo/baz.py")).unwrap();
+        assert_ne!(stree(dir.path()).try_get_bytes().unwrap(), added);
+    }
+}
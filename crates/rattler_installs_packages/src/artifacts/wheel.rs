@@ -1,8 +1,8 @@
 use crate::types::HasArtifactName;
 use crate::{
     types::{
-        ArtifactFromBytes, NormalizedPackageName, PackageName, RFC822ish, WheelCoreMetaDataError,
-        WheelCoreMetadata, WheelFilename,
+        ArtifactFromBytes, BuildTag, EntryPoints, NormalizedPackageName, PackageName,
+        ParseEntryPointsError, RFC822ish, WheelCoreMetaDataError, WheelCoreMetadata, WheelFilename,
     },
     utils::ReadAndSeek,
 };
@@ -18,7 +18,7 @@ use std::{
     str::FromStr,
 };
 use thiserror::Error;
-use zip::{result::ZipError, ZipArchive};
+use zip::{result::ZipError, CompressionMethod, ZipArchive};
 
 /// A wheel file (`.whl`) in its archived form that is stored somewhere on disk.
 pub struct Wheel {
@@ -114,6 +114,114 @@ impl Wheel {
 
         Ok((metadata_blob, metadata))
     }
+
+    /// Reads the entry points declared in the `entry_points.txt` file of the wheel without
+    /// installing it. Returns empty [`EntryPoints`] if the wheel does not declare any.
+    ///
+    /// All entry points are returned regardless of the extras they require.
+    pub fn entry_points(&self) -> Result<EntryPoints, WheelVitalsError> {
+        let mut archive = self.archive.lock();
+
+        let dist_info_prefix =
+            find_dist_info_metadata(&self.name, archive.file_names().map(|name| ((), name)))?
+                .1
+                .to_owned();
+
+        let entry_points_path = format!("{dist_info_prefix}.dist-info/entry_points.txt");
+        if !archive.file_names().any(|name| name == entry_points_path) {
+            return Ok(EntryPoints::default());
+        }
+        let entry_points_blob = read_entry_to_end(&mut archive, &entry_points_path)?;
+        Ok(EntryPoints::from_ini(
+            &String::from_utf8_lossy(&entry_points_blob),
+            None,
+        )?)
+    }
+
+    /// Extracts every entry of the wheel archive to `dest` while preserving the structure of the
+    /// archive, e.g. scripts end up in `<name>-<version>.data/scripts`. Unlike installing the
+    /// wheel, files are not moved to the location of their category, scripts are not rewritten and
+    /// no entry points are generated. This is useful to inspect or audit the contents of a wheel.
+    ///
+    /// Returns the paths of the extracted files relative to `dest`. An entry with a path that would
+    /// end up outside of `dest` results in an error.
+    pub fn extract_contents(&self, dest: &Path) -> Result<Vec<PathBuf>, WheelVitalsError> {
+        let mut archive = self.archive.lock();
+        let mut files = Vec::with_capacity(archive.len());
+        for index in 0..archive.len() {
+            let mut zip_entry = archive
+                .by_index(index)
+                .map_err(|err| WheelVitalsError::from_zip(format!("<index {index}>"), err))?;
+            let Some(relative_path) = zip_entry.enclosed_name().map(ToOwned::to_owned) else {
+                return Err(WheelVitalsError::ZipError(
+                    zip_entry.name().to_owned(),
+                    ZipError::InvalidArchive("the path of the entry escapes the destination"),
+                ));
+            };
+
+            let destination = dest.join(&relative_path);
+            if zip_entry.is_dir() {
+                fs::create_dir_all(&destination)?;
+                continue;
+            }
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let mut file = fs::File::create(&destination)?;
+            std::io::copy(&mut zip_entry, &mut file)?;
+
+            // Retain the permissions of executable files
+            #[cfg(unix)]
+            if let Some(mode) = zip_entry.unix_mode().filter(|mode| mode & 0o111 != 0) {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&destination, std::fs::Permissions::from_mode(mode & 0o777))?;
+            }
+
+            files.push(relative_path);
+        }
+
+        Ok(files)
+    }
+
+    /// Returns the build tag from the filename of the wheel. The build tag distinguishes wheels
+    /// that are otherwise identical, e.g. when a wheel was rebuilt with a different compiler.
+    pub fn build_tag(&self) -> Option<&BuildTag> {
+        self.name.build_tag.as_ref()
+    }
+
+    /// Reads the informational fields of the `WHEEL` file, see [`WheelInfo`]. Together with the
+    /// [`Self::build_tag`] this helps to tell apart two wheels of the same version.
+    pub fn wheel_info(&self) -> Result<WheelInfo, WheelVitalsError> {
+        let mut archive = self.archive.lock();
+
+        let dist_info_prefix =
+            find_dist_info_metadata(&self.name, archive.file_names().map(|name| ((), name)))?
+                .1
+                .to_owned();
+
+        let wheel_path = format!("{dist_info_prefix}.dist-info/WHEEL");
+        let wheel_blob = read_entry_to_end(&mut archive, &wheel_path)?;
+        let mut parsed = parse_format_metadata_and_check_version(&wheel_blob, "Wheel-Version")?;
+
+        Ok(WheelInfo {
+            generator: parsed.take_all("Generator").into_iter().next(),
+            build: parsed.take_all("Build").into_iter().next(),
+        })
+    }
+}
+
+/// The informational fields of the `WHEEL` file of a wheel that are not needed to install it.
+///
+/// See [`Wheel::wheel_info`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WheelInfo {
+    /// The tool that generated the wheel, e.g. `bdist_wheel (0.41.2)`.
+    pub generator: Option<String>,
+
+    /// The build number of the wheel. If present this should match the build tag in the filename,
+    /// see [`Wheel::build_tag`].
+    pub build: Option<String>,
 }
 
 #[derive(Debug, Error)]
@@ -140,6 +248,9 @@ pub enum WheelVitalsError {
     #[error("invalid METADATA")]
     InvalidMetadata(#[from] WheelCoreMetaDataError),
 
+    #[error("invalid entry_points.txt")]
+    InvalidEntryPoints(#[from] ParseEntryPointsError),
+
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 
@@ -151,6 +262,9 @@ pub enum WheelVitalsError {
 
     #[error("missing key from WHEEL '{0}'")]
     MissingKeyInWheel(String),
+
+    #[error("the wheel file {0} uses an unsupported compression method ({1})")]
+    UnsupportedCompression(String, String),
 }
 
 impl WheelVitalsError {
@@ -173,6 +287,9 @@ impl WheelVitalsError {
     pub(crate) fn from_async_zip(file: String, err: async_zip::error::ZipError) -> Self {
         match err {
             async_zip::error::ZipError::UpstreamReadError(err) => WheelVitalsError::IoError(err),
+            async_zip::error::ZipError::CompressionNotSupported(method) => {
+                WheelVitalsError::UnsupportedCompression(file, compression_method_name(method))
+            }
             _ => WheelVitalsError::AsyncZipError(file, err),
         }
     }
@@ -184,12 +301,66 @@ fn read_entry_to_end<R: ReadAndSeek>(
     name: &str,
 ) -> Result<Vec<u8>, WheelVitalsError> {
     let mut bytes = Vec::new();
-    archive
+    let result = archive
         .by_name(name)
-        .map_err(|err| WheelVitalsError::from_zip(name.to_string(), err))?
-        .read_to_end(&mut bytes)?;
+        .and_then(|mut file| Ok(file.read_to_end(&mut bytes)?));
+
+    match result {
+        Ok(_) => Ok(bytes),
+        Err(ZipError::UnsupportedArchive(msg)) => match entry_compression(archive, name) {
+            Some(method) => Err(WheelVitalsError::UnsupportedCompression(
+                name.to_string(),
+                format!("{method:?}"),
+            )),
+            None => Err(WheelVitalsError::from_zip(
+                name.to_string(),
+                ZipError::UnsupportedArchive(msg),
+            )),
+        },
+        Err(err) => Err(WheelVitalsError::from_zip(name.to_string(), err)),
+    }
+}
+
+/// Returns the compression method of an entry without decompressing it.
+fn entry_compression<R: ReadAndSeek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Option<CompressionMethod> {
+    let index = archive
+        .file_names()
+        .position(|file_name| file_name == name)?;
+    let method = archive.by_index_raw(index).ok()?.compression();
+    Some(method)
+}
 
-    Ok(bytes)
+/// Returns a human readable name of a zip compression method identifier.
+fn compression_method_name(method: u16) -> String {
+    match method {
+        9 => String::from("deflate64"),
+        12 => String::from("bzip2"),
+        14 => String::from("lzma"),
+        93 => String::from("zstd"),
+        95 => String::from("xz"),
+        method => format!("method {method}"),
+    }
+}
+
+/// Parse a key value file and immediately check the version.
+pub(crate) fn parse_format_metadata_and_check_version(
+    input: &[u8],
+    version_field: &str,
+) -> Result<RFC822ish, WheelVitalsError> {
+    let input = String::from_utf8_lossy(input);
+    let mut parsed = RFC822ish::from_str(&input).map_err(WheelVitalsError::FailedToParseWheel)?;
+
+    let version = parsed
+        .take(version_field)
+        .map_err(|_| WheelVitalsError::MissingKeyInWheel(version_field.into()))?;
+    if !version.starts_with("1.") {
+        return Err(WheelVitalsError::UnsupportedWheelVersion(version));
+    }
+
+    Ok(parsed)
 }
 
 /// Locates the `.dist-info` directory in a list of files. The function returns `.dist-info` prefix.
@@ -222,3 +393,150 @@ pub(crate) fn find_dist_info_metadata<'a, T>(
         _ => Err(WheelVitalsError::DistInfoMissing),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::EntryPoint;
+    use crate::utils::{make_wheel, wheel_archive, wheel_from_archive};
+
+    const METADATA: (&str, &str) = (
+        "foo-1.0.dist-info/METADATA",
+        "Metadata-Version: 2.1\nName: foo\nVersion: 1.0\n",
+    );
+
+    /// Creates an in-memory wheel of which all entries claim to be compressed with the given
+    /// compression method.
+    fn wheel_with_compression_method(method: u16) -> Wheel {
+        let mut bytes = wheel_archive(&[METADATA]);
+
+        // Patch the compression method in the local file header and the central directory header
+        let method = method.to_le_bytes();
+        bytes[8..10].copy_from_slice(&method);
+        let central_directory = bytes
+            .windows(4)
+            .position(|window| window == [0x50, 0x4b, 0x01, 0x02])
+            .unwrap();
+        bytes[central_directory + 10..central_directory + 12].copy_from_slice(&method);
+
+        wheel_from_archive("foo-1.0-py3-none-any.whl", bytes)
+    }
+
+    #[test]
+    fn test_unsupported_compression() {
+        let wheel = wheel_with_compression_method(97);
+        let err = wheel.metadata().unwrap_err();
+        assert!(
+            matches!(err, WheelVitalsError::UnsupportedCompression(ref file, _) if file == "foo-1.0.dist-info/METADATA"),
+            "unexpected error: {err:?}"
+        );
+    }
+
+    #[test]
+    fn test_entry_points() {
+        let wheel = make_wheel(
+            "foo-1.0-py3-none-any.whl",
+            &[
+                METADATA,
+                (
+                    "foo-1.0.dist-info/entry_points.txt",
+                    "[console_scripts]\n\
+                    foo = foo.cli:main\n\
+                    foo-server = foo.server:main [server]\n\
+                    \n\
+                    [gui_scripts]\n\
+                    foo-gui = foo.gui:main\n\
+                    \n\
+                    [pytest11]\n\
+                    foo = foo.pytest_plugin\n",
+                ),
+            ],
+        );
+
+        let entry_points = wheel.entry_points().unwrap();
+        fn names(entry_points: &[EntryPoint]) -> Vec<&str> {
+            entry_points
+                .iter()
+                .map(|entry_point| entry_point.script_name.as_str())
+                .collect()
+        }
+        assert_eq!(
+            entry_points.groups.keys().collect::<Vec<_>>(),
+            ["console_scripts", "gui_scripts", "pytest11"]
+        );
+        assert_eq!(names(entry_points.console_scripts()), ["foo", "foo-server"]);
+        assert_eq!(names(entry_points.gui_scripts()), ["foo-gui"]);
+        let plugin = &entry_points.group("pytest11")[0];
+        assert_eq!(plugin.script_name, "foo");
+        assert_eq!(plugin.module, "foo.pytest_plugin");
+        assert_eq!(plugin.function, None);
+
+        // A wheel without entry points
+        let wheel = make_wheel("foo-1.0-py3-none-any.whl", &[METADATA]);
+        assert_eq!(wheel.entry_points().unwrap(), EntryPoints::default());
+    }
+
+    #[test]
+    fn test_wheel_info() {
+        let wheel = make_wheel(
+            "foo-1.0-2-py3-none-any.whl",
+            &[
+                METADATA,
+                (
+                    "foo-1.0.dist-info/WHEEL",
+                    "Wheel-Version: 1.0\n\
+                    Generator: bdist_wheel (0.41.2)\n\
+                    Root-Is-Purelib: true\n\
+                    Build: 2\n\
+                    Tag: py3-none-any\n",
+                ),
+            ],
+        );
+
+        assert_eq!(wheel.build_tag(), Some(&BuildTag::from_str("2").unwrap()));
+        assert_eq!(
+            wheel.wheel_info().unwrap(),
+            WheelInfo {
+                generator: Some(String::from("bdist_wheel (0.41.2)")),
+                build: Some(String::from("2")),
+            }
+        );
+
+        // A wheel without a WHEEL file
+        let wheel = make_wheel("foo-1.0-py3-none-any.whl", &[METADATA]);
+        assert_eq!(wheel.build_tag(), None);
+        assert!(matches!(
+            wheel.wheel_info(),
+            Err(WheelVitalsError::WheelMissing)
+        ));
+    }
+
+    #[test]
+    fn test_extract_contents() {
+        let wheel_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/wheels/native_script-1.0.0-py3-none-any.whl");
+        let wheel = Wheel::from_path(&wheel_path, &"native_script".parse().unwrap()).unwrap();
+        let dest = tempfile::tempdir().unwrap();
+
+        let mut files = wheel.extract_contents(dest.path()).unwrap();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                "native_script/__init__.py",
+                "native_script-1.0.0.data/scripts/native-tool",
+                "native_script-1.0.0.dist-info/METADATA",
+                "native_script-1.0.0.dist-info/RECORD",
+                "native_script-1.0.0.dist-info/WHEEL",
+            ]
+            .map(PathBuf::from)
+        );
+
+        // The script is extracted verbatim and is not moved to a scripts directory
+        let script = dest
+            .path()
+            .join("native_script-1.0.0.data/scripts/native-tool");
+        assert!(fs::read(script).unwrap().starts_with(b"\x7fELF"));
+        assert!(!dest.path().join("bin").exists());
+    }
+}
@@ -0,0 +1,5 @@
+//! Reading the configuration of other tools to ease the migration to RIP. See [`PipConfig`].
+
+mod pip_config;
+
+pub use pip_config::{PipConfig, PipConfigError};
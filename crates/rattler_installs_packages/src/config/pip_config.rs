@@ -0,0 +1,609 @@
+use crate::{
+    index::PackageSourcesBuilder,
+    resolve::solve_options::{PreReleaseResolution, ResolveOptions, SDistResolution},
+    utils::normalize_index_url,
+};
+use configparser::ini::Ini;
+use fs_err as fs;
+use itertools::Itertools;
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+use url::Url;
+
+/// The index that pip uses when no `index-url` is configured.
+const DEFAULT_INDEX_URL: &str = "https://pypi.org/simple/";
+
+/// The sections of the configuration files that apply to `pip install`. Options in later sections
+/// take precedence over options in earlier sections, regardless of the file they are in.
+const SECTIONS: [&str; 2] = ["global", "install"];
+
+/// The options of pip's configuration that are relevant to RIP. The configuration is read from
+/// pip's configuration files (`pip.conf` or `pip.ini` on windows) and from `PIP_<OPTION>`
+/// environment variables, see the
+/// [pip documentation](https://pip.pypa.io/en/stable/topics/configuration/).
+///
+/// Use [`PipConfig::load`] to read the configuration like pip does and
+/// [`PipConfig::package_sources_builder`] and [`PipConfig::apply_to_resolve_options`] to map it
+/// onto the types of RIP.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PipConfig {
+    /// The index url that replaces the default index (`index-url`).
+    pub index_url: Option<Url>,
+
+    /// Additional indexes to search for packages (`extra-index-url`).
+    pub extra_index_urls: Vec<Url>,
+
+    /// Ignore the package index (`no-index`).
+    pub no_index: bool,
+
+    /// Urls or paths to look for archives in (`find-links`).
+    pub find_links: Vec<String>,
+
+    /// Allow pre-releases to be selected (`pre`).
+    pub pre: bool,
+
+    /// Prefer wheels over sdists, even if the sdist is newer (`prefer-binary`).
+    pub prefer_binary: bool,
+
+    /// The packages for which sdists must not be used, `:all:` refers to all packages
+    /// (`only-binary`).
+    pub only_binary: Vec<String>,
+
+    /// The packages for which wheels must not be used, `:all:` refers to all packages
+    /// (`no-binary`).
+    pub no_binary: Vec<String>,
+}
+
+/// An error that can occur when reading the pip configuration with [`PipConfig`].
+#[derive(Debug, Error)]
+#[allow(missing_docs)]
+pub enum PipConfigError {
+    #[error("failed to read {}", .0.display())]
+    Io(PathBuf, #[source] io::Error),
+
+    #[error("failed to parse {}: {1}", .0.display())]
+    Parse(PathBuf, String),
+
+    #[error("invalid value '{value}' for '{key}' in {origin}: {message}")]
+    InvalidValue {
+        key: String,
+        value: String,
+        origin: String,
+        message: String,
+    },
+}
+
+impl PipConfig {
+    /// Reads the configuration from the files returned by [`PipConfig::config_files`] and the
+    /// `PIP_<OPTION>` environment variables of the current process.
+    pub fn load() -> Result<Self, PipConfigError> {
+        Self::from_files_and_env(&Self::config_files(), std::env::vars())
+    }
+
+    /// Returns the paths of the configuration files that pip reads on this platform, the files do
+    /// not have to exist. The files are ordered by increasing priority: the site-wide files, the
+    /// files of the user, the file of the active virtual environment and the file set with
+    /// `PIP_CONFIG_FILE`. Like pip, the files of the user are skipped if `PIP_CONFIG_FILE` refers
+    /// to an existing file. If `PIP_CONFIG_FILE` is set to the null device no files are read at
+    /// all.
+    pub fn config_files() -> Vec<PathBuf> {
+        config_files(|name| std::env::var_os(name))
+    }
+
+    /// Reads the configuration from the given files and environment variables. Files that do not
+    /// exist are skipped. Options that are not relevant to RIP are ignored.
+    ///
+    /// Only the `[global]` and `[install]` sections of the files are used. Like pip, the options
+    /// of the `[install]` section of any file take precedence over the `[global]` section of all
+    /// files. Within a section, options in later files take precedence over options in earlier
+    /// files. Environment variables take precedence over all files.
+    pub fn from_files_and_env(
+        files: &[PathBuf],
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, PipConfigError> {
+        let mut parsed_files = Vec::new();
+        for file in files {
+            let contents = match fs::read_to_string(file) {
+                Ok(contents) => contents,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(PipConfigError::Io(file.clone(), err)),
+            };
+            // Indented lines continue the value of the preceding option, which is commonly used
+            // to list multiple urls
+            let mut ini = Ini::new_cs();
+            ini.set_multiline(true);
+            let sections = ini
+                .read(contents)
+                .map_err(|message| PipConfigError::Parse(file.clone(), message))?;
+            parsed_files.push((file, sections));
+        }
+
+        // The value and the origin of every option, later values replace earlier ones
+        let mut options = HashMap::new();
+        for section in SECTIONS {
+            for (file, sections) in &parsed_files {
+                for (key, value) in sections.get(section).into_iter().flatten() {
+                    options.insert(
+                        normalize_key(key),
+                        (
+                            value.clone().unwrap_or_default(),
+                            file.display().to_string(),
+                        ),
+                    );
+                }
+            }
+        }
+        for (name, value) in env {
+            let Some(key) = name.strip_prefix("PIP_") else {
+                continue;
+            };
+            if key == "CONFIG_FILE" {
+                continue;
+            }
+            options.insert(
+                normalize_key(key),
+                (value, format!("environment variable {name}")),
+            );
+        }
+
+        let mut config = Self::default();
+        for (key, (value, origin)) in options {
+            let invalid = |message: String| PipConfigError::InvalidValue {
+                key: key.clone(),
+                value: value.clone(),
+                origin: origin.clone(),
+                message,
+            };
+            let parse_url = |url: &str| {
+                Url::parse(url)
+                    .map(normalize_index_url)
+                    .map_err(|err| invalid(err.to_string()))
+            };
+            let bool_value =
+                || parse_bool(&value).ok_or_else(|| invalid(String::from("expected a boolean")));
+
+            match key.as_str() {
+                "index-url" => config.index_url = Some(parse_url(value.trim())?),
+                "extra-index-url" => {
+                    config.extra_index_urls =
+                        value.split_whitespace().map(parse_url).try_collect()?
+                }
+                "no-index" => config.no_index = bool_value()?,
+                "find-links" => config.find_links = split_list(&value),
+                "pre" => config.pre = bool_value()?,
+                "prefer-binary" => config.prefer_binary = bool_value()?,
+                "only-binary" => config.only_binary = parse_format_control(&value),
+                "no-binary" => config.no_binary = parse_format_control(&value),
+                _ => {}
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Returns a [`PackageSourcesBuilder`] with the indices from the configuration. Like pip, PyPI
+    /// is used as the base index if no `index-url` is configured. Every `extra-index-url` is added
+    /// as an extra index with the url as its alias.
+    ///
+    /// RIP always resolves against an index and cannot look for archives in a directory, so
+    /// `no-index` and `find-links` are ignored with a warning.
+    pub fn package_sources_builder(&self) -> PackageSourcesBuilder {
+        if self.no_index {
+            tracing::warn!(
+                "ignoring 'no-index' from the pip configuration, an index is always used"
+            );
+        }
+        if !self.find_links.is_empty() {
+            tracing::warn!(
+                "ignoring 'find-links' from the pip configuration, it is not supported: {}",
+                self.find_links.join(", ")
+            );
+        }
+
+        let index_url = self.index_url.clone().unwrap_or_else(|| {
+            Url::parse(DEFAULT_INDEX_URL).expect("the default index url is valid")
+        });
+        self.extra_index_urls
+            .iter()
+            .unique()
+            .fold(PackageSourcesBuilder::new(index_url), |builder, url| {
+                builder.with_index(url.as_str(), url)
+            })
+    }
+
+    /// Applies the options from the configuration that influence the resolution to `options`.
+    /// Options that are not configured are left untouched.
+    ///
+    /// `pre` allows pre-releases for all packages. `only-binary` and `no-binary` are only
+    /// supported for all packages at once (`:all:`), the packages that are listed individually
+    /// are ignored with a warning.
+    pub fn apply_to_resolve_options(&self, mut options: ResolveOptions) -> ResolveOptions {
+        if self.pre {
+            options.pre_release_resolution = PreReleaseResolution::Allow;
+        }
+
+        let all = |packages: &[String]| packages.iter().any(|package| package == ":all:");
+        if all(&self.only_binary) {
+            options.sdist_resolution = SDistResolution::OnlyWheels;
+        } else if all(&self.no_binary) {
+            options.sdist_resolution = SDistResolution::OnlySDists;
+        } else if self.prefer_binary {
+            options.sdist_resolution = SDistResolution::PreferWheels;
+        }
+
+        let per_package = self
+            .only_binary
+            .iter()
+            .chain(&self.no_binary)
+            .filter(|package| package.as_str() != ":all:")
+            .collect_vec();
+        if !per_package.is_empty() {
+            tracing::warn!(
+                "ignoring 'only-binary' and 'no-binary' for individual packages from the pip configuration: {}",
+                per_package.iter().join(", ")
+            );
+        }
+
+        options
+    }
+}
+
+/// Determines the configuration files pip reads, see [`PipConfig::config_files`]. Environment
+/// variables are looked up with `var`.
+fn config_files(var: impl Fn(&str) -> Option<OsString>) -> Vec<PathBuf> {
+    let var = |name: &str| var(name).filter(|value| !value.is_empty());
+    let file_name = if cfg!(windows) { "pip.ini" } else { "pip.conf" };
+
+    let config_file = var("PIP_CONFIG_FILE").map(PathBuf::from);
+    if config_file.as_deref().map_or(false, is_null_device) {
+        return Vec::new();
+    }
+
+    let mut files = Vec::new();
+
+    // The site-wide configuration
+    if cfg!(windows) {
+        if let Some(program_data) = var("ProgramData") {
+            files.push(Path::new(&program_data).join("pip").join(file_name));
+        }
+    } else if cfg!(target_os = "macos") {
+        files.push(Path::new("/Library/Application Support/pip").join(file_name));
+    } else {
+        let config_dirs = var("XDG_CONFIG_DIRS").unwrap_or_else(|| OsString::from("/etc/xdg"));
+        files
+            .extend(std::env::split_paths(&config_dirs).map(|dir| dir.join("pip").join(file_name)));
+        files.push(Path::new("/etc").join(file_name));
+    }
+
+    // The configuration of the user, the legacy location comes first. These are replaced by the
+    // file from `PIP_CONFIG_FILE` if it exists.
+    let load_user_config = !config_file.as_deref().map_or(false, Path::exists);
+    let home = var(if cfg!(windows) { "USERPROFILE" } else { "HOME" }).map(PathBuf::from);
+    if let Some(home) = home.as_ref().filter(|_| load_user_config) {
+        let legacy_dir = if cfg!(windows) { "pip" } else { ".pip" };
+        files.push(home.join(legacy_dir).join(file_name));
+    }
+    let user_config_dir = if cfg!(windows) {
+        var("APPDATA").map(|app_data| PathBuf::from(app_data).join("pip"))
+    } else if cfg!(target_os = "macos") {
+        home.as_ref().map(|home| {
+            let dir = home.join("Library/Application Support/pip");
+            if dir.is_dir() {
+                dir
+            } else {
+                home.join(".config/pip")
+            }
+        })
+    } else {
+        var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".config")))
+            .map(|dir| dir.join("pip"))
+    };
+    if load_user_config {
+        files.extend(user_config_dir.map(|dir| dir.join(file_name)));
+    }
+
+    // The configuration of the active virtual environment
+    if let Some(virtual_env) = var("VIRTUAL_ENV") {
+        files.push(Path::new(&virtual_env).join(file_name));
+    }
+
+    files.extend(config_file);
+    files
+}
+
+/// Returns true if the path refers to the null device, which pip uses to disable reading
+/// configuration files.
+fn is_null_device(path: &Path) -> bool {
+    if cfg!(windows) {
+        path.as_os_str().eq_ignore_ascii_case("nul")
+    } else {
+        path == Path::new("/dev/null")
+    }
+}
+
+/// Normalizes the name of an option from a file (`index_url`, `Index-Url`) or from an environment
+/// variable (`INDEX_URL`) to the name of the command line option (`index-url`).
+fn normalize_key(key: &str) -> String {
+    key.trim().to_lowercase().replace('_', "-")
+}
+
+/// Parses a boolean the way pip does.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "y" | "yes" | "t" | "true" | "on" | "1" => Some(true),
+        "n" | "no" | "f" | "false" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Splits an option that holds multiple values, the values are separated by whitespace.
+fn split_list(value: &str) -> Vec<String> {
+    value.split_whitespace().map(ToOwned::to_owned).collect()
+}
+
+/// Parses the packages of `only-binary` or `no-binary`, which are separated by whitespace or
+/// commas. `:none:` clears the packages that precede it.
+fn parse_format_control(value: &str) -> Vec<String> {
+    let mut packages = Vec::new();
+    for package in value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|package| !package.is_empty())
+    {
+        if package == ":none:" {
+            packages.clear();
+        } else {
+            packages.push(package.to_owned());
+        }
+    }
+    packages
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::NormalizedPackageName;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_files_and_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let global = dir.path().join("global.conf");
+        fs::write(
+            &global,
+            "# site-wide configuration\n\
+            [global]\n\
+            index-url = https://mirror.example.com/simple\n\
+            extra-index-url =\n    \
+                https://a.example.com/simple/\n    \
+                https://b.example.com/simple/\n\
+            timeout = 60\n\
+            \n\
+            [install]\n\
+            pre = true\n\
+            only_binary = numpy,scipy\n\
+            \n\
+            [freeze]\n\
+            no-binary = :all:\n",
+        )
+        .unwrap();
+        let user = dir.path().join("user.conf");
+        fs::write(
+            &user,
+            "[install]\n\
+            Index-Url: https://user.example.com/simple/\n\
+            only-binary = :none: :all:\n",
+        )
+        .unwrap();
+
+        let files = [global, dir.path().join("missing.conf"), user];
+        let config = PipConfig::from_files_and_env(&files, []).unwrap();
+        assert_eq!(
+            config,
+            PipConfig {
+                index_url: Some(Url::parse("https://user.example.com/simple/").unwrap()),
+                extra_index_urls: vec![
+                    Url::parse("https://a.example.com/simple/").unwrap(),
+                    Url::parse("https://b.example.com/simple/").unwrap(),
+                ],
+                pre: true,
+                only_binary: vec![String::from(":all:")],
+                ..Default::default()
+            }
+        );
+
+        // Environment variables take precedence over the files
+        let config = PipConfig::from_files_and_env(
+            &files,
+            env(&[
+                ("PIP_EXTRA_INDEX_URL", "https://c.example.com/simple"),
+                ("PIP_PRE", "no"),
+                ("PIP_FIND_LINKS", "/wheels https://example.com/wheels/"),
+                ("PIP_CONFIG_FILE", "/dev/null"),
+                ("PATH", "/usr/bin"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.extra_index_urls,
+            [Url::parse("https://c.example.com/simple/").unwrap()]
+        );
+        assert!(!config.pre);
+        assert_eq!(
+            config.find_links,
+            ["/wheels", "https://example.com/wheels/"]
+        );
+    }
+
+    #[test]
+    fn test_section_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let site = dir.path().join("site.conf");
+        fs::write(
+            &site,
+            "[install]\nindex-url = https://install.example.com/simple/\n",
+        )
+        .unwrap();
+        let user = dir.path().join("user.conf");
+        fs::write(
+            &user,
+            "[global]\nindex-url = https://global.example.com/simple/\npre = true\n",
+        )
+        .unwrap();
+
+        // The `[install]` section of the site-wide file takes precedence over the `[global]`
+        // section of the file of the user, even though the latter has a higher priority
+        let config = PipConfig::from_files_and_env(&[site, user], []).unwrap();
+        assert_eq!(
+            config.index_url,
+            Some(Url::parse("https://install.example.com/simple/").unwrap())
+        );
+        assert!(config.pre);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("pip.conf");
+
+        fs::write(&file, "[global\nindex-url = https://example.com\n").unwrap();
+        let err = PipConfig::from_files_and_env(&[file.clone()], []).unwrap_err();
+        assert!(
+            matches!(err, PipConfigError::Parse(ref path, _) if path == &file),
+            "unexpected error: {err:?}"
+        );
+
+        fs::write(&file, "[global]\npre\n").unwrap();
+        let err = PipConfig::from_files_and_env(&[file.clone()], []).unwrap_err();
+        assert!(
+            matches!(err, PipConfigError::InvalidValue { ref key, .. } if key == "pre"),
+            "unexpected error: {err:?}"
+        );
+
+        let err = PipConfig::from_files_and_env(&[], env(&[("PIP_PRE", "maybe")])).unwrap_err();
+        assert!(
+            matches!(err, PipConfigError::InvalidValue { ref key, .. } if key == "pre"),
+            "unexpected error: {err:?}"
+        );
+    }
+
+    #[test]
+    fn test_apply_config() {
+        let config = PipConfig {
+            extra_index_urls: vec![
+                Url::parse("https://a.example.com/simple/").unwrap(),
+                Url::parse("https://a.example.com/simple/").unwrap(),
+            ],
+            pre: true,
+            prefer_binary: true,
+            no_binary: vec![String::from("numpy")],
+            ..Default::default()
+        };
+
+        let sources = config.package_sources_builder().build().unwrap();
+        let name: NormalizedPackageName = "foo".parse().unwrap();
+        assert_eq!(
+            sources
+                .index_url(&name)
+                .into_iter()
+                .map(Url::as_str)
+                .collect_vec(),
+            ["https://pypi.org/simple/", "https://a.example.com/simple/"]
+        );
+
+        let options = config.apply_to_resolve_options(ResolveOptions::default());
+        assert_eq!(options.pre_release_resolution, PreReleaseResolution::Allow);
+        assert_eq!(options.sdist_resolution, SDistResolution::PreferWheels);
+
+        let options = PipConfig {
+            only_binary: vec![String::from(":all:")],
+            ..Default::default()
+        }
+        .apply_to_resolve_options(ResolveOptions::default());
+        assert_eq!(options.sdist_resolution, SDistResolution::OnlyWheels);
+        assert_eq!(
+            options.pre_release_resolution,
+            PreReleaseResolution::default()
+        );
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn test_config_files() {
+        let vars = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+
+        assert_eq!(
+            config_files(vars(&[
+                ("HOME", "/home/user"),
+                ("VIRTUAL_ENV", "/home/user/venv"),
+                ("PIP_CONFIG_FILE", "/nonexistent/pip.conf"),
+            ])),
+            [
+                "/etc/xdg/pip/pip.conf",
+                "/etc/pip.conf",
+                "/home/user/.pip/pip.conf",
+                "/home/user/.config/pip/pip.conf",
+                "/home/user/venv/pip.conf",
+                "/nonexistent/pip.conf",
+            ]
+            .map(PathBuf::from)
+        );
+
+        assert_eq!(
+            config_files(vars(&[
+                ("HOME", "/home/user"),
+                ("XDG_CONFIG_DIRS", "/opt/xdg:/etc/xdg"),
+                ("XDG_CONFIG_HOME", "/home/user/config"),
+            ])),
+            [
+                "/opt/xdg/pip/pip.conf",
+                "/etc/xdg/pip/pip.conf",
+                "/etc/pip.conf",
+                "/home/user/.pip/pip.conf",
+                "/home/user/config/pip/pip.conf",
+            ]
+            .map(PathBuf::from)
+        );
+
+        // An existing `PIP_CONFIG_FILE` replaces the files of the user
+        let config_file = tempfile::NamedTempFile::new().unwrap();
+        let config_file_path = config_file.path().to_str().unwrap().to_owned();
+        let vars_with_config_file = |name: &str| match name {
+            "HOME" => Some(OsString::from("/home/user")),
+            "PIP_CONFIG_FILE" => Some(OsString::from(&config_file_path)),
+            _ => None,
+        };
+        assert_eq!(
+            config_files(vars_with_config_file),
+            [
+                PathBuf::from("/etc/xdg/pip/pip.conf"),
+                PathBuf::from("/etc/pip.conf"),
+                config_file.path().to_path_buf(),
+            ]
+        );
+
+        // Pointing `PIP_CONFIG_FILE` at the null device disables all files
+        assert!(config_files(vars(&[
+            ("HOME", "/home/user"),
+            ("PIP_CONFIG_FILE", "/dev/null")
+        ]))
+        .is_empty());
+    }
+}
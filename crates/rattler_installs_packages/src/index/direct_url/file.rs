@@ -9,7 +9,6 @@ use crate::types::{
 use crate::wheel_builder::{WheelBuildError, WheelBuilder};
 use indexmap::IndexMap;
 use miette::IntoDiagnostic;
-use parking_lot::Mutex;
 use pep440_rs::Version;
 use rattler_digest::Sha256;
 use std::fs::File;
@@ -88,10 +87,7 @@ pub(crate) async fn get_stree_from_file_path(
         url: url.clone(),
     };
 
-    let mut stree = STree {
-        name: stree_file_name,
-        location: Mutex::new(path),
-    };
+    let mut stree = STree::new(stree_file_name, path);
 
     let wheel_metadata = wheel_builder
         .get_sdist_metadata(&stree)
@@ -1,5 +1,6 @@
 use crate::index::git_interop::{git_clone, GitSource, ParsedUrl};
 use crate::index::package_database::DirectUrlArtifactResponse;
+use crate::index::ProxyConfig;
 use crate::resolve::PypiVersion;
 use crate::types::{
     ArtifactHashes, ArtifactInfo, ArtifactName, ArtifactType, DirectUrlJson, DirectUrlSource,
@@ -17,6 +18,7 @@ use url::Url;
 pub(crate) async fn get_artifacts_and_metadata<P: Into<NormalizedPackageName>>(
     p: P,
     url: Url,
+    proxy: &ProxyConfig,
     wheel_builder: &Arc<WheelBuilder>,
 ) -> miette::Result<DirectUrlArtifactResponse> {
     let normalized_package_name = p.into();
@@ -28,7 +30,7 @@ pub(crate) async fn get_artifacts_and_metadata<P: Into<NormalizedPackageName>>(
         rev: parsed_url.revision,
     };
 
-    let (mut location, git_rev) = git_clone(&git_source).into_diagnostic()?;
+    let (mut location, git_rev) = git_clone(&git_source, proxy).into_diagnostic()?;
 
     if let Some(subdirectory) = parsed_url.subdirectory {
         location.push(&subdirectory);
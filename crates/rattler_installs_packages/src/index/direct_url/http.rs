@@ -1,6 +1,7 @@
 use crate::artifacts::{SDist, Wheel};
 use crate::index::http::Http;
-use crate::index::{parse_hash, CacheMode};
+use crate::index::package_database::download_to_cache;
+use crate::index::{parse_hash, PackageSources};
 use crate::resolve::PypiVersion;
 use crate::types::{
     ArtifactFromBytes, ArtifactHashes, ArtifactInfo, ArtifactType, DirectUrlHashes, DirectUrlJson,
@@ -9,21 +10,25 @@ use crate::types::{
 };
 use crate::utils::ReadAndSeek;
 use crate::wheel_builder::WheelBuilder;
+use fs_err as fs;
 use indexmap::IndexMap;
 use miette::IntoDiagnostic;
 use pep440_rs::Version;
 use rattler_digest::Sha256;
-use reqwest::header::HeaderMap;
-use reqwest::Method;
+use std::io::{Read, Seek};
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
 use url::Url;
 
-/// Get artifact by file URL
+/// Get artifact by http URL. The artifact is stored in `artifacts_dir`, see [`download_to_cache`]
+/// for when later requests for the same url are served from there.
 pub(crate) async fn get_artifacts_and_metadata<P: Into<NormalizedPackageName>>(
     http: &Http,
+    artifacts_dir: &Path,
     p: P,
     url: Url,
+    sources: &PackageSources,
     wheel_builder: &Arc<WheelBuilder>,
 ) -> miette::Result<crate::index::package_database::DirectUrlArtifactResponse> {
     let str_name = url.path();
@@ -31,38 +36,37 @@ pub(crate) async fn get_artifacts_and_metadata<P: Into<NormalizedPackageName>>(
 
     let normalized_package_name = p.into();
 
-    // Get the contents of the artifact
-    let artifact_bytes = http
-        .request(
-            url.clone(),
-            Method::GET,
-            HeaderMap::default(),
-            CacheMode::Default,
-        )
-        .await?;
-
-    let mut bytes = artifact_bytes
-        .into_body()
-        .into_local()
-        .await
-        .into_diagnostic()?;
-
-    let artifact_hash = {
-        let mut bytes_for_hash = vec![];
-        bytes.rewind().into_diagnostic()?;
-        bytes.read_to_end(&mut bytes_for_hash).into_diagnostic()?;
-        bytes.rewind().into_diagnostic()?;
-        ArtifactHashes {
-            sha256: Some(rattler_digest::compute_bytes_digest::<Sha256>(
-                bytes_for_hash.clone(),
-            )),
+    // Get the contents of the artifact, the download is verified against the hash in the url.
+    let filename = url
+        .path_segments()
+        .and_then(|segments| segments.last())
+        .filter(|filename| !filename.is_empty())
+        .unwrap_or("artifact");
+    let path = download_to_cache(
+        http,
+        sources,
+        artifacts_dir,
+        &url,
+        filename,
+        url_hash.as_ref().and_then(|hash| hash.sha256),
+    )
+    .await?;
+    let mut bytes: Box<dyn ReadAndSeek + Send> = Box::new(fs::File::open(&path).into_diagnostic()?);
+
+    let artifact_hash = match url_hash {
+        Some(hash) => hash,
+        None => {
+            let mut bytes_for_hash = vec![];
+            bytes.read_to_end(&mut bytes_for_hash).into_diagnostic()?;
+            bytes.rewind().into_diagnostic()?;
+            ArtifactHashes {
+                sha256: Some(rattler_digest::compute_bytes_digest::<Sha256>(
+                    bytes_for_hash,
+                )),
+            }
         }
     };
 
-    if let Some(hash) = url_hash.clone() {
-        assert_eq!(hash, artifact_hash);
-    };
-
     let hash_str = format!(
         "{:x}",
         artifact_hash
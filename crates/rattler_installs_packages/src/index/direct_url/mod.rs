@@ -1,7 +1,9 @@
+use std::path::Path;
 use std::sync::Arc;
 
 use crate::index::http::Http;
 use crate::index::package_database::DirectUrlArtifactResponse;
+use crate::index::PackageSources;
 use crate::types::NormalizedPackageName;
 use crate::wheel_builder::WheelBuilder;
 use url::Url;
@@ -10,11 +12,16 @@ pub(crate) mod file;
 pub(crate) mod git;
 pub(crate) mod http;
 
-/// Get artifact directly from file, vcs, or url
+/// Get artifact directly from file, vcs, or url. Artifacts that are downloaded are stored in
+/// `artifacts_dir`. Git repositories are fetched through the proxy of `sources`, other urls are
+/// fetched with the client of `http` and must not be redirected to a host that `sources` does not
+/// allow.
 pub(crate) async fn fetch_artifact_and_metadata_by_direct_url<P: Into<NormalizedPackageName>>(
     http: &Http,
+    artifacts_dir: &Path,
     p: P,
     url: Url,
+    sources: &PackageSources,
     wheel_builder: &Arc<WheelBuilder>,
 ) -> miette::Result<DirectUrlArtifactResponse> {
     let p = p.into();
@@ -24,11 +31,24 @@ pub(crate) async fn fetch_artifact_and_metadata_by_direct_url<P: Into<Normalized
         super::direct_url::file::get_artifacts_and_metadata(p.clone(), url, wheel_builder).await
     } else if url.scheme() == "https" {
         // This can be a Wheel or SDist artifact
-        super::direct_url::http::get_artifacts_and_metadata(http, p.clone(), url, wheel_builder)
-            .await
+        super::direct_url::http::get_artifacts_and_metadata(
+            http,
+            artifacts_dir,
+            p.clone(),
+            url,
+            sources,
+            wheel_builder,
+        )
+        .await
     } else if url.scheme() == "git+https" || url.scheme() == "git+file" {
         // This can be a STree artifact
-        super::direct_url::git::get_artifacts_and_metadata(p.clone(), url, wheel_builder).await
+        super::direct_url::git::get_artifacts_and_metadata(
+            p.clone(),
+            url,
+            sources.proxy(),
+            wheel_builder,
+        )
+        .await
     } else {
         Err(miette::miette!(
             "Usage of insecure protocol or unsupported scheme {:?}",
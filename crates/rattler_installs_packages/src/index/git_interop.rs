@@ -13,6 +13,8 @@ use regex::Regex;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
+use crate::index::ProxyConfig;
+
 /// A Git repository URL or a local path to a Git repository
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -215,9 +217,12 @@ pub enum SourceError {
     GitErrorStr(&'static str),
 }
 
-/// Create a `git` command with the given subcommand.
-fn git_command(sub_cmd: &str) -> Command {
+/// Create a `git` command with the given subcommand that connects through the given proxies. This
+/// also applies to commands that do not seem to touch the network, e.g. a `checkout` in a partial
+/// clone fetches the missing blobs from the remote.
+fn git_command(sub_cmd: &str, proxy: &ProxyConfig) -> Command {
     let mut command = Command::new("git");
+    proxy.apply_to_command(&mut command);
     command.arg(sub_cmd);
 
     command
@@ -295,7 +300,12 @@ fn get_revision_sha(dest: &PathBuf, rev: Option<String>) -> Result<GitRev, Sourc
 }
 
 /// Fetch the git repository specified by the given source and place it in the cache directory.
-pub fn git_clone(source: &GitSource) -> Result<(PathBuf, GitRev), SourceError> {
+/// Every git command that might fetch from a remote, including the checkout of a partial clone and
+/// the submodules, connects through the given proxies.
+pub fn git_clone(
+    source: &GitSource,
+    proxy: &ProxyConfig,
+) -> Result<(PathBuf, GitRev), SourceError> {
     // test if git is available locally as we fetch the git from PATH,
     if !Command::new("git")
         .arg("--version")
@@ -333,7 +343,7 @@ pub fn git_clone(source: &GitSource) -> Result<(PathBuf, GitRev), SourceError> {
         GitUrl::Url(_) => {
             // If the cache_path exists, initialize the repo and fetch the specified revision.
             if !cache_path.exists() {
-                let mut command = git_command("clone");
+                let mut command = git_command("clone", proxy);
                 if support_partial_clone().is_ok() {
                     command.arg("--filter=blob:none");
                 } else {
@@ -366,7 +376,7 @@ pub fn git_clone(source: &GitSource) -> Result<(PathBuf, GitRev), SourceError> {
                 SourceError::GitError(format!("{}: Path not found on system", e))
             })?;
 
-            let mut command = git_command("clone");
+            let mut command = git_command("clone", proxy);
 
             command
                 .arg("--recursive")
@@ -391,7 +401,7 @@ pub fn git_clone(source: &GitSource) -> Result<(PathBuf, GitRev), SourceError> {
 
     let git_rev = get_revision_sha(&cache_path, source.rev.clone())?;
 
-    let mut checkout = git_command("checkout");
+    let mut checkout = git_command("checkout", proxy);
 
     let cmd = if !git_rev.is_head() {
         Some(checkout.args(["-q", git_rev.get_commit().as_str()]))
@@ -418,7 +428,7 @@ pub fn git_clone(source: &GitSource) -> Result<(PathBuf, GitRev), SourceError> {
 
     // update submodules
     if cache_path.join(".gitmodules").exists() {
-        let mut submodule = git_command("submodule");
+        let mut submodule = git_command("submodule", proxy);
         let output = submodule
             .current_dir(&cache_path)
             .arg("update")
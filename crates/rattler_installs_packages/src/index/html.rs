@@ -2,7 +2,9 @@
 use std::str::FromStr;
 use std::{borrow::Borrow, default::Default};
 
-use crate::{types::ArtifactHashes, types::ArtifactName, types::NormalizedPackageName};
+use crate::{
+    types::ArtifactHashes, types::ArtifactName, types::NormalizedPackageName, utils::redact_url,
+};
 use miette::{miette, IntoDiagnostic};
 use pep440_rs::VersionSpecifiers;
 
@@ -13,15 +15,19 @@ use url::Url;
 
 use crate::types::{ArtifactInfo, DistInfoMetadata, ProjectInfo, Yanked};
 
-/// Parse a hash from url fragment
+/// Parse a hash from url fragment, e.g. `sha256=<hex digest>`.
+///
+/// PEP 503 allows the fragment to use any hash algorithm supported by `hashlib`. Only sha256 hashes
+/// are used, `None` is returned for other algorithms (older indices often use md5) or if the digest
+/// is invalid.
 pub fn parse_hash(s: &str) -> Option<ArtifactHashes> {
-    if let Some(("sha256", hex)) = s.split_once('=') {
-        Some(ArtifactHashes {
-            sha256: parse_digest_from_hex::<Sha256>(hex),
-        })
-    } else {
-        None
+    let (algorithm, hex) = s.split_once('=')?;
+    if !algorithm.eq_ignore_ascii_case("sha256") {
+        return None;
     }
+    Some(ArtifactHashes {
+        sha256: Some(parse_digest_from_hex::<Sha256>(hex)?),
+    })
 }
 
 fn into_artifact_info(
@@ -55,8 +61,11 @@ fn into_artifact_info(
         .transpose()
         .ok()?;
 
+    // PEP 714 renamed `data-dist-info-metadata` to `data-core-metadata`, prefer the new name but
+    // fall back to the old one for older indices.
     let metadata_attr = attributes
-        .get("data-dist-info-metadata")
+        .get("data-core-metadata")
+        .or_else(|| attributes.get("data-dist-info-metadata"))
         .flatten()
         .map(|a| a.as_utf8_str());
 
@@ -122,11 +131,15 @@ pub fn parse_project_info_html(base: &Url, body: &str) -> miette::Result<Project
             .into_diagnostic()
             .map_err(|e| {
                 miette!(
-                    "error parsing segment '{last_segment}' from url '{base}' into a normalized package name, error: {e}"
+                    "error parsing segment '{last_segment}' from url '{}' into a normalized package name, error: {e}",
+                    redact_url(base)
                 )
             })?
     } else {
-        return Err(miette!("no package segments found in url: '{base}'"));
+        return Err(miette!(
+            "no package segments found in url: '{}'",
+            redact_url(base)
+        ));
     };
 
     // Select repository version
@@ -183,17 +196,66 @@ pub fn parse_project_info_html(base: &Url, body: &str) -> miette::Result<Project
 /// Parse package names from a pypyi repository index.
 #[tracing::instrument(level = "debug", skip(body))]
 pub fn parse_package_names_html(body: &str) -> miette::Result<Vec<String>> {
-    let dom = tl::parse(body, tl::ParserOptions::default()).into_diagnostic()?;
-    let names = dom.query_selector("a");
+    Ok(PackageNamesParser::default().feed(body.as_bytes()))
+}
 
-    if let Some(names) = names {
-        let names = names
-            .filter_map(|a| a.get(dom.parser()))
-            .map(|node| node.inner_text(dom.parser()).to_string())
-            .collect();
-        Ok(names)
-    } else {
-        Ok(Vec::new())
+/// An incremental parser for the package names in a pypi repository index. The index of pypi.org
+/// is hundreds of megabytes, this parser allows extracting the names while the index is still
+/// being downloaded.
+///
+/// The parser extracts the text of every `<a>` element. It only understands enough HTML to do
+/// that, which is all the simple repository API requires.
+#[derive(Default)]
+pub struct PackageNamesParser {
+    /// The name of the tag that is currently being parsed, if any.
+    tag: Option<Vec<u8>>,
+    /// Whether the name of the current tag has been fully read.
+    tag_name_complete: bool,
+    /// The quote character if we are inside a quoted attribute value.
+    quote: Option<u8>,
+    /// The text of the `<a>` element we are in, if any.
+    anchor_text: Option<Vec<u8>>,
+}
+
+impl PackageNamesParser {
+    /// Feeds the next chunk of the document to the parser and returns the names that were
+    /// completed by this chunk. Chunks may be split at arbitrary byte boundaries.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<String> {
+        let mut names = Vec::new();
+        for &byte in chunk {
+            let Some(tag) = self.tag.as_mut() else {
+                if byte == b'<' {
+                    self.tag = Some(Vec::new());
+                    self.tag_name_complete = false;
+                } else if let Some(text) = self.anchor_text.as_mut() {
+                    text.push(byte);
+                }
+                continue;
+            };
+
+            if let Some(quote) = self.quote {
+                if byte == quote {
+                    self.quote = None;
+                }
+            } else if byte == b'>' {
+                if tag.eq_ignore_ascii_case(b"a") {
+                    self.anchor_text = Some(Vec::new());
+                } else if tag.eq_ignore_ascii_case(b"/a") {
+                    if let Some(text) = self.anchor_text.take() {
+                        names.push(String::from_utf8_lossy(&text).into_owned());
+                    }
+                }
+                self.tag = None;
+            } else if byte == b'"' || byte == b'\'' {
+                self.quote = Some(byte);
+                self.tag_name_complete = true;
+            } else if byte.is_ascii_whitespace() && !tag.is_empty() {
+                self.tag_name_complete = true;
+            } else if !self.tag_name_complete && !byte.is_ascii_whitespace() {
+                tag.push(byte);
+            }
+        }
+        names
     }
 }
 
@@ -201,6 +263,32 @@ pub fn parse_package_names_html(body: &str) -> miette::Result<Vec<String>> {
 mod test {
     use super::*;
 
+    #[test]
+    fn test_package_names_parser_chunks() {
+        let html = r#"<html><body>
+            <a href="/simple/flask/">Flask</a>
+            <A HREF='/simple/a>b/'>a-b</A>
+            <a href="/simple/nested/"><span>nested</span></a>
+            <abbr>not-a-package</abbr>
+            <a href="/simple/ünïcode/">ünïcode</a>
+        </body></html>"#;
+
+        let expected = ["Flask", "a-b", "nested", "ünïcode"];
+
+        // Feeding the whole document at once
+        let names = PackageNamesParser::default().feed(html.as_bytes());
+        assert_eq!(names, expected);
+
+        // Feeding one byte at a time should produce the same result
+        let mut parser = PackageNamesParser::default();
+        let names = html
+            .as_bytes()
+            .chunks(1)
+            .flat_map(|chunk| parser.feed(chunk))
+            .collect::<Vec<_>>();
+        assert_eq!(names, expected);
+    }
+
     #[test]
     fn test_sink_simple() {
         let parsed = parse_project_info_html(
@@ -305,6 +393,80 @@ mod test {
         "###);
     }
 
+    #[test]
+    fn test_fragment_hashes() {
+        let sha256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        let parsed = parse_project_info_html(
+            &Url::parse("https://example.com/simple/link/").unwrap(),
+            &format!(
+                r#"<html>
+                <body>
+                  <a href="link-1.0.tar.gz#sha256={sha256}">link1</a>
+                  <a href="link-2.0.tar.gz#SHA256={upper}">link2</a>
+                  <a href="link-3.0.tar.gz#md5=d41d8cd98f00b204e9800998ecf8427e">link3</a>
+                  <a href="link-4.0.tar.gz#sha256=invalid">link4</a>
+                  <a href="link-5.0-py3-none-any.whl#sha256={sha256}" data-dist-info-metadata="sha256={sha256}">link5</a>
+                </body>
+              </html>
+            "#,
+                upper = sha256.to_uppercase()
+            ),
+        )
+        .unwrap();
+
+        let hashes = parsed
+            .files
+            .iter()
+            .map(|file| {
+                file.hashes
+                    .as_ref()
+                    .and_then(|hashes| hashes.sha256)
+                    .map(|hash| format!("{hash:x}"))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hashes,
+            [
+                Some(sha256.to_string()),
+                Some(sha256.to_string()),
+                None,
+                None,
+                Some(sha256.to_string())
+            ]
+        );
+
+        // The hash of the metadata file is read from the attribute
+        let metadata = &parsed.files[4].dist_info_metadata;
+        assert!(metadata.available);
+        assert_eq!(
+            metadata.hashes.sha256.map(|hash| format!("{hash:x}")),
+            Some(sha256.to_string())
+        );
+    }
+
+    #[test]
+    fn test_core_metadata_attribute() {
+        let parsed = parse_project_info_html(
+            &Url::parse("https://example.com/simple/link/").unwrap(),
+            r#"<html>
+                <body>
+                  <a href="link-1.0.tar.gz" data-core-metadata="true">link1</a>
+                  <a href="link-2.0.tar.gz" data-dist-info-metadata="true">link2</a>
+                  <a href="link-3.0.tar.gz">link3</a>
+                </body>
+              </html>
+            "#,
+        )
+        .unwrap();
+
+        let available = parsed
+            .files
+            .iter()
+            .map(|file| file.dist_info_metadata.available)
+            .collect::<Vec<_>>();
+        assert_eq!(available, vec![true, true, false]);
+    }
+
     #[test]
     fn test_package_name_parsing() {
         let html = r#"
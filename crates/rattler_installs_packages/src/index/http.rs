@@ -1,13 +1,13 @@
 use super::file_store::FileLock;
 use super::file_store::FileStore;
 use super::package_database::NotCached;
-use crate::utils::{ReadAndSeek, SeekSlice, StreamingOrLocal};
+use crate::utils::{redact_url, ReadAndSeek, SeekSlice, StreamingOrLocal};
 use bytes::Bytes;
 use futures::{Stream, StreamExt, TryStreamExt};
 use http_cache_semantics::{AfterResponse, BeforeRequest, CachePolicy};
 use miette::Diagnostic;
-use reqwest::header::{ACCEPT, CACHE_CONTROL};
-use reqwest::{header::HeaderMap, Method};
+use reqwest::header::{HeaderValue, ACCEPT, CACHE_CONTROL, RETRY_AFTER};
+use reqwest::{header::HeaderMap, Method, StatusCode};
 use reqwest_middleware::ClientWithMiddleware;
 use serde::{Deserialize, Serialize};
 use std::io;
@@ -16,7 +16,7 @@ use std::io::BufWriter;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 use url::Url;
@@ -24,6 +24,14 @@ use url::Url;
 const CURRENT_VERSION: u8 = 1;
 const CACHE_BOM: &str = "RIP";
 
+/// The maximum number of times a request is retried when the server responds with
+/// `429 Too Many Requests` and a `Retry-After` header.
+const MAX_RETRY_AFTER_ATTEMPTS: usize = 3;
+
+/// The maximum duration we are willing to wait before retrying a rate limited request, regardless
+/// of what the server tells us.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
 // Attached to HTTP responses, to make testing easier
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum CacheStatus {
@@ -54,7 +62,7 @@ pub struct Http {
 #[derive(Debug, Error, Diagnostic)]
 pub enum HttpRequestError {
     #[error(transparent)]
-    HttpError(#[from] reqwest_middleware::Error),
+    HttpError(reqwest_middleware::Error),
 
     #[error(transparent)]
     IoError(#[from] io::Error),
@@ -66,7 +74,23 @@ pub enum HttpRequestError {
 
 impl From<reqwest::Error> for HttpRequestError {
     fn from(e: reqwest::Error) -> Self {
-        Self::HttpError(e.into())
+        Self::from(reqwest_middleware::Error::from(e))
+    }
+}
+
+impl From<reqwest_middleware::Error> for HttpRequestError {
+    /// The error of a request includes the url of the request, which is redacted because it may
+    /// contain the credentials of the index.
+    fn from(e: reqwest_middleware::Error) -> Self {
+        match e {
+            reqwest_middleware::Error::Reqwest(mut e) => {
+                if let Some(url) = e.url_mut() {
+                    *url = redact_url(url);
+                }
+                Self::HttpError(reqwest_middleware::Error::Reqwest(e))
+            }
+            e => Self::HttpError(e),
+        }
     }
 }
 
@@ -80,6 +104,14 @@ impl Http {
     }
 
     /// Performs a single request caching the result internally if requested.
+    ///
+    /// The cache policy of a response, which includes its `ETag` and `Last-Modified` headers, is
+    /// stored together with the body. When a cached response is stale the request is sent with the
+    /// matching `If-None-Match`/`If-Modified-Since` headers and a `304 Not Modified` response is
+    /// served from the cache without downloading the body again.
+    ///
+    /// The url of the response, after following any redirects, is attached to it as a [`Url`]
+    /// extension.
     pub async fn request(
         &self,
         url: Url,
@@ -87,7 +119,7 @@ impl Http {
         headers: HeaderMap,
         cache_mode: CacheMode,
     ) -> Result<http::Response<StreamingOrLocal>, HttpRequestError> {
-        tracing::info!(url=%url, cache_mode=?cache_mode, "executing request");
+        tracing::info!(url=%redact_url(&url), cache_mode=?cache_mode, "executing request");
 
         // Construct a request using the reqwest client.
         let request = self
@@ -97,12 +129,13 @@ impl Http {
             .build()?;
 
         if cache_mode == CacheMode::NoStore {
-            let mut response =
-                convert_response(self.client.execute(request).await?.error_for_status()?)
-                    .map(body_to_streaming_or_local);
+            let response = self.execute(request).await?.error_for_status()?;
+            let final_url = response.url().clone();
+            let mut response = convert_response(response).map(body_to_streaming_or_local);
 
-            // Add the `CacheStatus` to the response
+            // Add the `CacheStatus` and the url after redirects to the response
             response.extensions_mut().insert(CacheStatus::Uncacheable);
+            response.extensions_mut().insert(final_url);
 
             Ok(response)
         } else {
@@ -114,7 +147,7 @@ impl Http {
             }) {
                 match old_policy.before_request(&request, SystemTime::now()) {
                     BeforeRequest::Fresh(parts) => {
-                        tracing::debug!(url=%url, "is fresh");
+                        tracing::debug!(url=%redact_url(&url), "is fresh");
                         let mut response = http::Response::from_parts(
                             parts,
                             StreamingOrLocal::Local(Box::new(old_body)),
@@ -135,7 +168,6 @@ impl Http {
                         // to date or not.
                         let request = convert_request(self.client.clone(), new_parts)?;
                         let response = self
-                            .client
                             .execute(request.try_clone().expect("clone of request cannot fail"))
                             .await?;
                         let final_url = response.url().clone();
@@ -143,7 +175,7 @@ impl Http {
                         // Determine what to do based on the response headers.
                         match old_policy.after_response(&request, &response, SystemTime::now()) {
                             AfterResponse::NotModified(_, new_parts) => {
-                                tracing::debug!(url=%url, "stale, but not modified");
+                                tracing::debug!(url=%redact_url(&url), "stale, but not modified");
                                 Ok(make_response(
                                     new_parts,
                                     StreamingOrLocal::Local(Box::new(old_body)),
@@ -152,7 +184,7 @@ impl Http {
                                 ))
                             }
                             AfterResponse::Modified(new_policy, parts) => {
-                                tracing::debug!(url=%url, "stale, but *and* modified");
+                                tracing::debug!(url=%redact_url(&url), "stale, but *and* modified");
                                 drop(old_body);
                                 let new_body = if new_policy.is_storable() {
                                     let new_body = fill_cache_async(
@@ -183,7 +215,6 @@ impl Http {
                 }
 
                 let response = self
-                    .client
                     .execute(request.try_clone().expect("failed to clone request?"))
                     .await?
                     .error_for_status()?;
@@ -203,6 +234,57 @@ impl Http {
             }
         }
     }
+
+    /// Executes a request. If the server responds with `429 Too Many Requests` and tells us how
+    /// long to wait through the `Retry-After` header, the request is retried after that duration
+    /// (bounded by [`MAX_RETRY_AFTER`]) for a limited number of times. Rate limited responses
+    /// without a `Retry-After` header are returned as is.
+    async fn execute(
+        &self,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Response, HttpRequestError> {
+        let mut attempt = 0;
+        loop {
+            let response = self
+                .client
+                .execute(request.try_clone().expect("clone of request cannot fail"))
+                .await?;
+
+            if response.status() != StatusCode::TOO_MANY_REQUESTS
+                || attempt >= MAX_RETRY_AFTER_ATTEMPTS
+            {
+                return Ok(response);
+            }
+
+            let Some(retry_after) = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| parse_retry_after(value, SystemTime::now()))
+            else {
+                return Ok(response);
+            };
+
+            let retry_after = retry_after.min(MAX_RETRY_AFTER);
+            tracing::warn!(
+                url=%redact_url(response.url()),
+                "rate limited by the server, retrying in {}s",
+                retry_after.as_secs_f32()
+            );
+            tokio::time::sleep(retry_after).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Parses the value of a `Retry-After` header which is either a number of seconds or an HTTP-date.
+fn parse_retry_after(value: &HeaderValue, now: SystemTime) -> Option<Duration> {
+    let value = value.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
 }
 
 /// Constructs a `http::Response` from parts.
@@ -407,7 +489,6 @@ fn convert_response(
     // Take the headers from the response
     let headers = builder.headers_mut().unwrap();
     *headers = std::mem::take(response.headers_mut());
-    std::mem::swap(response.headers_mut(), headers);
 
     // Take the extensions from the response
     let extensions = builder.extensions_mut().unwrap();
@@ -443,7 +524,19 @@ mod tests {
     use std::{fs, io::BufWriter, sync::Arc};
     use tempfile::TempDir;
 
-    use super::{key_for_request, read_cache, CacheMode, Http};
+    use super::{key_for_request, parse_retry_after, read_cache, CacheMode, CacheStatus, Http};
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::Router;
+    use flate2::{write::GzEncoder, Compression};
+    use http::header::{
+        ACCEPT_ENCODING, CONTENT_ENCODING, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    };
+    use http::StatusCode;
+    use std::future::IntoFuture;
+    use std::io::{Read, Write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, SystemTime};
 
     fn get_http_client() -> (Arc<Http>, TempDir) {
         let tempdir = tempfile::tempdir().unwrap();
@@ -505,4 +598,172 @@ mod tests {
 
         assert!(read_again.is_err());
     }
+
+    #[tokio::test]
+    async fn test_compressed_responses_are_cached_decompressed() {
+        const BODY: &str = "<html><body>compressed</body></html>";
+
+        async fn compressed_page(headers: axum::http::HeaderMap) -> impl IntoResponse {
+            let accepts_gzip = headers
+                .get(ACCEPT_ENCODING)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.contains("gzip"));
+            if !accepts_gzip {
+                return StatusCode::NOT_ACCEPTABLE.into_response();
+            }
+
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(BODY.as_bytes()).unwrap();
+            (
+                [(CONTENT_ENCODING, "gzip"), (CACHE_CONTROL, "max-age=3600")],
+                encoder.finish().unwrap(),
+            )
+                .into_response()
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let router = Router::new().route("/page", get(compressed_page));
+        tokio::spawn(axum::serve(listener, router).into_future());
+
+        let url = url::Url::parse(&format!("http://{address}/page")).unwrap();
+        let (client, _tmpdir) = get_http_client();
+        let response = client
+            .request(
+                url.clone(),
+                Method::GET,
+                HeaderMap::new(),
+                CacheMode::Default,
+            )
+            .await
+            .unwrap();
+        let mut body = Vec::new();
+        response.into_body().read_to_end(&mut body).await.unwrap();
+        assert_eq!(body, BODY.as_bytes());
+
+        // The cache contains the decompressed body
+        let key = key_for_request(&url, Method::GET, &HeaderMap::new());
+        let lock = client.http_cache.lock(&key.as_slice()).await.unwrap();
+        let (_, _, mut cached) = read_cache(
+            lock.reader().unwrap().detach_unlocked(),
+            CACHE_BOM,
+            CURRENT_VERSION,
+        )
+        .unwrap();
+        let mut cached_body = String::new();
+        cached.read_to_string(&mut cached_body).unwrap();
+        assert_eq!(cached_body, BODY);
+    }
+
+    #[derive(Default)]
+    struct RevalidationCounters {
+        full: AtomicUsize,
+        not_modified: AtomicUsize,
+    }
+
+    async fn etag_page(
+        axum::Extension(counters): axum::Extension<Arc<RevalidationCounters>>,
+        headers: axum::http::HeaderMap,
+    ) -> axum::response::Response {
+        if headers.get(IF_NONE_MATCH).map(|v| v.as_bytes()) == Some(b"\"v1\"") {
+            counters.not_modified.fetch_add(1, Ordering::SeqCst);
+            return (StatusCode::NOT_MODIFIED, [(ETAG, "\"v1\"")]).into_response();
+        }
+        counters.full.fetch_add(1, Ordering::SeqCst);
+        ([(ETAG, "\"v1\"")], "etag body").into_response()
+    }
+
+    async fn last_modified_page(
+        axum::Extension(counters): axum::Extension<Arc<RevalidationCounters>>,
+        headers: axum::http::HeaderMap,
+    ) -> axum::response::Response {
+        const LAST_MODIFIED_DATE: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+        if headers.get(IF_MODIFIED_SINCE).map(|v| v.as_bytes())
+            == Some(LAST_MODIFIED_DATE.as_bytes())
+        {
+            counters.not_modified.fetch_add(1, Ordering::SeqCst);
+            return (
+                StatusCode::NOT_MODIFIED,
+                [(LAST_MODIFIED, LAST_MODIFIED_DATE)],
+            )
+                .into_response();
+        }
+        counters.full.fetch_add(1, Ordering::SeqCst);
+        ([(LAST_MODIFIED, LAST_MODIFIED_DATE)], "last-modified body").into_response()
+    }
+
+    #[tokio::test]
+    async fn test_stale_responses_are_revalidated() {
+        let counters = Arc::new(RevalidationCounters::default());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let router = Router::new()
+            .route("/etag", get(etag_page))
+            .route("/last-modified", get(last_modified_page))
+            .layer(axum::Extension(counters.clone()));
+        tokio::spawn(axum::serve(listener, router).into_future());
+
+        let (client, _tmpdir) = get_http_client();
+
+        // This is what `fetch_simple_api` sends to always check for new artifacts
+        let mut headers = HeaderMap::new();
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("max-age=0"));
+
+        for (path, expected_body) in [
+            ("etag", "etag body"),
+            ("last-modified", "last-modified body"),
+        ] {
+            let url = url::Url::parse(&format!("http://{address}/{path}")).unwrap();
+            for expected_status in [CacheStatus::Miss, CacheStatus::StaleButValidated] {
+                let response = client
+                    .request(
+                        url.clone(),
+                        Method::GET,
+                        headers.clone(),
+                        CacheMode::Default,
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    response.extensions().get::<CacheStatus>(),
+                    Some(&expected_status)
+                );
+                let mut body = Vec::new();
+                response.into_body().read_to_end(&mut body).await.unwrap();
+                assert_eq!(body, expected_body.as_bytes());
+            }
+        }
+
+        // Both pages were only downloaded once, the second request was answered with a 304
+        assert_eq!(counters.full.load(Ordering::SeqCst), 2);
+        assert_eq!(counters.not_modified.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+
+        assert_eq!(
+            parse_retry_after(&HeaderValue::from_static("120"), now),
+            Some(Duration::from_secs(120))
+        );
+
+        let date = httpdate::fmt_http_date(now + Duration::from_secs(30));
+        assert_eq!(
+            parse_retry_after(&HeaderValue::from_str(&date).unwrap(), now),
+            Some(Duration::from_secs(30))
+        );
+
+        // A date in the past means we can retry immediately
+        let date = httpdate::fmt_http_date(now - Duration::from_secs(30));
+        assert_eq!(
+            parse_retry_after(&HeaderValue::from_str(&date).unwrap(), now),
+            Some(Duration::ZERO)
+        );
+
+        assert_eq!(
+            parse_retry_after(&HeaderValue::from_static("soon"), now),
+            None
+        );
+    }
 }
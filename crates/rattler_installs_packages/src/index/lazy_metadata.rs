@@ -2,25 +2,51 @@ use crate::artifacts::wheel::{find_dist_info_metadata, WheelVitalsError};
 use crate::types::{WheelCoreMetadata, WheelFilename};
 use async_http_range_reader::AsyncHttpRangeReader;
 use async_zip::base::read::seek::ZipFileReader;
+use std::io::SeekFrom;
+use std::ops::Range;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio_util::compat::TokioAsyncReadCompatExt;
 
+/// The signature that marks the start of the End Of Central Directory record.
+const EOCD_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x05, 0x06];
+
+/// The size of the End Of Central Directory record excluding the trailing comment.
+const EOCD_SIZE: u64 = 22;
+
+/// The signature of the zip64 End Of Central Directory locator.
+const ZIP64_LOCATOR_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x06, 0x07];
+
+/// The size of the zip64 End Of Central Directory locator, which directly precedes the End Of
+/// Central Directory record.
+const ZIP64_LOCATOR_SIZE: u64 = 20;
+
+/// The signature of the zip64 End Of Central Directory record.
+const ZIP64_EOCD_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x06, 0x06];
+
+/// The size of the zip64 End Of Central Directory record excluding the extensible data sector.
+const ZIP64_EOCD_SIZE: u64 = 56;
+
+/// The number of bytes at the end of the file that are requested first. This covers the End Of
+/// Central Directory record for all wheels that do not have an unusually large archive comment.
+const INITIAL_TAIL_SIZE: u64 = 8192;
+
 /// Reads the metadata from a wheel by only reading parts of the wheel zip.
 ///
 /// This function uses [`AsyncHttpRangeReader`] which allows reading parts of a file by performing
 /// http range requests. First the end of the file is read to index the central directory of the
 /// zip. This provides an index into the file which allows accessing the exact bytes that contain
 /// the METADATA file.
+///
+/// The End Of Central Directory record is read first to determine the exact location and size of
+/// the central directory, so that it can be fetched in a single request regardless of how many
+/// files the wheel contains.
 pub(crate) async fn lazy_read_wheel_metadata(
     name: &WheelFilename,
     stream: &mut AsyncHttpRangeReader,
 ) -> Result<(Vec<u8>, WheelCoreMetadata), WheelVitalsError> {
-    // Make sure we have the back part of the stream.
-    // Best guess for the central directory size inside the zip
-    const CENTRAL_DIRECTORY_SIZE: u64 = 16384;
-    // Because the zip index is at the back
-    stream
-        .prefetch(stream.len().saturating_sub(CENTRAL_DIRECTORY_SIZE)..stream.len())
-        .await;
+    // Make sure we have the entire central directory of the zip, which is located at the back.
+    let central_directory = locate_central_directory(stream).await?;
+    stream.prefetch(central_directory.start..stream.len()).await;
 
     // Construct a zip reader to uses the stream.
     let mut reader = ZipFileReader::new(stream.compat())
@@ -85,3 +111,310 @@ pub(crate) async fn lazy_read_wheel_metadata(
 
     Ok((contents, metadata))
 }
+
+/// The location of the central directory as described by the End Of Central Directory record.
+#[derive(Debug, PartialEq, Eq)]
+enum CentralDirectoryLocation {
+    /// The byte range of the central directory.
+    Range(Range<u64>),
+
+    /// The archive is a zip64 archive, the central directory is described by the zip64 End Of
+    /// Central Directory record at the given offset.
+    Zip64(u64),
+}
+
+/// Locates the central directory of the zip archive by reading the End Of Central Directory
+/// record at the end of the stream. Returns the byte range of the central directory.
+async fn locate_central_directory(
+    stream: &mut AsyncHttpRangeReader,
+) -> std::io::Result<Range<u64>> {
+    let len = stream.len();
+    let max_tail_size = (EOCD_SIZE + u16::MAX as u64).min(len);
+    let mut tail_size = INITIAL_TAIL_SIZE.min(len);
+    loop {
+        let tail_start = len - tail_size;
+        let tail = read_range(stream, tail_start..len).await?;
+
+        if let Some(eocd_offset) = find_eocd(&tail) {
+            return match parse_eocd(&tail, eocd_offset, tail_start)? {
+                CentralDirectoryLocation::Range(range) => Ok(range),
+                CentralDirectoryLocation::Zip64(offset) => {
+                    let record = read_range(stream, offset..offset + ZIP64_EOCD_SIZE).await?;
+                    parse_zip64_eocd(&record, offset)
+                }
+            };
+        }
+
+        // The record might be preceded by a large comment, retry with the largest possible tail.
+        if tail_size >= max_tail_size {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "could not find the end of central directory record",
+            ));
+        }
+        tail_size = max_tail_size;
+    }
+}
+
+/// Fetches and reads the given range of bytes from the stream.
+async fn read_range(
+    stream: &mut AsyncHttpRangeReader,
+    range: Range<u64>,
+) -> std::io::Result<Vec<u8>> {
+    if range.end > stream.len() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "range exceeds the length of the file",
+        ));
+    }
+    stream.prefetch(range.clone()).await;
+    let mut bytes = vec![0; (range.end - range.start) as usize];
+    stream.seek(SeekFrom::Start(range.start)).await?;
+    stream.read_exact(&mut bytes).await?;
+    Ok(bytes)
+}
+
+/// Returns the offset of the End Of Central Directory record in the given tail of a zip archive.
+fn find_eocd(tail: &[u8]) -> Option<usize> {
+    (0..=tail.len().checked_sub(EOCD_SIZE as usize)?)
+        .rev()
+        .find(|&offset| tail[offset..].starts_with(&EOCD_SIGNATURE))
+}
+
+/// Parses the End Of Central Directory record found at `eocd_offset` in `tail`. `tail_start` is the
+/// offset of the tail in the archive.
+fn parse_eocd(
+    tail: &[u8],
+    eocd_offset: usize,
+    tail_start: u64,
+) -> std::io::Result<CentralDirectoryLocation> {
+    let eocd = &tail[eocd_offset..];
+    let entries = u16::from_le_bytes(eocd[10..12].try_into().unwrap());
+    let size = u32::from_le_bytes(eocd[12..16].try_into().unwrap());
+    let offset = u32::from_le_bytes(eocd[16..20].try_into().unwrap());
+
+    // If any of the values do not fit the record, they are stored in the zip64 record instead.
+    if entries == u16::MAX || size == u32::MAX || offset == u32::MAX {
+        let locator = eocd_offset
+            .checked_sub(ZIP64_LOCATOR_SIZE as usize)
+            .map(|start| &tail[start..eocd_offset])
+            .ok_or_else(|| zip64_error("the zip64 end of central directory locator is missing"))?;
+        return parse_zip64_locator(locator).map(CentralDirectoryLocation::Zip64);
+    }
+
+    let (size, offset) = (size as u64, offset as u64);
+    if offset + size > tail_start + eocd_offset as u64 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "the central directory overlaps the end of central directory record",
+        ));
+    }
+    Ok(CentralDirectoryLocation::Range(offset..offset + size))
+}
+
+/// Parses the zip64 End Of Central Directory locator and returns the offset of the zip64 End Of
+/// Central Directory record.
+fn parse_zip64_locator(locator: &[u8]) -> std::io::Result<u64> {
+    if !locator.starts_with(&ZIP64_LOCATOR_SIGNATURE) {
+        return Err(zip64_error(
+            "invalid zip64 end of central directory locator signature",
+        ));
+    }
+    Ok(u64::from_le_bytes(locator[8..16].try_into().unwrap()))
+}
+
+/// Parses the zip64 End Of Central Directory record located at `record_offset` and returns the
+/// range of the central directory.
+fn parse_zip64_eocd(record: &[u8], record_offset: u64) -> std::io::Result<Range<u64>> {
+    if record.len() < ZIP64_EOCD_SIZE as usize || !record.starts_with(&ZIP64_EOCD_SIGNATURE) {
+        return Err(zip64_error(
+            "invalid zip64 end of central directory record signature",
+        ));
+    }
+    let size = u64::from_le_bytes(record[40..48].try_into().unwrap());
+    let offset = u64::from_le_bytes(record[48..56].try_into().unwrap());
+    match offset.checked_add(size) {
+        Some(end) if end <= record_offset => Ok(offset..end),
+        _ => Err(zip64_error(
+            "the central directory overlaps the zip64 end of central directory record",
+        )),
+    }
+}
+
+fn zip64_error(msg: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("failed to parse zip64 archive: {msg}"),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::ArtifactRequest;
+    use crate::types::PackageName;
+    use crate::utils::get_package_db;
+    use async_http_range_reader::CheckSupportMethod;
+    use reqwest::header::HeaderMap;
+    use reqwest::Client;
+    use reqwest_middleware::ClientWithMiddleware;
+    use std::io::Write;
+
+    #[test]
+    fn test_locate_eocd() {
+        let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        archive
+            .start_file("foo.txt", zip::write::FileOptions::default())
+            .unwrap();
+        archive.write_all(b"foo").unwrap();
+        archive.set_comment("a comment");
+        let bytes = archive.finish().unwrap().into_inner();
+
+        let eocd_offset = find_eocd(&bytes).unwrap();
+        assert_eq!(
+            eocd_offset,
+            bytes.len() - EOCD_SIZE as usize - "a comment".len()
+        );
+
+        let CentralDirectoryLocation::Range(central_directory) =
+            parse_eocd(&bytes, eocd_offset, 0).unwrap()
+        else {
+            panic!("expected a regular zip archive");
+        };
+        assert_eq!(central_directory.end, eocd_offset as u64);
+        assert!(bytes[central_directory.start as usize..].starts_with(b"PK\x01\x02"));
+
+        assert_eq!(find_eocd(&bytes[..eocd_offset]), None);
+    }
+
+    /// Converts a regular zip archive into a zip64 archive by moving the central directory
+    /// information into a zip64 End Of Central Directory record.
+    fn to_zip64(mut bytes: Vec<u8>) -> Vec<u8> {
+        let eocd_offset = find_eocd(&bytes).unwrap();
+        let eocd = bytes.split_off(eocd_offset);
+        let entries = u16::from_le_bytes(eocd[10..12].try_into().unwrap()) as u64;
+        let size = u32::from_le_bytes(eocd[12..16].try_into().unwrap()) as u64;
+        let offset = u32::from_le_bytes(eocd[16..20].try_into().unwrap()) as u64;
+
+        // zip64 End Of Central Directory record
+        let record_offset = bytes.len() as u64;
+        bytes.extend_from_slice(&ZIP64_EOCD_SIGNATURE);
+        bytes.extend_from_slice(&(ZIP64_EOCD_SIZE - 12).to_le_bytes());
+        bytes.extend_from_slice(&45u16.to_le_bytes());
+        bytes.extend_from_slice(&45u16.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&entries.to_le_bytes());
+        bytes.extend_from_slice(&entries.to_le_bytes());
+        bytes.extend_from_slice(&size.to_le_bytes());
+        bytes.extend_from_slice(&offset.to_le_bytes());
+
+        // zip64 End Of Central Directory locator
+        bytes.extend_from_slice(&ZIP64_LOCATOR_SIGNATURE);
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&record_offset.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+
+        // End Of Central Directory record that defers to the zip64 record
+        bytes.extend_from_slice(&EOCD_SIGNATURE);
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&u16::MAX.to_le_bytes());
+        bytes.extend_from_slice(&u16::MAX.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes
+    }
+
+    #[tokio::test]
+    async fn test_locate_zip64_eocd() {
+        let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        archive
+            .start_file(
+                "foo-1.0.dist-info/METADATA",
+                zip::write::FileOptions::default().large_file(true),
+            )
+            .unwrap();
+        archive
+            .write_all(b"Metadata-Version: 2.1\nName: foo\nVersion: 1.0\n")
+            .unwrap();
+        let bytes = to_zip64(archive.finish().unwrap().into_inner());
+
+        // Locate the central directory through the zip64 records
+        let eocd_offset = find_eocd(&bytes).unwrap();
+        let CentralDirectoryLocation::Zip64(record_offset) =
+            parse_eocd(&bytes, eocd_offset, 0).unwrap()
+        else {
+            panic!("expected a zip64 archive");
+        };
+        let record_end = (record_offset + ZIP64_EOCD_SIZE) as usize;
+        let central_directory =
+            parse_zip64_eocd(&bytes[record_offset as usize..record_end], record_offset).unwrap();
+        assert_eq!(central_directory.end, record_offset);
+        assert!(bytes[central_directory.start as usize..].starts_with(b"PK\x01\x02"));
+
+        // The zip reader used for lazy reading should be able to find the METADATA file
+        let reader = ZipFileReader::new(std::io::Cursor::new(bytes.clone()).compat())
+            .await
+            .unwrap();
+        let file_names = reader
+            .file()
+            .entries()
+            .iter()
+            .filter_map(|entry| Some((entry, entry.filename().as_str().ok()?)));
+        let name =
+            WheelFilename::from_filename("foo-1.0-py3-none-any.whl", &"foo".parse().unwrap())
+                .unwrap();
+        let (_, dist_info_prefix) = find_dist_info_metadata(&name, file_names).unwrap();
+        assert_eq!(dist_info_prefix, "foo-1.0");
+
+        // A corrupt locator should result in an error
+        let mut corrupt = bytes;
+        let locator_offset = eocd_offset - ZIP64_LOCATOR_SIZE as usize;
+        corrupt[locator_offset] = 0;
+        assert!(parse_eocd(&corrupt, eocd_offset, 0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lazy_read_many_files() {
+        // botocore wheels contain thousands of files which results in a central directory that is
+        // much larger than a single request.
+        let (package_db, _dir) = get_package_db();
+        let name = "botocore".parse::<PackageName>().unwrap();
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::FromIndex(name.into()))
+            .await
+            .unwrap();
+        let version = "1.34.0".parse().unwrap();
+        let artifact_info = artifacts
+            .iter()
+            .flat_map(|(_, artifacts)| artifacts.iter())
+            .find(|a| {
+                a.filename
+                    .as_wheel()
+                    .is_some_and(|wheel| wheel.version == version)
+            })
+            .unwrap();
+        let wheel_name = artifact_info.filename.as_wheel().unwrap();
+
+        let (mut reader, _) = AsyncHttpRangeReader::new(
+            ClientWithMiddleware::from(Client::new()),
+            artifact_info.url.clone(),
+            CheckSupportMethod::Head,
+            HeaderMap::default(),
+        )
+        .await
+        .unwrap();
+
+        let (_, metadata) = lazy_read_wheel_metadata(wheel_name, &mut reader)
+            .await
+            .unwrap();
+        assert_eq!(metadata.name.as_str(), "botocore");
+
+        // One request for the tail, one for the rest of the central directory and one for the
+        // METADATA file.
+        let ranges = reader.requested_ranges().await;
+        assert!(ranges.len() <= 3, "requested {} ranges", ranges.len());
+    }
+}
@@ -8,10 +8,18 @@ pub mod html;
 mod http;
 mod lazy_metadata;
 mod package_database;
+mod package_search;
 mod package_sources;
+mod proxy;
+mod resolution_cache;
 
-pub use package_database::{ArtifactRequest, CheckAvailablePackages, PackageDb};
-pub use package_sources::{PackageSources, PackageSourcesBuilder};
+pub use package_database::{ArtifactRequest, CheckAvailablePackages, LocalWheel, PackageDb};
+pub use package_sources::{
+    HostNotAllowedError, IndexStrategy, PackageSources, PackageSourcesBuilder, DEFAULT_CONCURRENCY,
+};
+
+pub use proxy::{ProxyConfig, ProxyConfigError};
 
 pub use self::http::CacheMode;
+pub(crate) use self::http::HttpRequestError;
 pub use html::parse_hash;
@@ -1,27 +1,36 @@
 use crate::artifacts::{SDist, STree, Wheel};
 use crate::index::file_store::FileStore;
 
-use crate::index::html::{parse_package_names_html, parse_project_info_html};
+use crate::index::html::{parse_project_info_html, PackageNamesParser};
 use crate::index::http::{CacheMode, Http, HttpRequestError};
-use crate::index::package_sources::PackageSources;
-use crate::resolve::PypiVersion;
+use crate::index::package_search::{normalize_index_name, normalize_prefix, SimilarNames};
+use crate::index::package_sources::{IndexStrategy, PackageSources};
+use crate::index::resolution_cache::ResolutionCache;
+use crate::python_env::WheelTags;
+use crate::resolve::solve_options::ResolveOptions;
+use crate::resolve::{PinnedPackage, PypiVersion};
 use crate::types::{
-    ArtifactInfo, ArtifactType, DirectUrlHashes, DirectUrlJson, DirectUrlSource, ProjectInfo,
-    STreeFilename, WheelCoreMetadata,
+    ArtifactHashes, ArtifactInfo, ArtifactName, ArtifactType, DirectUrlHashes, DirectUrlJson,
+    DirectUrlSource, DistInfoMetadata, PackageInfo, PackageName, ProjectInfo, STreeFilename,
+    Version, WheelCoreMetadata, Yanked,
 };
 
-use crate::wheel_builder::{WheelBuildError, WheelBuilder, WheelCache};
+use crate::utils::{redact_url, StreamingOrLocal};
+use crate::wheel_builder::{ExtractedSDistCache, WheelBuildError, WheelBuilder, WheelCache};
 use crate::{
     types::ArtifactFromBytes, types::InnerAsArtifactName, types::NormalizedPackageName,
     types::WheelFilename,
 };
-use async_http_range_reader::{AsyncHttpRangeReader, CheckSupportMethod};
+use async_http_range_reader::AsyncHttpRangeReader;
 use async_recursion::async_recursion;
 use elsa::sync::FrozenMap;
-use futures::{pin_mut, stream, StreamExt};
+use fs_err as fs;
+use futures::{pin_mut, stream, Stream, StreamExt, TryFutureExt, TryStreamExt};
 use indexmap::IndexMap;
 use miette::{self, Diagnostic, IntoDiagnostic};
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use pep508_rs::{MarkerEnvironment, Requirement};
+use rattler_digest::{Sha256, Sha256Hash};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT_ENCODING, CONTENT_TYPE};
 use reqwest::Method;
 
 use reqwest::{header::CACHE_CONTROL, StatusCode};
@@ -31,6 +40,8 @@ use std::borrow::Borrow;
 use std::path::PathBuf;
 
 use itertools::Itertools;
+use parking_lot::Mutex;
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::{fmt::Display, io::Read, path::Path};
 
@@ -54,11 +65,25 @@ pub struct PackageDb {
     /// Cache to locally built wheels
     local_wheel_cache: WheelCache,
 
+    /// Cache of extracted sdists
+    extracted_sdist_cache: ExtractedSDistCache,
+
     /// Reference to the cache directory for all caches
     cache_dir: PathBuf,
 
     /// Option to that determines if we always want to check if there are new available artifacts
     check_available_artifacts: CheckAvailablePackages,
+
+    /// A cache of previous resolutions, see [`PackageDb::with_resolution_cache`].
+    resolution_cache: Option<ResolutionCache>,
+
+    /// Whether downloaded artifacts are stored in the http cache, see
+    /// [`PackageDb::with_artifact_caching`].
+    cache_artifacts: bool,
+
+    /// The versions of the packages whose metadata contains fields that are not supported by its
+    /// `Metadata-Version` and that have already been warned about.
+    warned_unsupported_fields: Mutex<HashSet<(PackageName, Version)>>,
 }
 
 /// Type of request to get from the `available_artifacts` function.
@@ -87,6 +112,22 @@ pub enum CheckAvailablePackages {
     UseServerTime,
 }
 
+/// A wheel from the local filesystem, see [`PackageDb::get_local_wheel`].
+pub struct LocalWheel {
+    /// The opened wheel
+    pub wheel: Wheel,
+
+    /// Information about the wheel as if it was referred to by a direct url
+    pub artifact_info: Arc<ArtifactInfo>,
+
+    /// The metadata of the wheel
+    pub metadata: WheelCoreMetadata,
+
+    /// The `direct_url.json` to write when installing the wheel, this can be passed to
+    /// [`crate::install::InstallWheelOptions::direct_url_json`].
+    pub direct_url_json: DirectUrlJson,
+}
+
 pub(crate) struct DirectUrlArtifactResponse {
     pub(crate) artifact_info: Arc<ArtifactInfo>,
     pub(crate) artifact_versions: VersionArtifacts,
@@ -110,6 +151,7 @@ impl PackageDb {
 
         let metadata_cache = FileStore::new(&cache_dir.join("metadata")).into_diagnostic()?;
         let local_wheel_cache = WheelCache::new(cache_dir.join("local_wheels"));
+        let extracted_sdist_cache = ExtractedSDistCache::new(cache_dir.join("extracted_sdists"));
 
         Ok(Self {
             http,
@@ -117,21 +159,157 @@ impl PackageDb {
             metadata_cache,
             artifacts: Default::default(),
             local_wheel_cache,
+            extracted_sdist_cache,
             cache_dir: cache_dir.to_owned(),
             check_available_artifacts,
+            resolution_cache: None,
+            cache_artifacts: true,
+            warned_unsupported_fields: Default::default(),
         })
     }
 
+    /// Enables caching of resolutions. The result of [`crate::resolve::resolve`] is stored in the
+    /// cache directory keyed by the requirements, the marker environment, the compatible tags, the
+    /// resolve options and the `index_generation`. When the same resolution is requested again
+    /// the pinned packages are read from the cache after checking that all of their artifacts are
+    /// still available in the index.
+    ///
+    /// The `index_generation` must change whenever the contents of the index change, otherwise
+    /// newly released versions are not picked up. A good candidate is the `ETag` of the simple
+    /// index or the time of the last synchronization of a mirror.
+    pub fn with_resolution_cache(
+        mut self,
+        index_generation: impl Into<String>,
+    ) -> miette::Result<Self> {
+        self.resolution_cache = Some(
+            ResolutionCache::new(&self.cache_dir.join("resolutions"), index_generation.into())
+                .into_diagnostic()?,
+        );
+        Ok(self)
+    }
+
+    /// Determines whether downloaded wheels and sdists are stored in the http cache, which is the
+    /// default.
+    ///
+    /// For one-shot installs caching the artifacts only doubles the disk io. When caching is
+    /// disabled the download is spooled into a temporary file instead, which is handed to the
+    /// [`Wheel`] directly. Only small artifacts are kept in memory, larger artifacts are written
+    /// to disk while they are downloaded.
+    pub fn with_artifact_caching(mut self, enabled: bool) -> Self {
+        self.cache_artifacts = enabled;
+        self
+    }
+
     /// Returns the cache directory
     pub fn cache_dir(&self) -> &Path {
         &self.cache_dir
     }
 
+    /// Returns the sources from which packages are obtained
+    pub fn sources(&self) -> &PackageSources {
+        &self.sources
+    }
+
     /// Returns the local wheel cache
     pub fn local_wheel_cache(&self) -> &WheelCache {
         &self.local_wheel_cache
     }
 
+    /// Returns the cache of extracted sdists
+    pub fn extracted_sdist_cache(&self) -> &ExtractedSDistCache {
+        &self.extracted_sdist_cache
+    }
+
+    /// Returns the key under which a resolution is stored in the resolution cache, or `None` if the
+    /// cache is disabled or the resolution cannot be cached.
+    pub(crate) fn resolution_cache_key(
+        &self,
+        requirements: &[&Requirement],
+        env_markers: &MarkerEnvironment,
+        compatible_tags: Option<&WheelTags>,
+        options: &ResolveOptions,
+    ) -> Option<Vec<u8>> {
+        self.resolution_cache.as_ref()?.key(
+            self.sources.describe(),
+            requirements,
+            env_markers,
+            compatible_tags,
+            options,
+        )
+    }
+
+    /// Reads a resolution from the resolution cache. The artifacts of the cached packages are
+    /// looked up in the index, if any of them is no longer available the entry is considered stale
+    /// and `None` is returned.
+    #[async_recursion]
+    pub(crate) async fn cached_resolution(&self, key: &[u8]) -> Option<Vec<PinnedPackage>> {
+        let cached = self.resolution_cache.as_ref()?.get(key).await?;
+
+        let mut packages = Vec::with_capacity(cached.packages.len());
+        for package in cached.packages {
+            let available = match self
+                .available_artifacts(ArtifactRequest::FromIndex(package.name.clone()))
+                .await
+            {
+                Ok(available) => available,
+                Err(err) => {
+                    tracing::debug!(
+                        "ignoring cached resolution, failed to fetch artifacts of {}: {err}",
+                        package.name.as_str()
+                    );
+                    return None;
+                }
+            };
+            let artifacts_of_version =
+                available
+                    .iter()
+                    .find_map(|(version, artifacts)| match version {
+                        PypiVersion::Version { version, .. } if version == &package.version => {
+                            Some(artifacts)
+                        }
+                        _ => None,
+                    })?;
+
+            let mut artifacts = Vec::with_capacity(package.artifacts.len());
+            for filename in &package.artifacts {
+                let Some(artifact) = artifacts_of_version
+                    .iter()
+                    .find(|artifact| &artifact.filename.to_string() == filename)
+                else {
+                    tracing::debug!(
+                        "ignoring cached resolution, {filename} is no longer available"
+                    );
+                    return None;
+                };
+                artifacts.push(artifact.clone());
+            }
+
+            packages.push(PinnedPackage {
+                name: package.name,
+                version: package.version,
+                url: None,
+                extras: package.extras.into_iter().collect(),
+                artifacts,
+                metadata: None,
+            });
+        }
+
+        Some(packages)
+    }
+
+    /// Stores a resolution in the resolution cache. Resolutions that contain packages from direct
+    /// urls are not stored. Failures are logged and otherwise ignored.
+    pub(crate) async fn cache_resolution(&self, key: &[u8], packages: &[PinnedPackage]) {
+        if packages.iter().any(|package| package.url.is_some()) {
+            return;
+        }
+        if let Some(cache) = &self.resolution_cache {
+            if let Err(err) = cache.put(key, packages).await {
+                tracing::warn!("failed to store resolution in the cache: {err}");
+            }
+        }
+    }
+
     /// Downloads and caches information about available artifacts of a package from the index.
     pub async fn available_artifacts(
         &self,
@@ -150,9 +328,19 @@ impl PackageDb {
                     .into_iter()
                     .map(|url| url.join(&format!("{}/", p.as_str())).expect("invalid url"))
                     .collect_vec();
+                // The responses are processed in the order of the indices, this ensures that if
+                // multiple indices serve the same file the one from the first index is used. If
+                // only the first index that has the package is used, the indices are queried one
+                // by one so lower priority indices never see the request.
+                let first_match = self.sources.index_strategy() == IndexStrategy::FirstMatch;
+                let concurrency = if first_match {
+                    1
+                } else {
+                    self.sources.concurrency()
+                };
                 let request_iter = stream::iter(urls)
                     .map(|url| fetch_simple_api(&http, url, self.check_available_artifacts))
-                    .buffer_unordered(10)
+                    .buffered(concurrency)
                     .filter_map(|result| async { result.transpose() });
 
                 pin_mut!(request_iter);
@@ -161,7 +349,7 @@ impl PackageDb {
                 let mut result = VersionArtifacts::default();
                 while let Some(response) = request_iter.next().await {
                     for artifact in response?.files {
-                        result
+                        let artifacts = result
                             .entry(PypiVersion::Version {
                                 version: artifact.filename.version().clone(),
                                 package_allows_prerelease: artifact
@@ -169,13 +357,25 @@ impl PackageDb {
                                     .version()
                                     .any_prerelease(),
                             })
-                            .or_default()
-                            .push(Arc::new(artifact));
+                            .or_default();
+
+                        if artifacts
+                            .iter()
+                            .any(|existing| is_same_artifact(existing, &artifact))
+                        {
+                            continue;
+                        }
+                        artifacts.push(Arc::new(artifact));
+                    }
+
+                    if first_match {
+                        break;
                     }
                 }
 
                 // Sort the artifact infos by name, this is just to have a consistent order and make
-                // the resolution output consistent.
+                // the resolution output consistent. The sort is stable so artifacts with the same
+                // name remain in the order of the indices.
                 for artifact_infos in result.values_mut() {
                     artifact_infos.sort_by(|a, b| a.filename.cmp(&b.filename));
                 }
@@ -196,12 +396,35 @@ impl PackageDb {
         }
     }
 
-    /// Returns the metadata from a set of artifacts. This function assumes that metadata is
-    /// consistent for all artifacts of a single version.
-    pub async fn get_metadata<'a, A: Borrow<ArtifactInfo>>(
+    /// Returns the versions of a package on the index together with their artifacts, sorted from
+    /// the highest to the lowest version. This is an owned form of
+    /// [`PackageDb::available_artifacts`].
+    ///
+    /// Versions of which all artifacts are yanked are left out unless `include_yanked` is set. The
+    /// yanked artifacts of other versions are always included, see [`ArtifactInfo::yanked`].
+    pub async fn versions(
+        &self,
+        name: NormalizedPackageName,
+        include_yanked: bool,
+    ) -> miette::Result<Vec<(PypiVersion, Vec<Arc<ArtifactInfo>>)>> {
+        let artifacts = self
+            .available_artifacts(ArtifactRequest::FromIndex(name))
+            .await?;
+        Ok(artifacts
+            .iter()
+            .filter(|(_, artifacts)| {
+                include_yanked || artifacts.iter().any(|artifact| !artifact.yanked.yanked)
+            })
+            .map(|(version, artifacts)| (version.clone(), artifacts.clone()))
+            .collect())
+    }
+
+    /// Returns the metadata from a set of artifacts like [`PackageDb::get_metadata`] but only
+    /// looks at the metadata and artifacts that are already cached. Returns `None` if the metadata
+    /// cannot be read without hitting the network or building an sdist.
+    pub async fn get_cached_metadata<'a, A: Borrow<ArtifactInfo>>(
         &self,
         artifacts: &'a [A],
-        wheel_builder: Option<&Arc<WheelBuilder>>,
     ) -> miette::Result<Option<(&'a A, WheelCoreMetadata)>> {
         // Check if we already have information about any of the artifacts cached.
         // Return if we do
@@ -217,7 +440,52 @@ impl PackageDb {
         // Apparently we dont have any metadata cached yet.
         // Next up check if we have downloaded any artifacts but do not have the metadata stored yet
         // In this case we can just return it
-        let result = self.metadata_for_cached_artifacts(artifacts).await?;
+        self.metadata_for_cached_artifacts(artifacts).await
+    }
+
+    /// Returns the metadata from a set of artifacts. This function assumes that metadata is
+    /// consistent for all artifacts of a single version.
+    pub async fn get_metadata<'a, A: Borrow<ArtifactInfo>>(
+        &self,
+        artifacts: &'a [A],
+        wheel_builder: Option<&Arc<WheelBuilder>>,
+    ) -> miette::Result<Option<(&'a A, WheelCoreMetadata)>> {
+        let result = self.find_metadata(artifacts, wheel_builder).await?;
+        if let Some((_, metadata)) = &result {
+            self.warn_unsupported_fields(metadata);
+        }
+        Ok(result)
+    }
+
+    /// Warns about the fields of the metadata that are not supported by its `Metadata-Version`.
+    /// The metadata of a package is requested many times, so the warning is only logged once per
+    /// version of a package.
+    fn warn_unsupported_fields(&self, metadata: &WheelCoreMetadata) {
+        if metadata.unsupported_fields.is_empty()
+            || !self
+                .warned_unsupported_fields
+                .lock()
+                .insert((metadata.name.clone(), metadata.version.clone()))
+        {
+            return;
+        }
+
+        tracing::warn!(
+            "the metadata of {} {} contains the fields {} which are not supported by its Metadata-Version {}",
+            metadata.name.as_source_str(),
+            metadata.version,
+            metadata.unsupported_fields.join(", "),
+            metadata.metadata_version.0
+        );
+    }
+
+    /// Finds the metadata from a set of artifacts, see [`PackageDb::get_metadata`].
+    async fn find_metadata<'a, A: Borrow<ArtifactInfo>>(
+        &self,
+        artifacts: &'a [A],
+        wheel_builder: Option<&Arc<WheelBuilder>>,
+    ) -> miette::Result<Option<(&'a A, WheelCoreMetadata)>> {
+        let result = self.get_cached_metadata(artifacts).await?;
         if result.is_some() {
             return Ok(result);
         }
@@ -231,8 +499,14 @@ impl PackageDb {
             return Ok(result);
         }
 
-        // No wheels found with metadata, try to get metadata from sdists
-        // by building them or using the appropriate hooks
+        // No wheels found with metadata, check if the index advertises the metadata of any of the
+        // sdists. This avoids downloading and building the sdist.
+        let result = self.get_pep658_metadata_sdists(artifacts).await;
+        if result.is_some() {
+            return Ok(result);
+        }
+
+        // Try to get metadata from sdists by building them or using the appropriate hooks
         if let Some(wheel_builder) = wheel_builder {
             let sdist = self.get_metadata_sdists(artifacts, wheel_builder).await?;
             if sdist.is_some() {
@@ -249,6 +523,45 @@ impl PackageDb {
         Ok(None)
     }
 
+    /// Concurrently warms the caches for the given packages. For each package the available
+    /// artifacts are fetched and the metadata of the newest version is retrieved. This allows a
+    /// caller to fetch the metadata of (for instance) the initial requirements before invoking the
+    /// solver, which otherwise requests metadata one package at a time.
+    ///
+    /// Prefetching is best-effort, failures are logged and otherwise ignored. They will surface
+    /// again when the information is actually requested.
+    pub async fn prefetch_metadata(&self, names: &[NormalizedPackageName]) {
+        let prefetch = |name: NormalizedPackageName| async move {
+            let artifacts = self
+                .available_artifacts(ArtifactRequest::FromIndex(name.clone()))
+                .await?;
+
+            // Artifacts are sorted in descending order, so prefer the newest stable release.
+            let newest = artifacts
+                .iter()
+                .find(|(version, _)| !version.any_prerelease())
+                .or_else(|| artifacts.first());
+
+            if let Some((_, artifacts)) = newest {
+                self.get_metadata(artifacts, None).await?;
+            }
+
+            Ok::<_, miette::Report>(())
+        };
+
+        let requests = stream::iter(names.iter().cloned())
+            .map(|name| async move { (name.clone(), prefetch(name).await) })
+            .buffer_unordered(self.sources.concurrency());
+
+        pin_mut!(requests);
+
+        while let Some((name, result)) = requests.next().await {
+            if let Err(err) = result {
+                tracing::debug!("failed to prefetch metadata for {}: {err}", name.as_str());
+            }
+        }
+    }
+
     /// Opens the specified artifact info. Downloads the artifact data from the remote location if
     /// the information is not already cached.
     #[async_recursion]
@@ -257,16 +570,17 @@ impl PackageDb {
         artifact_info: &ArtifactInfo,
         builder: Option<Arc<WheelBuilder>>,
     ) -> miette::Result<(Wheel, Option<DirectUrlJson>)> {
-        // TODO: add support for this currently there are not saved
+        // Artifacts that are downloaded from a direct url are stored in the artifacts cache, see
+        // `download_to_cache`.
         if artifact_info.is_direct_url {
             if let Some(builder) = builder {
-                let response = super::direct_url::fetch_artifact_and_metadata_by_direct_url(
-                    &self.http,
-                    artifact_info.filename.distribution_name(),
-                    artifact_info.url.clone(),
-                    &builder,
-                )
-                .await?;
+                let response = self
+                    .fetch_direct_url_artifact(
+                        artifact_info.filename.distribution_name(),
+                        artifact_info.url.clone(),
+                        &builder,
+                    )
+                    .await?;
 
                 let whl = match response.artifact {
                     ArtifactType::Wheel(wheel) => wheel,
@@ -288,7 +602,7 @@ impl PackageDb {
         if artifact_info.is::<SDist>() {
             if let Some(builder) = builder {
                 let sdist = self
-                    .get_cached_artifact::<SDist>(artifact_info, CacheMode::Default)
+                    .get_cached_artifact::<SDist>(artifact_info, self.artifact_cache_mode())
                     .await?;
 
                 let whl = builder.build_wheel(&sdist).await.into_diagnostic()?;
@@ -322,11 +636,154 @@ impl PackageDb {
 
         // Otherwise just retrieve the wheel
         let cached_whl = self
-            .get_cached_artifact::<Wheel>(artifact_info, CacheMode::Default)
+            .get_cached_artifact::<Wheel>(artifact_info, self.artifact_cache_mode())
             .await?;
         Ok((cached_whl, None))
     }
 
+    /// Opens a wheel from the local filesystem without consulting the index. `location` is either a
+    /// path to a `.whl` file or a `file://` url. The name of the package is derived from the
+    /// filename of the wheel.
+    ///
+    /// The metadata of the wheel is stored in the metadata cache so it does not have to be
+    /// extracted again when the wheel is referred to by a direct url during a resolution.
+    ///
+    /// ```no_run
+    /// # async fn example(package_db: &rattler_installs_packages::index::PackageDb, venv: &rattler_installs_packages::python_env::VEnv) -> miette::Result<()> {
+    /// use miette::IntoDiagnostic;
+    /// use rattler_installs_packages::install::InstallWheelOptions;
+    ///
+    /// let local_wheel = package_db.get_local_wheel("./foo-1.0-py3-none-any.whl").await?;
+    /// venv.install_wheel(
+    ///     &local_wheel.wheel,
+    ///     &InstallWheelOptions {
+    ///         direct_url_json: Some(local_wheel.direct_url_json),
+    ///         ..Default::default()
+    ///     },
+    /// )
+    /// .into_diagnostic()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_local_wheel(&self, location: &str) -> miette::Result<LocalWheel> {
+        let path = if location.starts_with("file://") {
+            Url::parse(location)
+                .into_diagnostic()?
+                .to_file_path()
+                .map_err(|_| miette::miette!("'{location}' is not a valid file url"))?
+        } else {
+            PathBuf::from(location)
+        };
+        let path = fs::canonicalize(&path).into_diagnostic()?;
+
+        // The name of the distribution is the first component of the filename
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| name.ends_with(".whl"))
+            .and_then(|name| name.split('-').next())
+            .ok_or_else(|| miette::miette!("'{}' is not a wheel file", path.display()))?;
+        let name: NormalizedPackageName = name
+            .parse::<PackageName>()
+            .map_err(|e| miette::miette!("invalid package name in '{}': {e}", path.display()))?
+            .into();
+
+        let wheel = Wheel::from_path(&path, &name)?;
+        let (metadata_bytes, metadata) = wheel.metadata().into_diagnostic()?;
+        let sha256 = rattler_digest::compute_file_digest::<Sha256>(&path).into_diagnostic()?;
+
+        let url = Url::from_file_path(&path)
+            .map_err(|_| miette::miette!("cannot convert '{}' to a url", path.display()))?;
+        let artifact_info = Arc::new(ArtifactInfo {
+            filename: ArtifactName::Wheel(wheel.name.clone()),
+            url: url.clone(),
+            is_direct_url: true,
+            hashes: Some(ArtifactHashes {
+                sha256: Some(sha256),
+            }),
+            requires_python: metadata.requires_python.clone(),
+            dist_info_metadata: DistInfoMetadata::default(),
+            yanked: Yanked::default(),
+        });
+        self.put_metadata_in_cache(&artifact_info, &metadata_bytes)
+            .await?;
+
+        Ok(LocalWheel {
+            wheel,
+            artifact_info,
+            metadata,
+            direct_url_json: DirectUrlJson {
+                url,
+                source: DirectUrlSource::Archive {
+                    hashes: Some(DirectUrlHashes {
+                        sha256: format!("{sha256:x}"),
+                    }),
+                },
+            },
+        })
+    }
+
+    /// Downloads the artifact to the local cache directory and returns the path to the file. If
+    /// the [`ArtifactInfo`] contains a sha256 hash the downloaded file is verified against it, an
+    /// error is returned if the hashes don't match.
+    ///
+    /// If the artifact was already downloaded before, the cached file is returned without hitting
+    /// the network. Artifacts of a local index are not copied, the path of the file in the index
+    /// is returned instead.
+    pub async fn download_artifact(&self, artifact_info: &ArtifactInfo) -> miette::Result<PathBuf> {
+        let expected_hash = artifact_info.hashes.as_ref().and_then(|h| h.sha256);
+        if artifact_info.url.scheme() == "file" {
+            let path = file_url_to_path(&artifact_info.url)?;
+            if let Some(expected_hash) = expected_hash {
+                let digest =
+                    rattler_digest::compute_file_digest::<Sha256>(&path).into_diagnostic()?;
+                if digest != expected_hash {
+                    miette::bail!(
+                        "hash mismatch for '{}', expected {expected_hash:x} but got {digest:x}",
+                        artifact_info.filename
+                    );
+                }
+            }
+            return Ok(path);
+        }
+
+        self.sources.check_host_allowed(&artifact_info.url)?;
+        download_to_cache(
+            &self.http,
+            &self.sources,
+            &self.artifacts_dir(),
+            &artifact_info.url,
+            &artifact_info.filename.to_string(),
+            expected_hash,
+        )
+        .await
+    }
+
+    /// Fetches an artifact from a direct url, unless the host of the url is not allowed by the
+    /// package sources.
+    async fn fetch_direct_url_artifact<P: Into<NormalizedPackageName>>(
+        &self,
+        p: P,
+        url: Url,
+        wheel_builder: &Arc<WheelBuilder>,
+    ) -> miette::Result<DirectUrlArtifactResponse> {
+        self.sources.check_host_allowed(&url)?;
+        super::direct_url::fetch_artifact_and_metadata_by_direct_url(
+            &self.http,
+            &self.artifacts_dir(),
+            p,
+            url,
+            &self.sources,
+            wheel_builder,
+        )
+        .await
+    }
+
+    /// The directory in which downloaded artifacts are stored, see [`download_to_cache`].
+    fn artifacts_dir(&self) -> PathBuf {
+        self.cache_dir.join("artifacts")
+    }
+
     /// Get artifact directly from file, vcs, or url
     async fn get_artifact_by_direct_url<P: Into<NormalizedPackageName>>(
         &self,
@@ -340,13 +797,9 @@ impl PackageDb {
             return Ok(cached);
         }
 
-        let response = super::direct_url::fetch_artifact_and_metadata_by_direct_url(
-            &self.http,
-            p.clone(),
-            url,
-            wheel_builder,
-        )
-        .await?;
+        let response = self
+            .fetch_direct_url_artifact(p.clone(), url, wheel_builder)
+            .await?;
 
         self.put_metadata_in_cache(&response.artifact_info, &response.metadata.0)
             .await?;
@@ -468,13 +921,13 @@ impl PackageDb {
 
             let metadata = if ai.is_direct_url {
                 if let Some(wheel_builder) = wheel_builder {
-                    let response = super::direct_url::fetch_artifact_and_metadata_by_direct_url(
-                        &self.http,
-                        ai.filename.distribution_name(),
-                        ai.url.clone(),
-                        wheel_builder,
-                    )
-                    .await;
+                    let response = self
+                        .fetch_direct_url_artifact(
+                            ai.filename.distribution_name(),
+                            ai.url.clone(),
+                            wheel_builder,
+                        )
+                        .await;
                     match response {
                         Err(err) => Err(miette::miette!(err.to_string())),
                         Ok(response) => Ok(response.metadata),
@@ -485,7 +938,7 @@ impl PackageDb {
             } else {
                 // Otherwise download the entire artifact
                 let artifact = self
-                    .get_cached_artifact::<Wheel>(ai, CacheMode::Default)
+                    .get_cached_artifact::<Wheel>(ai, self.artifact_cache_mode())
                     .await?;
                 artifact.metadata().into_diagnostic()
             };
@@ -523,20 +976,20 @@ impl PackageDb {
         for ai in sdists {
             let artifact_info: &ArtifactInfo = ai.borrow();
             let metadata = if artifact_info.is_direct_url {
-                let response = super::direct_url::fetch_artifact_and_metadata_by_direct_url(
-                    &self.http,
-                    artifact_info.filename.distribution_name(),
-                    artifact_info.url.clone(),
-                    wheel_builder,
-                )
-                .await;
+                let response = self
+                    .fetch_direct_url_artifact(
+                        artifact_info.filename.distribution_name(),
+                        artifact_info.url.clone(),
+                        wheel_builder,
+                    )
+                    .await;
                 match response {
                     Err(err) => Err(WheelBuildError::Error(err.to_string())),
                     Ok(response) => Ok(response.metadata),
                 }
             } else {
                 let artifact = self
-                    .get_cached_artifact::<SDist>(artifact_info, CacheMode::Default)
+                    .get_cached_artifact::<SDist>(artifact_info, self.artifact_cache_mode())
                     .await?;
                 wheel_builder.get_sdist_metadata(&artifact).await
             };
@@ -564,6 +1017,33 @@ impl PackageDb {
         Ok(None)
     }
 
+    /// Try to retrieve the metadata of sdists for which the index advertises the metadata
+    /// separately (PEP 658). Failures are not fatal, the caller should fall back to building the
+    /// sdist.
+    async fn get_pep658_metadata_sdists<'a, A: Borrow<ArtifactInfo>>(
+        &self,
+        artifacts: &'a [A],
+    ) -> Option<(&'a A, WheelCoreMetadata)> {
+        let sdists = artifacts.iter().filter(|artifact_info| {
+            let ai = (*artifact_info).borrow();
+            ai.is::<SDist>() && ai.dist_info_metadata.available && !ai.is_direct_url
+        });
+
+        for artifact_info in sdists {
+            match self.get_pep658_metadata(artifact_info).await {
+                Ok(result) => return Some(result),
+                Err(err) => {
+                    tracing::warn!(
+                        "failed to retrieve metadata advertised for '{}': {err}, falling back to building the sdist",
+                        artifact_info.borrow().filename
+                    );
+                }
+            }
+        }
+
+        None
+    }
+
     async fn get_metadata_stree<'a, A: Borrow<ArtifactInfo>>(
         &self,
         artifacts: &'a [A],
@@ -587,13 +1067,13 @@ impl PackageDb {
                         artifact_info.filename
                     )
                 });
-            let response = super::direct_url::fetch_artifact_and_metadata_by_direct_url(
-                &self.http,
-                stree_name.distribution.clone(),
-                artifact_info.url.clone(),
-                wheel_builder,
-            )
-            .await;
+            let response = self
+                .fetch_direct_url_artifact(
+                    stree_name.distribution.clone(),
+                    artifact_info.url.clone(),
+                    wheel_builder,
+                )
+                .await;
 
             match response {
                 Ok(direct_response) => {
@@ -623,17 +1103,30 @@ impl PackageDb {
         &self,
         artifact_info: &ArtifactInfo,
     ) -> miette::Result<Option<WheelCoreMetadata>> {
-        tracing::info!(url=%artifact_info.url, "lazy reading artifact");
+        self.sources.check_host_allowed(&artifact_info.url)?;
+        tracing::info!(url=%redact_url(&artifact_info.url), "lazy reading artifact");
 
         // Check if the artifact is the same type as the info.
         let name = WheelFilename::try_as(&artifact_info.filename)
             .expect("the specified artifact does not refer to type requested to read");
 
-        // Construct an async reader
-        let Ok((mut reader, _)) = AsyncHttpRangeReader::new(
+        // Construct an async reader. The range requests are made to the url after redirects, which
+        // must be allowed as well.
+        let Ok(head_response) = AsyncHttpRangeReader::initial_head_request(
             self.http.client.clone(),
             artifact_info.url.clone(),
-            CheckSupportMethod::Head,
+            HeaderMap::default(),
+        )
+        .await
+        else {
+            return Ok(None);
+        };
+        let final_url = head_response.url().clone();
+        self.sources.check_host_allowed(&final_url)?;
+        let Ok(mut reader) = AsyncHttpRangeReader::from_head_response(
+            self.http.client.clone(),
+            head_response,
+            final_url,
             HeaderMap::default(),
         )
         .await
@@ -655,7 +1148,7 @@ impl PackageDb {
         Ok(None)
     }
 
-    /// Retrieve the PEP658 metadata for the given artifact.
+    /// Retrieve the PEP658 metadata for the given artifact, this can be either a wheel or an sdist.
     /// This assumes that the metadata is available in the repository
     /// This can be checked with the ArtifactInfo
     async fn get_pep658_metadata<'a, A: Borrow<ArtifactInfo>>(
@@ -663,46 +1156,123 @@ impl PackageDb {
         artifact_info: &'a A,
     ) -> miette::Result<(&'a A, WheelCoreMetadata)> {
         let ai = artifact_info.borrow();
+        self.sources.check_host_allowed(&ai.url)?;
 
-        // Check if the artifact is the same type as the info.
-        WheelFilename::try_as(&ai.filename)
-            .expect("the specified artifact does not refer to type requested to read");
-
-        // Turn into PEP658 compliant URL
+        // Turn into PEP658 compliant URL ({file_url}.metadata)
         let mut url = ai.url.clone();
-        url.set_path(&url.path().replace(".whl", ".whl.metadata"));
+        url.set_fragment(None);
+        url.set_path(&format!("{}.metadata", url.path()));
 
         let mut bytes = Vec::new();
-        self.http
-            .request(url, Method::GET, HeaderMap::default(), CacheMode::NoStore)
-            .await?
-            .into_body()
-            .read_to_end(&mut bytes)
-            .await
-            .into_diagnostic()?;
+        if url.scheme() == "file" {
+            bytes = fs::read(file_url_to_path(&url)?).into_diagnostic()?;
+        } else {
+            let response = self
+                .http
+                .request(
+                    url,
+                    Method::GET,
+                    artifact_request_headers(),
+                    CacheMode::NoStore,
+                )
+                .await?;
+            self.sources.check_response_host_allowed(&response)?;
+            response
+                .into_body()
+                .read_to_end(&mut bytes)
+                .await
+                .into_diagnostic()?;
+        }
 
-        let metadata = WheelCoreMetadata::try_from(bytes.as_slice()).into_diagnostic()?;
+        // The metadata of an sdist is its PKG-INFO, which is only as reliable as the PKG-INFO in
+        // the sdist itself
+        let metadata = if ai.is::<SDist>() {
+            PackageInfo::from_bytes(&bytes).and_then(PackageInfo::into_sdist_metadata)
+        } else {
+            WheelCoreMetadata::try_from(bytes.as_slice())
+        }
+        .into_diagnostic()?;
         self.put_metadata_in_cache(ai, &bytes).await?;
         Ok((artifact_info, metadata))
     }
 
     /// Get all package names in the index.
+    ///
+    /// This collects [`Self::stream_package_names`], prefer the stream if you don't need all
+    /// names.
     pub async fn get_package_names(&self) -> miette::Result<Vec<String>> {
-        let index_url = self.sources.default_index_url();
-        let response = self
-            .http
-            .request(
-                index_url,
-                Method::GET,
-                HeaderMap::default(),
-                CacheMode::Default,
-            )
-            .await?;
+        self.stream_package_names().try_collect().await
+    }
+
+    /// Stream the package names in the index. Names are yielded as soon as they are parsed, so
+    /// consumers that only look for a specific name don't have to wait for the whole index to be
+    /// downloaded. The listing of an index like PyPI is large, so it is not stored in the http
+    /// cache, which would require buffering the whole response first.
+    pub fn stream_package_names(&self) -> impl Stream<Item = miette::Result<String>> + '_ {
+        async move {
+            let index_url = self.sources.default_index_url();
+            let response = self
+                .http
+                .request(
+                    index_url,
+                    Method::GET,
+                    HeaderMap::default(),
+                    CacheMode::NoStore,
+                )
+                .await?;
+
+            Ok::<_, miette::Report>(package_names_from_body(response.into_body()))
+        }
+        .try_flatten_stream()
+    }
+
+    /// Returns at most `limit` package names from the index that start with `prefix`. Names are
+    /// normalized before they are compared, so `Flask` and `flask` match.
+    pub async fn search(
+        &self,
+        prefix: &str,
+        limit: usize,
+    ) -> miette::Result<Vec<NormalizedPackageName>> {
+        let prefix = normalize_prefix(prefix);
+        self.stream_package_names()
+            .try_filter_map(|name| {
+                let name =
+                    normalize_index_name(&name).filter(|name| name.as_str().starts_with(&prefix));
+                async move { Ok(name) }
+            })
+            .take(limit)
+            .try_collect()
+            .await
+    }
+
+    /// Returns at most `limit` package names from the index that are within a small edit
+    /// distance of `name`, closest first. This can be used to suggest the correct name when a
+    /// package cannot be found. The names are taken from the index listing, no request is made
+    /// per name.
+    pub async fn find_similar(
+        &self,
+        name: &str,
+        limit: usize,
+    ) -> miette::Result<Vec<NormalizedPackageName>> {
+        let mut similar = SimilarNames::new(name, limit);
+        let names = self.stream_package_names();
+        pin_mut!(names);
+        while let Some(candidate) = names.try_next().await? {
+            if let Some(candidate) = normalize_index_name(&candidate) {
+                similar.add(candidate);
+            }
+        }
+        Ok(similar.finish())
+    }
 
-        let mut bytes = response.into_body().into_local().await.into_diagnostic()?;
-        let mut source = String::new();
-        bytes.read_to_string(&mut source).into_diagnostic()?;
-        parse_package_names_html(&source)
+    /// Returns the [`CacheMode`] with which artifacts are downloaded, see
+    /// [`PackageDb::with_artifact_caching`].
+    fn artifact_cache_mode(&self) -> CacheMode {
+        if self.cache_artifacts {
+            CacheMode::Default
+        } else {
+            CacheMode::NoStore
+        }
     }
 
     /// Opens the specified artifact info. Depending on the specified `cache_mode`, downloads the
@@ -723,18 +1293,27 @@ impl PackageDb {
                 )
             });
 
+        // Artifacts of a local index are read from disk
+        if artifact_info.url.scheme() == "file" {
+            let file = fs::File::open(file_url_to_path(&artifact_info.url)?).into_diagnostic()?;
+            return A::from_bytes(name.clone(), Box::new(file));
+        }
+
         // Get the contents of the artifact
+        self.sources.check_host_allowed(&artifact_info.url)?;
         let artifact_bytes = self
             .http
             .request(
                 artifact_info.url.clone(),
                 Method::GET,
-                HeaderMap::default(),
+                artifact_request_headers(),
                 cache_mode,
             )
             .await?;
+        self.sources.check_response_host_allowed(&artifact_bytes)?;
 
-        // Turn the response into a seekable response.
+        // Turn the response into a seekable response. Responses that are not stored in the cache
+        // are spooled into a temporary file.
         let bytes = artifact_bytes
             .into_body()
             .into_local()
@@ -744,11 +1323,121 @@ impl PackageDb {
     }
 }
 
+/// Returns the headers for requests of artifacts and their `.metadata` files. These are requested
+/// without compression, like pip does. Some servers mark a `.tar.gz` as gzip encoded, the client
+/// would then return the decompressed tar instead of the file that was published.
+fn artifact_request_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("identity"));
+    headers
+}
+
+/// Downloads the artifact at `url` into `artifacts_dir` and returns the path to the file. If an
+/// `expected_hash` is given the downloaded file is verified against it, an error is returned if
+/// the hashes don't match. An error is also returned if the request is redirected to a host that
+/// `sources` does not allow.
+///
+/// Artifacts with a known hash are stored by that hash, otherwise by the hash of the url. If an
+/// artifact with a known hash was already downloaded before, the cached file is returned without
+/// hitting the network. The contents at a url without a hash might change, those artifacts are
+/// revalidated through the http cache instead.
+pub(crate) async fn download_to_cache(
+    http: &Http,
+    sources: &PackageSources,
+    artifacts_dir: &Path,
+    url: &Url,
+    filename: &str,
+    expected_hash: Option<Sha256Hash>,
+) -> miette::Result<PathBuf> {
+    let key = match expected_hash {
+        Some(hash) => format!("{hash:x}"),
+        None => format!(
+            "url-{:x}",
+            rattler_digest::compute_bytes_digest::<Sha256>(url.as_str())
+        ),
+    };
+    let directory = artifacts_dir.join(key);
+    let path = directory.join(filename);
+    if expected_hash.is_some() && path.is_file() {
+        tracing::debug!("using cached artifact {}", path.display());
+        return Ok(path);
+    }
+
+    let cache_mode = if expected_hash.is_some() {
+        CacheMode::NoStore
+    } else {
+        CacheMode::Default
+    };
+    let response = http
+        .request(
+            url.clone(),
+            Method::GET,
+            artifact_request_headers(),
+            cache_mode,
+        )
+        .await?;
+    sources.check_response_host_allowed(&response)?;
+    let mut body = response.into_body().into_local().await.into_diagnostic()?;
+
+    let filename = filename.to_owned();
+    tokio::task::spawn_blocking(move || {
+        fs::create_dir_all(&directory).into_diagnostic()?;
+
+        // Write to a temporary file first so that a partial download is never observed.
+        let mut file = tempfile::NamedTempFile::new_in(&directory).into_diagnostic()?;
+        let mut reader = rattler_digest::HashingReader::<_, Sha256>::new(&mut body);
+        std::io::copy(&mut reader, &mut file).into_diagnostic()?;
+        let (_, digest) = reader.finalize();
+
+        if let Some(expected_hash) = expected_hash {
+            if digest != expected_hash {
+                miette::bail!(
+                    "hash mismatch for '{filename}', expected {expected_hash:x} but got {digest:x}"
+                );
+            }
+        }
+
+        file.persist(&path).into_diagnostic()?;
+        Ok(path)
+    })
+    .await
+    .into_diagnostic()?
+}
+
+/// Converts a `file://` url to the path it refers to.
+fn file_url_to_path(url: &Url) -> miette::Result<PathBuf> {
+    url.to_file_path()
+        .map_err(|_| miette::miette!("'{}' does not refer to a local path", redact_url(url)))
+}
+
+/// Reads a simple API page from a local directory tree, e.g. an offline mirror of an index. Like
+/// a web server would, the `index.html` is read if the url refers to a directory. Relative links
+/// are resolved against the url. Returns `None` if the page does not exist.
+fn read_local_simple_api(url: &Url) -> miette::Result<Option<ProjectInfo>> {
+    let mut path = file_url_to_path(url)?;
+    if path.is_dir() {
+        path.push("index.html");
+    }
+    let html = match fs::read_to_string(&path) {
+        Ok(html) => html,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).into_diagnostic(),
+    };
+    parse_project_info_html(url, &html).map(Some)
+}
+
 async fn fetch_simple_api(
     http: &Http,
     url: Url,
     check_available_artifacts: CheckAvailablePackages,
 ) -> miette::Result<Option<ProjectInfo>> {
+    if url.scheme() == "file" {
+        return read_local_simple_api(&url);
+    }
+
+    // Note that `Accept-Encoding` is not set here. The http client negotiates compression itself
+    // and transparently decompresses the body, which means the cache stores the decompressed
+    // pages. Setting the header explicitly would disable the automatic decompression.
     let mut headers = HeaderMap::new();
     // If we always want to check if there are new available artifacts, we'll set the cache control
     // to max-age=0, so that we always get a non-cached server response.
@@ -786,30 +1475,111 @@ async fn fetch_simple_api(
         .await
         .into_diagnostic()?;
 
-    let content_type: mime::Mime = content_type.parse().into_diagnostic()?;
-    match (
-        content_type.type_().as_str(),
-        content_type.subtype().as_str(),
-    ) {
-        ("text", "html") => {
-            parse_project_info_html(&url, std::str::from_utf8(&bytes).into_diagnostic()?).map(Some)
-        }
-        _ => miette::bail!(
-            "simple API page expected Content-Type: text/html, but got {}",
-            &content_type
-        ),
+    // Some misconfigured mirrors send compressed pages without a `Content-Encoding` header, in
+    // which case the http client does not decompress them.
+    if bytes.starts_with(&GZIP_MAGIC) {
+        tracing::warn!(
+            "the simple API page at {} is gzip compressed but does not declare a Content-Encoding, decompressing it",
+            redact_url(&url)
+        );
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut decompressed)
+            .into_diagnostic()?;
+        bytes = decompressed;
+    }
+
+    let is_html = content_type.parse::<mime::Mime>().map_or(false, |mime| {
+        mime.type_() == "text" && mime.subtype() == "html"
+    });
+    if !is_html {
+        // Fall back to looking at the contents of the page
+        match sniff_simple_api_format(&bytes) {
+            Some(SimpleApiFormat::Html) => tracing::warn!(
+                "the simple API page at {} has Content-Type {content_type} but looks like html, parsing it as html",
+                redact_url(&url)
+            ),
+            Some(SimpleApiFormat::Json) => miette::bail!(
+                "the simple API page at {} looks like json, the json simple API is not supported",
+                redact_url(&url)
+            ),
+            None => miette::bail!(
+                "simple API page expected Content-Type: text/html, but got {}",
+                &content_type
+            ),
+        }
     }
+
+    parse_project_info_html(&url, std::str::from_utf8(&bytes).into_diagnostic()?).map(Some)
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::types::PackageName;
+/// The first bytes of a gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// The format of a simple API page as determined by [`sniff_simple_api_format`].
+#[derive(Debug, Eq, PartialEq)]
+enum SimpleApiFormat {
+    Html,
+    Json,
+}
+
+/// Determines the format of a simple API page from its contents, for servers that send a wrong
+/// `Content-Type`. Returns `None` if the page is neither html nor json.
+fn sniff_simple_api_format(bytes: &[u8]) -> Option<SimpleApiFormat> {
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+    match bytes.iter().find(|byte| !byte.is_ascii_whitespace())? {
+        b'<' => Some(SimpleApiFormat::Html),
+        b'{' => Some(SimpleApiFormat::Json),
+        _ => None,
+    }
+}
+
+/// Incrementally parses the package names from the body of a repository index.
+fn package_names_from_body(body: StreamingOrLocal) -> impl Stream<Item = miette::Result<String>> {
+    use tokio::io::AsyncReadExt;
+
+    let names = stream::try_unfold(
+        (body, PackageNamesParser::default(), false),
+        |(mut body, mut parser, done)| async move {
+            if done {
+                return Ok::<_, miette::Report>(None);
+            }
+
+            let mut buf = vec![0u8; 64 * 1024];
+            let bytes_read = match &mut body {
+                StreamingOrLocal::Streaming(reader) => reader.read(&mut buf).await,
+                StreamingOrLocal::Local(reader) => reader.read(&mut buf),
+            }
+            .into_diagnostic()?;
+
+            let names = parser.feed(&buf[..bytes_read]);
+            Ok(Some((
+                stream::iter(names.into_iter().map(Ok::<_, miette::Report>)),
+                (body, parser, bytes_read == 0),
+            )))
+        },
+    );
+    names.try_flatten()
+}
+
+/// Returns true if two artifacts refer to the same file. Mirrors commonly serve the exact same
+/// files and the same wheel is sometimes uploaded under multiple aliases of its tags, the
+/// resolver only needs to consider one of them. Files with the same name but a different hash are
+/// different artifacts.
+fn is_same_artifact(a: &ArtifactInfo, b: &ArtifactInfo) -> bool {
+    let has_hash = a.hashes.as_ref().is_some_and(|hashes| !hashes.is_empty());
+    a.url == b.url || (a.hashes == b.hashes && (has_hash || a.filename == b.filename))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
     use reqwest::Client;
     use tempfile::TempDir;
     use tokio::task::JoinHandle;
 
-    use crate::index::package_sources::PackageSourcesBuilder;
+    use crate::index::package_sources::{HostNotAllowedError, PackageSourcesBuilder};
+    use crate::index::ProxyConfig;
     use axum::response::{Html, IntoResponse};
     use axum::routing::get;
     use axum::Router;
@@ -873,6 +1643,35 @@ mod test {
         Ok((url, join_handle))
     }
 
+    /// Starts a server that serves a simple index with the given links for every package.
+    async fn make_index_with_files(
+        files: &[&str],
+    ) -> anyhow::Result<(Url, JoinHandle<Result<(), std::io::Error>>)> {
+        let addr = SocketAddr::new([127, 0, 0, 1].into(), 0);
+        let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+        let address = listener.local_addr()?;
+
+        let links = files
+            .iter()
+            .map(|file| {
+                let filename = file.split_once('#').map_or(*file, |(filename, _)| filename);
+                format!(r#"<a href="/files/{file}">{filename}</a>"#)
+            })
+            .join("\n");
+        let html = format!("<html><body>{links}</body></html>");
+        let router = Router::new().route(
+            "/simple/:package/",
+            get(move || {
+                let html = html.clone();
+                async move { Html(html) }
+            }),
+        );
+        let join_handle = tokio::spawn(axum::serve(listener, router).into_future());
+
+        let url = format!("http://{}/simple/", address).parse()?;
+        Ok((url, join_handle))
+    }
+
     fn make_package_db() -> (TempDir, PackageDb) {
         let url = Url::parse("https://pypi.org/simple/").unwrap();
 
@@ -982,6 +1781,828 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_requests_through_proxy() -> anyhow::Result<()> {
+        // The simple server acts as the proxy, it serves any host. The index itself cannot be
+        // resolved, so requests only succeed if they are routed through the proxy.
+        let (mut proxy_url, _server) = make_simple_server("foo").await?;
+        proxy_url.set_path("/");
+        let index = Url::parse("http://pypi.invalid/simple/")?;
+
+        let make_db = |proxy: ProxyConfig, cache_dir: &Path| {
+            let client = proxy.apply(Client::builder())?.build()?;
+            let sources = PackageSourcesBuilder::new(index.clone())
+                .with_proxy(proxy)
+                .build()?;
+            anyhow::Ok(
+                PackageDb::new(
+                    sources,
+                    ClientWithMiddleware::from(client),
+                    cache_dir,
+                    Default::default(),
+                )
+                .unwrap(),
+            )
+        };
+
+        let proxy = ProxyConfig {
+            http: Some(proxy_url),
+            ..Default::default()
+        };
+        let cache_dir = TempDir::new()?;
+        let package_db = make_db(proxy.clone(), cache_dir.path())?;
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::FromIndex("foo".parse()?))
+            .await
+            .unwrap();
+        let artifact = artifacts.values().flatten().next().unwrap();
+        assert_eq!(
+            artifact.url.as_str(),
+            "http://pypi.invalid/files/foo-1.0-py3-none-any.whl"
+        );
+
+        // Hosts in `no_proxy` are connected to directly
+        let cache_dir = TempDir::new()?;
+        let package_db = make_db(
+            ProxyConfig {
+                no_proxy: Some(String::from("pypi.invalid")),
+                ..proxy
+            },
+            cache_dir.path(),
+        )?;
+        assert!(package_db
+            .available_artifacts(ArtifactRequest::FromIndex("foo".parse()?))
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    /// Starts a minimal socks5 proxy without authentication that connects every request to
+    /// `target`, regardless of the requested address.
+    async fn make_socks_proxy(target: SocketAddr) -> anyhow::Result<(Url, JoinHandle<()>)> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener =
+            tokio::net::TcpListener::bind(SocketAddr::new([127, 0, 0, 1].into(), 0)).await?;
+        let address = listener.local_addr()?;
+        let join_handle = tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    // Greeting, accept without authentication
+                    let mut header = [0u8; 2];
+                    stream.read_exact(&mut header).await?;
+                    let mut methods = vec![0u8; header[1] as usize];
+                    stream.read_exact(&mut methods).await?;
+                    stream.write_all(&[5, 0]).await?;
+
+                    // Connect request, the requested address is ignored
+                    let mut request = [0u8; 4];
+                    stream.read_exact(&mut request).await?;
+                    let address_len = match request[3] {
+                        1 => 4,
+                        4 => 16,
+                        _ => stream.read_u8().await? as usize,
+                    };
+                    let mut address = vec![0u8; address_len + 2];
+                    stream.read_exact(&mut address).await?;
+                    stream.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+
+                    let mut upstream = tokio::net::TcpStream::connect(target).await?;
+                    tokio::io::copy_bidirectional(&mut stream, &mut upstream).await?;
+                    std::io::Result::Ok(())
+                });
+            }
+        });
+
+        Ok((format!("socks5h://{address}").parse()?, join_handle))
+    }
+
+    #[tokio::test]
+    async fn test_requests_through_socks_proxy() -> anyhow::Result<()> {
+        let (server_url, _server) = make_simple_server("foo").await?;
+        let target = server_url.socket_addrs(|| None)?[0];
+        let (proxy_url, _proxy) = make_socks_proxy(target).await?;
+
+        // The index cannot be resolved, the host name is resolved by the proxy
+        let index = Url::parse("http://pypi.invalid/simple/")?;
+        let proxy = ProxyConfig {
+            http: Some(proxy_url),
+            ..Default::default()
+        };
+        let client = proxy.apply(Client::builder())?.build()?;
+        let sources = PackageSourcesBuilder::new(index)
+            .with_proxy(proxy)
+            .build()?;
+        let cache_dir = TempDir::new()?;
+        let package_db = PackageDb::new(
+            sources,
+            ClientWithMiddleware::from(client),
+            cache_dir.path(),
+            Default::default(),
+        )
+        .unwrap();
+
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::FromIndex("foo".parse()?))
+            .await
+            .unwrap();
+        assert_eq!(artifacts.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_extra_indices() -> anyhow::Result<()> {
+        let hash = |c: char| c.to_string().repeat(64);
+        let (first_index, _first_server) = make_index_with_files(&[
+            &format!("foo-1.0-py3-none-any.whl#sha256={}", hash('a')),
+            &format!("foo-1.0.tar.gz#sha256={}", hash('b')),
+        ])
+        .await?;
+        let (second_index, _second_server) = make_index_with_files(&[
+            // The exact same file as on the first index
+            &format!("foo-1.0-py3-none-any.whl#sha256={}", hash('a')),
+            // A file with the same name but different contents
+            &format!("foo-1.0.tar.gz#sha256={}", hash('c')),
+            &format!("foo-2.0-py3-none-any.whl#sha256={}", hash('d')),
+        ])
+        .await?;
+
+        let first_port = first_index.port();
+        let artifacts = |sources: PackageSources| async move {
+            let cache_dir = TempDir::new().unwrap();
+            let package_db = PackageDb::new(
+                sources,
+                ClientWithMiddleware::from(Client::new()),
+                cache_dir.path(),
+                Default::default(),
+            )
+            .unwrap();
+            package_db
+                .available_artifacts(ArtifactRequest::FromIndex("foo".parse().unwrap()))
+                .await
+                .unwrap()
+                .iter()
+                .map(|(version, artifacts)| {
+                    (
+                        version.to_string(),
+                        artifacts
+                            .iter()
+                            .map(|artifact| {
+                                format!(
+                                    "{} from {}",
+                                    artifact.filename,
+                                    if artifact.url.port() == first_port {
+                                        "first"
+                                    } else {
+                                        "second"
+                                    }
+                                )
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // The files of both indices are merged, identical files are only included once
+        let merged = artifacts(
+            PackageSourcesBuilder::new(first_index.clone())
+                .with_index("second", &second_index)
+                .build()?,
+        )
+        .await;
+        assert_eq!(
+            merged,
+            [
+                (
+                    String::from("2.0"),
+                    vec![String::from("foo-2.0-py3-none-any.whl from second")]
+                ),
+                (
+                    String::from("1.0"),
+                    vec![
+                        String::from("foo-1.0-py3-none-any.whl from first"),
+                        String::from("foo-1.0.tar.gz from first"),
+                        String::from("foo-1.0.tar.gz from second"),
+                    ]
+                ),
+            ]
+        );
+
+        // An override pins the package to a single index
+        let pinned = artifacts(
+            PackageSourcesBuilder::new(first_index.clone())
+                .with_index("second", &second_index)
+                .with_override("foo".parse()?, "second")
+                .build()?,
+        )
+        .await;
+        assert_eq!(
+            pinned,
+            [
+                (
+                    String::from("2.0"),
+                    vec![String::from("foo-2.0-py3-none-any.whl from second")]
+                ),
+                (
+                    String::from("1.0"),
+                    vec![
+                        String::from("foo-1.0-py3-none-any.whl from second"),
+                        String::from("foo-1.0.tar.gz from second"),
+                    ]
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_deduplicate_artifacts() -> anyhow::Result<()> {
+        let hash = |c: char| c.to_string().repeat(64);
+        let (index, _server) = make_index_with_files(&[
+            &format!("foo-1.0-py3-none-any.whl#sha256={}", hash('a')),
+            // The same file listed twice
+            &format!("foo-1.0-py3-none-any.whl#sha256={}", hash('a')),
+            // The same wheel under an alias of its tags
+            &format!("foo-1.0-py2.py3-none-any.whl#sha256={}", hash('a')),
+            &format!("foo-1.0.tar.gz#sha256={}", hash('b')),
+        ])
+        .await?;
+
+        let cache_dir = TempDir::new()?;
+        let package_db = PackageDb::new(
+            index.into(),
+            ClientWithMiddleware::from(Client::new()),
+            cache_dir.path(),
+            Default::default(),
+        )
+        .unwrap();
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::FromIndex("foo".parse()?))
+            .await
+            .unwrap();
+
+        let filenames = artifacts
+            .values()
+            .flatten()
+            .map(|artifact| artifact.filename.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(filenames, ["foo-1.0-py3-none-any.whl", "foo-1.0.tar.gz"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_first_match_index_strategy() -> anyhow::Result<()> {
+        let hash = |c: char| c.to_string().repeat(64);
+        let (public_index, _public_server) =
+            make_index_with_files(&[&format!("foo-99.0-py3-none-any.whl#sha256={}", hash('a'))])
+                .await?;
+        let (private_index, _private_server) =
+            make_index_with_files(&[&format!("foo-1.0-py3-none-any.whl#sha256={}", hash('b'))])
+                .await?;
+
+        let versions = |index_strategy: IndexStrategy| {
+            let sources = PackageSourcesBuilder::new(public_index.clone())
+                .with_index("private", &private_index)
+                .with_index_strategy(index_strategy)
+                .build()
+                .unwrap();
+            async move {
+                let cache_dir = TempDir::new().unwrap();
+                let package_db = PackageDb::new(
+                    sources,
+                    ClientWithMiddleware::from(Client::new()),
+                    cache_dir.path(),
+                    Default::default(),
+                )
+                .unwrap();
+                package_db
+                    .available_artifacts(ArtifactRequest::FromIndex("foo".parse().unwrap()))
+                    .await
+                    .unwrap()
+                    .keys()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        // Merging allows the public index to shadow the private package
+        assert_eq!(versions(IndexStrategy::Merge).await, ["99.0", "1.0"]);
+
+        // The private index has priority, the public index is ignored
+        assert_eq!(versions(IndexStrategy::FirstMatch).await, ["1.0"]);
+
+        // A package that is not on the private index is taken from the public index
+        let (empty_index, _empty_server) = make_simple_server("bar").await?;
+        let sources = PackageSourcesBuilder::new(public_index.clone())
+            .with_index("empty", &empty_index)
+            .with_index_strategy(IndexStrategy::FirstMatch)
+            .build()?;
+        let cache_dir = TempDir::new()?;
+        let package_db = PackageDb::new(
+            sources,
+            ClientWithMiddleware::from(Client::new()),
+            cache_dir.path(),
+            Default::default(),
+        )
+        .unwrap();
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::FromIndex("foo".parse()?))
+            .await
+            .unwrap();
+        assert_eq!(
+            artifacts
+                .keys()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["99.0"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mislabeled_simple_api() -> anyhow::Result<()> {
+        use axum::http::header::CONTENT_TYPE;
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let page = |name: &str| {
+            format!(
+                r#"<html><body><a href="/files/{name}-1.0-py3-none-any.whl">{name}-1.0-py3-none-any.whl</a></body></html>"#
+            )
+        };
+        let html = page("plain");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(page("gzipped").as_bytes())?;
+        let gzipped = encoder.finish()?;
+
+        // Pages with a wrong Content-Type and gzip compressed pages without a Content-Encoding
+        let addr = SocketAddr::new([127, 0, 0, 1].into(), 0);
+        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        let address = listener.local_addr()?;
+        let router = Router::new()
+            .route(
+                "/simple/gzipped/",
+                get(move || async move { ([(CONTENT_TYPE, "application/octet-stream")], gzipped) }),
+            )
+            .route(
+                "/simple/plain/",
+                get(move || async move { ([(CONTENT_TYPE, "text/plain")], html) }),
+            )
+            .route(
+                "/simple/garbage/",
+                get(|| async { ([(CONTENT_TYPE, "text/plain")], "garbage") }),
+            );
+        let _server = tokio::spawn(axum::serve(listener, router).into_future());
+
+        let index: Url = format!("http://{address}/simple/").parse()?;
+        let cache_dir = TempDir::new()?;
+        let package_db = PackageDb::new(
+            index.into(),
+            ClientWithMiddleware::from(Client::new()),
+            cache_dir.path(),
+            Default::default(),
+        )
+        .unwrap();
+
+        for name in ["gzipped", "plain"] {
+            let artifacts = package_db
+                .available_artifacts(ArtifactRequest::FromIndex(name.parse().unwrap()))
+                .await
+                .unwrap();
+            assert_eq!(artifacts.len(), 1, "{name}");
+        }
+        assert!(package_db
+            .available_artifacts(ArtifactRequest::FromIndex("garbage".parse().unwrap()))
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sniff_simple_api_format() {
+        assert_eq!(
+            sniff_simple_api_format(b"\n  <!DOCTYPE html>"),
+            Some(SimpleApiFormat::Html)
+        );
+        assert_eq!(
+            sniff_simple_api_format(b"\xef\xbb\xbf<html>"),
+            Some(SimpleApiFormat::Html)
+        );
+        assert_eq!(
+            sniff_simple_api_format(br#"{"meta": {}}"#),
+            Some(SimpleApiFormat::Json)
+        );
+        assert_eq!(sniff_simple_api_format(b"garbage"), None);
+        assert_eq!(sniff_simple_api_format(b""), None);
+    }
+
+    #[tokio::test]
+    async fn test_versions() -> anyhow::Result<()> {
+        let mirror = TempDir::new()?;
+        fs::create_dir_all(mirror.path().join("foo"))?;
+        fs::write(
+            mirror.path().join("foo/index.html"),
+            r#"<html><body>
+            <a href="foo-1.0-py3-none-any.whl">foo-1.0-py3-none-any.whl</a>
+            <a href="foo-1.0.tar.gz">foo-1.0.tar.gz</a>
+            <a href="foo-2.0-py3-none-any.whl" data-yanked="broken">foo-2.0-py3-none-any.whl</a>
+            <a href="foo-1.5-py3-none-any.whl" data-yanked="outdated">foo-1.5-py3-none-any.whl</a>
+            <a href="foo-1.5.tar.gz">foo-1.5.tar.gz</a>
+            </body></html>"#,
+        )?;
+
+        let cache_dir = TempDir::new()?;
+        let package_db = PackageDb::new(
+            Url::from_directory_path(mirror.path()).unwrap().into(),
+            ClientWithMiddleware::from(Client::new()),
+            cache_dir.path(),
+            Default::default(),
+        )
+        .unwrap();
+
+        let versions = |include_yanked| {
+            let package_db = &package_db;
+            async move {
+                package_db
+                    .versions("foo".parse().unwrap(), include_yanked)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|(version, artifacts)| (version.to_string(), artifacts.len()))
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(
+            versions(true).await,
+            [
+                (String::from("2.0"), 1),
+                (String::from("1.5"), 2),
+                (String::from("1.0"), 2)
+            ]
+        );
+        // 1.5 still has an sdist that is not yanked
+        assert_eq!(
+            versions(false).await,
+            [(String::from("1.5"), 2), (String::from("1.0"), 2)]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_local_index() -> anyhow::Result<()> {
+        // A mirror with a page per package that links to the files with a relative href
+        let mirror = TempDir::new()?;
+        let wheel_name = "miniblack-23.1.0-py3-none-any.whl";
+        fs::create_dir_all(mirror.path().join("simple/miniblack"))?;
+        fs::create_dir_all(mirror.path().join("packages"))?;
+        fs::write(
+            mirror.path().join("simple/miniblack/index.html"),
+            format!(
+                r#"<html><body><a href="../../packages/{wheel_name}">{wheel_name}</a></body></html>"#
+            ),
+        )?;
+        fs::copy(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../../test-data/wheels")
+                .join(wheel_name),
+            mirror.path().join("packages").join(wheel_name),
+        )?;
+
+        let index = Url::from_directory_path(mirror.path().join("simple")).unwrap();
+        let cache_dir = TempDir::new()?;
+        let package_db = PackageDb::new(
+            index.into(),
+            ClientWithMiddleware::from(Client::new()),
+            cache_dir.path(),
+            Default::default(),
+        )
+        .unwrap();
+
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::FromIndex("miniblack".parse().unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(artifacts.len(), 1);
+        let artifact_info = artifacts[0][0].clone();
+        assert_eq!(
+            artifact_info.url,
+            Url::from_file_path(mirror.path().join("packages").join(wheel_name)).unwrap()
+        );
+
+        let (wheel, _) = package_db.get_wheel(&artifact_info, None).await.unwrap();
+        let (_, metadata) = wheel.metadata().unwrap();
+        assert_eq!(metadata.name.as_str(), "miniblack");
+
+        // Downloading a local artifact returns the file in the mirror
+        let path = package_db.download_artifact(&artifact_info).await.unwrap();
+        assert_eq!(path, mirror.path().join("packages").join(wheel_name));
+
+        // Packages that are not in the mirror have no artifacts
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::FromIndex("boltons".parse().unwrap()))
+            .await
+            .unwrap();
+        assert!(artifacts.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pep658_sdist_metadata() -> anyhow::Result<()> {
+        // A mirror where the metadata of every sdist is available separately. The sdists have a
+        // hash, so their metadata can be stored in the cache.
+        let mirror = TempDir::new()?;
+        let packages = [
+            (
+                "static",
+                "Metadata-Version: 2.2\nName: static\nVersion: 1.0\nRequires-Dist: a\n",
+            ),
+            // Before PEP 643 the PKG-INFO often does not list the requirements
+            (
+                "legacy",
+                "Metadata-Version: 2.1\nName: legacy\nVersion: 1.0\n",
+            ),
+            (
+                "dynamic",
+                "Metadata-Version: 2.2\nName: dynamic\nVersion: 1.0\nDynamic: Requires-Dist\n",
+            ),
+        ];
+        fs::create_dir_all(mirror.path().join("packages"))?;
+        for (name, metadata) in packages {
+            let sdist_name = format!("{name}-1.0.tar.gz");
+            let sha256 = rattler_digest::compute_bytes_digest::<Sha256>(&sdist_name);
+            fs::create_dir_all(mirror.path().join("simple").join(name))?;
+            fs::write(
+                mirror.path().join("simple").join(name).join("index.html"),
+                format!(
+                    r#"<html><body><a href="../../packages/{sdist_name}#sha256={sha256:x}" data-core-metadata="true">{sdist_name}</a></body></html>"#
+                ),
+            )?;
+            fs::write(
+                mirror
+                    .path()
+                    .join("packages")
+                    .join(format!("{sdist_name}.metadata")),
+                metadata,
+            )?;
+        }
+
+        let index = Url::from_directory_path(mirror.path().join("simple")).unwrap();
+        let cache_dir = TempDir::new()?;
+        let package_db = PackageDb::new(
+            index.into(),
+            ClientWithMiddleware::from(Client::new()),
+            cache_dir.path(),
+            Default::default(),
+        )
+        .unwrap();
+        let artifacts = |name: &'static str| {
+            let package_db = &package_db;
+            async move {
+                package_db
+                    .available_artifacts(ArtifactRequest::FromIndex(name.parse().unwrap()))
+                    .await
+                    .unwrap()[0]
+                    .clone()
+            }
+        };
+
+        let static_artifacts = artifacts("static").await;
+        let (_, metadata) = package_db
+            .get_pep658_metadata_sdists(&static_artifacts)
+            .await
+            .unwrap();
+        assert_eq!(metadata.requires_dist.len(), 1);
+        assert!(package_db
+            .metadata_from_cache(&static_artifacts[0])
+            .await
+            .is_some());
+
+        // The metadata of the other sdists is not reliable, they have to be built. It is not
+        // cached either.
+        for name in ["legacy", "dynamic"] {
+            let artifacts = artifacts(name).await;
+            assert!(
+                package_db
+                    .get_pep658_metadata_sdists(&artifacts)
+                    .await
+                    .is_none(),
+                "the metadata of {name} was used"
+            );
+            assert!(package_db
+                .metadata_from_cache(&artifacts[0])
+                .await
+                .is_none());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_metadata() -> anyhow::Result<()> {
+        let wheel_name = "miniblack-23.1.0-py3-none-any.whl";
+        let wheel = fs::read(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../../test-data/wheels")
+                .join(wheel_name),
+        )?;
+
+        let addr = SocketAddr::new([127, 0, 0, 1].into(), 0);
+        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        let address = listener.local_addr()?;
+        let router = Router::new()
+            .route(
+                "/simple/miniblack/",
+                get(move || async move {
+                    Html(format!(
+                        r#"<html><body><a href="/files/{wheel_name}">{wheel_name}</a></body></html>"#
+                    ))
+                }),
+            )
+            .route(
+                "/files/:file",
+                get(move || {
+                    let wheel = wheel.clone();
+                    async move { wheel }
+                }),
+            );
+        let server = tokio::spawn(axum::serve(listener, router).into_future());
+        let index: Url = format!("http://{address}/simple/").parse()?;
+
+        let cache_dir = TempDir::new()?;
+        let package_db = PackageDb::new(
+            index.into(),
+            ClientWithMiddleware::from(Client::new()),
+            cache_dir.path(),
+            Default::default(),
+        )
+        .unwrap();
+
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::FromIndex("miniblack".parse()?))
+            .await
+            .unwrap()[0]
+            .clone();
+        assert!(package_db
+            .get_cached_metadata(&artifacts)
+            .await
+            .unwrap()
+            .is_none());
+
+        // A package that cannot be found does not fail the prefetch of the others
+        package_db
+            .prefetch_metadata(&["miniblack".parse()?, "missing".parse()?])
+            .await;
+
+        // The metadata is now read from the cache, without the server
+        server.abort();
+        let (_, metadata) = package_db
+            .get_cached_metadata(&artifacts)
+            .await
+            .unwrap()
+            .expect("the metadata of miniblack was not prefetched");
+        assert_eq!(metadata.name.as_str(), "miniblack");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_allowed_hosts() -> anyhow::Result<()> {
+        let (index, _server) = make_index_with_files(&["foo-1.0-py3-none-any.whl"]).await?;
+        let cache_dir = TempDir::new()?;
+        let package_db = PackageDb::new(
+            PackageSourcesBuilder::new(index)
+                .with_allowed_hosts(["files.example.com"])
+                .build()?,
+            ClientWithMiddleware::from(Client::new()),
+            cache_dir.path(),
+            Default::default(),
+        )
+        .unwrap();
+
+        // The index itself can still be queried
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::FromIndex("foo".parse().unwrap()))
+            .await
+            .unwrap();
+        let artifact_info = artifacts
+            .iter()
+            .flat_map(|(_, artifacts)| artifacts.iter())
+            .next()
+            .unwrap()
+            .clone();
+
+        // But the artifacts it links to are not downloaded
+        let err = package_db
+            .get_wheel(&artifact_info, None)
+            .await
+            .err()
+            .unwrap();
+        let err = err.downcast_ref::<HostNotAllowedError>().unwrap();
+        assert_eq!(err.url, artifact_info.url);
+
+        let err = package_db
+            .download_artifact(&artifact_info)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<HostNotAllowedError>().is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_allowed_hosts_redirect() -> anyhow::Result<()> {
+        let wheel_name = "miniblack-23.1.0-py3-none-any.whl";
+        let wheel = fs::read(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../../test-data/wheels")
+                .join(wheel_name),
+        )?;
+
+        // The server redirects the downloads to itself under the name `localhost`
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let router = Router::new()
+            .route(
+                "/simple/miniblack/",
+                get(move || async move {
+                    Html(format!(
+                        r#"<html><body><a href="/files/{wheel_name}">{wheel_name}</a></body></html>"#
+                    ))
+                }),
+            )
+            .route(
+                "/files/:file",
+                get(move |axum::extract::Path(file): axum::extract::Path<String>| async move {
+                    axum::response::Redirect::temporary(&format!(
+                        "http://localhost:{}/redirected/{file}",
+                        address.port()
+                    ))
+                }),
+            )
+            .route(
+                "/redirected/:file",
+                get(move || {
+                    let wheel = wheel.clone();
+                    async move { wheel }
+                }),
+            );
+        let _server = tokio::spawn(axum::serve(listener, router).into_future());
+        let index: Url = format!("http://{address}/simple/").parse()?;
+
+        let make_package_db = |allowed_hosts: &[&str]| {
+            let cache_dir = TempDir::new().unwrap();
+            let package_db = PackageDb::new(
+                PackageSourcesBuilder::new(index.clone())
+                    .with_allowed_hosts(allowed_hosts.iter().copied())
+                    .build()
+                    .unwrap(),
+                ClientWithMiddleware::from(Client::new()),
+                cache_dir.path(),
+                Default::default(),
+            )
+            .unwrap();
+            (cache_dir, package_db)
+        };
+        async fn artifact_info(package_db: &PackageDb) -> Arc<ArtifactInfo> {
+            package_db
+                .available_artifacts(ArtifactRequest::FromIndex("miniblack".parse().unwrap()))
+                .await
+                .unwrap()[0][0]
+                .clone()
+        }
+
+        // If the host that is redirected to is allowed the download succeeds
+        let (_cache_dir, package_db) = make_package_db(&["127.0.0.1", "localhost"]);
+        let artifact = artifact_info(&package_db).await;
+        assert!(package_db.download_artifact(&artifact).await.is_ok());
+
+        // Otherwise the downloads fail, even though the host of the artifact is allowed
+        let (_cache_dir, package_db) = make_package_db(&["127.0.0.1"]);
+        let artifact = artifact_info(&package_db).await;
+        let err = package_db.download_artifact(&artifact).await.unwrap_err();
+        let err = err.downcast_ref::<HostNotAllowedError>().unwrap();
+        assert_eq!(err.url.host_str(), Some("localhost"));
+        let err = package_db.get_wheel(&artifact, None).await.err().unwrap();
+        assert!(err.downcast_ref::<HostNotAllowedError>().is_some());
+        let err = package_db
+            .get_lazy_metadata_wheel(&artifact)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<HostNotAllowedError>().is_some());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_pep658() {
         let (_cache_dir, package_db) = make_package_db();
@@ -1003,6 +2624,271 @@ mod test {
 
         let (_artifact, _metadata) = package_db.get_pep658_metadata(artifact_info).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_download_artifact() {
+        let (_cache_dir, package_db) = make_package_db();
+        let name = "boltons".parse::<PackageName>().unwrap();
+
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::FromIndex(name.into()))
+            .await
+            .unwrap();
+        let artifact_info = artifacts
+            .iter()
+            .flat_map(|(_, artifacts)| artifacts.iter())
+            .find(|a| a.is::<Wheel>() && a.hashes.is_some())
+            .unwrap();
+
+        let path = package_db.download_artifact(artifact_info).await.unwrap();
+        assert!(path.is_file());
+        assert_eq!(
+            path.file_name().unwrap().to_str().unwrap(),
+            artifact_info.filename.to_string()
+        );
+
+        // A second request should return the cached file
+        let cached = package_db.download_artifact(artifact_info).await.unwrap();
+        assert_eq!(path, cached);
+
+        // A mismatching hash should result in an error
+        let mut corrupt = (**artifact_info).clone();
+        corrupt.hashes = Some(ArtifactHashes {
+            sha256: Some(rattler_digest::compute_bytes_digest::<Sha256>("corrupt")),
+        });
+        assert!(package_db.download_artifact(&corrupt).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_download_to_cache_revalidates_urls_without_hash() -> anyhow::Result<()> {
+        // Every request for the artifact returns different contents
+        let requests = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let router = Router::new().route(
+            "/files/artifact",
+            get(move || {
+                let request = requests.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async move { format!("contents {request}") }
+            }),
+        );
+        let _server = tokio::spawn(axum::serve(listener, router).into_future());
+
+        let (_cache_dir, package_db) = make_package_db();
+        let artifacts_dir = package_db.artifacts_dir();
+        let url: Url = format!("http://{address}/files/artifact").parse()?;
+        let download = |expected_hash| {
+            download_to_cache(
+                &package_db.http,
+                &package_db.sources,
+                &artifacts_dir,
+                &url,
+                "artifact",
+                expected_hash,
+            )
+        };
+
+        // Without a hash the url is requested again, changed contents are picked up
+        let path = download(None).await.unwrap();
+        assert_eq!(fs::read_to_string(&path)?, "contents 0");
+        let path = download(None).await.unwrap();
+        assert_eq!(fs::read_to_string(&path)?, "contents 1");
+
+        // With a hash the previously downloaded file is used
+        let hash = rattler_digest::compute_bytes_digest::<Sha256>("contents 2");
+        let path = download(Some(hash)).await.unwrap();
+        assert_eq!(fs::read_to_string(&path)?, "contents 2");
+        let path = download(Some(hash)).await.unwrap();
+        assert_eq!(fs::read_to_string(path)?, "contents 2");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_gzip_encoded_artifact() -> anyhow::Result<()> {
+        // A server that marks a `.tar.gz` as gzip encoded if the client accepts it
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        std::io::Write::write_all(&mut encoder, b"a tar archive")?;
+        let published = encoder.finish()?;
+        let served = published.clone();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let router = Router::new().route(
+            "/files/foo-1.0.tar.gz",
+            get(move |headers: axum::http::HeaderMap| {
+                let served = served.clone();
+                async move {
+                    let accepts_gzip = headers
+                        .get(ACCEPT_ENCODING)
+                        .and_then(|value| value.to_str().ok())
+                        .is_some_and(|value| value.contains("gzip"));
+                    if accepts_gzip {
+                        ([(reqwest::header::CONTENT_ENCODING, "gzip")], served).into_response()
+                    } else {
+                        served.into_response()
+                    }
+                }
+            }),
+        );
+        let _server = tokio::spawn(axum::serve(listener, router).into_future());
+
+        let (_cache_dir, package_db) = make_package_db();
+        let url: Url = format!("http://{address}/files/foo-1.0.tar.gz").parse()?;
+        let path = download_to_cache(
+            &package_db.http,
+            &package_db.sources,
+            &package_db.artifacts_dir(),
+            &url,
+            "foo-1.0.tar.gz",
+            Some(rattler_digest::compute_bytes_digest::<Sha256>(&published)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read(path)?, published);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_wheel_without_artifact_caching() {
+        let (cache_dir, package_db) = make_package_db();
+        let package_db = package_db.with_artifact_caching(false);
+        let name = "boltons".parse::<PackageName>().unwrap();
+
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::FromIndex(name.into()))
+            .await
+            .unwrap();
+        let artifact_info = artifacts
+            .iter()
+            .flat_map(|(_, artifacts)| artifacts.iter())
+            .find(|a| a.is::<Wheel>())
+            .unwrap()
+            .clone();
+
+        fn count_files(dir: &Path) -> usize {
+            fs::read_dir(dir)
+                .into_iter()
+                .flatten()
+                .filter_map(Result::ok)
+                .map(|entry| {
+                    let path = entry.path();
+                    if path.is_dir() {
+                        count_files(&path)
+                    } else {
+                        1
+                    }
+                })
+                .sum()
+        }
+        let count_cached_files = || count_files(&cache_dir.path().join("http"));
+        let cached_files = count_cached_files();
+
+        // The wheel can be read but is not stored in the http cache
+        let (wheel, _) = package_db.get_wheel(&artifact_info, None).await.unwrap();
+        let (_, metadata) = wheel.metadata().unwrap();
+        assert_eq!(metadata.name.as_str(), "boltons");
+        assert_eq!(count_cached_files(), cached_files);
+    }
+
+    #[tokio::test]
+    async fn test_direct_url_artifact_cache() {
+        let (cache_dir, package_db) = make_package_db();
+        let package_db = Arc::new(package_db);
+        let env_markers = Arc::new(
+            crate::python_env::Pep508EnvMakers::from_env()
+                .await
+                .unwrap()
+                .0,
+        );
+        let wheel_builder = WheelBuilder::new(
+            package_db.clone(),
+            env_markers,
+            None,
+            ResolveOptions::default(),
+        )
+        .unwrap();
+        let url: Url = "https://files.pythonhosted.org/packages/29/a2/76daec910034d765f1018d22660c0970fb99f77143a42841d067b522903e/cowpy-1.1.5-py3-none-any.whl".parse().unwrap();
+        let request = || ArtifactRequest::DirectUrl {
+            name: "cowpy".parse().unwrap(),
+            url: url.clone(),
+            wheel_builder: wheel_builder.clone(),
+        };
+
+        let artifacts = package_db.available_artifacts(request()).await.unwrap();
+        let artifact_info = artifacts.values().flatten().next().unwrap().clone();
+
+        // A second database that cannot reach the network is served from the artifact cache
+        let offline_client = Client::builder()
+            .proxy(reqwest::Proxy::all("http://127.0.0.1:1").unwrap())
+            .build()
+            .unwrap();
+        let offline_db = PackageDb::new(
+            Url::parse("https://pypi.org/simple/").unwrap().into(),
+            ClientWithMiddleware::from(offline_client),
+            cache_dir.path(),
+            CheckAvailablePackages::default(),
+        )
+        .unwrap();
+        let cached = offline_db.available_artifacts(request()).await.unwrap();
+        assert_eq!(cached.values().flatten().next(), Some(&artifact_info));
+
+        let (wheel, direct_url_json) = offline_db
+            .get_wheel(&artifact_info, Some(wheel_builder.clone()))
+            .await
+            .unwrap();
+        assert_eq!(wheel.name.distribution.as_str(), "cowpy");
+        assert_eq!(direct_url_json.unwrap().url, url);
+    }
+
+    #[tokio::test]
+    async fn test_get_local_wheel() {
+        let (cache_dir, package_db) = make_package_db();
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/wheels/miniblack-23.1.0-py3-none-any.whl");
+        let url = Url::from_file_path(path.canonicalize().unwrap()).unwrap();
+
+        // Both paths and file urls are accepted
+        let from_path = package_db
+            .get_local_wheel(path.to_str().unwrap())
+            .await
+            .unwrap();
+        let local_wheel = package_db.get_local_wheel(url.as_str()).await.unwrap();
+        assert_eq!(from_path.artifact_info, local_wheel.artifact_info);
+
+        assert_eq!(local_wheel.metadata.name.as_str(), "miniblack");
+        assert!(local_wheel.artifact_info.is_direct_url);
+        assert_eq!(local_wheel.artifact_info.url, url);
+        assert!(matches!(
+            local_wheel.direct_url_json.source,
+            DirectUrlSource::Archive { hashes: Some(_) }
+        ));
+
+        // The metadata is cached
+        assert!(package_db
+            .metadata_from_cache(&local_wheel.artifact_info)
+            .await
+            .is_some());
+
+        // Install the wheel and check that the direct_url.json refers to the wheel
+        let target = cache_dir.path().join("target");
+        let installed = crate::install::install_wheel(
+            &local_wheel.wheel,
+            cache_dir.path(),
+            &crate::install::InstallPaths::for_target(&target, false),
+            Path::new("/invalid"),
+            &crate::install::InstallWheelOptions {
+                direct_url_json: Some(local_wheel.direct_url_json),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let direct_url = fs::read_to_string(installed.dist_info.join("direct_url.json")).unwrap();
+        assert!(direct_url.contains("archive_info"));
+        assert!(direct_url.contains(url.as_str()));
+
+        assert!(package_db.get_local_wheel("foo.tar.gz").await.is_err());
+    }
 }
 
 #[derive(Debug, Diagnostic)]
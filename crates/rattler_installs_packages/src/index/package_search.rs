@@ -0,0 +1,134 @@
+//! Helpers to search the package names of an index, e.g. to suggest the correct name when a user
+//! made a typo.
+
+use crate::types::{NormalizedPackageName, PackageName};
+use std::str::FromStr;
+
+/// Normalizes a (partial) package name in the same way as [`PackageName`]. Unlike [`PackageName`]
+/// this also accepts strings that are not valid package names, like a prefix ending in `-`.
+pub(crate) fn normalize_prefix(prefix: &str) -> String {
+    let mut normalized = prefix.replace(['-', '_', '.'], "-");
+    normalized.make_ascii_lowercase();
+    normalized
+}
+
+/// Parses a name from the index, invalid names are ignored.
+pub(crate) fn normalize_index_name(name: &str) -> Option<NormalizedPackageName> {
+    PackageName::from_str(name.trim()).ok().map(Into::into)
+}
+
+/// The maximum edit distance for a name to be considered similar to `name`. Short names allow
+/// fewer edits, otherwise almost every short name on pypi would be considered similar.
+pub(crate) fn max_edit_distance(name: &str) -> usize {
+    if name.len() <= 4 {
+        1
+    } else {
+        2
+    }
+}
+
+/// Computes the Levenshtein distance between `a` and `b`, or `None` if the distance exceeds `max`.
+pub(crate) fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a = a.as_bytes();
+    let b = b.as_bytes();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        // The distance can never decrease again if every entry of the row exceeds the maximum
+        if current.iter().all(|&distance| distance > max) {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    let distance = previous[b.len()];
+    (distance <= max).then_some(distance)
+}
+
+/// Keeps track of the names closest to a name, keeping at most `limit` candidates.
+pub(crate) struct SimilarNames {
+    name: String,
+    max_distance: usize,
+    limit: usize,
+    candidates: Vec<(usize, NormalizedPackageName)>,
+}
+
+impl SimilarNames {
+    pub fn new(name: &str, limit: usize) -> Self {
+        let name = normalize_prefix(name);
+        Self {
+            max_distance: max_edit_distance(&name),
+            name,
+            limit,
+            candidates: Vec::new(),
+        }
+    }
+
+    /// Adds a name from the index if it is similar enough.
+    pub fn add(&mut self, candidate: NormalizedPackageName) {
+        let Some(distance) = edit_distance(&self.name, candidate.as_str(), self.max_distance)
+        else {
+            return;
+        };
+
+        let index = self
+            .candidates
+            .partition_point(|(other, other_name)| (*other, other_name) <= (distance, &candidate));
+        if index < self.limit {
+            self.candidates.insert(index, (distance, candidate));
+            self.candidates.truncate(self.limit);
+        }
+    }
+
+    /// Returns the candidates, closest first.
+    pub fn finish(self) -> Vec<NormalizedPackageName> {
+        self.candidates.into_iter().map(|(_, name)| name).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("flask", "flask", 2), Some(0));
+        assert_eq!(edit_distance("flask", "falsk", 2), Some(2));
+        assert_eq!(edit_distance("requests", "reqeusts", 2), Some(2));
+        assert_eq!(edit_distance("numpy", "numpyy", 2), Some(1));
+        assert_eq!(edit_distance("numpy", "scipy", 2), None);
+        assert_eq!(edit_distance("a", "abcd", 2), None);
+    }
+
+    #[test]
+    fn test_similar_names() {
+        let mut similar = SimilarNames::new("Flsk", 2);
+        for name in ["flask", "Flask-Login", "flisk", "django", "fask", "flsk"] {
+            similar.add(normalize_index_name(name).unwrap());
+        }
+        let names = similar.finish();
+        assert_eq!(
+            names.iter().map(|name| name.as_str()).collect::<Vec<_>>(),
+            ["flsk", "fask"]
+        );
+    }
+
+    #[test]
+    fn test_normalize_prefix() {
+        assert_eq!(normalize_prefix("Flask_"), "flask-");
+        assert!(normalize_index_name("Flask_Login")
+            .unwrap()
+            .as_str()
+            .starts_with(&normalize_prefix("flask_")));
+    }
+}
@@ -0,0 +1,251 @@
+use miette::Diagnostic;
+use reqwest::{ClientBuilder, NoProxy, Proxy};
+use std::process::Command;
+use thiserror::Error;
+use url::Url;
+
+/// The proxies through which requests to package indices and artifacts are made.
+///
+/// The configuration applies to the http client with [`ProxyConfig::apply`] and to the `git`
+/// commands that fetch direct git urls when it is passed to
+/// [`super::PackageSourcesBuilder::with_proxy`]. Because the client is constructed by the caller
+/// of [`super::PackageDb::new`], both have to be done to route all requests through the proxy.
+///
+/// Besides `http` and `https` proxies the http client supports `socks5` proxies and `socks5h`
+/// proxies, which resolve host names through the proxy. [`ProxyConfig::apply`] fails for any other
+/// scheme, requests are never silently made without the configured proxy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxyConfig {
+    /// The proxy used for `http` urls
+    pub http: Option<Url>,
+
+    /// The proxy used for `https` urls
+    pub https: Option<Url>,
+
+    /// A comma separated list of hosts that are connected to directly, in the format of the
+    /// `NO_PROXY` environment variable. `*` disables the proxies for all hosts.
+    pub no_proxy: Option<String>,
+}
+
+/// An error that can occur when reading a [`ProxyConfig`] from the environment.
+#[derive(Debug, Error, Diagnostic)]
+#[error("invalid proxy url '{value}' in {variable}")]
+pub struct ProxyConfigError {
+    /// The environment variable that contains the invalid url
+    pub variable: String,
+
+    /// The value of the environment variable, with the credentials in it replaced by `***`
+    pub value: String,
+
+    /// The reason the url is invalid
+    #[source]
+    pub source: url::ParseError,
+}
+
+impl ProxyConfig {
+    /// Reads the proxy configuration from the `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and
+    /// `NO_PROXY` environment variables, like curl and pip do. The lowercase variants of the
+    /// variables take precedence and `ALL_PROXY` is used for schemes that do not have a proxy of
+    /// their own. Proxy urls without a scheme are assumed to be `http` proxies.
+    pub fn from_env() -> Result<Self, ProxyConfigError> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, ProxyConfigError> {
+        let var = |name: &str| {
+            [name.to_lowercase(), name.to_owned()]
+                .into_iter()
+                .find_map(|variable| {
+                    let value = var(&variable)?;
+                    (!value.trim().is_empty()).then_some((variable, value))
+                })
+        };
+        let proxy = |name: &str| {
+            var(name)
+                .or_else(|| var("ALL_PROXY"))
+                .map(|(variable, value)| parse_proxy_url(variable, value))
+                .transpose()
+        };
+
+        Ok(Self {
+            http: proxy("HTTP_PROXY")?,
+            https: proxy("HTTPS_PROXY")?,
+            no_proxy: var("NO_PROXY").map(|(_, value)| value),
+        })
+    }
+
+    /// Returns true if no proxy is configured.
+    pub fn is_empty(&self) -> bool {
+        self.http.is_none() && self.https.is_none()
+    }
+
+    /// Configures the proxies of a client builder. Any proxy that the builder would otherwise
+    /// pick up from the environment is replaced, so the configuration is the only source of
+    /// proxies for the client. An error is returned if the scheme of a proxy is not supported.
+    pub fn apply(&self, builder: ClientBuilder) -> reqwest::Result<ClientBuilder> {
+        let mut builder = builder.no_proxy();
+        let no_proxy = || self.no_proxy.as_deref().and_then(NoProxy::from_string);
+        if let Some(http) = &self.http {
+            builder = builder.proxy(Proxy::http(http.as_str())?.no_proxy(no_proxy()));
+        }
+        if let Some(https) = &self.https {
+            builder = builder.proxy(Proxy::https(https.as_str())?.no_proxy(no_proxy()));
+        }
+        Ok(builder)
+    }
+
+    /// Configures the proxies of a `git` command through the environment variables that git (or
+    /// rather curl) reads. The proxy variables of the current process are removed so the
+    /// configuration is the only source of proxies. If no proxy is configured the command is left
+    /// untouched, which lets git use the proxies from its own configuration and the environment.
+    pub(crate) fn apply_to_command(&self, command: &mut Command) {
+        if self.is_empty() {
+            return;
+        }
+
+        for variable in ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY", "NO_PROXY"] {
+            command.env_remove(variable);
+            command.env_remove(variable.to_lowercase());
+        }
+        if let Some(http) = &self.http {
+            command.env("http_proxy", http.as_str());
+        }
+        if let Some(https) = &self.https {
+            command.env("https_proxy", https.as_str());
+        }
+        if let Some(no_proxy) = &self.no_proxy {
+            command.env("no_proxy", no_proxy);
+        }
+    }
+}
+
+/// Parses the url of a proxy, urls without a scheme are `http` proxies.
+fn parse_proxy_url(variable: String, value: String) -> Result<Url, ProxyConfigError> {
+    let trimmed = value.trim();
+    let url = if trimmed.contains("://") {
+        Url::parse(trimmed)
+    } else {
+        Url::parse(&format!("http://{trimmed}"))
+    };
+    url.map_err(|source| ProxyConfigError {
+        variable,
+        value: redact_credentials(&value),
+        source,
+    })
+}
+
+/// Replaces the credentials in a proxy url that cannot be parsed with `***`, similar to
+/// [`crate::utils::redact_url`]. Everything before the last `@` of the authority is considered
+/// to be the credentials.
+fn redact_credentials(value: &str) -> String {
+    let start = value.find("://").map_or(0, |index| index + 3);
+    let authority = &value[start..];
+    let authority = &authority[..authority.find('/').unwrap_or(authority.len())];
+    match authority.rfind('@') {
+        Some(at) => format!("{}***{}", &value[..start], &value[start + at..]),
+        None => value.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn vars(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let config = ProxyConfig::from_vars(vars(&[
+            ("HTTPS_PROXY", "http://upper.example.com:3128"),
+            ("https_proxy", "lower.example.com:3128"),
+            ("ALL_PROXY", "socks5://socks.example.com:1080"),
+            ("NO_PROXY", "localhost,.internal.example.com"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            config,
+            ProxyConfig {
+                http: Some(Url::parse("socks5://socks.example.com:1080").unwrap()),
+                https: Some(Url::parse("http://lower.example.com:3128").unwrap()),
+                no_proxy: Some(String::from("localhost,.internal.example.com")),
+            }
+        );
+
+        assert!(ProxyConfig::from_vars(vars(&[])).unwrap().is_empty());
+        assert!(ProxyConfig::from_vars(vars(&[("HTTP_PROXY", "http://[::1")])).is_err());
+
+        // The credentials of an invalid url are not shown in the error
+        let err = ProxyConfig::from_vars(vars(&[("HTTP_PROXY", "http://user:secret@[::1:3128")]))
+            .unwrap_err();
+        assert_eq!(err.value, "http://***@[::1:3128");
+        assert!(!err.to_string().contains("secret"));
+    }
+
+    #[test]
+    fn test_apply() {
+        let url = |url: &str| Some(Url::parse(url).unwrap());
+        let build = |http, https| {
+            ProxyConfig {
+                http,
+                https,
+                no_proxy: None,
+            }
+            .apply(reqwest::Client::builder())
+            .and_then(|builder| builder.build())
+        };
+
+        build(url("http://proxy.example.com:3128"), None).unwrap();
+        build(None, url("https://proxy.example.com:3128")).unwrap();
+        build(
+            url("socks5://localhost:1080"),
+            url("socks5h://localhost:1080"),
+        )
+        .unwrap();
+
+        // Unsupported proxies are an error instead of connecting directly
+        assert!(build(url("ftp://proxy.example.com:21"), None).is_err());
+    }
+
+    #[test]
+    fn test_apply_to_command() {
+        let mut command = Command::new("git");
+        ProxyConfig::default().apply_to_command(&mut command);
+        assert_eq!(command.get_envs().count(), 0);
+
+        let config = ProxyConfig {
+            http: None,
+            https: Some(Url::parse("http://proxy.example.com:3128").unwrap()),
+            no_proxy: Some(String::from("localhost")),
+        };
+        let mut command = Command::new("git");
+        config.apply_to_command(&mut command);
+        // Environment variables are case-insensitive on windows
+        let envs = command
+            .get_envs()
+            .map(|(key, value)| {
+                (
+                    key.to_string_lossy().to_lowercase(),
+                    value.map(|value| value.to_string_lossy().into_owned()),
+                )
+            })
+            .collect::<Vec<_>>();
+        let set = |key: &str| {
+            envs.iter()
+                .find_map(|(k, value)| value.as_deref().filter(|_| k == key))
+        };
+        let removed = |key: &str| envs.iter().any(|(k, value)| k == key && value.is_none());
+        assert_eq!(set("https_proxy"), Some("http://proxy.example.com:3128/"));
+        assert_eq!(set("no_proxy"), Some("localhost"));
+
+        // The proxies of the current process are not inherited
+        assert_eq!(set("http_proxy"), None);
+        assert!(removed("http_proxy"));
+        assert!(removed("all_proxy"));
+    }
+}
@@ -0,0 +1,200 @@
+//! A cache that stores the result of previous resolutions. See
+//! [`super::PackageDb::with_resolution_cache`].
+
+use super::file_store::FileStore;
+use crate::python_env::WheelTags;
+use crate::resolve::solve_options::ResolveOptions;
+use crate::resolve::PinnedPackage;
+use crate::types::{Extra, NormalizedPackageName};
+use pep440_rs::Version;
+use pep508_rs::{MarkerEnvironment, Requirement, VersionOrUrl};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Stores pinned packages keyed by everything that influences the outcome of a resolution.
+pub(crate) struct ResolutionCache {
+    store: FileStore,
+
+    /// An opaque value that changes whenever the contents of the index change.
+    index_generation: String,
+}
+
+/// The key of a cached resolution. This is serialized and hashed to locate the entry in the
+/// [`FileStore`].
+#[derive(Serialize)]
+struct ResolutionCacheKey<'a> {
+    index_generation: &'a str,
+    index_sources: Vec<String>,
+    requirements: Vec<String>,
+    env_markers: &'a MarkerEnvironment,
+    compatible_tags: Option<&'a WheelTags>,
+    options: BTreeMap<&'static str, String>,
+}
+
+/// A resolution as it is stored in the cache. Only the names of the artifacts are stored, the
+/// artifacts themselves are looked up in the index again when the entry is read.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct CachedResolution {
+    pub packages: Vec<CachedPackage>,
+}
+
+/// A single pinned package in a [`CachedResolution`].
+#[derive(Serialize, Deserialize)]
+pub(crate) struct CachedPackage {
+    pub name: NormalizedPackageName,
+    pub version: Version,
+    pub extras: Vec<Extra>,
+    pub artifacts: Vec<String>,
+}
+
+impl ResolutionCache {
+    pub fn new(cache_dir: &Path, index_generation: String) -> io::Result<Self> {
+        Ok(Self {
+            store: FileStore::new(cache_dir)?,
+            index_generation,
+        })
+    }
+
+    /// Computes the key under which the resolution of `requirements` is stored. Returns `None` if
+    /// the resolution cannot be cached, e.g. because it refers to direct urls whose contents can
+    /// change without the index changing.
+    pub fn key(
+        &self,
+        index_sources: Vec<String>,
+        requirements: &[&Requirement],
+        env_markers: &MarkerEnvironment,
+        compatible_tags: Option<&WheelTags>,
+        options: &ResolveOptions,
+    ) -> Option<Vec<u8>> {
+        // Metadata is not stored in the cache
+        if options.retain_metadata {
+            return None;
+        }
+
+        if requirements
+            .iter()
+            .any(|req| matches!(req.version_or_url, Some(VersionOrUrl::Url(_))))
+        {
+            return None;
+        }
+
+        let mut requirements = requirements
+            .iter()
+            .map(|req| req.to_string())
+            .collect::<Vec<_>>();
+        requirements.sort();
+
+        let key = ResolutionCacheKey {
+            index_generation: &self.index_generation,
+            index_sources,
+            requirements,
+            env_markers,
+            compatible_tags,
+            options: options_key(options),
+        };
+        serde_json::to_vec(&key).ok()
+    }
+
+    /// Reads the resolution stored under `key`, returns `None` if there is no such entry or if it
+    /// cannot be read.
+    pub async fn get(&self, key: &[u8]) -> Option<CachedResolution> {
+        let mut reader = self.store.get(&key).await?;
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    /// Stores the resolution under `key`, replacing any previous entry.
+    pub async fn put(&self, key: &[u8], packages: &[PinnedPackage]) -> io::Result<()> {
+        let mut packages = packages
+            .iter()
+            .map(|package| {
+                let mut extras = package.extras.iter().cloned().collect::<Vec<_>>();
+                extras.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+                CachedPackage {
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                    extras,
+                    artifacts: package
+                        .artifacts
+                        .iter()
+                        .map(|artifact| artifact.filename.to_string())
+                        .collect(),
+                }
+            })
+            .collect::<Vec<_>>();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let bytes = serde_json::to_vec(&CachedResolution { packages })?;
+        let lock = self.store.lock(&key).await?;
+        let mut writer = lock.begin()?;
+        writer.write_all(&bytes)?;
+        writer.commit()?;
+        Ok(())
+    }
+}
+
+/// Returns a deterministic description of the options that influence the outcome of a resolution.
+fn options_key(options: &ResolveOptions) -> BTreeMap<&'static str, String> {
+    let pinned = |packages: &HashMap<NormalizedPackageName, PinnedPackage>| {
+        let mut pinned = packages
+            .values()
+            .map(|p| format!("{}=={}", p.name.as_str(), p.version))
+            .collect::<Vec<_>>();
+        pinned.sort();
+        pinned.join(",")
+    };
+
+    BTreeMap::from([
+        (
+            "sdist_resolution",
+            format!("{:?}", options.sdist_resolution),
+        ),
+        (
+            "pre_release_resolution",
+            format!("{:?}", options.pre_release_resolution),
+        ),
+        (
+            "on_wheel_build_failure",
+            format!("{:?}", options.on_wheel_build_failure),
+        ),
+        ("python_location", format!("{:?}", options.python_location)),
+        ("locked_packages", pinned(&options.locked_packages)),
+        ("favored_packages", pinned(&options.favored_packages)),
+        (
+            "env_variables",
+            format!(
+                "{:?}",
+                options.env_variables.iter().collect::<BTreeMap<_, _>>()
+            ),
+        ),
+        (
+            "allow_yanked_if_pinned",
+            options.allow_yanked_if_pinned.to_string(),
+        ),
+        (
+            "skip_sdist_fallback",
+            options.skip_sdist_fallback.to_string(),
+        ),
+        ("no_deps", options.no_deps.to_string()),
+        ("frozen", options.frozen.to_string()),
+        ("unknown_extras", format!("{:?}", options.unknown_extras)),
+        (
+            "resolution_strategy",
+            format!("{:?}", options.resolution_strategy),
+        ),
+        (
+            "exclude",
+            format!(
+                "{:?}",
+                options
+                    .exclude
+                    .iter()
+                    .map(|name| name.as_str())
+                    .collect::<BTreeSet<_>>()
+            ),
+        ),
+    ])
+}
@@ -1,6 +1,18 @@
 use crate::python_env::PythonInterpreterVersion;
 use std::borrow::Cow;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+/// An error that is returned by [`InstallPaths::custom`] if one of the paths is invalid.
+#[derive(Debug, Error)]
+#[error("the {category} path '{}' escapes the installation destination", path.display())]
+pub struct InvalidInstallPathError {
+    /// The installation category of the path, e.g. `purelib`.
+    pub category: &'static str,
+
+    /// The offending path.
+    pub path: PathBuf,
+}
 
 /// A struct of installation categories to where they should be stored relative to the
 /// installation destination.
@@ -50,6 +62,109 @@ impl InstallPaths {
         }
     }
 
+    /// Populates mappings of installation targets for a flat target directory, similar to pip's
+    /// `--target` option. All python packages and data are installed directly into `dir` and scripts
+    /// are placed in `dir/bin`.
+    ///
+    /// The returned paths are rooted at `dir`. If `dir` is an absolute path the destination passed
+    /// to [`super::install_wheel`] is effectively ignored.
+    pub fn for_target(dir: impl Into<PathBuf>, windows: bool) -> Self {
+        let dir = dir.into();
+        Self {
+            purelib: dir.clone(),
+            platlib: dir.clone(),
+            scripts: dir.join("bin"),
+            data: dir.clone(),
+            headers: dir.join("include"),
+            windows,
+        }
+    }
+
+    /// Populates mappings of installation targets for a prefix, similar to pip's `--prefix` option.
+    /// On posix systems this follows the `posix_prefix` scheme, on windows the `nt` scheme is used.
+    ///
+    /// The returned paths are rooted at `prefix`. If `prefix` is an absolute path the destination
+    /// passed to [`super::install_wheel`] is effectively ignored.
+    pub fn for_prefix<V: Into<PythonInterpreterVersion>>(
+        prefix: impl Into<PathBuf>,
+        version: V,
+        windows: bool,
+    ) -> Self {
+        let prefix = prefix.into();
+        let version = version.into();
+
+        let (site_packages, scripts, headers) = if windows {
+            (
+                prefix.join("Lib").join("site-packages"),
+                prefix.join("Scripts"),
+                prefix.join("Include"),
+            )
+        } else {
+            let python = format!("python{}.{}", version.major, version.minor);
+            (
+                prefix.join("lib").join(&python).join("site-packages"),
+                prefix.join("bin"),
+                prefix.join("include").join(&python),
+            )
+        };
+
+        Self {
+            purelib: site_packages.clone(),
+            platlib: site_packages,
+            scripts,
+            data: prefix,
+            headers,
+            windows,
+        }
+    }
+
+    /// Constructs the mappings of installation targets from the given paths. This can be used for
+    /// layouts that are not covered by the other constructors, e.g. the `dist-packages` directory
+    /// that Debian uses instead of `site-packages`.
+    ///
+    /// Relative paths are resolved against the destination passed to [`super::install_wheel`] and
+    /// must not contain `..` components. Absolute paths are used as is, like with
+    /// [`Self::for_target`]. The `headers` path is the directory in which a subdirectory is created
+    /// for the headers of each distribution.
+    pub fn custom(
+        purelib: impl Into<PathBuf>,
+        platlib: impl Into<PathBuf>,
+        scripts: impl Into<PathBuf>,
+        data: impl Into<PathBuf>,
+        headers: impl Into<PathBuf>,
+        windows: bool,
+    ) -> Result<Self, InvalidInstallPathError> {
+        let paths = Self {
+            purelib: purelib.into(),
+            platlib: platlib.into(),
+            scripts: scripts.into(),
+            data: data.into(),
+            headers: headers.into(),
+            windows,
+        };
+
+        for (category, path) in [
+            ("purelib", &paths.purelib),
+            ("platlib", &paths.platlib),
+            ("scripts", &paths.scripts),
+            ("data", &paths.data),
+            ("headers", &paths.headers),
+        ] {
+            if !path.is_absolute()
+                && path
+                    .components()
+                    .any(|component| component == Component::ParentDir)
+            {
+                return Err(InvalidInstallPathError {
+                    category,
+                    path: path.clone(),
+                });
+            }
+        }
+
+        Ok(paths)
+    }
+
     /// Determines whether this is a windows InstallPath
     pub fn is_windows(&self) -> bool {
         self.windows
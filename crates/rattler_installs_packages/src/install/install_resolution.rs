@@ -0,0 +1,813 @@
+use super::{
+    wheel_file_destinations, InstallError, InstallPaths, InstallWheelOptions, InstalledWheel,
+    WheelFileContents,
+};
+use crate::{
+    artifacts::{wheel::WheelVitalsError, Wheel},
+    index::PackageDb,
+    python_env::{uninstall_distribution, UninstallDistributionOptions, VEnv},
+    resolve::PinnedPackage,
+    types::{DirectUrlJson, Extra, NormalizedPackageName, PackageName},
+    wheel_builder::WheelBuilder,
+};
+use futures::{pin_mut, stream, StreamExt};
+use indexmap::IndexMap;
+use std::{
+    any::Any,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    num::NonZeroUsize,
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{mpsc::channel, Arc},
+};
+use thiserror::Error;
+use tokio::runtime::RuntimeFlavor;
+
+/// An event that is reported while installing a resolution with [`install_resolution`].
+#[derive(Debug, Clone)]
+pub enum InstallResolutionEvent {
+    /// The wheel of a package is being downloaded, or built if the package only has an sdist.
+    Fetching(NormalizedPackageName),
+
+    /// The wheel of a package is being installed.
+    Installing(NormalizedPackageName),
+
+    /// A package has been installed into the `.dist-info` directory at the given path.
+    Installed(NormalizedPackageName, PathBuf),
+
+    /// A package that was installed before the installation failed has been removed again.
+    RolledBack(NormalizedPackageName),
+}
+
+/// An error that can occur when installing a resolution with [`install_resolution`].
+#[derive(Debug, Error)]
+#[allow(missing_docs)]
+pub enum InstallResolutionError {
+    #[error("there are no artifacts available for {0}")]
+    NoArtifacts(String),
+
+    #[error("failed to obtain a wheel for {0}: {1}")]
+    FailedToFetchWheel(String, String),
+
+    #[error("failed to read the metadata of the wheel of {0}")]
+    FailedToReadMetadata(String, #[source] Box<WheelVitalsError>),
+
+    #[error("failed to install {0}")]
+    FailedToInstall(String, #[source] Box<InstallError>),
+
+    #[error("{first} and {second} both install '{path}' with different contents")]
+    ConflictingFiles {
+        path: String,
+        first: String,
+        second: String,
+    },
+}
+
+/// Options for [`install_resolution`].
+#[derive(Clone)]
+pub struct InstallResolutionOptions<'i> {
+    /// The options that are used to install every wheel. The `direct_url_json` and
+    /// `unpacked_wheel` fields are ignored, see [`install_resolution`].
+    pub wheel_options: InstallWheelOptions<'i>,
+
+    /// The maximum number of wheels that are installed at the same time. Defaults to the number
+    /// of available cpus.
+    pub max_concurrent_installs: usize,
+}
+
+impl Default for InstallResolutionOptions<'_> {
+    fn default() -> Self {
+        Self {
+            wheel_options: InstallWheelOptions::default(),
+            max_concurrent_installs: std::thread::available_parallelism()
+                .map_or(1, NonZeroUsize::get),
+        }
+    }
+}
+
+/// Installs the packages of a resolution into a virtual environment.
+///
+/// The wheel of every package is downloaded, or built from an sdist with the `wheel_builder`,
+/// before anything is installed. Up to [`crate::index::PackageSources::concurrency`] wheels are
+/// obtained concurrently. The wheels are then installed on up to
+/// [`InstallResolutionOptions::max_concurrent_installs`] threads, a package is only installed
+/// after its dependencies. The `direct_url.json` of packages that were obtained from a direct url
+/// is written as well, [`InstallWheelOptions::direct_url_json`] and
+/// [`InstallWheelOptions::unpacked_wheel`] of the wheel options are therefore ignored.
+///
+/// Before anything is installed the files of all wheels are checked for conflicts. If two wheels
+/// contain a different file at the same location [`InstallResolutionError::ConflictingFiles`] is
+/// returned, so the outcome never depends on which wheel happens to be installed last. Identical
+/// files, e.g. the `__init__.py` of a shared namespace package, are allowed.
+///
+/// If installing any of the packages fails, the packages that were already installed by this
+/// function are uninstalled again, and the files that the failed installations had written so far
+/// are removed. Distributions that were already present in the environment
+/// before and were overwritten are not restored. A panic while installing a package is resumed
+/// after this rollback.
+///
+/// Progress is reported through `on_event`.
+pub async fn install_resolution(
+    pinned_packages: &[PinnedPackage],
+    package_db: &PackageDb,
+    wheel_builder: Option<Arc<WheelBuilder>>,
+    venv: &VEnv,
+    options: &InstallResolutionOptions<'_>,
+    mut on_event: impl FnMut(InstallResolutionEvent),
+) -> Result<Vec<InstalledWheel>, InstallResolutionError> {
+    // Obtain all the wheels first, so a failure to download or build a wheel does not leave the
+    // environment in a partially installed state.
+    let mut fetched = HashMap::new();
+    {
+        let fetches = stream::iter(pinned_packages)
+            .map(|package| {
+                on_event(InstallResolutionEvent::Fetching(package.name.clone()));
+                let wheel_builder = wheel_builder.clone();
+                async move {
+                    (
+                        package,
+                        fetch_wheel(package, package_db, wheel_builder).await,
+                    )
+                }
+            })
+            .buffer_unordered(package_db.sources().concurrency());
+        pin_mut!(fetches);
+        while let Some((package, result)) = fetches.next().await {
+            fetched.insert(package.name.clone(), result?);
+        }
+    }
+
+    // The wheels finish downloading in any order, the order of the resolution is kept to make
+    // the install order deterministic.
+    let mut wheels = HashMap::new();
+    let mut dependencies = IndexMap::new();
+    for package in pinned_packages {
+        if let Some((wheel, direct_url_json, package_dependencies)) = fetched.remove(&package.name)
+        {
+            wheels.insert(package.name.clone(), (wheel, direct_url_json));
+            dependencies.insert(package.name.clone(), package_dependencies);
+        }
+    }
+
+    let order = install_order(&dependencies);
+    check_conflicting_files(
+        order.iter().map(|name| (name, &wheels[name].0)),
+        venv.install_paths(),
+        options.wheel_options.extras.as_ref(),
+    )?;
+
+    // Dependencies that are not part of the resolution are not waited for
+    for package_dependencies in dependencies.values_mut() {
+        package_dependencies.retain(|name| wheels.contains_key(name));
+    }
+
+    // Installing the wheels blocks the current thread. On a multi-threaded runtime the other
+    // tasks of this thread are moved elsewhere in the meantime, which is not possible on a
+    // current-thread runtime.
+    let install =
+        || install_concurrently(venv, wheels, &dependencies, order, options, &mut on_event);
+    let in_multi_thread_runtime = tokio::runtime::Handle::try_current()
+        .is_ok_and(|handle| handle.runtime_flavor() == RuntimeFlavor::MultiThread);
+    let (installed, failure) = if in_multi_thread_runtime {
+        tokio::task::block_in_place(install)
+    } else {
+        install()
+    };
+
+    if let Some(failure) = failure {
+        let site_packages = venv.root().join(venv.install_paths().site_packages());
+        rollback(&site_packages, installed, &mut on_event);
+        return match failure {
+            InstallFailure::Error(name, err) => Err(InstallResolutionError::FailedToInstall(
+                name.to_string(),
+                Box::new(err),
+            )),
+            InstallFailure::Panic(payload) => std::panic::resume_unwind(payload),
+        };
+    }
+
+    Ok(installed
+        .into_iter()
+        .map(|(_, installed_wheel)| installed_wheel)
+        .collect())
+}
+
+/// The reason why [`install_concurrently`] stopped installing packages.
+enum InstallFailure {
+    /// Installing the package failed.
+    Error(NormalizedPackageName, InstallError),
+
+    /// An installation thread panicked with the given payload.
+    Panic(Box<dyn Any + Send>),
+}
+
+/// Installs the wheels on up to [`InstallResolutionOptions::max_concurrent_installs`] threads, a
+/// package is only installed after all of its `dependencies` have been installed. Once an
+/// installation fails no new installations are started, but the running ones are finished so they
+/// can be rolled back as well.
+///
+/// Returns the packages that were installed and the failure that stopped the installation, if
+/// any.
+fn install_concurrently(
+    venv: &VEnv,
+    mut wheels: HashMap<NormalizedPackageName, (Wheel, Option<DirectUrlJson>)>,
+    dependencies: &IndexMap<NormalizedPackageName, Vec<NormalizedPackageName>>,
+    mut pending: Vec<NormalizedPackageName>,
+    options: &InstallResolutionOptions,
+    on_event: &mut impl FnMut(InstallResolutionEvent),
+) -> (
+    Vec<(NormalizedPackageName, InstalledWheel)>,
+    Option<InstallFailure>,
+) {
+    let max_concurrent_installs = options.max_concurrent_installs.max(1);
+    let mut installed = Vec::with_capacity(pending.len());
+    let mut installed_names = HashSet::new();
+    let mut failure = None;
+    std::thread::scope(|scope| {
+        let (tx, rx) = channel();
+        let mut running = 0;
+        loop {
+            // Start installing the packages of which all dependencies have been installed. If
+            // nothing can be started while nothing is running the remaining packages depend on
+            // each other, in which case the cycle is broken by the install order.
+            while failure.is_none() && running < max_concurrent_installs {
+                let ready = pending.iter().position(|name| {
+                    dependencies[name]
+                        .iter()
+                        .all(|dependency| installed_names.contains(dependency))
+                });
+                let Some(index) = ready.or((running == 0 && !pending.is_empty()).then_some(0))
+                else {
+                    break;
+                };
+                let name = pending.remove(index);
+                let (wheel, direct_url_json) = wheels
+                    .remove(&name)
+                    .expect("the order only contains packages of the resolution");
+
+                on_event(InstallResolutionEvent::Installing(name.clone()));
+                let tx = tx.clone();
+                scope.spawn(move || {
+                    // A panic is sent back as well, otherwise the receiving end would wait for
+                    // this thread forever.
+                    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                        install_one(venv, &wheel, direct_url_json, &options.wheel_options)
+                    }));
+                    let _ = tx.send((name, result));
+                });
+                running += 1;
+            }
+
+            if running == 0 {
+                break;
+            }
+
+            let (name, result) = rx
+                .recv()
+                .expect("every installation thread sends its result");
+            running -= 1;
+            match result {
+                Ok(Ok(installed_wheel)) => {
+                    on_event(InstallResolutionEvent::Installed(
+                        name.clone(),
+                        installed_wheel.dist_info.clone(),
+                    ));
+                    installed_names.insert(name.clone());
+                    installed.push((name, installed_wheel));
+                }
+                Ok(Err(err)) => {
+                    failure.get_or_insert(InstallFailure::Error(name, err));
+                }
+                Err(payload) => {
+                    // A panic takes precedence over an error, it is resumed after the rollback
+                    if !matches!(failure, Some(InstallFailure::Panic(_))) {
+                        failure = Some(InstallFailure::Panic(payload));
+                    }
+                }
+            }
+        }
+    });
+
+    (installed, failure)
+}
+
+/// Downloads the wheel of a package, or builds it if the package only has an sdist, and reads
+/// the names of its dependencies from the metadata of the wheel.
+async fn fetch_wheel(
+    package: &PinnedPackage,
+    package_db: &PackageDb,
+    wheel_builder: Option<Arc<WheelBuilder>>,
+) -> Result<(Wheel, Option<DirectUrlJson>, Vec<NormalizedPackageName>), InstallResolutionError> {
+    let artifact_info = package
+        .artifacts
+        .first()
+        .ok_or_else(|| InstallResolutionError::NoArtifacts(package.name.to_string()))?;
+
+    let (wheel, direct_url_json) = package_db
+        .get_wheel(artifact_info, wheel_builder)
+        .await
+        .map_err(|err| {
+            InstallResolutionError::FailedToFetchWheel(package.name.to_string(), err.to_string())
+        })?;
+    let (_, metadata) = wheel.metadata().map_err(|err| {
+        InstallResolutionError::FailedToReadMetadata(package.name.to_string(), Box::new(err))
+    })?;
+    let dependencies = metadata
+        .requires_dist
+        .iter()
+        .filter_map(|requirement| PackageName::from_str(&requirement.name).ok())
+        .map(NormalizedPackageName::from)
+        .filter(|name| name != &package.name)
+        .collect();
+
+    Ok((wheel, direct_url_json, dependencies))
+}
+
+/// Checks that no two wheels install a different file at the same location. The wheels are
+/// checked in the given order, which makes the reported conflict deterministic.
+fn check_conflicting_files<'a>(
+    wheels: impl IntoIterator<Item = (&'a NormalizedPackageName, &'a Wheel)>,
+    paths: &InstallPaths,
+    extras: Option<&HashSet<Extra>>,
+) -> Result<(), InstallResolutionError> {
+    let mut owners: HashMap<PathBuf, (&NormalizedPackageName, WheelFileContents)> = HashMap::new();
+    for (name, wheel) in wheels {
+        let destinations = wheel_file_destinations(wheel, paths, extras).map_err(|err| {
+            InstallResolutionError::FailedToInstall(name.to_string(), Box::new(err))
+        })?;
+        for destination in destinations {
+            match owners.entry(destination.path) {
+                Entry::Vacant(entry) => {
+                    entry.insert((name, destination.contents));
+                }
+                Entry::Occupied(entry) => {
+                    let (owner, contents) = entry.get();
+                    if *contents != destination.contents {
+                        return Err(InstallResolutionError::ConflictingFiles {
+                            path: entry.key().display().to_string(),
+                            first: owner.to_string(),
+                            second: name.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Installs a single wheel with the `direct_url.json` that belongs to it.
+fn install_one(
+    venv: &VEnv,
+    wheel: &Wheel,
+    direct_url_json: Option<DirectUrlJson>,
+    options: &InstallWheelOptions,
+) -> Result<InstalledWheel, InstallError> {
+    venv.install_wheel(
+        wheel,
+        &InstallWheelOptions {
+            direct_url_json,
+            unpacked_wheel: None,
+            ..options.clone()
+        },
+    )
+}
+
+/// Uninstalls the given distributions in reverse order. Failures are logged but otherwise
+/// ignored, the error that caused the rollback is more relevant to the caller.
+fn rollback(
+    site_packages: &Path,
+    installed: Vec<(NormalizedPackageName, InstalledWheel)>,
+    on_event: &mut impl FnMut(InstallResolutionEvent),
+) {
+    for (name, installed_wheel) in installed.into_iter().rev() {
+        let dist_info = installed_wheel
+            .dist_info
+            .strip_prefix(site_packages)
+            .unwrap_or(&installed_wheel.dist_info);
+        match uninstall_distribution(
+            site_packages,
+            dist_info,
+            &UninstallDistributionOptions::default(),
+        ) {
+            Ok(()) => on_event(InstallResolutionEvent::RolledBack(name)),
+            Err(err) => {
+                tracing::warn!("failed to roll back the installation of {name}: {err}");
+            }
+        }
+    }
+}
+
+/// Returns the names of the packages in an order in which every package comes after its
+/// dependencies. Dependencies that are not part of `dependencies` are ignored and cycles are
+/// broken arbitrarily. Packages without a dependency relation keep their relative order.
+fn install_order(
+    dependencies: &IndexMap<NormalizedPackageName, Vec<NormalizedPackageName>>,
+) -> Vec<NormalizedPackageName> {
+    fn visit(
+        name: &NormalizedPackageName,
+        dependencies: &IndexMap<NormalizedPackageName, Vec<NormalizedPackageName>>,
+        visited: &mut HashSet<NormalizedPackageName>,
+        order: &mut Vec<NormalizedPackageName>,
+    ) {
+        let Some(package_dependencies) = dependencies.get(name) else {
+            return;
+        };
+        if !visited.insert(name.clone()) {
+            return;
+        }
+        for dependency in package_dependencies.iter() {
+            visit(dependency, dependencies, visited, order);
+        }
+        order.push(name.clone());
+    }
+
+    let mut visited = HashSet::new();
+    let mut order = Vec::with_capacity(dependencies.len());
+    for name in dependencies.keys() {
+        visit(name, dependencies, &mut visited, &mut order);
+    }
+    order
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        python_env::{Pep508EnvMakers, PythonLocation},
+        resolve::{resolve, solve_options::ResolveOptions},
+        types::{
+            ArtifactInfo, ArtifactName, DistInfoMetadata, RecordHashAlgorithm, RecordHasher, Yanked,
+        },
+        utils::{self, get_package_db},
+    };
+    use futures::FutureExt;
+    use pep508_rs::Requirement;
+    use std::io::Write;
+    use url::Url;
+
+    const WHEEL_INFO: &str =
+        "Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: true\nTag: py3-none-any\n";
+
+    /// Creates an in-memory wheel of `name` that contains the given files.
+    fn make_wheel(name: &str, files: &[(&str, &str)]) -> Wheel {
+        let metadata = format!("Metadata-Version: 2.1\nName: {name}\nVersion: 1.0.0\n");
+        let metadata_path = format!("{name}-1.0.0.dist-info/METADATA");
+        let wheel_info_path = format!("{name}-1.0.0.dist-info/WHEEL");
+        let mut entries = vec![
+            (metadata_path.as_str(), metadata.as_str()),
+            (wheel_info_path.as_str(), WHEEL_INFO),
+        ];
+        entries.extend_from_slice(files);
+        utils::make_wheel(&format!("{name}-1.0.0-py3-none-any.whl"), &entries)
+    }
+
+    /// Writes a wheel that contains the given files to `dir` and returns a package of the
+    /// resolution that refers to it.
+    fn pinned_wheel(dir: &Path, name: &str, files: &[(&str, &str)]) -> PinnedPackage {
+        let filename = format!("{name}-1.0.0-py3-none-any.whl");
+        let path = dir.join(&filename);
+        let wheel_info_path = format!("{name}-1.0.0.dist-info/WHEEL");
+        let mut entries = vec![(wheel_info_path.as_str(), WHEEL_INFO)];
+        entries.extend_from_slice(files);
+        std::fs::write(&path, utils::wheel_archive(&entries)).unwrap();
+
+        let name: NormalizedPackageName = name.parse().unwrap();
+        PinnedPackage {
+            artifacts: vec![Arc::new(ArtifactInfo {
+                filename: ArtifactName::from_filename(&filename, None, &name).unwrap(),
+                url: Url::from_file_path(&path).unwrap(),
+                is_direct_url: false,
+                hashes: None,
+                requires_python: None,
+                dist_info_metadata: DistInfoMetadata::default(),
+                yanked: Yanked::default(),
+            })],
+            name,
+            version: "1.0.0".parse().unwrap(),
+            url: None,
+            extras: HashSet::new(),
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_check_conflicting_files() {
+        let paths = InstallPaths::for_venv((3, 11, 4), false);
+        let name = |name: &str| name.parse::<NormalizedPackageName>().unwrap();
+        let (a, b, c) = (name("a"), name("b"), name("c"));
+
+        // Identical files, like the __init__.py of a namespace package, are fine
+        let wheel_a = make_wheel("a", &[("ns/__init__.py", ""), ("ns/a.py", "a = 1")]);
+        let wheel_b = make_wheel("b", &[("ns/__init__.py", ""), ("ns/b.py", "b = 1")]);
+        check_conflicting_files([(&a, &wheel_a), (&b, &wheel_b)], &paths, None).unwrap();
+
+        // Different files at the same location are reported, in the given order
+        let wheel_c = make_wheel("c", &[("ns/a.py", "a = 2")]);
+        let err = check_conflicting_files(
+            [(&a, &wheel_a), (&b, &wheel_b), (&c, &wheel_c)],
+            &paths,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            InstallResolutionError::ConflictingFiles { ref first, ref second, .. }
+                if first == "a" && second == "c"
+        ));
+        let err =
+            check_conflicting_files([(&c, &wheel_c), (&a, &wheel_a)], &paths, None).unwrap_err();
+        assert!(matches!(
+            err,
+            InstallResolutionError::ConflictingFiles { ref first, ref second, .. }
+                if first == "c" && second == "a"
+        ));
+    }
+
+    #[test]
+    fn test_check_conflicting_scripts() {
+        let paths = InstallPaths::for_venv((3, 11, 4), false);
+        let name = |name: &str| name.parse::<NormalizedPackageName>().unwrap();
+        let (a, b, c, d) = (name("a"), name("b"), name("c"), name("d"));
+        let wheel = |name: &str, entry_points: &str| {
+            make_wheel(
+                name,
+                &[(
+                    &format!("{name}-1.0.0.dist-info/entry_points.txt"),
+                    entry_points,
+                )],
+            )
+        };
+        let is_conflict = |result: Result<(), InstallResolutionError>| {
+            matches!(
+                result,
+                Err(InstallResolutionError::ConflictingFiles { ref path, .. })
+                    if path.ends_with("tool")
+            )
+        };
+
+        // Two packages that generate the same script for different entry points
+        let wheel_a = wheel("a", "[console_scripts]\ntool = a:main\n");
+        let wheel_b = wheel("b", "[gui_scripts]\ntool = b:main\n");
+        assert!(is_conflict(check_conflicting_files(
+            [(&a, &wheel_a), (&b, &wheel_b)],
+            &paths,
+            None
+        )));
+
+        // The same entry point generates the same script
+        let wheel_c = wheel("c", "[console_scripts]\ntool = a:main\n");
+        check_conflicting_files([(&a, &wheel_a), (&c, &wheel_c)], &paths, None).unwrap();
+
+        // A script from the data directory conflicts with a generated script
+        let wheel_d = make_wheel("d", &[("d-1.0.0.data/scripts/tool", "#!/bin/sh\n")]);
+        assert!(is_conflict(check_conflicting_files(
+            [(&d, &wheel_d), (&a, &wheel_a)],
+            &paths,
+            None
+        )));
+
+        // Entry points of extras that are not installed don't generate a script
+        let wheel_b = wheel("b", "[gui_scripts]\ntool = b:main [gui]\n");
+        check_conflicting_files(
+            [(&a, &wheel_a), (&b, &wheel_b)],
+            &paths,
+            Some(&HashSet::new()),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_install_order() {
+        let name = |name: &str| name.parse::<NormalizedPackageName>().unwrap();
+        let deps = |names: &[&str]| names.iter().map(|n| name(n)).collect::<Vec<_>>();
+
+        // a -> b -> c, d is unrelated, c and e depend on each other
+        let a = deps(&["b", "not-in-resolution"]);
+        let b = deps(&["c"]);
+        let c = deps(&["e"]);
+        let d = deps(&[]);
+        let e = deps(&["c"]);
+        let dependencies = IndexMap::from([
+            (name("a"), a),
+            (name("d"), d),
+            (name("b"), b),
+            (name("c"), c),
+            (name("e"), e),
+        ]);
+
+        assert_eq!(
+            install_order(&dependencies),
+            deps(&["e", "c", "b", "a", "d"])
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_install_resolution() {
+        let (package_db, _cache_dir) = get_package_db();
+        let env_markers = Arc::new(Pep508EnvMakers::from_env().await.unwrap().0);
+        let wheel_builder = WheelBuilder::new(
+            package_db.clone(),
+            env_markers.clone(),
+            None,
+            ResolveOptions::default(),
+        )
+        .unwrap();
+        let requirements = [Requirement::from_str("python-dateutil==2.8.2").unwrap()];
+        let pinned_packages = resolve(
+            package_db.clone(),
+            &requirements,
+            env_markers,
+            None,
+            wheel_builder.clone(),
+            ResolveOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let venv_dir = tempfile::tempdir().unwrap();
+        let venv = VEnv::create(venv_dir.path(), PythonLocation::System).unwrap();
+        let mut events = Vec::new();
+        let installed = install_resolution(
+            &pinned_packages,
+            &package_db,
+            Some(wheel_builder),
+            &venv,
+            &InstallResolutionOptions::default(),
+            |event| events.push(event),
+        )
+        .await
+        .unwrap();
+        assert_eq!(installed.len(), pinned_packages.len());
+        assert!(installed.iter().all(|wheel| wheel.dist_info.is_dir()));
+
+        // six is installed before python-dateutil, which depends on it
+        let installed_names = events
+            .iter()
+            .filter_map(|event| match event {
+                InstallResolutionEvent::Installed(name, _) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(installed_names, ["six", "python-dateutil"]);
+
+        let output = venv.execute_command("import dateutil").unwrap();
+        assert!(output.status.success());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_install_resolution_rollback() {
+        let (package_db, _cache_dir) = get_package_db();
+        let wheel_dir = tempfile::tempdir().unwrap();
+
+        // b depends on a, so a is installed first. The hash of the last file of b does not match
+        // its RECORD which makes its installation fail after some of its files have been written.
+        let a = pinned_wheel(
+            wheel_dir.path(),
+            "a",
+            &[
+                (
+                    "a-1.0.0.dist-info/METADATA",
+                    "Metadata-Version: 2.1\nName: a\nVersion: 1.0.0\n",
+                ),
+                ("a.py", "a = 1\n"),
+            ],
+        );
+        let b_files = [
+            (
+                "b-1.0.0.dist-info/METADATA",
+                "Metadata-Version: 2.1\nName: b\nVersion: 1.0.0\nRequires-Dist: a\n",
+            ),
+            ("b/__init__.py", ""),
+            ("b/sub/module.py", "b = 1\n"),
+            ("b/sub/broken.py", "b = 2\n"),
+        ];
+        let hash = |contents: &str| {
+            let mut hasher = RecordHasher::new([RecordHashAlgorithm::Sha256]);
+            hasher.write_all(contents.as_bytes()).unwrap();
+            hasher
+                .finalize()
+                .get(RecordHashAlgorithm::Sha256)
+                .to_string()
+        };
+        let mut b_record = format!("b-1.0.0.dist-info/WHEEL,{},\n", hash(WHEEL_INFO));
+        for (path, contents) in b_files {
+            let contents = if path.ends_with("broken.py") {
+                "something else"
+            } else {
+                contents
+            };
+            b_record.push_str(&format!("{path},{},\n", hash(contents)));
+        }
+        b_record.push_str("b-1.0.0.dist-info/RECORD,,\n");
+        let mut b_entries = vec![("b-1.0.0.dist-info/RECORD", b_record.as_str())];
+        b_entries.extend_from_slice(&b_files);
+        let b = pinned_wheel(wheel_dir.path(), "b", &b_entries);
+
+        let venv_dir = tempfile::tempdir().unwrap();
+        let venv = VEnv::create(venv_dir.path(), PythonLocation::System).unwrap();
+        let site_packages = venv_dir.path().join(venv.install_paths().site_packages());
+        let list_site_packages = || {
+            let mut entries = std::fs::read_dir(&site_packages)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .collect::<Vec<_>>();
+            entries.sort();
+            entries
+        };
+        let before = list_site_packages();
+
+        let mut events = Vec::new();
+        let err = install_resolution(
+            &[b, a],
+            &package_db,
+            None,
+            &venv,
+            &InstallResolutionOptions {
+                wheel_options: InstallWheelOptions {
+                    tolerate_missing_record: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            |event| events.push(event),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, InstallResolutionError::FailedToInstall(ref name, _) if name == "b"));
+
+        // a was installed and removed again, the files that b wrote before it failed are removed
+        // as well. The environment is the same as before.
+        assert!(events.iter().any(
+            |event| matches!(event, InstallResolutionEvent::Installed(name, _) if name.as_str() == "a")
+        ));
+        assert!(events.iter().any(
+            |event| matches!(event, InstallResolutionEvent::RolledBack(name) if name.as_str() == "a")
+        ));
+        assert_eq!(list_site_packages(), before);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_install_resolution_panic() {
+        let (package_db, _cache_dir) = get_package_db();
+        let wheel_dir = tempfile::tempdir().unwrap();
+        let a = pinned_wheel(
+            wheel_dir.path(),
+            "a",
+            &[(
+                "a-1.0.0.dist-info/METADATA",
+                "Metadata-Version: 2.1\nName: a\nVersion: 1.0.0\n",
+            )],
+        );
+        let b = pinned_wheel(
+            wheel_dir.path(),
+            "b",
+            &[
+                (
+                    "b-1.0.0.dist-info/METADATA",
+                    "Metadata-Version: 2.1\nName: b\nVersion: 1.0.0\nRequires-Dist: a\n",
+                ),
+                ("b.py", "b = 1\n"),
+            ],
+        );
+
+        let venv_dir = tempfile::tempdir().unwrap();
+        let venv = VEnv::create(venv_dir.path(), PythonLocation::System).unwrap();
+        let site_packages = venv_dir.path().join(venv.install_paths().site_packages());
+        let panic_on_b = |installed: &InstalledWheel| {
+            if installed.dist_info.ends_with("b-1.0.0.dist-info") {
+                panic!("installing b panicked");
+            }
+        };
+
+        // The panic is propagated instead of waiting for the panicked thread forever, after the
+        // packages that were installed have been rolled back.
+        let mut events = Vec::new();
+        let result = AssertUnwindSafe(install_resolution(
+            &[a, b],
+            &package_db,
+            None,
+            &venv,
+            &InstallResolutionOptions {
+                wheel_options: InstallWheelOptions {
+                    tolerate_missing_record: true,
+                    post_install: Some(&panic_on_b),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            |event| events.push(event),
+        ))
+        .catch_unwind()
+        .await;
+        assert!(result.is_err());
+        assert!(events.iter().any(
+            |event| matches!(event, InstallResolutionEvent::RolledBack(name) if name.as_str() == "a")
+        ));
+
+        // The files of b were written before it panicked, they are removed as well
+        assert!(!site_packages.join("b.py").exists());
+        assert!(!site_packages.join("b-1.0.0.dist-info").exists());
+    }
+}
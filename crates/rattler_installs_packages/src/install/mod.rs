@@ -3,33 +3,44 @@
 use crate::{
     artifacts::wheel::WheelVitalsError,
     artifacts::Wheel,
-    python_env::{ByteCodeCompiler, CompilationError},
-    types::{DirectUrlJson, EntryPoint, Extra, Record, RecordEntry},
-    utils::ReadAndSeek,
-    win::launcher::{build_windows_launcher, LauncherType, WindowsLauncherArch},
+    python_env::{
+        ByteCodeCompiler, CompilationError, PythonInterpreterVersion, WheelTag, WheelTags,
+    },
+    types::{
+        DirectUrlJson, EntryPoint, EntryPoints, Extra, Record, RecordEntry, RecordHashAlgorithm,
+        RecordHasher, RecordHashes, RecordHashingReader,
+    },
+    utils::{normalize_path, ReadAndSeek},
+    win::launcher::build_windows_launcher,
 };
-use configparser::ini::Ini;
-use data_encoding::BASE64URL_NOPAD;
-use rattler_digest::Sha256;
+use itertools::Itertools;
+use pep440_rs::Version;
 use std::str::FromStr;
 use std::{
     borrow::Cow,
-    collections::{HashMap, HashSet},
+    collections::HashSet,
     ffi::OsStr,
     fs,
     io::{BufRead, BufReader, Read, Write},
     path::{Component, Path, PathBuf},
-    sync::mpsc::channel,
+    sync::mpsc::{channel, Receiver, Sender},
 };
 use thiserror::Error;
 use zip::result::ZipError;
 use zip::ZipArchive;
 
 mod install_paths;
-
-use crate::artifacts::wheel::find_dist_info_metadata;
-use crate::types::{HasArtifactName, RFC822ish, WheelCoreMetaDataError};
-pub use install_paths::InstallPaths;
+mod install_resolution;
+mod unpacked_wheel_cache;
+
+use crate::artifacts::wheel::{find_dist_info_metadata, parse_format_metadata_and_check_version};
+use crate::types::{HasArtifactName, RFC822ish, WheelCoreMetaDataError, WheelCoreMetadata};
+pub use crate::win::launcher::{LauncherType, WindowsLauncherArch};
+pub use install_paths::{InstallPaths, InvalidInstallPathError};
+pub use install_resolution::{
+    install_resolution, InstallResolutionError, InstallResolutionEvent, InstallResolutionOptions,
+};
+pub use unpacked_wheel_cache::{UnpackedWheel, UnpackedWheelCache};
 
 #[derive(Debug, Error)]
 #[allow(missing_docs)]
@@ -52,9 +63,21 @@ pub enum InstallError {
     #[error("RECORD file doesn't match wheel contents: {0}")]
     RecordFile(String),
 
+    #[error("invalid Tag '{0}' in WHEEL")]
+    InvalidWheelTag(String),
+
+    #[error("the wheel is not compatible with the target environment, it supports: {0}")]
+    IncompatibleWheelTags(String),
+
+    #[error("the wheel requires python {requires} but the target interpreter is python {found}")]
+    RequiresPythonMismatch { requires: String, found: String },
+
     #[error("unrecognized .data directory: {0}")]
     UnsupportedDataDirectory(String),
 
+    #[error("invalid path in .data directory: {0}")]
+    InvalidDataPath(String),
+
     #[error("entry_points.txt invalid, {0}")]
     EntryPointsInvalid(String),
 
@@ -80,7 +103,7 @@ impl InstallError {
 /// Additional optional settings to pass to [`install_wheel`].
 ///
 /// Not all options in this struct are relevant. Typically, you will default a number of fields.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct InstallWheelOptions<'i> {
     /// When specified an INSTALLER file is written to the dist-info folder of the package.
     /// INSTALLER files are used to track the installer of a package. See [PEP 376](https://peps.python.org/pep-0376/) for more information.
@@ -92,19 +115,127 @@ pub struct InstallWheelOptions<'i> {
     /// performed. This is the default.
     pub extras: Option<HashSet<Extra>>,
 
-    /// The architecture of the launcher executable that is created for every entry point on windows.
-    /// If this field is `None` the architecture will be determined based on the architecture of the
-    /// current process.
+    /// The architecture of the launcher executable that is created for every entry point when
+    /// installing for windows (see [`InstallPaths::is_windows`]), regardless of the host operating
+    /// system. If this field is `None` the architecture will be determined based on the
+    /// architecture of the current process. Set this explicitly when installing into an environment
+    /// for a different architecture, e.g. when creating a win-arm64 environment from an x86_64 host.
     pub launcher_arch: Option<WindowsLauncherArch>,
 
+    /// Determines the shebang of the entry point scripts that are created when installing for unix.
+    /// By default the absolute path of the python executable is used. This option has no effect
+    /// when installing for windows.
+    pub shebang_style: ShebangStyle,
+
+    /// Determines how the entry points in the `gui_scripts` group are installed on unix. On
+    /// windows gui scripts use a launcher that does not open a console window, unix has no such
+    /// distinction and by default gui scripts are installed exactly like console scripts. Either
+    /// way the group of every entry point is preserved in the `entry_points.txt` file of the
+    /// installed `.dist-info` directory. This option has no effect when installing for windows.
+    pub unix_gui_scripts: UnixGuiScripts,
+
+    /// Determines how files are placed in the destination directory. Anything other than
+    /// [`LinkMode::Copy`] requires [`Self::unpacked_wheel`] to be set, otherwise the files are
+    /// copied from the wheel. Scripts and generated files are always written as regular files.
+    pub link_mode: LinkMode,
+
+    /// A directory that contains the unpacked contents of the wheel that is being installed. Files
+    /// are linked from this directory according to [`Self::link_mode`]. The hashes of the files
+    /// are still verified against the RECORD of the wheel. See [`UnpackedWheelCache`] to obtain
+    /// such a directory.
+    pub unpacked_wheel: Option<&'i Path>,
+
     /// A reference to a bytecode compiler that can be used to compile the bytecode of the wheel. If
     /// this field is `None` bytecode compilation will be skipped.
     pub byte_code_compiler: Option<&'i ByteCodeCompiler>,
 
+    /// Also record the hash and size of the compiled bytecode files in the RECORD file. By default
+    /// these are recorded without a hash, like pip does. With this option every entry in the
+    /// RECORD, except for the RECORD itself, has a hash which allows verifying the integrity of
+    /// the entire installed tree. By default bytecode embeds the modification time of its source,
+    /// use a [`ByteCodeCompiler`] with a hash based [`crate::python_env::InvalidationMode`] to get
+    /// hashes that are stable across installations.
+    pub hash_generated_files: bool,
+
+    /// The hash algorithm that is used for the entries of the RECORD file that is written. Files
+    /// from the wheel are always verified against the RECORD of the wheel, regardless of which
+    /// supported algorithm the wheel uses. Defaults to sha256.
+    pub record_hash_algorithm: RecordHashAlgorithm,
+
     /// The `direct_url.json` file that should be written to the dist-info folder of the package.
     /// because when using `unpack` on the wheel we do not know where it came from.
     /// This needs to be supplied manually.
     pub direct_url_json: Option<DirectUrlJson>,
+
+    /// Install wheels that do not contain a RECORD file instead of returning an error. The files
+    /// of such a wheel cannot be verified, they are installed as is and a RECORD is generated from
+    /// the files that were written, like pip does. Wheels that do contain a RECORD are always
+    /// verified against it.
+    pub tolerate_missing_record: bool,
+
+    /// When specified the `Tag` entries of the WHEEL file are checked against these tags before
+    /// anything is installed. If none of the tags of the wheel is compatible an error is returned.
+    /// If the WHEEL file does not declare any tags the tags from the filename are used instead.
+    pub verify_tags: Option<&'i WheelTags>,
+
+    /// When specified the `Requires-Python` field of the METADATA of the wheel is checked against
+    /// this interpreter version before anything is installed. An error is returned if the version
+    /// is not supported by the wheel. Leave this `None` to unpack a wheel regardless of the
+    /// interpreter, e.g. for inspection.
+    pub verify_requires_python: Option<PythonInterpreterVersion>,
+
+    /// A function that is called after the wheel has been installed and its RECORD has been
+    /// written, e.g. to run additional post-processing on the installed files. The function
+    /// receives the same [`InstalledWheel`] that is returned from [`install_wheel`].
+    pub post_install: Option<&'i (dyn Fn(&InstalledWheel) + Send + Sync)>,
+}
+
+/// Defines how the shebang of the entry point scripts on unix is constructed.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub enum ShebangStyle {
+    /// Use the absolute path of the python executable, e.g. `#!/path/to/venv/bin/python`.
+    #[default]
+    AbsolutePath,
+
+    /// Resolve the python interpreter with the given major and minor version from the `PATH`
+    /// using `env`, e.g. `#!/usr/bin/env python3.11`. This results in scripts that are portable
+    /// across machines where the absolute path of the interpreter differs.
+    Env(PythonInterpreterVersion),
+}
+
+/// Defines how gui scripts are installed on unix, see [`InstallWheelOptions::unix_gui_scripts`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum UnixGuiScripts {
+    /// Install gui scripts exactly like console scripts. This matches the behavior of pip.
+    #[default]
+    Console,
+
+    /// Detach gui scripts from the terminal they are started from, which is the closest equivalent
+    /// of `pythonw` on windows. The script forks, the child starts a new session with `setsid`
+    /// and its standard streams are redirected to `/dev/null`, and the parent exits immediately.
+    /// As a result the exit code of the application is not returned to the caller.
+    Detach,
+}
+
+/// Defines how the files of a wheel are placed in the destination directory when the wheel has
+/// already been unpacked elsewhere, see [`InstallWheelOptions::unpacked_wheel`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum LinkMode {
+    /// Copy the files from the wheel.
+    #[default]
+    Copy,
+
+    /// Hardlink the files from the unpacked wheel. Falls back to copying if the files cannot be
+    /// hardlinked, e.g. because they reside on a different filesystem.
+    Hardlink,
+
+    /// Symlink the files from the unpacked wheel. Falls back to copying if the files cannot be
+    /// symlinked.
+    Symlink,
+
+    /// Clone the files from the unpacked wheel using copy-on-write if the filesystem supports it.
+    /// Falls back to copying otherwise.
+    Reflink,
 }
 
 #[derive(Debug)]
@@ -112,9 +243,18 @@ pub struct InstallWheelOptions<'i> {
 pub struct InstalledWheel {
     /// The path to the *.dist-info directory of the unpacked wheel.
     pub dist_info: PathBuf,
+
+    /// The paths of all files that were installed, including the generated files and the RECORD
+    /// itself. These are the entries of the RECORD resolved against the destination directory.
+    pub files: Vec<PathBuf>,
 }
 
 /// Unpacks a wheel to the given filesystem.
+///
+/// If the installation fails or panics, including a panic in
+/// [`InstallWheelOptions::post_install`], the files and directories that were written are removed
+/// again. Files of a previous installation that were overwritten are not restored.
+///
 /// The following functionality is still missing:
 /// - REQUESTED (<https://peps.python.org/pep-0376/#requested>)
 pub fn install_wheel(
@@ -132,19 +272,22 @@ pub fn install_wheel(
             .1
             .to_owned();
 
-    // Read the WHEEL from the archive.
-    let wheel_path = format!("{dist_info_prefix}.dist-info/WHEEL");
-    let wheel_metadata = read_entry_to_end(&mut archive, &wheel_path)
-        .map_err(|err| InstallError::ZipError(wheel_path, err))?;
-
-    // Parse the contents of the wheel and verify its version.
-    let mut parsed = parse_format_metadata_and_check_version(&wheel_metadata, "Wheel-Version")?;
+    // Read the WHEEL from the archive, parse it and verify its version.
+    let mut parsed = read_wheel_file(&mut archive, &dist_info_prefix)?;
 
     // Find the value for Root-Is-Purelib
     let root_is_purelib = parse_root_is_purelib(&mut parsed)
         .map_err(WheelVitalsError::InvalidMetadata)
         .map_err(InstallError::FailedToParseWheelVitals)?;
 
+    // Make sure the wheel can be installed in the target environment
+    if let Some(target_tags) = options.verify_tags {
+        verify_wheel_tags(wheel, &mut parsed, target_tags)?;
+    }
+    if let Some(python_version) = &options.verify_requires_python {
+        verify_requires_python(&mut archive, &dist_info_prefix, python_version)?;
+    }
+
     // Construct a path transformer, this is used to move files into the right location.
     let transformer = WheelPathTransformer {
         data: format!("{dist_info_prefix}.data"),
@@ -161,7 +304,10 @@ pub fn install_wheel(
                 arch: options.launcher_arch,
             }
         } else {
-            TrampolineMakerKind::Unix
+            TrampolineMakerKind::Unix {
+                shebang_style: options.shebang_style.clone(),
+                gui_scripts: options.unix_gui_scripts,
+            }
         },
     };
 
@@ -169,18 +315,24 @@ pub fn install_wheel(
 
     // Read the RECORD file from the wheel
     let record_filename = format!("{dist_info_prefix}.dist-info/RECORD");
-    let record = Record::from_reader(
-        &mut archive
-            .by_name(&record_filename)
-            .map_err(|err| WheelVitalsError::from_zip(record_filename.clone(), err))?,
-    )?;
+    let record = match archive.by_name(&record_filename) {
+        Ok(mut entry) => Some(Record::from_reader(&mut entry)?),
+        Err(ZipError::FileNotFound) if options.tolerate_missing_record => {
+            tracing::warn!(
+                "{} does not contain a RECORD file, its files cannot be verified",
+                wheel.name
+            );
+            None
+        }
+        Err(err) => return Err(WheelVitalsError::from_zip(record_filename.clone(), err).into()),
+    };
     let record_relative_path = Path::new(&record_filename);
 
     // Read `entry_points.txt` and parse any scripts we need to create.
     let scripts = Scripts::from_wheel(&mut archive, &dist_info_prefix, options.extras.as_ref())?;
 
     let mut resulting_records = Vec::new();
-    let (pyc_tx, pyc_rx) = channel();
+    let mut installation = PartialInstallation::new();
     for index in 0..archive.len() {
         let mut zip_entry = archive
             .by_index(index)
@@ -212,11 +364,35 @@ pub fn install_wheel(
 
         // If the entry refers to a directory we simply create it.
         if zip_entry.is_dir() {
+            installation.add_directory(&destination);
             fs::create_dir_all(&destination)
                 .map_err(|err| InstallError::IoError(destination.display().to_string(), err))?;
             continue;
         }
 
+        // Setuptools wrapper scripts of entry points are replaced by the generated scripts
+        if is_script && scripts.is_entrypoint_wrapper(&destination) {
+            continue;
+        }
+        installation.add_file(&destination);
+
+        // Find the hash of the file in the RECORD of the wheel. The file is hashed with the same
+        // algorithm to verify it, and with the algorithm of the RECORD we write.
+        let relative_path_string = relative_path.display().to_string();
+        let recorded_hash = record
+            .iter()
+            .flat_map(|record| record.iter())
+            .find(|entry| {
+                // Strip any preceding slashes from the path since all paths in the wheel
+                // RECORD should be relative.
+                entry.path.trim_start_matches('/') == relative_path_string
+            })
+            .and_then(|entry| entry.hash.as_deref());
+        let verify_algorithm = recorded_hash
+            .and_then(RecordHashAlgorithm::from_record_hash)
+            .unwrap_or(options.record_hash_algorithm);
+        let algorithms = [verify_algorithm, options.record_hash_algorithm];
+
         // Determine if the entry is executable
         let executable = zip_entry
             .unix_mode()
@@ -224,11 +400,7 @@ pub fn install_wheel(
             .unwrap_or(false);
 
         // If the file is a script
-        let (size, encoded_hash) = if is_script {
-            if scripts.is_entrypoint_wrapper(&destination) {
-                continue;
-            }
-
+        let (size, hashes) = if is_script {
             // Use a BufReader to make it easy to peek at the first few bytes without actually
             // reading the contents of the file.
             let mut buf_reader = BufReader::new(zip_entry);
@@ -261,25 +433,45 @@ pub fn install_wheel(
                 let relative_path = pathdiff::diff_paths(&destination, &site_packages).expect(
                     "can always create relative path from site-packages to the scripts directory",
                 );
-                let record =
-                    write_generated_file(&relative_path, &site_packages, trampoline, true)?;
+                let record = write_generated_file(
+                    &relative_path,
+                    &site_packages,
+                    trampoline,
+                    true,
+                    options.record_hash_algorithm,
+                )?;
                 resulting_records.push(record);
 
                 // The hash has most likely changed so we don't check it.
                 continue;
             } else {
-                // Otherwise copy the file verbatim
-                write_wheel_file(&mut buf_reader, &destination, true)?
+                // Otherwise copy the file verbatim. This is most likely a native executable which
+                // has to be executable even if the mode in the archive says otherwise.
+                let result = write_wheel_file(&mut buf_reader, &destination, true, algorithms)?;
+                #[cfg(unix)]
+                set_executable(&destination)?;
+                result
             }
+        } else if let Some(unpacked_wheel) = options
+            .unpacked_wheel
+            .filter(|_| options.link_mode != LinkMode::Copy)
+        {
+            // Link the file from the unpacked wheel.
+            link_wheel_file(
+                &unpacked_wheel.join(&relative_path),
+                &destination,
+                options.link_mode,
+                algorithms,
+            )?
         } else {
             // Otherwise copy the file to its final destination.
-            write_wheel_file(&mut zip_entry, &destination, executable)?
+            write_wheel_file(&mut zip_entry, &destination, executable, algorithms)?
         };
 
         // If the file is a python file we need to compile it to bytecode
         if let Some(bytecode_compiler) = options.byte_code_compiler.as_ref() {
             if destination.extension() == Some(OsStr::new("py")) {
-                let pyc_tx = pyc_tx.clone();
+                let pyc_tx = installation.pyc_sender();
                 let cloned_destination = destination.clone();
                 bytecode_compiler
                     .compile(&destination, move |result| {
@@ -295,54 +487,53 @@ pub fn install_wheel(
             }
         }
 
-        // Make sure the hash matches with what we expect
-        if let Some(encoded_hash) = encoded_hash {
-            let relative_path_string = relative_path.display().to_string();
-
-            // Find the record in the RECORD entries
-            let recorded_hash = record
-                .iter()
-                .find(|entry| {
-                    // Strip any preceding slashes from the path since all paths in the wheel
-                    // RECORD should be relative.
-                    entry.path.trim_start_matches('/') == relative_path_string
-                })
-                .and_then(|entry| entry.hash.as_ref())
-                .ok_or_else(|| {
-                    InstallError::RecordFile(format!(
-                        "missing hash for {} (expected {})",
-                        relative_path.display(),
-                        encoded_hash
-                    ))
-                })?;
-
-            // Ensure that the hashes match
-            if &encoded_hash != recorded_hash {
-                return Err(InstallError::RecordFile(format!(
-                    "hash mismatch for {}. Recorded: {}, Actual: {}",
+        // Make sure the hash matches with what we expect. Without a RECORD there is nothing to
+        // verify against.
+        if record.is_some() {
+            let recorded_hash = recorded_hash.ok_or_else(|| {
+                InstallError::RecordFile(format!(
+                    "missing hash for {} (expected {})",
                     relative_path.display(),
-                    recorded_hash,
-                    encoded_hash,
-                )));
+                    hashes.get(verify_algorithm)
+                ))
+            })?;
+            let encoded_hash = hashes.get(verify_algorithm);
+            if encoded_hash != recorded_hash {
+                return Err(InstallError::RecordFile(
+                    if RecordHashAlgorithm::from_record_hash(recorded_hash).is_none() {
+                        format!(
+                            "unsupported hash algorithm for {}. Recorded: {}",
+                            relative_path.display(),
+                            recorded_hash,
+                        )
+                    } else {
+                        format!(
+                            "hash mismatch for {}. Recorded: {}, Actual: {}",
+                            relative_path.display(),
+                            recorded_hash,
+                            encoded_hash,
+                        )
+                    },
+                ));
             }
-
-            // Store the hash
-            resulting_records.push(RecordEntry {
-                path: pathdiff::diff_paths(&destination, &site_packages)
-                    .unwrap_or_else(|| {
-                        dunce::canonicalize(&destination).expect("failed to canonicalize path")
-                    })
-                    .display()
-                    .to_string()
-                    // Replace \ with /. This is not strictly necessary, and the spec even
-                    // specifies that the OS separators should be used, but in the case that we
-                    // are unpacking for a different OS from Windows, it makes sense to use
-                    // forward slashes everywhere. Windows can work with both anyway.
-                    .replace('\\', "/"),
-                hash: Some(encoded_hash),
-                size,
-            })
         }
+
+        // Store the hash
+        resulting_records.push(RecordEntry {
+            path: pathdiff::diff_paths(&destination, &site_packages)
+                .unwrap_or_else(|| {
+                    dunce::canonicalize(&destination).expect("failed to canonicalize path")
+                })
+                .display()
+                .to_string()
+                // Replace \ with /. This is not strictly necessary, and the spec even
+                // specifies that the OS separators should be used, but in the case that we
+                // are unpacking for a different OS from Windows, it makes sense to use
+                // forward slashes everywhere. Windows can work with both anyway.
+                .replace('\\', "/"),
+            hash: Some(hashes.get(options.record_hash_algorithm).to_string()),
+            size,
+        })
     }
 
     // Generate the script entrypoints
@@ -352,7 +543,9 @@ pub fn install_wheel(
         &scripts.console_scripts,
         &trampoline_maker,
         LauncherType::Console,
+        options.record_hash_algorithm,
         &mut resulting_records,
+        &mut installation,
     )?;
     write_script_entrypoint(
         dest,
@@ -360,39 +553,49 @@ pub fn install_wheel(
         &scripts.gui_scripts,
         &trampoline_maker,
         LauncherType::Gui,
+        options.record_hash_algorithm,
         &mut resulting_records,
+        &mut installation,
     )?;
 
     // Add the RECORD file itself to the records
     resulting_records.push(RecordEntry {
-        path: record_relative_path.display().to_string(),
+        path: record_relative_path
+            .display()
+            .to_string()
+            .replace('\\', "/"),
         hash: None,
         size: None,
     });
 
     // Write the INSTALLER if requested
     if let Some(installer) = options.installer.as_ref() {
+        installation
+            .add_file(&site_packages.join(format!("{dist_info_prefix}.dist-info/INSTALLER")));
         resulting_records.push(write_generated_file(
             Path::new(&format!("{dist_info_prefix}.dist-info/INSTALLER")),
             &site_packages,
             format!("{}\n", installer.trim()),
             false,
+            options.record_hash_algorithm,
         )?);
     }
 
     // Write `direct_url.json` if requested
     if let Some(direct_url_json) = options.direct_url_json.as_ref() {
+        installation
+            .add_file(&site_packages.join(format!("{dist_info_prefix}.dist-info/direct_url.json")));
         resulting_records.push(write_generated_file(
             Path::new(&format!("{dist_info_prefix}.dist-info/direct_url.json")),
             &site_packages,
             serde_json::to_string(direct_url_json)?,
             false,
+            options.record_hash_algorithm,
         )?);
     }
 
     // Write all the compiled bytecode files to the RECORD file
-    drop(pyc_tx);
-    for (source, result) in pyc_rx {
+    while let Some((source, result)) = installation.next_compiled_file() {
         let absolute_path = match result {
             Ok(absolute_path) => absolute_path,
             Err(CompilationError::NotAPythonFile | CompilationError::SourceNotFound) => {
@@ -411,21 +614,244 @@ pub fn install_wheel(
         };
         let relative_path = pathdiff::diff_paths(&absolute_path, &site_packages)
             .expect("can always create relative path from site-packages");
+        let (hash, size) = if options.hash_generated_files {
+            let (hash, size) = hash_file(&absolute_path, options.record_hash_algorithm)?;
+            (Some(hash), Some(size))
+        } else {
+            (None, None)
+        };
         let record = RecordEntry {
             path: relative_path.display().to_string().replace('\\', "/"),
-            hash: None,
-            size: None,
+            hash,
+            size,
         };
         resulting_records.push(record);
     }
 
+    let files = resulting_records
+        .iter()
+        .map(|entry| normalize_path(&site_packages.join(&entry.path)))
+        .collect();
+
     // Write the resulting RECORD file
+    installation.add_file(&site_packages.join(record_relative_path));
     Record::from_iter(resulting_records)
         .write_to_path(&site_packages.join(record_relative_path))?;
 
-    Ok(InstalledWheel {
+    let installed_wheel = InstalledWheel {
         dist_info: site_packages.join(format!("{dist_info_prefix}.dist-info")),
-    })
+        files,
+    };
+    if let Some(post_install) = options.post_install {
+        post_install(&installed_wheel);
+    }
+
+    installation.complete();
+    Ok(installed_wheel)
+}
+
+/// The result of compiling a python file to bytecode, together with the path of the python file.
+type CompiledFile = (PathBuf, Result<PathBuf, CompilationError>);
+
+/// Keeps track of the files and directories that are written by [`install_wheel`]. Unless the
+/// installation is completed they are removed again when this is dropped, which also happens when
+/// the installation panics.
+struct PartialInstallation {
+    /// The files that are written, whether they already existed or not.
+    files: Vec<PathBuf>,
+
+    /// The directories that did not exist before they were written.
+    directories: Vec<PathBuf>,
+
+    /// The channel through which the bytecode compiler reports the files it wrote.
+    pyc_tx: Option<Sender<CompiledFile>>,
+    pyc_rx: Receiver<CompiledFile>,
+
+    completed: bool,
+}
+
+impl PartialInstallation {
+    fn new() -> Self {
+        let (pyc_tx, pyc_rx) = channel();
+        Self {
+            files: Vec::new(),
+            directories: Vec::new(),
+            pyc_tx: Some(pyc_tx),
+            pyc_rx,
+            completed: false,
+        }
+    }
+
+    /// Records that a file is written, must be called before the file is written.
+    fn add_file(&mut self, path: &Path) {
+        if let Some(parent) = path.parent() {
+            self.add_directory(parent);
+        }
+        self.files.push(path.to_path_buf());
+    }
+
+    /// Records that a directory is created, must be called before the directory is created.
+    fn add_directory(&mut self, path: &Path) {
+        for ancestor in path.ancestors().take_while(|ancestor| !ancestor.exists()) {
+            self.directories.push(ancestor.to_path_buf());
+        }
+    }
+
+    /// Returns a sender through which the bytecode compiler reports a file it wrote.
+    fn pyc_sender(&self) -> Sender<CompiledFile> {
+        self.pyc_tx
+            .clone()
+            .expect("files are not compiled after waiting for the compiled files")
+    }
+
+    /// Waits for the next file that the bytecode compiler finished, returns `None` once all files
+    /// are finished. No new files can be compiled after this has been called.
+    fn next_compiled_file(&mut self) -> Option<CompiledFile> {
+        self.pyc_tx = None;
+        let (source, result) = self.pyc_rx.recv().ok()?;
+        if let Ok(path) = &result {
+            self.files.push(path.clone());
+        }
+        Some((source, result))
+    }
+
+    /// Marks the installation as completed, the written files are kept.
+    fn complete(mut self) {
+        self.completed = true;
+    }
+}
+
+impl Drop for PartialInstallation {
+    fn drop(&mut self) {
+        if self.completed {
+            return;
+        }
+
+        // Wait for the bytecode compiler, otherwise it might write files after they were removed
+        while self.next_compiled_file().is_some() {}
+
+        for file in &self.files {
+            match fs::remove_file(file) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                    tracing::warn!("failed to remove {}: {err}", file.display());
+                }
+                _ => {}
+            }
+        }
+
+        // Remove the deepest directories first, directories that are not empty are kept
+        self.directories
+            .sort_by_key(|directory| std::cmp::Reverse(directory.components().count()));
+        for directory in &self.directories {
+            let _ = fs::remove_dir(directory);
+        }
+    }
+}
+
+/// A file that is installed from a wheel, see [`wheel_file_destinations`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct WheelFileDestination {
+    /// The destination of the file relative to the root of the environment.
+    pub path: PathBuf,
+
+    /// Identifies the contents of the file, files with the same contents do not conflict.
+    pub contents: WheelFileContents,
+}
+
+/// The contents of a [`WheelFileDestination`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum WheelFileContents {
+    /// A file from the archive with its CRC-32 checksum and uncompressed size.
+    Archived { crc32: u32, size: u64 },
+
+    /// An entry point script that is generated during installation.
+    EntryPoint(LauncherType, EntryPoint),
+}
+
+/// Returns where the files of a wheel are installed, relative to the root of the environment,
+/// without installing anything. This includes the entry point scripts of the `console_scripts`
+/// and `gui_scripts` groups, which are filtered by `extras` like [`InstallWheelOptions::extras`].
+/// Directories and the other files that are generated during installation, like the `RECORD` file
+/// and `INSTALLER`, are not included.
+pub(crate) fn wheel_file_destinations(
+    wheel: &Wheel,
+    paths: &InstallPaths,
+    extras: Option<&HashSet<Extra>>,
+) -> Result<Vec<WheelFileDestination>, InstallError> {
+    let mut archive = wheel.archive.lock();
+    let dist_info_prefix =
+        find_dist_info_metadata(wheel.name(), archive.file_names().map(|name| ((), name)))?
+            .1
+            .to_owned();
+    let mut parsed = read_wheel_file(&mut archive, &dist_info_prefix)?;
+    let root_is_purelib = parse_root_is_purelib(&mut parsed)
+        .map_err(WheelVitalsError::InvalidMetadata)
+        .map_err(InstallError::FailedToParseWheelVitals)?;
+    let transformer = WheelPathTransformer {
+        data: format!("{dist_info_prefix}.data"),
+        root_is_purelib,
+        paths,
+        name: wheel.name.distribution.as_str(),
+    };
+    let record_path = PathBuf::from(format!("{dist_info_prefix}.dist-info/RECORD"));
+    let scripts = Scripts::from_wheel(&mut archive, &dist_info_prefix, extras)?;
+
+    let mut destinations = Vec::new();
+    for index in 0..archive.len() {
+        let zip_entry = archive
+            .by_index(index)
+            .map_err(|e| InstallError::from_zip_error(format!("<index {index}>"), e))?;
+        let Some(relative_path) = zip_entry.enclosed_name().map(ToOwned::to_owned) else {
+            continue;
+        };
+        if zip_entry.is_dir() || relative_path == record_path {
+            continue;
+        }
+        let Some((path, is_script)) = transformer.analyze_path(&relative_path)? else {
+            continue;
+        };
+        if is_script && scripts.is_entrypoint_wrapper(&path) {
+            continue;
+        }
+        destinations.push(WheelFileDestination {
+            path,
+            contents: WheelFileContents::Archived {
+                crc32: zip_entry.crc32(),
+                size: zip_entry.size(),
+            },
+        });
+    }
+
+    for (launcher_type, entry_points) in [
+        (LauncherType::Console, scripts.console_scripts),
+        (LauncherType::Gui, scripts.gui_scripts),
+    ] {
+        for entry_point in entry_points {
+            destinations.push(WheelFileDestination {
+                path: paths
+                    .scripts()
+                    .join(script_file_name(paths, &entry_point).as_ref()),
+                contents: WheelFileContents::EntryPoint(launcher_type, entry_point),
+            });
+        }
+    }
+
+    Ok(destinations)
+}
+
+/// Reads the `WHEEL` file from the `.dist-info` directory of a wheel, parses it and verifies its
+/// version.
+fn read_wheel_file<R: ReadAndSeek>(
+    archive: &mut ZipArchive<R>,
+    dist_info_prefix: &str,
+) -> Result<RFC822ish, InstallError> {
+    let wheel_path = format!("{dist_info_prefix}.dist-info/WHEEL");
+    let wheel_metadata = read_entry_to_end(archive, &wheel_path)
+        .map_err(|err| InstallError::ZipError(wheel_path, err))?;
+    Ok(parse_format_metadata_and_check_version(
+        &wheel_metadata,
+        "Wheel-Version",
+    )?)
 }
 
 /// Parse the "Root-Is-Purelib" is from a parsed WHEEL file
@@ -455,55 +881,94 @@ fn read_entry_to_end<R: ReadAndSeek>(
     Ok(bytes)
 }
 
-/// Parse a key value file and immediately check the version.
-fn parse_format_metadata_and_check_version(
-    input: &[u8],
-    version_field: &str,
-) -> Result<RFC822ish, WheelVitalsError> {
-    let input = String::from_utf8_lossy(input);
-    let mut parsed = RFC822ish::from_str(&input).map_err(WheelVitalsError::FailedToParseWheel)?;
+/// Checks that at least one of the tags declared in the WHEEL file is compatible with the target
+/// tags.
+fn verify_wheel_tags(
+    wheel: &Wheel,
+    parsed: &mut RFC822ish,
+    target_tags: &WheelTags,
+) -> Result<(), InstallError> {
+    let mut declared_tags = Vec::new();
+    for tag in parsed.take_all("Tag") {
+        declared_tags.extend(
+            WheelTag::from_compound_string(&tag).map_err(|_| InstallError::InvalidWheelTag(tag))?,
+        );
+    }
+    if declared_tags.is_empty() {
+        declared_tags.extend(wheel.name.all_tags_iter());
+    }
 
-    let version = parsed
-        .take(version_field)
-        .map_err(|_| WheelVitalsError::MissingKeyInWheel(version_field.into()))?;
-    if !version.starts_with("1.") {
-        return Err(WheelVitalsError::UnsupportedWheelVersion(version));
+    if declared_tags
+        .iter()
+        .any(|tag| target_tags.is_compatible(tag))
+    {
+        Ok(())
+    } else {
+        Err(InstallError::IncompatibleWheelTags(
+            declared_tags.iter().map(ToString::to_string).join(", "),
+        ))
     }
+}
 
-    Ok(parsed)
+/// Checks that the `Requires-Python` of the METADATA of the wheel includes `python_version`.
+fn verify_requires_python<R: ReadAndSeek>(
+    archive: &mut ZipArchive<R>,
+    dist_info_prefix: &str,
+    python_version: &PythonInterpreterVersion,
+) -> Result<(), InstallError> {
+    let metadata_path = format!("{dist_info_prefix}.dist-info/METADATA");
+    let metadata_blob = read_entry_to_end(archive, &metadata_path)
+        .map_err(|err| WheelVitalsError::from_zip(metadata_path, err))?;
+    let metadata =
+        WheelCoreMetadata::try_from(metadata_blob.as_slice()).map_err(WheelVitalsError::from)?;
+
+    let Some(requires_python) = metadata.requires_python else {
+        return Ok(());
+    };
+    let found = format!(
+        "{}.{}.{}",
+        python_version.major, python_version.minor, python_version.patch
+    );
+    let version =
+        Version::from_str(&found).expect("a python interpreter version is always a valid version");
+    if requires_python.contains(&version) {
+        Ok(())
+    } else {
+        Err(InstallError::RequiresPythonMismatch {
+            requires: requires_python.to_string(),
+            found,
+        })
+    }
 }
 
 /// Construct trampolines for entry-points.
+#[allow(clippy::too_many_arguments)]
 fn write_script_entrypoint(
     dest: &Path,
     install_paths: &InstallPaths,
     entry_points: &Vec<EntryPoint>,
     trampoline_maker: &TrampolineMaker,
     launcher_type: LauncherType,
+    record_hash_algorithm: RecordHashAlgorithm,
     records: &mut Vec<RecordEntry>,
+    installation: &mut PartialInstallation,
 ) -> Result<(), InstallError> {
     // Make sure the script directory exists
     let scripts_dir = dest.join(install_paths.scripts());
+    installation.add_directory(&scripts_dir);
     fs::create_dir_all(&scripts_dir)
         .map_err(|err| InstallError::IoError(scripts_dir.display().to_string(), err))?;
 
     for entry_point in entry_points {
-        // Determine the name of the script
-        let script_name = if install_paths.is_windows() {
-            // Convert the entry point filename. We strip `.py` from the filename and add `.exe`.
-            Cow::Owned(format!(
-                "{}.exe",
-                entry_point
-                    .script_name
-                    .strip_suffix(".py")
-                    .unwrap_or(&entry_point.script_name)
-            ))
-        } else {
-            Cow::Borrowed(entry_point.script_name.as_str())
-        };
+        let script_name = script_file_name(install_paths, entry_point);
 
         // Construct the trampoline
-        let launch_script = entry_point.launch_script();
+        let launch_script =
+            if launcher_type == LauncherType::Gui && trampoline_maker.detach_gui_scripts() {
+                entry_point.detached_launch_script()
+            } else {
+                entry_point.launch_script()
+            };
         let trampoline =
             trampoline_maker.make_trampoline(launcher_type, launch_script.as_bytes())?;
 
@@ -512,14 +977,37 @@ fn write_script_entrypoint(
             .join(install_paths.scripts())
             .join(script_name.as_ref());
         let site_packages = dest.join(install_paths.site_packages());
+        installation.add_file(&script_path);
         let relative_path = pathdiff::diff_paths(script_path, &site_packages).expect("should always be able to create relative path from site-packages to the scripts directory");
-        let record = write_generated_file(&relative_path, &site_packages, &trampoline, true)?;
+        let record = write_generated_file(
+            &relative_path,
+            &site_packages,
+            &trampoline,
+            true,
+            record_hash_algorithm,
+        )?;
         records.push(record)
     }
 
     Ok(())
 }
 
+/// Returns the file name of the script that is generated for an entry point.
+fn script_file_name<'a>(install_paths: &InstallPaths, entry_point: &'a EntryPoint) -> Cow<'a, str> {
+    if install_paths.is_windows() {
+        // Convert the entry point filename. We strip `.py` from the filename and add `.exe`.
+        Cow::Owned(format!(
+            "{}.exe",
+            entry_point
+                .script_name
+                .strip_suffix(".py")
+                .unwrap_or(&entry_point.script_name)
+        ))
+    } else {
+        Cow::Borrowed(entry_point.script_name.as_str())
+    }
+}
+
 /// An object that can be used to generate trampolines.
 ///
 /// Trampolines are executable that execute a certain python script using a certain python
@@ -535,11 +1023,27 @@ struct TrampolineMaker {
 
 /// The type of trampoline to create
 enum TrampolineMakerKind {
-    Windows { arch: Option<WindowsLauncherArch> },
-    Unix,
+    Windows {
+        arch: Option<WindowsLauncherArch>,
+    },
+    Unix {
+        shebang_style: ShebangStyle,
+        gui_scripts: UnixGuiScripts,
+    },
 }
 
 impl TrampolineMaker {
+    /// Returns true if gui scripts should detach from the terminal, see [`UnixGuiScripts::Detach`].
+    fn detach_gui_scripts(&self) -> bool {
+        matches!(
+            self.kind,
+            TrampolineMakerKind::Unix {
+                gui_scripts: UnixGuiScripts::Detach,
+                ..
+            }
+        )
+    }
+
     /// Returns the bytes of a launcher executable/script that can be used to launch the given
     /// script.
     pub fn make_trampoline(
@@ -547,10 +1051,10 @@ impl TrampolineMaker {
         launcher_type: LauncherType,
         script: &[u8],
     ) -> Result<Vec<u8>, InstallError> {
-        let shebang = get_shebang(&self.python_executable);
-        match self.kind {
+        match &self.kind {
             TrampolineMakerKind::Windows { arch } => {
-                let arch = match arch {
+                let shebang = get_shebang(&self.python_executable, &ShebangStyle::AbsolutePath);
+                let arch = match *arch {
                     Some(windows_launcher_arch) => windows_launcher_arch,
                     None => match WindowsLauncherArch::current() {
                         Some(arch) => arch,
@@ -565,7 +1069,8 @@ impl TrampolineMaker {
                     launcher_type,
                 ))
             }
-            TrampolineMakerKind::Unix => {
+            TrampolineMakerKind::Unix { shebang_style, .. } => {
+                let shebang = get_shebang(&self.python_executable, shebang_style);
                 let mut bytes = format!("{}\n", shebang).into_bytes();
                 bytes.extend_from_slice(script);
                 Ok(bytes)
@@ -577,8 +1082,15 @@ impl TrampolineMaker {
 /// Returns the shebang to use when calling a python script.
 /// TODO: In the future we should make this much more configurable. This is much more complex in pip:
 ///  <https://github.com/pypa/pip/blob/7f8a6844037fb7255cfd0d34ff8e8cf44f2598d4/src/pip/_vendor/distlib/scripts.py#L158>
-fn get_shebang(python_executable: &Path) -> String {
-    format!(r"#!{}", dunce::simplified(python_executable).display())
+fn get_shebang(python_executable: &Path, style: &ShebangStyle) -> String {
+    match style {
+        ShebangStyle::AbsolutePath => {
+            format!(r"#!{}", dunce::simplified(python_executable).display())
+        }
+        ShebangStyle::Env(version) => {
+            format!(r"#!/usr/bin/env python{}.{}", version.major, version.minor)
+        }
+    }
 }
 
 /// The scripts that should be installed as part of the wheel installation.
@@ -603,41 +1115,28 @@ impl Scripts {
             Err(err) => return Err(InstallError::from_zip_error(entry_points_path, err)),
         };
 
-        // Parse the `entry_points.txt` file as an ini file.
-        let mut entry_points_mapping = {
-            let mut ini_contents = String::new();
-            entry_points_file
-                .read_to_string(&mut ini_contents)
-                .map_err(|err| {
-                    InstallError::EntryPointsInvalid(format!(
-                        "failed to read entry_points.txt contents: {}",
-                        err
-                    ))
-                })?;
-            Ini::new_cs().read(ini_contents).map_err(|err| {
+        // Parse the `entry_points.txt` file and extract the script entry points
+        let mut ini_contents = String::new();
+        entry_points_file
+            .read_to_string(&mut ini_contents)
+            .map_err(|err| {
                 InstallError::EntryPointsInvalid(format!(
-                    "failed to parse entry_points.txt contents: {}",
+                    "failed to read entry_points.txt contents: {}",
                     err
                 ))
-            })?
-        };
-
-        // Parse the script entry points
-        let console_scripts = entry_points_mapping
-            .remove("console_scripts")
-            .map(|e| parse_entry_points_from_ini_section(e, extras))
-            .transpose()?
-            .unwrap_or_default();
-
-        let gui_scripts = entry_points_mapping
-            .remove("gui_scripts")
-            .map(|e| parse_entry_points_from_ini_section(e, extras))
-            .transpose()?
-            .unwrap_or_default();
+            })?;
+        let mut entry_points = EntryPoints::from_ini(&ini_contents, extras)
+            .map_err(|err| InstallError::EntryPointsInvalid(err.to_string()))?;
 
         Ok(Scripts {
-            console_scripts,
-            gui_scripts,
+            console_scripts: entry_points
+                .groups
+                .remove("console_scripts")
+                .unwrap_or_default(),
+            gui_scripts: entry_points
+                .groups
+                .remove("gui_scripts")
+                .unwrap_or_default(),
         })
     }
 
@@ -667,35 +1166,12 @@ impl Scripts {
     }
 }
 
-/// Parse entry points from a section in the `entry_points.txt` file.
-fn parse_entry_points_from_ini_section(
-    entry_points: HashMap<String, Option<String>>,
-    extras: Option<&HashSet<Extra>>,
-) -> Result<Vec<EntryPoint>, InstallError> {
-    let mut result = Vec::new();
-    for (script_name, entry_point) in entry_points {
-        let entry_point = entry_point.ok_or_else(|| {
-            InstallError::EntryPointsInvalid(format!("missing entry point for {}", script_name))
-        })?;
-        match EntryPoint::parse(script_name.clone(), &entry_point, extras) {
-            Ok(None) => {}
-            Ok(Some(entry_point)) => result.push(entry_point),
-            Err(err) => {
-                return Err(InstallError::EntryPointsInvalid(format!(
-                    "failed to parse entry point for {}: {}",
-                    script_name, err
-                )));
-            }
-        }
-    }
-    Ok(result)
-}
-
 fn write_generated_file(
     relative_path: &Path,
     site_packages: &Path,
     content: impl AsRef<[u8]>,
     _executable: bool,
+    record_hash_algorithm: RecordHashAlgorithm,
 ) -> Result<RecordEntry, InstallError> {
     let mut options = fs::OpenOptions::new();
     options.write(true).create(true).truncate(true);
@@ -710,31 +1186,32 @@ fn write_generated_file(
         }
     }
 
-    let (size, digest) = options
+    let content = content.as_ref();
+    options
         .open(site_packages.join(relative_path))
-        .map(rattler_digest::HashingWriter::<_, Sha256>::new)
-        .and_then(|mut file| {
-            let content = content.as_ref();
-            file.write_all(content)?;
-            let (_, digest) = file.finalize();
-            Ok((content.len(), digest))
-        })
+        .and_then(|mut file| file.write_all(content))
         .map_err(|err| InstallError::IoError(relative_path.display().to_string(), err))?;
 
+    let mut hasher = RecordHasher::new([record_hash_algorithm]);
+    hasher
+        .write_all(content)
+        .expect("hashing in memory cannot fail");
     Ok(RecordEntry {
         path: relative_path.display().to_string().replace('\\', "/"),
-        hash: Some(format!("sha256={}", BASE64URL_NOPAD.encode(&digest))),
-        size: Some(size as _),
+        hash: Some(hasher.finalize().get(record_hash_algorithm).to_string()),
+        size: Some(content.len() as _),
     })
 }
 
-/// Write a file from a wheel archive to disk.
+/// Write a file from a wheel archive to disk. Returns the size of the file and its hashes for the
+/// given algorithms.
 fn write_wheel_file(
     mut reader: &mut impl Read,
     destination: &Path,
     _executable: bool,
-) -> Result<(Option<u64>, Option<String>), InstallError> {
-    let mut reader = rattler_digest::HashingReader::<_, Sha256>::new(&mut reader);
+    algorithms: impl IntoIterator<Item = RecordHashAlgorithm>,
+) -> Result<(Option<u64>, RecordHashes), InstallError> {
+    let mut reader = RecordHashingReader::new(&mut reader, algorithms);
 
     let mut options = fs::OpenOptions::new();
     options.write(true).create(true);
@@ -756,11 +1233,92 @@ fn write_wheel_file(
         .map_err(|err| InstallError::IoError(destination.display().to_string(), err))?;
     let size = std::io::copy(&mut reader, &mut file)
         .map_err(|err| InstallError::IoError(destination.display().to_string(), err))?;
-    let (_, digest) = reader.finalize();
-    Ok((
-        Some(size),
-        Some(format!("sha256={}", BASE64URL_NOPAD.encode(&digest))),
-    ))
+    Ok((Some(size), reader.finalize()))
+}
+
+/// Computes the hash of the file at `path` in the format used by RECORD files, together with its
+/// size.
+fn hash_file(path: &Path, algorithm: RecordHashAlgorithm) -> Result<(String, u64), InstallError> {
+    let mut file = fs::File::open(path)
+        .map_err(|err| InstallError::IoError(path.display().to_string(), err))?;
+    let mut hasher = RecordHasher::new([algorithm]);
+    let size = std::io::copy(&mut file, &mut hasher)
+        .map_err(|err| InstallError::IoError(path.display().to_string(), err))?;
+    Ok((hasher.finalize().get(algorithm).to_string(), size))
+}
+
+/// Makes the file at `path` executable for everyone who can read it, like pip does. The mode passed
+/// when opening a file is only applied to new files and is subject to the umask, so this is done
+/// explicitly.
+#[cfg(unix)]
+fn set_executable(path: &Path) -> Result<(), InstallError> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)
+        .map_err(|err| InstallError::IoError(path.display().to_string(), err))?
+        .permissions();
+    let mode = permissions.mode();
+    permissions.set_mode(mode | ((mode & 0o444) >> 2));
+    fs::set_permissions(path, permissions)
+        .map_err(|err| InstallError::IoError(path.display().to_string(), err))
+}
+
+/// Links a file from an unpacked wheel to its destination using the given [`LinkMode`]. The source
+/// file is hashed so that it can be verified against the RECORD of the wheel.
+fn link_wheel_file(
+    source: &Path,
+    destination: &Path,
+    link_mode: LinkMode,
+    algorithms: impl IntoIterator<Item = RecordHashAlgorithm>,
+) -> Result<(Option<u64>, RecordHashes), InstallError> {
+    let mut file = fs::File::open(source)
+        .map_err(|err| InstallError::IoError(source.display().to_string(), err))?;
+    let mut hasher = RecordHasher::new(algorithms);
+    let size = std::io::copy(&mut file, &mut hasher)
+        .map_err(|err| InstallError::IoError(source.display().to_string(), err))?;
+
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| InstallError::IoError(parent.display().to_string(), err))?;
+    }
+    link_file(source, destination, link_mode)
+        .map_err(|err| InstallError::IoError(destination.display().to_string(), err))?;
+
+    Ok((Some(size), hasher.finalize()))
+}
+
+/// Places `source` at `destination` using the given [`LinkMode`], falling back to copying the
+/// file if linking fails.
+fn link_file(source: &Path, destination: &Path, link_mode: LinkMode) -> std::io::Result<()> {
+    // Linking fails if the destination already exists.
+    match fs::remove_file(destination) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+
+    let result = match link_mode {
+        LinkMode::Copy => return fs::copy(source, destination).map(|_| ()),
+        LinkMode::Hardlink => fs::hard_link(source, destination),
+        LinkMode::Symlink => {
+            #[cfg(unix)]
+            {
+                std::os::unix::fs::symlink(source, destination)
+            }
+            #[cfg(windows)]
+            {
+                std::os::windows::fs::symlink_file(source, destination)
+            }
+        }
+        LinkMode::Reflink => reflink_copy::reflink(source, destination),
+    };
+
+    result.or_else(|err| {
+        tracing::debug!(
+            "failed to {link_mode:?} {} to {}, falling back to copying: {err}",
+            source.display(),
+            destination.display()
+        );
+        fs::copy(source, destination).map(|_| ())
+    })
 }
 
 /// Implements the logic to determine where a files from a wheel should be placed on the filesystem
@@ -789,11 +1347,20 @@ impl<'a> WheelPathTransformer<'a> {
         let (category, rest_of_path) = if let Ok(data_path) = path.strip_prefix(&self.data) {
             let mut components = data_path.components();
             if let Some(category) = components.next() {
+                // The first component is the category, the rest of the path is relative to the
+                // install location of that category and must not escape it.
+                let invalid_path = || InstallError::InvalidDataPath(path.display().to_string());
                 let Component::Normal(name) = category else {
-                    // TODO: Better error handling
-                    panic!("invalid path")
+                    return Err(invalid_path());
                 };
-                (name.to_string_lossy(), components.as_path())
+                let rest_of_path = components.as_path();
+                if !rest_of_path
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)))
+                {
+                    return Err(invalid_path());
+                }
+                (name.to_string_lossy(), rest_of_path)
             } else {
                 // This is the data directory itself. Discard that.
                 return Ok(None);
@@ -821,7 +1388,10 @@ mod test {
     use super::*;
     use crate::{
         artifacts::wheel::*,
-        python_env::{system_python_executable, ByteCodeCompiler, PythonLocation, VEnv, WheelTags},
+        python_env::{
+            system_python_executable, ByteCodeCompiler, InvalidationMode, PythonLocation, VEnv,
+            WheelTags,
+        },
         types::{
             DirectUrlHashes, DirectUrlJson, DirectUrlSource, NormalizedPackageName, WheelFilename,
         },
@@ -837,6 +1407,57 @@ mod test {
 
     const INSTALLER: &str = "pixi_test";
 
+    #[rstest]
+    #[case("foo-1.0.data/data/share/man/man1/foo.1", Some(("share/man/man1/foo.1", false)))]
+    #[case("foo-1.0.data/data/etc/jupyter/foo.json", Some(("etc/jupyter/foo.json", false)))]
+    #[case("foo-1.0.data/data/foo.cfg", Some(("foo.cfg", false)))]
+    #[case("foo-1.0.data/scripts/foo", Some(("bin/foo", true)))]
+    #[case("foo-1.0.data/headers/foo.h", Some(("include/foo/foo.h", false)))]
+    #[case(
+        "foo-1.0.data/platlib/foo/_foo.so",
+        Some(("lib/python3.11/site-packages/foo/_foo.so", false))
+    )]
+    #[case("foo/__init__.py", Some(("lib/python3.11/site-packages/foo/__init__.py", false)))]
+    #[case(
+        "foo-1.0.data2/foo.txt",
+        Some(("lib/python3.11/site-packages/foo-1.0.data2/foo.txt", false))
+    )]
+    #[case("foo-1.0.data", None)]
+    fn test_wheel_path_transformer(#[case] path: &str, #[case] expected: Option<(&str, bool)>) {
+        let paths = InstallPaths::for_venv((3, 11, 4), false);
+        let transformer = WheelPathTransformer {
+            data: String::from("foo-1.0.data"),
+            root_is_purelib: true,
+            paths: &paths,
+            name: "foo",
+        };
+
+        let destination = transformer.analyze_path(Path::new(path)).unwrap();
+        assert_eq!(
+            destination,
+            expected.map(|(path, is_script)| (PathBuf::from(path), is_script))
+        );
+    }
+
+    #[rstest]
+    #[case("foo-1.0.data/unknown/foo.txt")]
+    #[case("foo-1.0.data/data/../../foo.txt")]
+    fn test_wheel_path_transformer_errors(#[case] path: &str) {
+        let paths = InstallPaths::for_venv((3, 11, 4), false);
+        let transformer = WheelPathTransformer {
+            data: String::from("foo-1.0.data"),
+            root_is_purelib: true,
+            paths: &paths,
+            name: "foo",
+        };
+
+        let err = transformer.analyze_path(Path::new(path)).unwrap_err();
+        assert!(matches!(
+            err,
+            InstallError::UnsupportedDataDirectory(_) | InstallError::InvalidDataPath(_)
+        ));
+    }
+
     #[rstest]
     #[case("https://files.pythonhosted.org/packages/58/76/705b5c776f783d1ba7c630347463d4ae323282bbd859a8e9420c7ff79581/selenium-4.1.0-py3-none-any.whl", "27e7b64df961d609f3d57237caa0df123abbbe22d038f2ec9e332fb90ec1a939")]
     #[case("https://files.pythonhosted.org/packages/1e/27/47f73510c6b80d1ff0829474947537ae9ab8d516cc48c6320b7f3677fa54/selenium-2.53.2-py2.py3-none-any.whl", "fa8333cf3013497e60d87ba68cae65ead8e7fa208be88ab9c561556103f540ef")]
@@ -913,12 +1534,337 @@ mod test {
 
         // Determine the location where we would expect the RECORD file to exist
         let record_path = unpacked.dist_info.join("RECORD");
-        let record_content = fs::read_to_string(&unpacked.tmpdir.path().join(&record_path))
+        let record_content = fs::read_to_string(unpacked.tmpdir.path().join(&record_path))
             .unwrap_or_else(|_| panic!("failed to read RECORD from {}", record_path.display()));
 
         insta::assert_snapshot!(filename, record_content);
     }
 
+    fn miniblack_path() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/wheels/miniblack-23.1.0-py3-none-any.whl")
+    }
+
+    fn install_miniblack(
+        install_paths: &InstallPaths,
+        dest: &Path,
+        options: &InstallWheelOptions,
+    ) -> String {
+        let wheel = Wheel::from_path(&miniblack_path(), &"miniblack".parse().unwrap()).unwrap();
+
+        let unpacked =
+            install_wheel(&wheel, dest, install_paths, Path::new("/invalid"), options).unwrap();
+
+        fs::read_to_string(dest.join(unpacked.dist_info).join("RECORD")).unwrap()
+    }
+
+    #[rstest]
+    #[case((3, 11, 4), true)]
+    #[case((3, 7, 0), true)]
+    #[case((3, 6, 15), false)]
+    fn test_verify_requires_python(#[case] version: (u32, u32, u32), #[case] compatible: bool) {
+        // miniblack requires python >=3.7
+        let wheel = Wheel::from_path(&miniblack_path(), &"miniblack".parse().unwrap()).unwrap();
+        let tmpdir = tempdir().unwrap();
+        let result = install_wheel(
+            &wheel,
+            tmpdir.path(),
+            &InstallPaths::for_venv(version, false),
+            Path::new("/invalid"),
+            &InstallWheelOptions {
+                verify_requires_python: Some(version.into()),
+                ..Default::default()
+            },
+        );
+
+        match result {
+            Ok(_) => assert!(compatible),
+            Err(InstallError::RequiresPythonMismatch { requires, found }) => {
+                assert!(!compatible);
+                assert_eq!(requires, ">=3.7");
+                assert_eq!(found, "3.6.15");
+                // Nothing is installed
+                assert_eq!(fs::read_dir(tmpdir.path()).unwrap().count(), 0);
+            }
+            Err(err) => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn test_post_install() {
+        let tmpdir = tempdir().unwrap();
+        let calls = std::sync::Mutex::new(Vec::new());
+        let post_install = |installed: &InstalledWheel| {
+            // The RECORD has already been written when the hook is called
+            let record = fs::read_to_string(installed.dist_info.join("RECORD")).unwrap();
+            calls
+                .lock()
+                .unwrap()
+                .push((record, installed.files.clone()));
+        };
+
+        let record = install_miniblack(
+            &InstallPaths::for_venv((3, 11, 4), false),
+            tmpdir.path(),
+            &InstallWheelOptions {
+                post_install: Some(&post_install),
+                ..Default::default()
+            },
+        );
+
+        let calls = calls.into_inner().unwrap();
+        assert_eq!(calls.len(), 1);
+        let (record_in_hook, files) = &calls[0];
+        assert_eq!(record_in_hook, &record);
+        assert_eq!(files.len(), record.lines().count());
+        assert!(files.iter().all(|file| file.is_file()));
+        assert!(files.contains(&tmpdir.path().join("bin/black")));
+    }
+
+    #[test]
+    fn test_tolerate_missing_record() {
+        let wheel_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/wheels/no_record-1.0.0-py3-none-any.whl");
+        let wheel = Wheel::from_path(&wheel_path, &"no_record".parse().unwrap()).unwrap();
+        let tmpdir = tempdir().unwrap();
+        let install_paths = InstallPaths::for_venv((3, 11, 4), false);
+
+        // By default a wheel without a RECORD is rejected
+        let err = install_wheel(
+            &wheel,
+            tmpdir.path(),
+            &install_paths,
+            Path::new("/invalid"),
+            &InstallWheelOptions::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, InstallError::FailedToParseWheelVitals(_)));
+
+        // When tolerated, the files are installed and a RECORD is generated
+        let installed = install_wheel(
+            &wheel,
+            tmpdir.path(),
+            &install_paths,
+            Path::new("/invalid"),
+            &InstallWheelOptions {
+                tolerate_missing_record: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let site_packages = tmpdir.path().join(install_paths.site_packages());
+        assert_eq!(
+            fs::read_to_string(site_packages.join("no_record/__init__.py")).unwrap(),
+            "VALUE = 42\n"
+        );
+        assert!(tmpdir.path().join("share/no_record/config.toml").is_file());
+
+        let record = fs::read_to_string(installed.dist_info.join("RECORD")).unwrap();
+        let paths = record
+            .lines()
+            .map(|line| line.split(',').next().unwrap())
+            .collect::<Vec<_>>();
+        assert!(paths.contains(&"no_record/__init__.py"));
+        assert!(paths.contains(&"../../../share/no_record/config.toml"));
+        assert!(paths.contains(&"no_record-1.0.0.dist-info/RECORD"));
+        assert!(record
+            .lines()
+            .filter(|line| !line.starts_with("no_record-1.0.0.dist-info/RECORD"))
+            .all(|line| line.contains(",sha256=")));
+    }
+
+    #[test]
+    fn test_dist_info_with_non_normalized_name() {
+        // The dist-info directory is named `Mixed.Case-1.0.0.dist-info`
+        let wheel_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/wheels/mixed_case-1.0.0-py3-none-any.whl");
+        let wheel = Wheel::from_path(&wheel_path, &"mixed_case".parse().unwrap()).unwrap();
+        let (_, metadata) = wheel.metadata().unwrap();
+        assert_eq!(metadata.name.as_source_str(), "Mixed.Case");
+
+        let tmpdir = tempdir().unwrap();
+        let installed = install_wheel(
+            &wheel,
+            tmpdir.path(),
+            &InstallPaths::for_venv((3, 11, 4), false),
+            Path::new("/invalid"),
+            &InstallWheelOptions::default(),
+        )
+        .unwrap();
+        assert!(installed.dist_info.ends_with("Mixed.Case-1.0.0.dist-info"));
+        assert!(installed.dist_info.join("METADATA").is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_native_script_is_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmpdir = tempdir().unwrap();
+        let target = tmpdir.path().join("target");
+
+        // The wheel ships a native executable in the scripts directory without any executable
+        // bits. Also make sure that an existing file is made executable.
+        let script = target.join("bin/native-tool");
+        fs::create_dir_all(script.parent().unwrap()).unwrap();
+        fs::write(&script, "").unwrap();
+        fs::set_permissions(&script, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let wheel_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/wheels/native_script-1.0.0-py3-none-any.whl");
+        let wheel = Wheel::from_path(&wheel_path, &"native_script".parse().unwrap()).unwrap();
+        install_wheel(
+            &wheel,
+            tmpdir.path(),
+            &InstallPaths::for_target(&target, false),
+            Path::new("/invalid"),
+            &Default::default(),
+        )
+        .unwrap();
+
+        // The script is copied verbatim and is executable
+        assert!(fs::read(&script).unwrap().starts_with(b"\x7fELF"));
+        let mode = fs::metadata(&script).unwrap().permissions().mode();
+        assert_eq!(mode & 0o111, 0o111);
+    }
+
+    #[test]
+    fn test_install_target() {
+        let tmpdir = tempdir().unwrap();
+        let target = tmpdir.path().join("target");
+        let record = install_miniblack(
+            &InstallPaths::for_target(&target, false),
+            tmpdir.path(),
+            &Default::default(),
+        );
+
+        assert!(target.join("black/__init__.py").is_file());
+        assert!(target.join("miniblack-23.1.0.dist-info/METADATA").is_file());
+        assert!(target.join("bin/black").is_file());
+        assert!(record.lines().any(|line| line.starts_with("bin/black,")));
+    }
+
+    #[test]
+    fn test_install_prefix() {
+        let tmpdir = tempdir().unwrap();
+        let prefix = tmpdir.path().join("prefix");
+        let record = install_miniblack(
+            &InstallPaths::for_prefix(&prefix, (3, 8, 5), false),
+            tmpdir.path(),
+            &Default::default(),
+        );
+
+        let site_packages = prefix.join("lib/python3.8/site-packages");
+        assert!(site_packages.join("black/__init__.py").is_file());
+        assert!(prefix.join("bin/black").is_file());
+        assert!(record
+            .lines()
+            .any(|line| line.starts_with("../../../bin/black,")));
+    }
+
+    #[test]
+    fn test_install_custom_paths() {
+        // A debian style layout that uses dist-packages instead of site-packages
+        let tmpdir = tempdir().unwrap();
+        let paths = InstallPaths::custom(
+            "usr/lib/python3/dist-packages",
+            "usr/lib/python3/dist-packages",
+            "usr/bin",
+            "usr",
+            "usr/include/python3.11",
+            false,
+        )
+        .unwrap();
+        let record = install_miniblack(&paths, tmpdir.path(), &Default::default());
+
+        let dist_packages = tmpdir.path().join("usr/lib/python3/dist-packages");
+        assert!(dist_packages.join("black/__init__.py").is_file());
+        assert!(dist_packages
+            .join("miniblack-23.1.0.dist-info/METADATA")
+            .is_file());
+        assert!(tmpdir.path().join("usr/bin/black").is_file());
+        assert!(record
+            .lines()
+            .any(|line| line.starts_with("../../../bin/black,")));
+
+        // Relative paths may not escape the destination
+        let err = InstallPaths::custom(
+            "lib/site-packages",
+            "lib/site-packages",
+            "../bin",
+            "",
+            "include",
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(err.category, "scripts");
+        assert_eq!(err.path, Path::new("../bin"));
+    }
+
+    #[rstest]
+    #[case::copy(LinkMode::Copy)]
+    #[case::hardlink(LinkMode::Hardlink)]
+    #[case::symlink(LinkMode::Symlink)]
+    #[case::reflink(LinkMode::Reflink)]
+    fn test_link_mode(#[case] link_mode: LinkMode) {
+        let tmpdir = tempdir().unwrap();
+
+        // Unpack the wheel to link the files from
+        let unpacked_wheel = tmpdir.path().join("unpacked");
+        ZipArchive::new(fs::File::open(miniblack_path()).unwrap())
+            .unwrap()
+            .extract(&unpacked_wheel)
+            .unwrap();
+
+        let target = tmpdir.path().join("target");
+        let record = install_miniblack(
+            &InstallPaths::for_target(&target, false),
+            tmpdir.path(),
+            &InstallWheelOptions {
+                link_mode,
+                unpacked_wheel: Some(&unpacked_wheel),
+                ..Default::default()
+            },
+        );
+
+        // The installed files should have the same contents as the unpacked wheel
+        let installed = target.join("black/__init__.py");
+        let source = unpacked_wheel.join("black/__init__.py");
+        assert_eq!(fs::read(&installed).unwrap(), fs::read(&source).unwrap());
+        assert!(record
+            .lines()
+            .any(|line| line.starts_with("black/__init__.py,")));
+
+        #[cfg(unix)]
+        match link_mode {
+            LinkMode::Hardlink => {
+                use std::os::unix::fs::MetadataExt;
+                assert_eq!(
+                    fs::metadata(&installed).unwrap().ino(),
+                    fs::metadata(&source).unwrap().ino()
+                );
+            }
+            LinkMode::Symlink => {
+                assert!(fs::symlink_metadata(&installed)
+                    .unwrap()
+                    .file_type()
+                    .is_symlink());
+            }
+            LinkMode::Copy | LinkMode::Reflink => {
+                assert!(fs::symlink_metadata(&installed)
+                    .unwrap()
+                    .file_type()
+                    .is_file());
+            }
+        }
+
+        // Scripts are always written as regular files
+        assert!(fs::symlink_metadata(target.join("bin/black"))
+            .unwrap()
+            .file_type()
+            .is_file());
+    }
+
     #[test]
     fn test_installer() {
         let unpacked = unpack_wheel(
@@ -948,7 +1894,7 @@ mod test {
 
         // Determine the location where we would expect the RECORD file to exist
         let record_path = unpacked.dist_info.join("RECORD");
-        let record_content = fs::read_to_string(&unpacked.tmpdir.path().join(&record_path))
+        let record_content = fs::read_to_string(unpacked.tmpdir.path().join(&record_path))
             .unwrap_or_else(|_| panic!("failed to read RECORD from {}", record_path.display()));
 
         // Replace all cpython references with cpython-xxx to ensure that no matter the version of
@@ -959,6 +1905,235 @@ mod test {
         insta::assert_snapshot!(record_content);
     }
 
+    #[test]
+    fn test_hash_generated_files() {
+        let python_path = system_python_executable().unwrap();
+        let compiler = ByteCodeCompiler::new(python_path).unwrap();
+
+        let tmpdir = tempdir().unwrap();
+        let target = tmpdir.path().join("target");
+        let record = install_miniblack(
+            &InstallPaths::for_target(&target, false),
+            tmpdir.path(),
+            &InstallWheelOptions {
+                byte_code_compiler: Some(&compiler),
+                hash_generated_files: true,
+                ..Default::default()
+            },
+        );
+
+        // Every entry but the RECORD itself should have a hash, including the bytecode files
+        let record = Record::from_reader(record.as_bytes()).unwrap();
+        assert!(record.iter().any(|entry| entry.path.ends_with(".pyc")));
+        for entry in record.iter() {
+            if entry.path.ends_with("RECORD") {
+                continue;
+            }
+            let (hash, size) =
+                hash_file(&target.join(&entry.path), RecordHashAlgorithm::Sha256).unwrap();
+            assert_eq!(entry.hash.as_ref(), Some(&hash), "{}", entry.path);
+            assert_eq!(entry.size, Some(size), "{}", entry.path);
+        }
+    }
+
+    #[test]
+    fn test_reproducible_byte_code() {
+        let python_path = system_python_executable().unwrap();
+        let compiler =
+            ByteCodeCompiler::with_invalidation_mode(python_path, InvalidationMode::CheckedHash)
+                .unwrap();
+
+        // Installing the same wheel into the same location twice results in identical bytecode
+        let tmpdir = tempdir().unwrap();
+        let target = tmpdir.path().join("target");
+        let install = || {
+            let _ = fs::remove_dir_all(&target);
+            install_miniblack(
+                &InstallPaths::for_target(&target, false),
+                tmpdir.path(),
+                &InstallWheelOptions {
+                    byte_code_compiler: Some(&compiler),
+                    hash_generated_files: true,
+                    ..Default::default()
+                },
+            )
+        };
+        let first = install();
+        let second = install();
+
+        assert!(first.contains(".pyc,sha256="));
+        assert_eq!(first, second);
+
+        // The bytecode does not depend on the modification time of the source
+        let source = target.join("black/__init__.py");
+        let compiled = fs::read(compiler.compile_and_wait(&source).unwrap()).unwrap();
+        filetime::set_file_mtime(&source, filetime::FileTime::zero()).unwrap();
+        let recompiled = fs::read(compiler.compile_and_wait(&source).unwrap()).unwrap();
+        assert_eq!(compiled, recompiled);
+    }
+
+    #[rstest]
+    #[case::sha256(RecordHashAlgorithm::Sha256)]
+    #[case::sha512(RecordHashAlgorithm::Sha512)]
+    fn test_record_hash_algorithm(#[case] algorithm: RecordHashAlgorithm) {
+        // The RECORD of this wheel uses sha512 hashes, these must be verified regardless of the
+        // algorithm used for the installed RECORD.
+        let wheel = Wheel::from_path(
+            &Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../../test-data/wheels/sha512_record-1.0.0-py3-none-any.whl"),
+            &"sha512_record".parse().unwrap(),
+        )
+        .unwrap();
+
+        let tmpdir = tempdir().unwrap();
+        let target = tmpdir.path().join("target");
+        let unpacked = install_wheel(
+            &wheel,
+            tmpdir.path(),
+            &InstallPaths::for_target(&target, false),
+            Path::new("/invalid"),
+            &InstallWheelOptions {
+                installer: Some(String::from(INSTALLER)),
+                record_hash_algorithm: algorithm,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let record = Record::from_path(&unpacked.dist_info.join("RECORD")).unwrap();
+        for entry in record.iter() {
+            if entry.path.ends_with("RECORD") {
+                continue;
+            }
+            let hash = entry.hash.as_deref().unwrap();
+            assert_eq!(
+                RecordHashAlgorithm::from_record_hash(hash),
+                Some(algorithm),
+                "{}",
+                entry.path
+            );
+            let (actual, _) = hash_file(&target.join(&entry.path), algorithm).unwrap();
+            assert_eq!(hash, actual, "{}", entry.path);
+        }
+    }
+
+    /// Creates an in-memory wheel containing a single python file of which the RECORD entry has
+    /// the given hash. The other files of the wheel are recorded with their sha256 hash.
+    fn wheel_with_recorded_hash(content: &str, hash: &str) -> Wheel {
+        let sha256 = |content: &str| {
+            let mut hasher = RecordHasher::new([RecordHashAlgorithm::Sha256]);
+            hasher.write_all(content.as_bytes()).unwrap();
+            hasher
+                .finalize()
+                .get(RecordHashAlgorithm::Sha256)
+                .to_string()
+        };
+        let metadata = "Metadata-Version: 2.1\nName: foo\nVersion: 1.0\n";
+        let wheel = "Wheel-Version: 1.0\nRoot-Is-Purelib: true\nTag: py3-none-any\n";
+        let record = format!(
+            "foo/__init__.py,{hash},{}\n\
+            foo-1.0.dist-info/METADATA,{},{}\n\
+            foo-1.0.dist-info/WHEEL,{},{}\n\
+            foo-1.0.dist-info/RECORD,,\n",
+            content.len(),
+            sha256(metadata),
+            metadata.len(),
+            sha256(wheel),
+            wheel.len(),
+        );
+        crate::utils::make_wheel(
+            "foo-1.0-py3-none-any.whl",
+            &[
+                ("foo/__init__.py", content),
+                ("foo-1.0.dist-info/METADATA", metadata),
+                ("foo-1.0.dist-info/WHEEL", wheel),
+                ("foo-1.0.dist-info/RECORD", &record),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_verify_record_hash_algorithm() {
+        let install = |wheel: &Wheel| {
+            let tmpdir = tempdir().unwrap();
+            install_wheel(
+                wheel,
+                tmpdir.path(),
+                &InstallPaths::for_target(tmpdir.path().join("target"), false),
+                Path::new("/invalid"),
+                &Default::default(),
+            )
+            .map(|_| ())
+        };
+        let content = "print('hello')\n";
+        let mut hasher = RecordHasher::new([RecordHashAlgorithm::Sha512]);
+        hasher.write_all(content.as_bytes()).unwrap();
+        let sha512 = hasher
+            .finalize()
+            .get(RecordHashAlgorithm::Sha512)
+            .to_string();
+
+        // The hash is verified with the algorithm of the RECORD
+        install(&wheel_with_recorded_hash(content, &sha512)).unwrap();
+
+        // A file that doesn't match its recorded hash is rejected
+        let err = install(&wheel_with_recorded_hash("print('tampered')\n", &sha512)).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("hash mismatch for foo/__init__.py"),
+            "{err}"
+        );
+
+        // Algorithms that are not supported are rejected instead of reported as a mismatch
+        let err = install(&wheel_with_recorded_hash(
+            content,
+            "md5=iR0b7L4t8h7tXNBRpPfoYw",
+        ))
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("unsupported hash algorithm"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_verify_tags() {
+        let wheel = Wheel::from_path(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join(
+                "../../test-data/wheels/purelib_and_platlib-1.0.0-cp38-cp38-linux_x86_64.whl",
+            ),
+            &"purelib_and_platlib".parse().unwrap(),
+        )
+        .unwrap();
+        let install = |tags: &[&str]| {
+            let tags = tags
+                .iter()
+                .map(|tag| tag.parse().unwrap())
+                .collect::<WheelTags>();
+            let tmpdir = tempdir().unwrap();
+            install_wheel(
+                &wheel,
+                tmpdir.path(),
+                &InstallPaths::for_venv((3, 8, 5), false),
+                Path::new("/invalid"),
+                &InstallWheelOptions {
+                    verify_tags: Some(&tags),
+                    ..Default::default()
+                },
+            )
+            .map(|_| ())
+        };
+
+        install(&["cp38-cp38-linux_x86_64", "py3-none-any"]).unwrap();
+
+        // A cp38 wheel cannot be installed in a python 3.11 environment
+        let err = install(&["cp311-cp311-linux_x86_64", "py3-none-any"]).unwrap_err();
+        assert!(
+            matches!(err, InstallError::IncompatibleWheelTags(ref tags) if tags == "cp38-cp38-linux_x86_64"),
+            "{err}"
+        );
+    }
+
     #[test]
     fn test_headers() {
         // Create a virtual environment in a temporary directory
@@ -1009,18 +2184,28 @@ mod test {
         assert!(wheel.dist_info.join("direct_url.json").exists());
     }
 
-    #[test]
-    fn test_entry_points() {
+    #[rstest]
+    #[case::absolute_path(false)]
+    #[case::env(true)]
+    fn test_entry_points(#[case] env_shebang: bool) {
         // Create a virtual environment in a temporary directory
         let tmpdir = tempdir().unwrap();
         let venv = VEnv::create(tmpdir.path(), PythonLocation::System).unwrap();
+        let options = InstallWheelOptions {
+            shebang_style: if env_shebang {
+                ShebangStyle::Env(PythonLocation::System.version().unwrap())
+            } else {
+                ShebangStyle::AbsolutePath
+            },
+            ..Default::default()
+        };
 
         // Download our wheel file and install it in the virtual environment we just created
         let package_path = test_utils::download_and_cache_file(
             "https://files.pythonhosted.org/packages/29/a2/76daec910034d765f1018d22660c0970fb99f77143a42841d067b522903e/cowpy-1.1.5-py3-none-any.whl".parse().unwrap(),
             "de5ae7646dd30b4936013666c6bd019af9cf411cc3b377c8538cfd8414262921").unwrap();
         let wheel = Wheel::from_path(&package_path, &"cowpy".parse().unwrap()).unwrap();
-        venv.install_wheel(&wheel, &Default::default()).unwrap();
+        venv.install_wheel(&wheel, &options).unwrap();
 
         // Determine the location of the installed script
         let script_name = if venv.install_paths().is_windows() {
@@ -1028,14 +2213,19 @@ mod test {
         } else {
             "cowpy"
         };
-        let script_path = venv
-            .root()
-            .join(venv.install_paths().scripts())
-            .join(script_name);
+        let scripts_dir = venv.root().join(venv.install_paths().scripts());
+        let script_path = scripts_dir.join(script_name);
+
+        // Make sure the python interpreter of the venv is found first when using `env`.
+        let path = std::env::join_paths(std::iter::once(scripts_dir.clone()).chain(
+            std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default()),
+        ))
+        .unwrap();
 
         // Execute the script
         let output = std::process::Command::new(script_path)
             .arg("--list-eyes")
+            .env("PATH", path)
             .output()
             .unwrap();
 
@@ -1047,7 +2237,195 @@ mod test {
         }
 
         let stdout = String::from_utf8_lossy(&output.stdout);
-        insta::assert_snapshot!(stdout);
+        insta::assert_snapshot!("entry_points", stdout);
+    }
+
+    #[rstest]
+    #[case::no_filtering(None, &["extra-scripts", "extra-scripts-cli", "extra-scripts-cli-server", "extra-scripts-gui", "extra-scripts-server"])]
+    #[case::no_extras(Some(&[][..]), &["extra-scripts"])]
+    #[case::single_extra(Some(&["cli"][..]), &["extra-scripts", "extra-scripts-cli"])]
+    #[case::multiple_extras(Some(&["cli", "server"][..]), &["extra-scripts", "extra-scripts-cli", "extra-scripts-cli-server", "extra-scripts-server"])]
+    #[case::unrelated_extra(Some(&["docs"][..]), &["extra-scripts"])]
+    fn test_entry_points_with_extras(#[case] extras: Option<&[&str]>, #[case] expected: &[&str]) {
+        let tmpdir = tempdir().unwrap();
+        let target = tmpdir.path().join("target");
+        let wheel = Wheel::from_path(
+            &Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../../test-data/wheels/extra_scripts-1.0.0-py3-none-any.whl"),
+            &"extra_scripts".parse().unwrap(),
+        )
+        .unwrap();
+
+        let options = InstallWheelOptions {
+            extras: extras.map(|extras| {
+                extras
+                    .iter()
+                    .map(|extra| extra.parse().unwrap())
+                    .collect::<HashSet<Extra>>()
+            }),
+            ..Default::default()
+        };
+        install_wheel(
+            &wheel,
+            tmpdir.path(),
+            &InstallPaths::for_target(&target, false),
+            Path::new("/invalid"),
+            &options,
+        )
+        .unwrap();
+
+        // Only the scripts of the activated extras should have been created
+        let mut scripts = fs::read_dir(target.join("bin"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        scripts.sort();
+        assert_eq!(scripts, expected);
+    }
+
+    #[rstest]
+    #[case::console(UnixGuiScripts::Console, false)]
+    #[case::detach(UnixGuiScripts::Detach, true)]
+    fn test_unix_gui_scripts(#[case] unix_gui_scripts: UnixGuiScripts, #[case] detached: bool) {
+        let tmpdir = tempdir().unwrap();
+        let wheel = Wheel::from_path(
+            &Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../../test-data/wheels/extra_scripts-1.0.0-py3-none-any.whl"),
+            &"extra_scripts".parse().unwrap(),
+        )
+        .unwrap();
+
+        install_wheel(
+            &wheel,
+            tmpdir.path(),
+            &InstallPaths::for_venv((3, 11, 4), false),
+            Path::new("/usr/bin/python3"),
+            &InstallWheelOptions {
+                unix_gui_scripts,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // Only gui scripts are affected
+        let read_script = |name: &str| fs::read_to_string(tmpdir.path().join("bin").join(name));
+        let gui_script = read_script("extra-scripts-gui").unwrap();
+        let console_script = read_script("extra-scripts").unwrap();
+        assert!(gui_script.starts_with("#!/usr/bin/python3\n"));
+        assert_eq!(gui_script.contains("os.setsid()"), detached);
+        if detached {
+            // The script must detach before the entry point is imported
+            assert!(gui_script.find("os.setsid()") < gui_script.find("from "));
+        }
+        assert!(!console_script.contains("os.setsid()"));
+    }
+
+    #[rstest]
+    #[case::windows(true, "Lib/site-packages", "Scripts", ".exe")]
+    #[case::posix(false, "lib/python3.11/site-packages", "bin", "")]
+    fn test_install_for_other_os(
+        #[case] windows: bool,
+        #[case] site_packages: &str,
+        #[case] scripts: &str,
+        #[case] script_suffix: &str,
+    ) {
+        // The layout only depends on the install paths, not on the host operating system
+        let tmpdir = tempdir().unwrap();
+        let wheel = Wheel::from_path(
+            &Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../../test-data/wheels/extra_scripts-1.0.0-py3-none-any.whl"),
+            &"extra_scripts".parse().unwrap(),
+        )
+        .unwrap();
+
+        let python_executable = if windows {
+            Path::new("C:\\python\\python.exe")
+        } else {
+            Path::new("/usr/bin/python3")
+        };
+        let installed = install_wheel(
+            &wheel,
+            tmpdir.path(),
+            &InstallPaths::for_venv((3, 11, 4), windows),
+            python_executable,
+            &InstallWheelOptions {
+                launcher_arch: Some(WindowsLauncherArch::X86_64),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            installed.dist_info,
+            tmpdir
+                .path()
+                .join(site_packages)
+                .join("extra_scripts-1.0.0.dist-info")
+        );
+        assert!(tmpdir
+            .path()
+            .join(site_packages)
+            .join("extra_scripts/__init__.py")
+            .is_file());
+
+        // Console scripts are windows launchers or scripts with a shebang
+        let script = fs::read(
+            tmpdir
+                .path()
+                .join(scripts)
+                .join(format!("extra-scripts{script_suffix}")),
+        )
+        .unwrap();
+        if windows {
+            assert!(script
+                .starts_with(WindowsLauncherArch::X86_64.launcher_bytes(LauncherType::Console)));
+        } else {
+            assert!(script.starts_with(b"#!/usr/bin/python3\n"));
+        }
+
+        // The RECORD always uses forward slashes
+        let record = Record::from_path(&installed.dist_info.join("RECORD")).unwrap();
+        assert!(record.iter().all(|entry| !entry.path.contains('\\')));
+        let script_record = format!(
+            "{}{scripts}/extra-scripts{script_suffix}",
+            "../".repeat(site_packages.split('/').count())
+        );
+        assert!(record.iter().any(|entry| entry.path == script_record));
+    }
+
+    #[test]
+    fn test_forced_launcher_arch() {
+        for arch in [
+            WindowsLauncherArch::X86,
+            WindowsLauncherArch::X86_64,
+            WindowsLauncherArch::Arm64,
+        ] {
+            let trampoline_maker = TrampolineMaker {
+                python_executable: PathBuf::from("C:\\python\\python.exe"),
+                kind: TrampolineMakerKind::Windows { arch: Some(arch) },
+            };
+
+            for launcher_type in [LauncherType::Console, LauncherType::Gui] {
+                let trampoline = trampoline_maker
+                    .make_trampoline(launcher_type, b"print('hello')")
+                    .unwrap();
+                let launcher = arch.launcher_bytes(launcher_type);
+                assert!(
+                    trampoline.starts_with(launcher),
+                    "{arch:?} {launcher_type:?} trampoline does not start with the expected launcher"
+                );
+                assert_eq!(
+                    &trampoline[launcher.len()..launcher.len() + 2],
+                    b"#!",
+                    "the shebang should directly follow the launcher"
+                );
+            }
+        }
+
+        // Make sure that each architecture embeds a different launcher
+        assert_ne!(
+            WindowsLauncherArch::X86_64.launcher_bytes(LauncherType::Console),
+            WindowsLauncherArch::Arm64.launcher_bytes(LauncherType::Console)
+        );
     }
 
     async fn download_best_ruff_wheel() -> PathBuf {
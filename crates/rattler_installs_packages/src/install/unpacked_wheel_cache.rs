@@ -0,0 +1,174 @@
+use super::{write_wheel_file, InstallError};
+use crate::{
+    artifacts::Wheel,
+    types::{Record, RecordEntry, RecordHashAlgorithm},
+};
+use rattler_digest::Sha256Hash;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The name of the file in an unpacked wheel directory that records the hashes of all the files.
+/// The file is written last, its presence indicates that the wheel has been fully unpacked.
+const RECORD_FILE_NAME: &str = ".rip-record";
+
+/// A cache that unpacks wheels once into a content-addressed store. The files of an unpacked wheel
+/// can then be linked into multiple environments by passing the directory to
+/// [`super::InstallWheelOptions::unpacked_wheel`] together with a [`super::LinkMode`].
+///
+/// Wheels are stored in `<cache_dir>/unpacked/<sha256 of the wheel>/`.
+#[derive(Debug, Clone)]
+pub struct UnpackedWheelCache {
+    root: PathBuf,
+}
+
+/// A wheel that has been unpacked into an [`UnpackedWheelCache`].
+#[derive(Debug)]
+pub struct UnpackedWheel {
+    /// The directory that contains the files of the wheel, laid out exactly as in the archive.
+    pub path: PathBuf,
+
+    /// The hashes and sizes of all files in the unpacked wheel. The paths are relative to
+    /// [`Self::path`].
+    pub record: Record,
+}
+
+impl UnpackedWheelCache {
+    /// Constructs a new cache that stores unpacked wheels in the `unpacked` subdirectory of
+    /// `cache_dir`.
+    pub fn new(cache_dir: &Path) -> Self {
+        Self {
+            root: cache_dir.join("unpacked"),
+        }
+    }
+
+    /// Returns the unpacked wheel with the given sha256 hash, unpacking the `wheel` into the cache
+    /// if it is not already present.
+    ///
+    /// The wheel is first unpacked into a temporary directory which is moved into place once all
+    /// files have been written, so a partially unpacked wheel is never observed.
+    pub fn get_or_unpack(
+        &self,
+        wheel: &Wheel,
+        sha256: &Sha256Hash,
+    ) -> Result<UnpackedWheel, InstallError> {
+        let path = self.root.join(format!("{sha256:x}"));
+        let record_path = path.join(RECORD_FILE_NAME);
+        if record_path.is_file() {
+            return Ok(UnpackedWheel {
+                record: Record::from_path(&record_path)?,
+                path,
+            });
+        }
+
+        fs::create_dir_all(&self.root)
+            .map_err(|err| InstallError::IoError(self.root.display().to_string(), err))?;
+        let temp_dir = tempfile::tempdir_in(&self.root)
+            .map_err(|err| InstallError::IoError(self.root.display().to_string(), err))?;
+
+        let mut archive = wheel.archive.lock();
+        let mut entries = Vec::with_capacity(archive.len());
+        for index in 0..archive.len() {
+            let mut zip_entry = archive
+                .by_index(index)
+                .map_err(|e| InstallError::from_zip_error(format!("<index {index}>"), e))?;
+            let Some(relative_path) = zip_entry.enclosed_name().map(ToOwned::to_owned) else {
+                // Skip invalid paths
+                continue;
+            };
+
+            let destination = temp_dir.path().join(&relative_path);
+            if zip_entry.is_dir() {
+                fs::create_dir_all(&destination)
+                    .map_err(|err| InstallError::IoError(destination.display().to_string(), err))?;
+                continue;
+            }
+
+            let executable = zip_entry
+                .unix_mode()
+                .map(|v| v & 0o0111 != 0)
+                .unwrap_or(false);
+            let (size, hashes) = write_wheel_file(
+                &mut zip_entry,
+                &destination,
+                executable,
+                [RecordHashAlgorithm::Sha256],
+            )?;
+            entries.push(RecordEntry {
+                path: relative_path.display().to_string().replace('\\', "/"),
+                hash: Some(hashes.get(RecordHashAlgorithm::Sha256).to_string()),
+                size,
+            });
+        }
+        drop(archive);
+
+        let record = Record::from_iter(entries);
+        record.write_to_path(&temp_dir.path().join(RECORD_FILE_NAME))?;
+
+        // Move the unpacked wheel into place. If this fails another process might have unpacked
+        // the same wheel concurrently, in which case we use that one.
+        let temp_path = temp_dir.into_path();
+        if let Err(err) = fs::rename(&temp_path, &path) {
+            let _ = fs::remove_dir_all(&temp_path);
+            if !record_path.is_file() {
+                return Err(InstallError::IoError(path.display().to_string(), err));
+            }
+        }
+
+        Ok(UnpackedWheel { path, record })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::install::{install_wheel, InstallPaths, InstallWheelOptions, LinkMode};
+    use rattler_digest::{compute_file_digest, Sha256};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_unpacked_wheel_cache() {
+        let wheel_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/wheels/miniblack-23.1.0-py3-none-any.whl");
+        let wheel = Wheel::from_path(&wheel_path, &"miniblack".parse().unwrap()).unwrap();
+        let sha256 = compute_file_digest::<Sha256>(&wheel_path).unwrap();
+
+        let tmpdir = tempdir().unwrap();
+        let cache = UnpackedWheelCache::new(&tmpdir.path().join("cache"));
+        let unpacked = cache.get_or_unpack(&wheel, &sha256).unwrap();
+        assert_eq!(
+            unpacked.path,
+            tmpdir.path().join(format!("cache/unpacked/{sha256:x}"))
+        );
+        assert!(unpacked.path.join("black/__init__.py").is_file());
+        assert!(unpacked
+            .record
+            .iter()
+            .any(|entry| entry.path == "black/__init__.py" && entry.hash.is_some()));
+
+        // A second request should return the cached wheel
+        let cached = cache.get_or_unpack(&wheel, &sha256).unwrap();
+        assert_eq!(cached.path, unpacked.path);
+        assert_eq!(cached.record.iter().count(), unpacked.record.iter().count());
+
+        // Install the wheel by linking the files from the cache. The RECORD in the environment
+        // should refer to the installed files, not to the cache.
+        let target = tmpdir.path().join("target");
+        let installed = install_wheel(
+            &wheel,
+            tmpdir.path(),
+            &InstallPaths::for_target(target, false),
+            Path::new("/invalid"),
+            &InstallWheelOptions {
+                link_mode: LinkMode::Hardlink,
+                unpacked_wheel: Some(&unpacked.path),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let record = Record::from_path(&installed.dist_info.join("RECORD")).unwrap();
+        assert!(record.iter().any(|entry| entry.path == "black/__init__.py"));
+        assert!(record.iter().all(|entry| !entry.path.contains("unpacked")));
+    }
+}
@@ -17,9 +17,13 @@ mod utils;
 
 pub mod resolve;
 
+pub mod requirements;
+
 pub mod wheel_builder;
 
 mod win;
 
 pub mod artifacts;
-pub use utils::normalize_index_url;
+
+pub mod config;
+pub use utils::{normalize_index_url, redact_url};
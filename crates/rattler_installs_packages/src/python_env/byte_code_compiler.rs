@@ -49,12 +49,42 @@ pub enum SpawnCompilerError {
     FailedToStartPython(#[source] io::Error),
 }
 
+/// Determines how the interpreter checks whether a compiled bytecode file is up to date with its
+/// source. See [PEP 552](https://peps.python.org/pep-0552/) for more information.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum InvalidationMode {
+    /// Use the default of the python interpreter. This embeds the modification time of the source
+    /// in the bytecode, unless the `SOURCE_DATE_EPOCH` environment variable is set.
+    #[default]
+    Timestamp,
+
+    /// Embed a hash of the source in the bytecode, which is checked against the source when the
+    /// bytecode is loaded. The resulting bytecode is reproducible.
+    CheckedHash,
+
+    /// Embed a hash of the source in the bytecode, but never check it when the bytecode is loaded.
+    /// The resulting bytecode is reproducible.
+    UncheckedHash,
+}
+
+impl InvalidationMode {
+    /// Returns the name of the mode as understood by the compilation host, or `None` to use the
+    /// default of the interpreter.
+    fn as_arg(self) -> Option<&'static str> {
+        match self {
+            InvalidationMode::Timestamp => None,
+            InvalidationMode::CheckedHash => Some("checked-hash"),
+            InvalidationMode::UncheckedHash => Some("unchecked-hash"),
+        }
+    }
+}
+
 /// An object that allows compiling python source code to byte code in a separate process.
 pub struct ByteCodeCompiler {
     /// The channel that is used to send compilation requests to the compilation host. If this is
     /// dropped the attached thread will drop stdin of the child which will signal the child to
-    /// exit.
-    request_tx: Option<Sender<CompilationRequest>>,
+    /// exit. The sender is wrapped in a mutex so the compiler can be shared between threads.
+    request_tx: Mutex<Option<Sender<CompilationRequest>>>,
 
     /// Callback functions per compilation request. These are called when the compilation host
     /// finishes processing a request.
@@ -74,6 +104,17 @@ impl ByteCodeCompiler {
     ///
     /// This function spawns a new python process that will be used to compile python source code.
     pub fn new(python_path: &Path) -> Result<Self, SpawnCompilerError> {
+        Self::with_invalidation_mode(python_path, InvalidationMode::default())
+    }
+
+    /// Constructs a new instance that compiles bytecode with the given [`InvalidationMode`].
+    ///
+    /// With one of the hash based modes `PYTHONHASHSEED` is also fixed for the compilation
+    /// process, so the produced bytecode is identical across runs.
+    pub fn with_invalidation_mode(
+        python_path: &Path,
+        invalidation_mode: InvalidationMode,
+    ) -> Result<Self, SpawnCompilerError> {
         // Write the compilation host source code to a temporary file
         let compilation_source = tempfile::Builder::new()
             .prefix("pyc_compilation_host")
@@ -86,10 +127,13 @@ impl ByteCodeCompiler {
             .map_err(SpawnCompilerError::FailedToCreateSource)?;
 
         // Start the compilation process
-        let mut child = Command::new(python_path)
-            .arg("-Wi")
-            .arg("-u")
-            .arg(compilation_source.path())
+        let mut command = Command::new(python_path);
+        command.arg("-Wi").arg("-u").arg(compilation_source.path());
+        if let Some(mode) = invalidation_mode.as_arg() {
+            // The order of sets in the bytecode depends on the hash seed
+            command.arg(mode).env("PYTHONHASHSEED", "0");
+        }
+        let mut child = command
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .spawn()
@@ -177,7 +221,7 @@ impl ByteCodeCompiler {
         });
 
         Ok(Self {
-            request_tx: Some(request_tx),
+            request_tx: Mutex::new(Some(request_tx)),
             pending_callbacks,
             child: Some(child),
             _compilation_source: compilation_source,
@@ -214,6 +258,7 @@ impl ByteCodeCompiler {
             .push(Box::new(callback));
 
         self.request_tx
+            .lock()
             .as_ref()
             .expect("the channel is only dropped on drop")
             .send(source_path.to_owned())
@@ -236,7 +281,7 @@ impl ByteCodeCompiler {
         // Drop the request channel to signal the compilation host that we are done. This will
         // ensure that the stdin pipe of the compilation host is closed which will signal the host
         // to exit.
-        drop(self.request_tx.take());
+        drop(self.request_tx.get_mut().take());
 
         // Wait for the compilation host to exit
         self.child
@@ -250,7 +295,7 @@ impl ByteCodeCompiler {
 
 impl Drop for ByteCodeCompiler {
     fn drop(&mut self) {
-        drop(self.request_tx.take());
+        drop(self.request_tx.get_mut().take());
         if let Some(mut child) = self.child.take() {
             child.wait().unwrap();
         }
@@ -283,6 +328,27 @@ mod test {
         compiler.wait().unwrap();
     }
 
+    #[test]
+    fn test_invalidation_mode() {
+        let python_path = system_python_executable().unwrap();
+        let tmpdir = tempfile::tempdir().unwrap();
+        let source = tmpdir.path().join("module.py");
+        std::fs::write(&source, "VALUES = {'a', 'b', 'c'}\n").unwrap();
+
+        // The flags in the header of the bytecode indicate a checked hash based pyc
+        let compiler =
+            ByteCodeCompiler::with_invalidation_mode(python_path, InvalidationMode::CheckedHash)
+                .unwrap();
+        let pyc_file = compiler.compile_and_wait(&source).unwrap();
+        let first = std::fs::read(pyc_file).unwrap();
+        assert_eq!(&first[4..8], &[3, 0, 0, 0]);
+
+        // Changing the modification time of the source does not change the bytecode
+        filetime::set_file_mtime(&source, filetime::FileTime::zero()).unwrap();
+        let pyc_file = compiler.compile_and_wait(&source).unwrap();
+        assert_eq!(std::fs::read(pyc_file).unwrap(), first);
+    }
+
     #[test]
     fn test_failed_case() {
         let python_path = system_python_executable().unwrap();
@@ -2,11 +2,19 @@ import sys
 import json
 import importlib
 import compileall
+import py_compile
 from multiprocessing import Pool
 
+INVALIDATION_MODES = {
+    "checked-hash": py_compile.PycInvalidationMode.CHECKED_HASH,
+    "unchecked-hash": py_compile.PycInvalidationMode.UNCHECKED_HASH,
+}
 
-def compile_one(path):
-    success = compileall.compile_file(path, quiet=2, force=True)
+
+def compile_one(path, invalidation_mode):
+    success = compileall.compile_file(
+        path, quiet=2, force=True, invalidation_mode=invalidation_mode
+    )
     output_path = importlib.util.cache_from_source(path) if success else None
     return path, output_path
 
@@ -17,10 +25,15 @@ def compilation_finished(compilation_result):
 
 
 if __name__ == "__main__":
+    invalidation_mode = INVALIDATION_MODES.get(sys.argv[1]) if len(sys.argv) > 1 else None
     with sys.stdin:
         with Pool() as pool:
             while True:
                 path = sys.stdin.readline().strip()
                 if not path:
                     break
-                pool.apply_async(compile_one, (path,), callback=compilation_finished)
+                pool.apply_async(
+                    compile_one,
+                    (path, invalidation_mode),
+                    callback=compilation_finished,
+                )
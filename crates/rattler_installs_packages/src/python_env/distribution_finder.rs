@@ -6,7 +6,10 @@
 
 use crate::install::InstallPaths;
 use crate::python_env::WheelTag;
-use crate::{types::NormalizedPackageName, types::PackageName, types::RFC822ish};
+use crate::{
+    types::EntryPoints, types::NormalizedPackageName, types::PackageName,
+    types::ParseEntryPointsError, types::RFC822ish,
+};
 use fs_err as fs;
 use indexmap::IndexSet;
 use itertools::Itertools;
@@ -53,6 +56,30 @@ pub enum FindDistributionError {
     /// Failed to parse WHEEL tags
     #[error("failed to parse wheel tag {0}")]
     FailedToParseWheelTag(String),
+
+    /// Failed to parse an entry_points.txt file
+    #[error("failed to parse '{0}'")]
+    FailedToParseEntryPoints(PathBuf, #[source] ParseEntryPointsError),
+}
+
+impl Distribution {
+    /// Reads the entry points registered by the distribution from the `entry_points.txt` file in
+    /// its `.dist-info` directory. `root` is the directory that [`Distribution::dist_info`] is
+    /// relative to, e.g. the root of the virtualenv passed to [`find_distributions_in_venv`].
+    ///
+    /// This includes all groups, not just the scripts, which makes it possible to determine which
+    /// installed distributions register a plugin, e.g. for the `pytest11` group. Returns empty
+    /// [`EntryPoints`] if the distribution does not declare any entry points.
+    pub fn entry_points(&self, root: &Path) -> Result<EntryPoints, FindDistributionError> {
+        let path = root.join(&self.dist_info).join("entry_points.txt");
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(EntryPoints::default()),
+            Err(e) => return Err(e.into()),
+        };
+        EntryPoints::from_ini(&contents, None)
+            .map_err(|e| FindDistributionError::FailedToParseEntryPoints(path, e))
+    }
 }
 
 /// Locates the python distributions (packages) that have been installed in the specified directory.
@@ -202,4 +229,47 @@ mod test {
             }),
         });
     }
+
+    #[test]
+    fn test_entry_points() {
+        let venv_path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/find_distributions/");
+        let install_paths = InstallPaths::for_venv((3, 8, 5), true);
+        let distributions = find_distributions_in_venv(&venv_path, &install_paths).unwrap();
+
+        // Find the distributions that register entry points in a specific group
+        let providers = |group: &str| {
+            let mut names = distributions
+                .iter()
+                .filter(|dist| {
+                    !dist
+                        .entry_points(&venv_path)
+                        .unwrap()
+                        .group(group)
+                        .is_empty()
+                })
+                .map(|dist| dist.name.as_str())
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(providers("console_scripts"), ["flask", "pip", "setuptools"]);
+        assert_eq!(providers("babel.extractors"), ["jinja2"]);
+        assert_eq!(providers("distutils.commands"), ["setuptools"]);
+        assert!(providers("pytest11").is_empty());
+
+        let flask = distributions
+            .iter()
+            .find(|dist| dist.name.as_str() == "flask")
+            .unwrap();
+        let entry_points = flask.entry_points(&venv_path).unwrap();
+        assert_eq!(entry_points.console_scripts()[0].module, "flask.cli");
+
+        // Distributions without an entry_points.txt have no entry points
+        let click = distributions
+            .iter()
+            .find(|dist| dist.name.as_str() == "click")
+            .unwrap();
+        assert!(click.entry_points(&venv_path).unwrap().groups.is_empty());
+    }
 }
@@ -1,5 +1,5 @@
 use super::Pep508EnvMakers;
-use crate::python_env::{system_python_executable, FindPythonError};
+use crate::python_env::{system_python_executable, FindPythonError, PythonLocation};
 use std::io;
 use std::io::ErrorKind;
 use std::path::Path;
@@ -29,6 +29,14 @@ impl Pep508EnvMakers {
         Self::from_python(python.as_path()).await
     }
 
+    /// Try to determine the environment markers from the interpreter at the given location. Use this
+    /// to derive the environment markers from the same interpreter that is passed as
+    /// [`crate::resolve::solve_options::ResolveOptions::python_location`], so the markers that are
+    /// used for resolution match the interpreter that is used to build wheels.
+    pub async fn from_python_location(location: &PythonLocation) -> Result<Self, FromPythonError> {
+        Self::from_python(location.executable()?.as_path()).await
+    }
+
     /// Try to determine the environment markers from an existing python executable. The executable
     /// is used to run a simple python program to extract the information.
     pub async fn from_python(python: &Path) -> Result<Self, FromPythonError> {
@@ -0,0 +1,178 @@
+use super::Pep508EnvMakers;
+use pep508_rs::MarkerEnvironment;
+use thiserror::Error;
+
+/// An error that can occur when constructing environment markers for a named target.
+#[derive(Debug, Error)]
+#[allow(missing_docs)]
+pub enum ParseTargetError {
+    #[error("invalid target '{0}', expected a target in the form '<os>-<arch>-<implementation>-<python version>', e.g. 'linux-x86_64-cpython-3.11'")]
+    InvalidFormat(String),
+
+    #[error("unsupported operating system '{0}', expected one of 'linux', 'macos' or 'windows'")]
+    UnsupportedOs(String),
+
+    #[error("unsupported architecture '{0}' for {1}")]
+    UnsupportedArch(String, String),
+
+    #[error("unsupported python implementation '{0}', expected 'cpython' or 'pypy'")]
+    UnsupportedImplementation(String),
+
+    #[error("invalid python version '{0}', expected a version like '3.11' or '3.11.4'")]
+    InvalidPythonVersion(String),
+}
+
+impl Pep508EnvMakers {
+    /// Constructs the environment markers for a named target without requiring an interpreter for
+    /// that target. This allows resolving an environment for a platform other than the host, e.g.
+    /// resolving what would be installed on Linux from a Mac.
+    ///
+    /// The target is specified as `<os>-<arch>-<implementation>-<python version>`, for example
+    /// `linux-x86_64-cpython-3.11` or `windows-aarch64-pypy-3.10.13`. If the patch version of
+    /// python is omitted it is assumed to be `0`.
+    ///
+    /// The `platform_release` and `platform_version` markers cannot be derived from the target and
+    /// are left empty.
+    pub fn from_target(target: &str) -> Result<Self, ParseTargetError> {
+        let invalid_format = || ParseTargetError::InvalidFormat(target.to_string());
+        let mut parts = target.split('-');
+        let (Some(os), Some(arch), Some(implementation), Some(version), None) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            return Err(invalid_format());
+        };
+
+        let (os_name, sys_platform, platform_system) = match os {
+            "linux" => ("posix", "linux", "Linux"),
+            "macos" | "osx" => ("posix", "darwin", "Darwin"),
+            "windows" | "win" => ("nt", "win32", "Windows"),
+            _ => return Err(ParseTargetError::UnsupportedOs(os.to_string())),
+        };
+
+        let platform_machine = match (platform_system, arch) {
+            ("Windows", "x86_64" | "amd64") => "AMD64",
+            ("Windows", "aarch64" | "arm64") => "ARM64",
+            ("Windows", "x86" | "i686") => "x86",
+            ("Darwin", "x86_64") => "x86_64",
+            ("Darwin", "aarch64" | "arm64") => "arm64",
+            ("Linux", "x86_64" | "aarch64" | "i686" | "ppc64le" | "s390x" | "armv7l") => arch,
+            ("Linux", "arm64") => "aarch64",
+            ("Linux", "x86") => "i686",
+            _ => {
+                return Err(ParseTargetError::UnsupportedArch(
+                    arch.to_string(),
+                    platform_system.to_string(),
+                ))
+            }
+        };
+
+        let (implementation_name, platform_python_implementation) = match implementation {
+            "cpython" => ("cpython", "CPython"),
+            "pypy" => ("pypy", "PyPy"),
+            _ => {
+                return Err(ParseTargetError::UnsupportedImplementation(
+                    implementation.to_string(),
+                ))
+            }
+        };
+
+        let invalid_version = || ParseTargetError::InvalidPythonVersion(version.to_string());
+        let components = version
+            .split('.')
+            .map(|c| c.parse::<u32>().map_err(|_| invalid_version()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (major, minor, patch) = match components[..] {
+            [major, minor] => (major, minor, 0),
+            [major, minor, patch] => (major, minor, patch),
+            _ => return Err(invalid_version()),
+        };
+        let python_version = format!("{major}.{minor}");
+        let python_full_version = format!("{major}.{minor}.{patch}");
+
+        Ok(Self(MarkerEnvironment {
+            implementation_name: implementation_name.to_string(),
+            implementation_version: python_full_version.parse().map_err(|_| invalid_version())?,
+            os_name: os_name.to_string(),
+            platform_machine: platform_machine.to_string(),
+            platform_python_implementation: platform_python_implementation.to_string(),
+            platform_release: String::new(),
+            platform_system: platform_system.to_string(),
+            platform_version: String::new(),
+            python_full_version: python_full_version.parse().map_err(|_| invalid_version())?,
+            python_version: python_version.parse().map_err(|_| invalid_version())?,
+            sys_platform: sys_platform.to_string(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pep508_rs::Requirement;
+    use std::str::FromStr;
+
+    fn evaluate(env: &Pep508EnvMakers, requirement: &str) -> bool {
+        Requirement::from_str(requirement)
+            .unwrap()
+            .marker
+            .unwrap()
+            .evaluate(env, &[])
+    }
+
+    #[test]
+    fn test_from_target() {
+        let linux = Pep508EnvMakers::from_target("linux-x86_64-cpython-3.11").unwrap();
+        assert_eq!(linux.sys_platform, "linux");
+        assert_eq!(linux.platform_machine, "x86_64");
+        assert!(evaluate(&linux, "foo; python_full_version == '3.11.0'"));
+        assert!(evaluate(&linux, "foo; sys_platform == 'linux'"));
+        assert!(evaluate(&linux, "foo; python_version >= '3.11'"));
+        assert!(!evaluate(&linux, "foo; python_version < '3.11'"));
+        assert!(evaluate(
+            &linux,
+            "foo; platform_python_implementation == 'CPython'"
+        ));
+
+        let mac = Pep508EnvMakers::from_target("macos-arm64-cpython-3.8.10").unwrap();
+        assert_eq!(mac.platform_machine, "arm64");
+        assert!(evaluate(&mac, "foo; python_full_version == '3.8.10'"));
+        assert!(evaluate(&mac, "foo; sys_platform == 'darwin'"));
+        assert!(!evaluate(&mac, "foo; python_version >= '3.9'"));
+
+        let windows = Pep508EnvMakers::from_target("windows-x86_64-pypy-3.10").unwrap();
+        assert_eq!(windows.platform_machine, "AMD64");
+        assert!(evaluate(
+            &windows,
+            "foo; os_name == 'nt' and sys_platform == 'win32'"
+        ));
+        assert!(evaluate(&windows, "foo; implementation_name == 'pypy'"));
+    }
+
+    #[test]
+    fn test_from_invalid_target() {
+        assert!(matches!(
+            Pep508EnvMakers::from_target("linux-x86_64"),
+            Err(ParseTargetError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            Pep508EnvMakers::from_target("beos-x86_64-cpython-3.11"),
+            Err(ParseTargetError::UnsupportedOs(_))
+        ));
+        assert!(matches!(
+            Pep508EnvMakers::from_target("macos-x86-cpython-3.11"),
+            Err(ParseTargetError::UnsupportedArch(..))
+        ));
+        assert!(matches!(
+            Pep508EnvMakers::from_target("linux-x86_64-jython-3.11"),
+            Err(ParseTargetError::UnsupportedImplementation(_))
+        ));
+        assert!(matches!(
+            Pep508EnvMakers::from_target("linux-x86_64-cpython-three"),
+            Err(ParseTargetError::InvalidPythonVersion(_))
+        ));
+    }
+}
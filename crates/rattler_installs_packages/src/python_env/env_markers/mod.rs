@@ -2,6 +2,9 @@ use serde::{Deserialize, Serialize};
 use std::ops::Deref;
 
 mod from_env;
+mod from_target;
+
+pub use from_target::ParseTargetError;
 
 /// Describes the environment markers that can be used in dependency specifications to enable or
 /// disable certain dependencies based on runtime environment.
@@ -9,20 +9,28 @@ mod env_markers;
 
 mod system_python;
 
+mod outdated;
 mod uninstall;
 mod venv;
+mod verify;
 
 mod byte_code_compiler;
 
 pub use tags::{WheelTag, WheelTags};
 
-pub use byte_code_compiler::{ByteCodeCompiler, CompilationError, SpawnCompilerError};
+pub use byte_code_compiler::{
+    ByteCodeCompiler, CompilationError, InvalidationMode, SpawnCompilerError,
+};
 pub use distribution_finder::{
     find_distributions_in_directory, find_distributions_in_venv, Distribution,
     FindDistributionError,
 };
-pub use env_markers::Pep508EnvMakers;
+pub use env_markers::{ParseTargetError, Pep508EnvMakers};
+pub use outdated::{outdated, OutdatedDistribution};
 pub(crate) use system_python::{system_python_executable, FindPythonError};
 pub use system_python::{ParsePythonInterpreterVersionError, PythonInterpreterVersion};
-pub use uninstall::{uninstall_distribution, UninstallDistributionError};
+pub use uninstall::{
+    uninstall_distribution, UninstallDistributionError, UninstallDistributionOptions,
+};
 pub use venv::{PythonLocation, VEnv, VEnvError};
+pub use verify::{verify_installed, Discrepancy, VerificationReport, VerifyInstalledError};
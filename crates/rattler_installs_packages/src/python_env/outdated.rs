@@ -0,0 +1,207 @@
+//! Functionality to find the installed distributions for which a newer version is available, like
+//! `pip list --outdated`.
+
+use crate::{
+    index::{ArtifactRequest, PackageDb},
+    python_env::{find_distributions_in_venv, PythonLocation, VEnv, WheelTags},
+    resolve::PypiVersion,
+    types::{ArtifactInfo, NormalizedPackageName},
+};
+use miette::IntoDiagnostic;
+use pep440_rs::Version;
+use std::str::FromStr;
+
+/// An installed distribution for which a newer version is available on the index.
+///
+/// See [`outdated`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutdatedDistribution {
+    /// The name of the distribution
+    pub name: NormalizedPackageName,
+
+    /// The version that is installed in the environment
+    pub installed: Version,
+
+    /// The latest version on the index that can be installed in the environment
+    pub latest: Version,
+}
+
+/// Finds the distributions installed in `venv` for which a newer version is available from
+/// `package_db`.
+///
+/// Only versions that can actually be installed are considered: yanked artifacts, artifacts that
+/// do not support the python version of the environment and wheels that are not compatible with
+/// `tags` are ignored. Pre-releases are only considered if the installed version is a pre-release
+/// itself.
+///
+/// Distributions that were installed from a direct url (they have a `direct_url.json` file) do
+/// not have a meaningful latest version on the index and are skipped.
+pub async fn outdated(
+    venv: &VEnv,
+    package_db: &PackageDb,
+    tags: &WheelTags,
+) -> miette::Result<Vec<OutdatedDistribution>> {
+    let distributions =
+        find_distributions_in_venv(venv.root(), venv.install_paths()).into_diagnostic()?;
+
+    // Determine the version of the python interpreter to filter artifacts by their
+    // `requires_python` field.
+    let python_version = match PythonLocation::Custom(venv.python_executable()).version() {
+        Ok(version) => Some(
+            Version::from_str(&format!(
+                "{}.{}.{}",
+                version.major, version.minor, version.patch
+            ))
+            .expect("a python interpreter version is always a valid version"),
+        ),
+        Err(err) => {
+            tracing::warn!("could not determine the python version, artifacts will not be filtered by `requires_python`: {err}");
+            None
+        }
+    };
+
+    let mut result = Vec::new();
+    for distribution in distributions {
+        if venv
+            .root()
+            .join(&distribution.dist_info)
+            .join("direct_url.json")
+            .is_file()
+        {
+            tracing::info!(
+                "skipping '{}' because it was installed from a direct url",
+                distribution.name
+            );
+            continue;
+        }
+
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::FromIndex(distribution.name.clone()))
+            .await?;
+
+        // The versions are sorted from the highest to the lowest, the first installable version is
+        // the latest.
+        let allow_prerelease = distribution.version.any_prerelease();
+        let latest = artifacts.iter().find_map(|(version, artifacts)| {
+            let PypiVersion::Version { version, .. } = version else {
+                return None;
+            };
+            if version.any_prerelease() && !allow_prerelease {
+                return None;
+            }
+            artifacts
+                .iter()
+                .any(|artifact| is_installable(artifact, tags, python_version.as_ref()))
+                .then_some(version)
+        });
+
+        match latest {
+            Some(latest) if latest > &distribution.version => result.push(OutdatedDistribution {
+                name: distribution.name,
+                installed: distribution.version,
+                latest: latest.clone(),
+            }),
+            _ => {}
+        }
+    }
+
+    Ok(result)
+}
+
+/// Returns true if the artifact is not yanked, supports the python version and, in case of a
+/// wheel, is compatible with the tags. Sdists can always be built for the environment.
+fn is_installable(
+    artifact: &ArtifactInfo,
+    tags: &WheelTags,
+    python_version: Option<&Version>,
+) -> bool {
+    if artifact.yanked.yanked {
+        return false;
+    }
+
+    if let (Some(requires_python), Some(python_version)) =
+        (artifact.requires_python.as_ref(), python_version)
+    {
+        if !requires_python.contains(python_version) {
+            return false;
+        }
+    }
+
+    artifact
+        .filename
+        .as_wheel()
+        .map_or(true, |wheel| tags.wheel_compatibility(wheel).is_some())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        artifacts::Wheel, index::CheckAvailablePackages, install::InstallWheelOptions,
+        python_env::WheelTag,
+    };
+    use reqwest::Client;
+    use reqwest_middleware::ClientWithMiddleware;
+    use std::{fs, path::Path};
+    use tempfile::TempDir;
+    use url::Url;
+
+    #[tokio::test]
+    async fn test_outdated() {
+        // A local index on which only the sdist of 23.1.5 is a newer version that can be installed,
+        // the other versions are a pre-release, an incompatible wheel and a yanked wheel.
+        let mirror = TempDir::new().unwrap();
+        fs::create_dir_all(mirror.path().join("miniblack")).unwrap();
+        fs::write(
+            mirror.path().join("miniblack/index.html"),
+            r#"<html><body>
+            <a href="miniblack-24.0.0a1-py3-none-any.whl">miniblack-24.0.0a1-py3-none-any.whl</a>
+            <a href="miniblack-23.3.0-cp27-none-any.whl">miniblack-23.3.0-cp27-none-any.whl</a>
+            <a href="miniblack-23.2.0-py3-none-any.whl" data-yanked="broken">miniblack-23.2.0-py3-none-any.whl</a>
+            <a href="miniblack-23.1.5.tar.gz">miniblack-23.1.5.tar.gz</a>
+            <a href="miniblack-23.1.0-py3-none-any.whl">miniblack-23.1.0-py3-none-any.whl</a>
+            </body></html>"#,
+        )
+        .unwrap();
+
+        let cache_dir = TempDir::new().unwrap();
+        let package_db = PackageDb::new(
+            Url::from_directory_path(mirror.path()).unwrap().into(),
+            ClientWithMiddleware::from(Client::new()),
+            cache_dir.path(),
+            CheckAvailablePackages::default(),
+        )
+        .unwrap();
+
+        let venv_dir = TempDir::new().unwrap();
+        let venv = VEnv::create(venv_dir.path(), PythonLocation::System).unwrap();
+        let wheel_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/wheels/miniblack-23.1.0-py3-none-any.whl");
+        let wheel = Wheel::from_path(&wheel_path, &"miniblack".parse().unwrap()).unwrap();
+        let installed = venv
+            .install_wheel(&wheel, &InstallWheelOptions::default())
+            .unwrap();
+
+        let tags = WheelTags::from_tags(vec![WheelTag::from_str("py3-none-any").unwrap()]);
+        let result = outdated(&venv, &package_db, &tags).await.unwrap();
+        assert_eq!(
+            result,
+            [OutdatedDistribution {
+                name: "miniblack".parse().unwrap(),
+                installed: "23.1.0".parse().unwrap(),
+                latest: "23.1.5".parse().unwrap(),
+            }]
+        );
+
+        // A distribution that was installed from a direct url is skipped
+        fs::write(
+            installed.dist_info.join("direct_url.json"),
+            r#"{"url": "https://example.com/miniblack-23.1.0-py3-none-any.whl", "archive_info": {}}"#,
+        )
+        .unwrap();
+        assert!(outdated(&venv, &package_db, &tags)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -141,6 +141,14 @@ impl PythonInterpreterVersion {
         Self::from_path(python_path)
     }
 
+    /// Returns true if the `python_version` marker (`<major>.<minor>`) of the marker environment
+    /// matches this interpreter version.
+    pub fn matches_marker_environment(&self, env_markers: &pep508_rs::MarkerEnvironment) -> bool {
+        let release = &env_markers.python_version.version.release;
+        release.first() == Some(&u64::from(self.major))
+            && release.get(1) == Some(&u64::from(self.minor))
+    }
+
     /// Get the python version a path to the python executable
     pub fn from_path(path: &Path) -> Result<Self, ParsePythonInterpreterVersionError> {
         let output = std::process::Command::new(path)
@@ -154,7 +162,7 @@ impl PythonInterpreterVersion {
 
 #[cfg(test)]
 mod tests {
-    use crate::python_env::PythonInterpreterVersion;
+    use crate::python_env::{Pep508EnvMakers, PythonInterpreterVersion};
 
     #[test]
     pub fn parse_python_version() {
@@ -163,4 +171,13 @@ mod tests {
         assert_eq!(version.minor, 8);
         assert_eq!(version.patch, 5);
     }
+
+    #[test]
+    pub fn matches_marker_environment() {
+        let env_markers = Pep508EnvMakers::from_target("linux-x86_64-cpython-3.11.4").unwrap();
+        assert!(PythonInterpreterVersion::new(3, 11, 4).matches_marker_environment(&env_markers));
+        // Only the major and minor version are compared
+        assert!(PythonInterpreterVersion::new(3, 11, 0).matches_marker_environment(&env_markers));
+        assert!(!PythonInterpreterVersion::new(3, 9, 4).matches_marker_environment(&env_markers));
+    }
 }
@@ -34,6 +34,11 @@ impl WheelTags {
 
     /// Try to determine the platform tags by executing the python command and extracting `sys_tags`
     /// using the vendored `packaging` module.
+    ///
+    /// The tags are ordered like `sys_tags` orders them: interpreter specific tags first, followed
+    /// by the abi3 tags and finally the tags that are compatible with any platform, e.g.
+    /// `py3-none-any`. Universal wheels therefore have the lowest
+    /// [`WheelTags::compatibility`].
     pub async fn from_python(python: &Path) -> Result<Self, FromPythonError> {
         // Create a temporary directory to place our vendored packages in
         let vendored_dir = tempfile::tempdir()?;
@@ -91,7 +96,9 @@ impl WheelTags {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::types::WheelFilename;
     use itertools::Itertools;
+    use std::str::FromStr;
 
     #[tokio::test]
     pub async fn test_from_env() {
@@ -111,4 +118,77 @@ mod test {
             }
         }
     }
+
+    #[tokio::test]
+    pub async fn test_universal_wheels_have_lowest_priority() {
+        let tags = WheelTags::from_env().await.unwrap();
+        let score = |tag: &WheelTag| tags.compatibility(tag);
+
+        // Universal wheels are compatible with any python 3 interpreter
+        let universal = WheelTag::from_compound_string("py2.py3-none-any")
+            .unwrap()
+            .iter()
+            .filter_map(score)
+            .max()
+            .expect("universal wheels should be compatible");
+        assert_eq!(
+            Some(universal),
+            score(&WheelTag::from_str("py3-none-any").unwrap())
+        );
+
+        // Every platform specific tag is preferred over the universal ones
+        for tag in tags.tags().filter(|tag| tag.platform != "any") {
+            assert!(score(tag) > Some(universal), "{tag} should be preferred");
+        }
+
+        // An interpreter specific abi is preferred over abi3
+        let most_specific = tags.tags().next().unwrap();
+        if most_specific.interpreter.starts_with("cp") {
+            let abi3 = WheelTag {
+                abi: String::from("abi3"),
+                ..most_specific.clone()
+            };
+            assert!(score(most_specific) > score(&abi3));
+            assert!(score(&abi3) > Some(universal));
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_abi3_wheels_are_compatible_with_newer_interpreters() {
+        let tags = WheelTags::from_env().await.unwrap();
+        let most_specific = tags.tags().next().unwrap();
+        let Some(minor) = most_specific
+            .interpreter
+            .strip_prefix("cp3")
+            .and_then(|minor| minor.parse::<u32>().ok())
+        else {
+            // abi3 only exists for CPython
+            return;
+        };
+
+        // The stable abi of every older minor version is supported
+        for n in 2..=minor {
+            let tag = WheelTag {
+                interpreter: format!("cp3{n}"),
+                abi: String::from("abi3"),
+                platform: most_specific.platform.clone(),
+            };
+            assert!(tags.is_compatible(&tag), "{tag} should be compatible");
+        }
+
+        // An abi3 wheel built for an older python is selectable, but a wheel built for the
+        // specific interpreter is preferred
+        let name = "foo".parse().unwrap();
+        let score = |filename: &str| {
+            WheelFilename::from_filename(filename, &name)
+                .unwrap()
+                .all_tags_iter()
+                .filter_map(|tag| tags.compatibility(&tag))
+                .max()
+        };
+        let abi3 = score(&format!("foo-1.0-cp32-abi3-{}.whl", most_specific.platform));
+        let specific = score(&format!("foo-1.0-{most_specific}.whl"));
+        assert!(abi3.is_some());
+        assert!(specific > abi3);
+    }
 }
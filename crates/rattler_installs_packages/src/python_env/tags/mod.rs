@@ -4,9 +4,13 @@
 
 mod from_env;
 
+use crate::types::{ArtifactInfo, WheelFilename};
 use indexmap::IndexSet;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use serde_with::{DeserializeFromStr, SerializeDisplay};
+use std::borrow::Borrow;
+use std::cmp::Reverse;
 use std::fmt::{Debug, Display, Formatter};
 use std::str::FromStr;
 
@@ -85,12 +89,22 @@ impl Display for WheelTag {
 }
 
 /// Contains an ordered set of platform tags with which compatibility of wheels can be determined.
-#[derive(Debug, Clone)]
+///
+/// The tags serialize as an ordered list of strings, which allows persisting the exact set of tags
+/// that was used for a resolution.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct WheelTags {
     tags: IndexSet<WheelTag>,
 }
 
 impl WheelTags {
+    /// Constructs a new instance from a list of tags. The order of the tags is preserved, the first
+    /// tag is the most specific one. Duplicate tags are ignored.
+    pub fn from_tags(tags: Vec<WheelTag>) -> Self {
+        tags.into_iter().collect()
+    }
+
     /// Returns an iterator over the supported tags.
     pub fn tags(&self) -> impl Iterator<Item = &'_ WheelTag> + '_ {
         self.tags.iter()
@@ -109,6 +123,83 @@ impl WheelTags {
     pub fn is_compatible(&self, tag: &WheelTag) -> bool {
         self.tags.contains(tag)
     }
+
+    /// Determines the compatibility of a wheel with the tags in this instance, see
+    /// [`Self::compatibility`]. A wheel can contain multiple tags, the most compatible one
+    /// determines the compatibility of the wheel.
+    pub fn wheel_compatibility(&self, wheel: &WheelFilename) -> Option<i32> {
+        wheel
+            .all_tags_iter()
+            .filter_map(|tag| self.compatibility(&tag))
+            .max()
+    }
+
+    /// Returns the wheel from `artifacts` that is most compatible with the tags in this instance
+    /// together with its [`Self::compatibility`]. This is the artifact that would be downloaded
+    /// for a version with the given artifacts. Sdists and incompatible wheels are ignored, if
+    /// multiple wheels are equally compatible the first one is returned.
+    pub fn best_artifact<'a, A: Borrow<ArtifactInfo>>(
+        &self,
+        artifacts: &'a [A],
+    ) -> Option<(&'a A, i32)> {
+        artifacts
+            .iter()
+            .filter_map(|artifact| {
+                let wheel = artifact.borrow().filename.as_wheel()?;
+                Some((artifact, self.wheel_compatibility(wheel)?))
+            })
+            .min_by_key(|(_, compatibility)| Reverse(*compatibility))
+    }
+
+    /// Compresses the tags into compound strings (e.g.
+    /// `cp310-cp310-manylinux_2_17_x86_64.manylinux2014_x86_64`) by grouping consecutive tags that
+    /// share an interpreter and abi, and consecutive groups that share an abi and platforms. The
+    /// grouping preserves the order of the tags, expanding the result with
+    /// [`WheelTag::from_compound_string`] yields the original tags.
+    pub fn to_compound_strings(&self) -> Vec<String> {
+        // Group consecutive tags with the same interpreter and abi
+        let mut groups: Vec<(Vec<&str>, &str, Vec<&str>)> = Vec::new();
+        for tag in self.tags.iter() {
+            match groups.last_mut() {
+                Some((interpreters, abi, platforms))
+                    if interpreters[..] == [tag.interpreter.as_str()] && *abi == tag.abi =>
+                {
+                    platforms.push(tag.platform.as_str())
+                }
+                _ => groups.push((
+                    vec![tag.interpreter.as_str()],
+                    tag.abi.as_str(),
+                    vec![tag.platform.as_str()],
+                )),
+            }
+        }
+
+        // Group consecutive groups with the same abi and platforms
+        let mut compressed: Vec<(Vec<&str>, &str, Vec<&str>)> = Vec::new();
+        for (interpreters, abi, platforms) in groups {
+            match compressed.last_mut() {
+                Some((prev_interpreters, prev_abi, prev_platforms))
+                    if *prev_abi == abi && *prev_platforms == platforms =>
+                {
+                    prev_interpreters.extend(interpreters)
+                }
+                _ => compressed.push((interpreters, abi, platforms)),
+            }
+        }
+
+        compressed
+            .into_iter()
+            .map(|(interpreters, abi, platforms)| {
+                format!("{}-{}-{}", interpreters.join("."), abi, platforms.join("."))
+            })
+            .collect()
+    }
+
+    /// Returns the full ordered list of tags as a newline separated string, most specific tag
+    /// first. This is similar to the list of compatible tags printed by `pip debug --verbose`.
+    pub fn to_newline_separated(&self) -> String {
+        self.tags.iter().join("\n")
+    }
 }
 
 impl FromIterator<WheelTag> for WheelTags {
@@ -130,4 +221,106 @@ mod test {
         assert_eq!(tag.abi, "none");
         assert_eq!(tag.platform, "any");
     }
+
+    #[test]
+    fn test_wheel_tags_serde_roundtrip() {
+        let tags = WheelTags::from_tags(vec![
+            WheelTag::from_str("cp310-cp310-manylinux_2_17_x86_64").unwrap(),
+            WheelTag::from_str("cp310-abi3-manylinux_2_17_x86_64").unwrap(),
+            WheelTag::from_str("py3-none-any").unwrap(),
+        ]);
+
+        let json = serde_json::to_string(&tags).unwrap();
+        assert_eq!(
+            json,
+            r#"["cp310-cp310-manylinux_2_17_x86_64","cp310-abi3-manylinux_2_17_x86_64","py3-none-any"]"#
+        );
+
+        let deserialized: WheelTags = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.tags().collect_vec(), tags.tags().collect_vec());
+    }
+
+    #[test]
+    fn test_best_artifact() {
+        let tags = WheelTags::from_tags(
+            [
+                "cp311-cp311-manylinux_2_17_x86_64",
+                "cp311-abi3-manylinux_2_17_x86_64",
+                "cp37-abi3-manylinux_2_17_x86_64",
+                "py3-none-any",
+            ]
+            .into_iter()
+            .map(|tag| WheelTag::from_str(tag).unwrap())
+            .collect(),
+        );
+        let name = "foo".parse().unwrap();
+        let artifact = |filename: &str| ArtifactInfo {
+            filename: crate::types::ArtifactName::from_filename(filename, None, &name).unwrap(),
+            url: format!("https://example.com/{filename}").parse().unwrap(),
+            is_direct_url: false,
+            hashes: None,
+            requires_python: None,
+            dist_info_metadata: Default::default(),
+            yanked: Default::default(),
+        };
+
+        let artifacts = [
+            artifact("foo-1.0.tar.gz"),
+            artifact("foo-1.0-py2.py3-none-any.whl"),
+            artifact("foo-1.0-cp37-abi3-manylinux_2_17_x86_64.whl"),
+            artifact("foo-1.0-cp311-cp311-win_amd64.whl"),
+        ];
+        let (best, compatibility) = tags.best_artifact(&artifacts).unwrap();
+        assert_eq!(best, &artifacts[2]);
+        assert_eq!(compatibility, -2);
+
+        // Without the abi3 wheel the universal wheel is the best match
+        let (best, compatibility) = tags.best_artifact(&artifacts[..2]).unwrap();
+        assert_eq!(best, &artifacts[1]);
+        assert_eq!(compatibility, -3);
+
+        // There is no compatible wheel
+        assert!(tags.best_artifact(&artifacts[3..]).is_none());
+    }
+
+    #[test]
+    fn test_compound_strings() {
+        let tags = WheelTags::from_tags(
+            [
+                "cp310-cp310-manylinux_2_17_x86_64",
+                "cp310-cp310-manylinux2014_x86_64",
+                "cp310-abi3-manylinux_2_17_x86_64",
+                "cp310-abi3-manylinux2014_x86_64",
+                "cp39-abi3-manylinux_2_17_x86_64",
+                "cp39-abi3-manylinux2014_x86_64",
+                "py310-none-any",
+                "py3-none-any",
+            ]
+            .into_iter()
+            .map(|tag| WheelTag::from_str(tag).unwrap())
+            .collect(),
+        );
+
+        let compressed = tags.to_compound_strings();
+        assert_eq!(
+            compressed,
+            vec![
+                "cp310-cp310-manylinux_2_17_x86_64.manylinux2014_x86_64",
+                "cp310.cp39-abi3-manylinux_2_17_x86_64.manylinux2014_x86_64",
+                "py310.py3-none-any",
+            ]
+        );
+
+        // Expanding the compound strings should result in the original tags
+        let expanded = compressed
+            .iter()
+            .flat_map(|s| WheelTag::from_compound_string(s).unwrap())
+            .collect_vec();
+        assert_eq!(expanded, tags.tags().cloned().collect_vec());
+
+        assert_eq!(
+            tags.to_newline_separated().lines().next(),
+            Some("cp310-cp310-manylinux_2_17_x86_64")
+        );
+    }
 }
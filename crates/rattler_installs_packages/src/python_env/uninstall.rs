@@ -30,6 +30,34 @@ pub enum UninstallDistributionError {
     /// Failed to delete a directory
     #[error("failed to delete {0}")]
     FailedToDeleteDirectory(String, #[source] std::io::Error),
+
+    /// The distribution was installed by a different installer than expected, see
+    /// [`UninstallDistributionOptions::expected_installer`].
+    #[error("the distribution was installed by {}, not by {expected}", .found.as_deref().map_or_else(|| String::from("an unknown installer"), |found| format!("'{found}'")))]
+    InstallerMismatch {
+        /// The contents of the INSTALLER file, or `None` if there is no INSTALLER file.
+        found: Option<String>,
+        /// The installer that was expected.
+        expected: String,
+    },
+
+    /// Failed to read the INSTALLER file
+    #[error("failed to read the INSTALLER file")]
+    FailedToReadInstaller(#[source] std::io::Error),
+}
+
+/// Additional options that can be passed to [`uninstall_distribution`].
+#[derive(Debug, Default, Clone)]
+pub struct UninstallDistributionOptions {
+    /// When specified, the distribution is only uninstalled if the INSTALLER file in its
+    /// `.dist-info` directory matches this value. This avoids removing distributions that were
+    /// installed by another tool, like conda or the system package manager. See
+    /// [PEP 376](https://peps.python.org/pep-0376/) for more information.
+    pub expected_installer: Option<String>,
+
+    /// Uninstall the distribution even if it was installed by a different installer than
+    /// [`Self::expected_installer`].
+    pub force: bool,
 }
 
 /// Uninstall a python distribution from an environment
@@ -41,7 +69,32 @@ pub enum UninstallDistributionError {
 pub fn uninstall_distribution(
     site_packages_dir: &Path,
     dist_info_dir: &Path,
+    options: &UninstallDistributionOptions,
 ) -> Result<(), UninstallDistributionError> {
+    // Make sure the distribution was installed by us
+    if let Some(expected) = options.expected_installer.as_deref() {
+        let found =
+            match fs::read_to_string(site_packages_dir.join(dist_info_dir).join("INSTALLER")) {
+                Ok(installer) => Some(installer.trim().to_string()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(UninstallDistributionError::FailedToReadInstaller(e)),
+            };
+
+        if found.as_deref() != Some(expected.trim()) {
+            if !options.force {
+                return Err(UninstallDistributionError::InstallerMismatch {
+                    found,
+                    expected: expected.to_string(),
+                });
+            }
+            tracing::warn!(
+                "uninstalling {} which was installed by {}",
+                dist_info_dir.display(),
+                found.as_deref().unwrap_or("an unknown installer")
+            );
+        }
+    }
+
     // Load the RECORD file
     let record = match Record::from_path(&site_packages_dir.join(dist_info_dir).join("RECORD")) {
         Ok(record) => record,
@@ -67,8 +120,11 @@ pub fn uninstall_distribution(
                 ));
             }
         }
+        // The site-packages directory itself is kept, even if it becomes empty
         if let Some(parent) = entry_path.parent() {
-            directories.insert(parent.to_path_buf());
+            if !site_packages_dir.starts_with(parent) {
+                directories.insert(parent.to_path_buf());
+            }
         }
     }
 
@@ -113,7 +169,7 @@ mod test {
         let site_packages_dir = temp_dir.path().join("site-packages");
         fs::create_dir(&site_packages_dir).unwrap();
         let dist_info_dir = Path::new("test-1.0.0.dist-info");
-        fs::create_dir(&site_packages_dir.join(dist_info_dir)).unwrap();
+        fs::create_dir(site_packages_dir.join(dist_info_dir)).unwrap();
 
         let files = [
             "test-1.0.0.dist-info/RECORD",
@@ -123,6 +179,7 @@ mod test {
             "test/module/__init__.py",
             "test/__pycache__/__main__.cpython-39.pyc",
             "test/__pycache__/__init__.cpython-39.pyc",
+            "test.pth",
         ];
 
         // Create a RECORD file
@@ -150,7 +207,7 @@ mod test {
             .unwrap();
 
         // Uninstall the distribution
-        uninstall_distribution(&site_packages_dir, dist_info_dir).unwrap();
+        uninstall_distribution(&site_packages_dir, dist_info_dir, &Default::default()).unwrap();
 
         // Check that all files are gone
         for entry in record.iter() {
@@ -168,4 +225,46 @@ mod test {
         assert!(site_packages_dir.join("test/module/extra.py").is_file());
         assert!(!site_packages_dir.join("test/module/__init__.py").is_file());
     }
+
+    #[test]
+    fn test_uninstall_installer_mismatch() {
+        let temp_dir = tempdir().unwrap();
+        let site_packages_dir = temp_dir.path();
+        let dist_info_dir = Path::new("test-1.0.0.dist-info");
+        fs::create_dir(site_packages_dir.join(dist_info_dir)).unwrap();
+        fs::write(
+            site_packages_dir.join(dist_info_dir).join("INSTALLER"),
+            "conda\n",
+        )
+        .unwrap();
+        fs::File::create(site_packages_dir.join("test.py")).unwrap();
+        Record::from_iter(
+            ["test-1.0.0.dist-info/INSTALLER", "test.py"].map(|path| RecordEntry {
+                path: path.to_string(),
+                hash: None,
+                size: None,
+            }),
+        )
+        .write_to_path(&site_packages_dir.join(dist_info_dir).join("RECORD"))
+        .unwrap();
+
+        let options = UninstallDistributionOptions {
+            expected_installer: Some(String::from("rip")),
+            force: false,
+        };
+        let err = uninstall_distribution(site_packages_dir, dist_info_dir, &options).unwrap_err();
+        assert!(matches!(
+            err,
+            UninstallDistributionError::InstallerMismatch { found: Some(found), .. } if found == "conda"
+        ));
+        assert!(site_packages_dir.join("test.py").is_file());
+
+        // Forcing the uninstall removes the files
+        let options = UninstallDistributionOptions {
+            force: true,
+            ..options
+        };
+        uninstall_distribution(site_packages_dir, dist_info_dir, &options).unwrap();
+        assert!(!site_packages_dir.join("test.py").is_file());
+    }
 }
@@ -0,0 +1,306 @@
+//! Functionality to verify the integrity of an installed python distribution.
+
+use crate::{
+    types::{Record, RecordHashAlgorithm, RecordHasher},
+    utils::normalize_path,
+};
+use fs_err as fs;
+use std::{collections::HashSet, path::Path};
+use thiserror::Error;
+
+/// An error that can occur while verifying an installed python distribution.
+///
+/// See [`verify_installed`].
+#[derive(Debug, Error)]
+pub enum VerifyInstalledError {
+    /// The `RECORD` file is missing in the .dist-info folder, there is no way to tell which files
+    /// belong to the distribution.
+    #[error("the RECORD file is missing")]
+    RecordFileMissing,
+
+    /// The `RECORD` file is invalid.
+    #[error("the RECORD file is invalid")]
+    RecordFileInvalid(#[from] csv::Error),
+
+    /// Failed to read a file or directory
+    #[error("failed to read {0}")]
+    FailedToRead(String, #[source] std::io::Error),
+}
+
+/// A single difference between the `RECORD` file of a distribution and the files on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// A file listed in the `RECORD` does not exist.
+    Missing {
+        /// The path of the file as listed in the `RECORD`.
+        path: String,
+    },
+
+    /// The size of a file differs from the size recorded in the `RECORD`.
+    SizeMismatch {
+        /// The path of the file as listed in the `RECORD`.
+        path: String,
+        /// The recorded size in bytes.
+        recorded: u64,
+        /// The actual size in bytes.
+        actual: u64,
+    },
+
+    /// The hash of a file differs from the hash recorded in the `RECORD`.
+    HashMismatch {
+        /// The path of the file as listed in the `RECORD`.
+        path: String,
+        /// The recorded hash, e.g. `sha256=...`.
+        recorded: String,
+        /// The actual hash of the file in the same format.
+        actual: String,
+    },
+
+    /// A file exists in one of the directories of the distribution but is not listed in the
+    /// `RECORD`.
+    Extra {
+        /// The path of the file relative to the site-packages directory.
+        path: String,
+    },
+}
+
+/// The result of [`verify_installed`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerificationReport {
+    /// All the differences that were found, in the order of the `RECORD` file followed by the
+    /// extra files.
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl VerificationReport {
+    /// Returns true if the installed files match the `RECORD` file exactly.
+    pub fn is_ok(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+}
+
+/// Verifies the files of an installed python distribution against its `RECORD` file.
+///
+/// * site_packages_dir: The absolute path to the site-packages directory
+/// * dist_info_dir: The path off the `.dist-info` dir relative to `site_packages_dir`.
+///
+/// Every file listed in the `RECORD` must exist. If the `RECORD` lists a size or a hash for the
+/// file these must match as well, entries without a hash (like the `RECORD` itself) or with a hash
+/// of an unsupported [`crate::types::RecordHashAlgorithm`] are only checked for existence. Files in
+/// the directories of the distribution inside `site_packages_dir` that are not listed in the
+/// `RECORD` are reported as extra files, except for bytecode files in `__pycache__` directories
+/// which python creates on the fly.
+pub fn verify_installed(
+    site_packages_dir: &Path,
+    dist_info_dir: &Path,
+) -> Result<VerificationReport, VerifyInstalledError> {
+    // Load the RECORD file
+    let record = match Record::from_path(&site_packages_dir.join(dist_info_dir).join("RECORD")) {
+        Ok(record) => record,
+        Err(e) => {
+            return Err(match e.kind() {
+                csv::ErrorKind::Io(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    VerifyInstalledError::RecordFileMissing
+                }
+                _ => VerifyInstalledError::RecordFileInvalid(e),
+            });
+        }
+    };
+
+    let mut report = VerificationReport::default();
+    let mut recorded_paths = HashSet::new();
+    let mut directories = HashSet::new();
+    for entry in record.iter() {
+        // Entries of scripts are relative to site-packages and usually contain `..` components
+        let path = normalize_path(&site_packages_dir.join(&entry.path));
+        if let Some(parent) = path.parent() {
+            if parent != site_packages_dir && parent.starts_with(site_packages_dir) {
+                directories.insert(parent.to_path_buf());
+            }
+        }
+        recorded_paths.insert(path.clone());
+
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => {
+                report.discrepancies.push(Discrepancy::Missing {
+                    path: entry.path.clone(),
+                });
+                continue;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                report.discrepancies.push(Discrepancy::Missing {
+                    path: entry.path.clone(),
+                });
+                continue;
+            }
+            Err(e) => {
+                return Err(VerifyInstalledError::FailedToRead(entry.path.clone(), e));
+            }
+        };
+
+        if let Some(recorded) = entry.size {
+            if recorded != metadata.len() {
+                report.discrepancies.push(Discrepancy::SizeMismatch {
+                    path: entry.path.clone(),
+                    recorded,
+                    actual: metadata.len(),
+                });
+                continue;
+            }
+        }
+
+        let recorded = entry.hash.as_deref();
+        match recorded.map(|recorded| (recorded, RecordHashAlgorithm::from_record_hash(recorded))) {
+            Some((recorded, Some(algorithm))) => {
+                let mut hasher = RecordHasher::new([algorithm]);
+                fs::File::open(&path)
+                    .and_then(|mut file| std::io::copy(&mut file, &mut hasher))
+                    .map_err(|e| VerifyInstalledError::FailedToRead(entry.path.clone(), e))?;
+                let actual = hasher.finalize().get(algorithm).to_string();
+                if actual != recorded {
+                    report.discrepancies.push(Discrepancy::HashMismatch {
+                        path: entry.path.clone(),
+                        recorded: recorded.to_string(),
+                        actual,
+                    });
+                }
+            }
+            Some((recorded, None)) => {
+                tracing::debug!("cannot verify {}, unsupported hash {recorded}", entry.path);
+            }
+            None => {}
+        }
+    }
+
+    // Look for files that are not part of the RECORD
+    let mut extra = Vec::new();
+    for directory in directories {
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(VerifyInstalledError::FailedToRead(
+                    directory.display().to_string(),
+                    e,
+                ))
+            }
+        };
+        for dir_entry in entries {
+            let dir_entry = dir_entry.map_err(|e| {
+                VerifyInstalledError::FailedToRead(directory.display().to_string(), e)
+            })?;
+            let path = dir_entry.path();
+            if !path.is_file() || recorded_paths.contains(&path) || is_bytecode_cache(&path) {
+                continue;
+            }
+            let relative_path = pathdiff::diff_paths(&path, site_packages_dir)
+                .expect("can always create relative path from site-packages");
+            extra.push(relative_path.display().to_string().replace('\\', "/"));
+        }
+    }
+    extra.sort();
+    report
+        .discrepancies
+        .extend(extra.into_iter().map(|path| Discrepancy::Extra { path }));
+
+    Ok(report)
+}
+
+/// Returns true if the path refers to a bytecode file in a `__pycache__` directory.
+fn is_bytecode_cache(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "pyc")
+        && path
+            .parent()
+            .and_then(Path::file_name)
+            .is_some_and(|name| name == "__pycache__")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::RecordEntry;
+    use data_encoding::BASE64URL_NOPAD;
+    use rattler_digest::Sha256;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_verify_installed() {
+        let temp_dir = tempdir().unwrap();
+        let site_packages_dir = temp_dir.path().join("site-packages");
+        let dist_info_dir = Path::new("test-1.0.0.dist-info");
+        fs::create_dir_all(site_packages_dir.join(dist_info_dir)).unwrap();
+        fs::create_dir_all(site_packages_dir.join("test/__pycache__")).unwrap();
+
+        let files = [
+            ("test/__init__.py", "print('hello')\n"),
+            ("test/missing.py", "print('missing')\n"),
+            ("test/modified.py", "print('modified')\n"),
+            ("test/resized.py", "print('resized')\n"),
+            ("test-1.0.0.dist-info/METADATA", "Name: test\n"),
+        ];
+
+        // Create the files and a RECORD that describes them
+        let mut entries = Vec::new();
+        for (path, content) in files {
+            fs::write(site_packages_dir.join(path), content).unwrap();
+            let digest = rattler_digest::compute_bytes_digest::<Sha256>(content);
+            entries.push(RecordEntry {
+                path: path.to_string(),
+                hash: Some(format!("sha256={}", BASE64URL_NOPAD.encode(&digest))),
+                size: Some(content.len() as u64),
+            });
+        }
+        entries.push(RecordEntry {
+            path: String::from("test-1.0.0.dist-info/RECORD"),
+            hash: None,
+            size: None,
+        });
+        Record::from_iter(entries)
+            .write_to_path(&site_packages_dir.join(dist_info_dir).join("RECORD"))
+            .unwrap();
+
+        let report = verify_installed(&site_packages_dir, dist_info_dir).unwrap();
+        assert!(report.is_ok(), "{report:?}");
+
+        // Tamper with the environment
+        fs::remove_file(site_packages_dir.join("test/missing.py")).unwrap();
+        fs::write(
+            site_packages_dir.join("test/modified.py"),
+            "print('MODIFIED')\n",
+        )
+        .unwrap();
+        fs::write(
+            site_packages_dir.join("test/resized.py"),
+            "print('resized!')\n",
+        )
+        .unwrap();
+        fs::write(site_packages_dir.join("test/extra.py"), "").unwrap();
+        fs::write(
+            site_packages_dir.join("test/__pycache__/__init__.cpython-311.pyc"),
+            "",
+        )
+        .unwrap();
+
+        let report = verify_installed(&site_packages_dir, dist_info_dir).unwrap();
+        let paths = report
+            .discrepancies
+            .iter()
+            .map(|discrepancy| match discrepancy {
+                Discrepancy::Missing { path } => format!("missing {path}"),
+                Discrepancy::SizeMismatch { path, .. } => format!("size {path}"),
+                Discrepancy::HashMismatch { path, .. } => format!("hash {path}"),
+                Discrepancy::Extra { path } => format!("extra {path}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "missing test/missing.py",
+                "hash test/modified.py",
+                "size test/resized.py",
+                "extra test/extra.py",
+            ]
+        );
+    }
+}
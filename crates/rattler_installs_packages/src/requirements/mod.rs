@@ -0,0 +1,7 @@
+//! Parsing of pip style requirement files. See [`RequirementsTxt`].
+
+mod requirements_txt;
+
+pub use requirements_txt::{
+    EditableRequirement, RequirementEntry, RequirementHash, RequirementsTxt, RequirementsTxtError,
+};
@@ -0,0 +1,610 @@
+use crate::types::Extra;
+use fs_err as fs;
+use once_cell::sync::Lazy;
+use pep508_rs::Requirement;
+use rattler_digest::{parse_digest_from_hex, Sha256, Sha256Hash};
+use regex::Regex;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use thiserror::Error;
+use url::Url;
+
+/// The contents of a pip style `requirements.txt` file, including the files it includes with
+/// `-r` and `-c`. See the
+/// [pip documentation](https://pip.pypa.io/en/stable/reference/requirements-file-format/) for the
+/// format.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequirementsTxt {
+    /// The requirements in the order in which they appear, includes are expanded in place.
+    pub requirements: Vec<RequirementEntry>,
+
+    /// The requirements from constraint files (`-c`). These restrict the versions of packages
+    /// that are selected but do not cause a package to be selected.
+    pub constraints: Vec<RequirementEntry>,
+
+    /// The editable requirements (`-e`).
+    pub editables: Vec<EditableRequirement>,
+
+    /// The index url that replaces the default index (`--index-url`).
+    pub index_url: Option<Url>,
+
+    /// Additional indexes to search for packages (`--extra-index-url`).
+    pub extra_index_urls: Vec<Url>,
+
+    /// Ignore the package index (`--no-index`).
+    pub no_index: bool,
+
+    /// Urls or paths to look for archives in (`--find-links`).
+    pub find_links: Vec<String>,
+
+    /// Hosts that are trusted even if they are not served over https (`--trusted-host`).
+    pub trusted_hosts: Vec<String>,
+
+    /// Allow pre-releases to be selected (`--pre`).
+    pub pre: bool,
+
+    /// Require every requirement to be pinned with a hash (`--require-hashes`).
+    pub require_hashes: bool,
+
+    /// Prefer wheels over sdists, even if the sdist is newer (`--prefer-binary`).
+    pub prefer_binary: bool,
+
+    /// The packages for which sdists must not be used, `:all:` refers to all packages
+    /// (`--only-binary`).
+    pub only_binary: Vec<String>,
+
+    /// The packages for which wheels must not be used, `:all:` refers to all packages
+    /// (`--no-binary`).
+    pub no_binary: Vec<String>,
+}
+
+/// A single requirement together with the options that apply only to this requirement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequirementEntry {
+    /// The requirement. Relative paths are resolved to absolute `file://` urls.
+    pub requirement: Requirement,
+
+    /// The hashes of the artifacts that may be used for this requirement (`--hash`).
+    pub hashes: Vec<RequirementHash>,
+
+    /// The settings that are passed to the build backend (`--config-settings`).
+    pub config_settings: Vec<(String, String)>,
+}
+
+/// A hash of an artifact in the `<algorithm>:<hex digest>` format of `--hash`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequirementHash {
+    /// The name of the hash algorithm, e.g. `sha256`.
+    pub algorithm: String,
+
+    /// The hex encoded digest.
+    pub digest: String,
+}
+
+impl RequirementHash {
+    /// Returns the digest if this is a valid sha256 hash.
+    pub fn sha256(&self) -> Option<Sha256Hash> {
+        if self.algorithm == "sha256" {
+            parse_digest_from_hex::<Sha256>(&self.digest)
+        } else {
+            None
+        }
+    }
+}
+
+/// A requirement that should be installed in editable mode (`-e`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditableRequirement {
+    /// The location of the project. Relative paths are resolved to absolute `file://` urls.
+    pub url: Url,
+
+    /// The extras that are requested for the project.
+    pub extras: Vec<Extra>,
+}
+
+/// An error that can occur when reading a requirements file with [`RequirementsTxt`].
+#[derive(Debug, Error)]
+#[allow(missing_docs)]
+pub enum RequirementsTxtError {
+    #[error("failed to read {}", .0.display())]
+    Io(PathBuf, #[source] io::Error),
+
+    #[error("{} includes itself", .0.display())]
+    RecursiveInclude(PathBuf),
+
+    #[error("{}:{line}: {message}", .file.display())]
+    Parse {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl RequirementsTxt {
+    /// Reads a requirements file and all the files it includes.
+    pub fn from_path(path: &Path) -> Result<Self, RequirementsTxtError> {
+        let path = if path.is_relative() {
+            std::env::current_dir()
+                .map_err(|err| RequirementsTxtError::Io(path.to_path_buf(), err))?
+                .join(path)
+        } else {
+            path.to_path_buf()
+        };
+
+        let mut result = Self::default();
+        result.parse_file(&path, false, &mut Vec::new())?;
+        Ok(result)
+    }
+
+    /// Parses the contents of a requirements file. Relative paths in `contents` are resolved
+    /// relative to the directory of `path`, which is also used in error messages. The file itself
+    /// does not have to exist.
+    pub fn parse(contents: &str, path: &Path) -> Result<Self, RequirementsTxtError> {
+        let mut result = Self::default();
+        let canonical_path = dunce::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        result.parse_contents(contents, path, false, &mut vec![canonical_path])?;
+        Ok(result)
+    }
+
+    /// Returns the requirements without their options.
+    pub fn requirements(&self) -> impl Iterator<Item = &Requirement> {
+        self.requirements.iter().map(|entry| &entry.requirement)
+    }
+
+    fn parse_file(
+        &mut self,
+        path: &Path,
+        is_constraint: bool,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), RequirementsTxtError> {
+        // The same file can be included through different paths, e.g. `./a.txt` and
+        // `sub/../a.txt`, so included files are compared by their canonical path.
+        let canonical_path = dunce::canonicalize(path)
+            .map_err(|err| RequirementsTxtError::Io(path.to_path_buf(), err))?;
+        if stack.iter().any(|included| included == &canonical_path) {
+            return Err(RequirementsTxtError::RecursiveInclude(path.to_path_buf()));
+        }
+
+        let contents = fs::read_to_string(path)
+            .map_err(|err| RequirementsTxtError::Io(path.to_path_buf(), err))?;
+        stack.push(canonical_path);
+        let result = self.parse_contents(&contents, path, is_constraint, stack);
+        stack.pop();
+        result
+    }
+
+    fn parse_contents(
+        &mut self,
+        contents: &str,
+        path: &Path,
+        is_constraint: bool,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), RequirementsTxtError> {
+        let dir = path.parent().unwrap_or(Path::new(""));
+
+        for (line_number, line) in logical_lines(contents) {
+            let error = |message: String| RequirementsTxtError::Parse {
+                file: path.to_path_buf(),
+                line: line_number,
+                message,
+            };
+
+            let line = expand_env_variables(strip_comment(&line));
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            // Lines that start with a dash contain an option that applies to the whole file
+            if line.starts_with('-') {
+                let (option, value) = split_option(line);
+                let required_value =
+                    || value.ok_or_else(|| error(format!("'{option}' requires a value")));
+                let url = |value: &str| {
+                    Url::parse(value).map_err(|err| error(format!("invalid url '{value}': {err}")))
+                };
+                match option {
+                    "-r" | "--requirement" => {
+                        self.parse_file(&dir.join(required_value()?), is_constraint, stack)?
+                    }
+                    "-c" | "--constraint" => {
+                        self.parse_file(&dir.join(required_value()?), true, stack)?
+                    }
+                    "-e" | "--editable" => {
+                        if is_constraint {
+                            return Err(error(String::from(
+                                "editable requirements are not allowed in constraint files",
+                            )));
+                        }
+                        let editable = parse_editable(required_value()?, dir).map_err(error)?;
+                        self.editables.push(editable);
+                    }
+                    "-i" | "--index-url" => self.index_url = Some(url(required_value()?)?),
+                    "--extra-index-url" => self.extra_index_urls.push(url(required_value()?)?),
+                    "--no-index" => self.no_index = true,
+                    "-f" | "--find-links" => self.find_links.push(required_value()?.to_owned()),
+                    "--trusted-host" => self.trusted_hosts.push(required_value()?.to_owned()),
+                    "--pre" => self.pre = true,
+                    "--require-hashes" => self.require_hashes = true,
+                    "--prefer-binary" => self.prefer_binary = true,
+                    "--only-binary" => self
+                        .only_binary
+                        .extend(required_value()?.split(',').map(str::to_owned)),
+                    "--no-binary" => self
+                        .no_binary
+                        .extend(required_value()?.split(',').map(str::to_owned)),
+                    _ => return Err(error(format!("unsupported option '{option}'"))),
+                }
+                continue;
+            }
+
+            let entry = parse_requirement_line(line, dir).map_err(error)?;
+            if is_constraint {
+                self.constraints.push(entry);
+            } else {
+                self.requirements.push(entry);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Joins lines that end with a backslash with the next line. Returns the joined lines together
+/// with the (1 based) number of the line on which they start.
+fn logical_lines(contents: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (index, line) in contents.lines().enumerate() {
+        let (_, joined) = current.get_or_insert_with(|| (index + 1, String::new()));
+        match line.strip_suffix('\\') {
+            Some(line) => joined.push_str(line),
+            None => {
+                joined.push_str(line);
+                lines.extend(current.take());
+            }
+        }
+    }
+    lines.extend(current);
+    lines
+}
+
+/// Removes a comment from a line. A comment starts with a `#` at the start of the line or after
+/// whitespace, so url fragments are kept.
+fn strip_comment(line: &str) -> &str {
+    static COMMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"(^|\s+)#.*$").unwrap());
+    COMMENT
+        .find(line)
+        .map_or(line, |comment| &line[..comment.start()])
+}
+
+/// Replaces `${NAME}` with the value of the environment variable `NAME`, if it is set. Like pip
+/// only names that consist of uppercase letters, digits and underscores are expanded.
+fn expand_env_variables(line: &str) -> String {
+    static ENV_VARIABLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\$\{([A-Z0-9_]+)\}").unwrap());
+    ENV_VARIABLE
+        .replace_all(line, |captures: &regex::Captures| {
+            std::env::var(&captures[1]).unwrap_or_else(|_| captures[0].to_owned())
+        })
+        .into_owned()
+}
+
+/// Splits an option from its value, e.g. `--index-url=<url>`, `--index-url <url>`, `-r <file>` or
+/// `-r<file>`.
+fn split_option(line: &str) -> (&str, Option<&str>) {
+    let (option, value) = if line.starts_with("--") {
+        match line.find(|c: char| c == '=' || c.is_whitespace()) {
+            Some(index) => (&line[..index], &line[index + 1..]),
+            None => (line, ""),
+        }
+    } else {
+        line.split_at(line.char_indices().nth(2).map_or(line.len(), |(i, _)| i))
+    };
+    let value = value.trim();
+    (option, (!value.is_empty()).then_some(value))
+}
+
+/// Parses a line that contains a requirement followed by options that only apply to that
+/// requirement.
+fn parse_requirement_line(line: &str, dir: &Path) -> Result<RequirementEntry, String> {
+    // The options start at the first dash that follows whitespace and is not quoted
+    let mut quote = None;
+    let mut previous = ' ';
+    let mut options_start = line.len();
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '-') if previous.is_whitespace() && index > 0 => {
+                options_start = index;
+                break;
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    let (requirement, options) = line.split_at(options_start);
+
+    let mut entry = RequirementEntry {
+        requirement: parse_requirement(requirement.trim(), dir)?,
+        hashes: Vec::new(),
+        config_settings: Vec::new(),
+    };
+
+    let mut tokens = options.split_whitespace();
+    while let Some(token) = tokens.next() {
+        let (option, value) = match token.split_once('=') {
+            Some((option, value)) => (option, Some(value)),
+            None => (token, tokens.next()),
+        };
+        let value = value.ok_or_else(|| format!("'{option}' requires a value"))?;
+        match option {
+            "--hash" => {
+                let (algorithm, digest) = value
+                    .split_once(':')
+                    .ok_or_else(|| format!("invalid hash '{value}'"))?;
+                entry.hashes.push(RequirementHash {
+                    algorithm: algorithm.to_owned(),
+                    digest: digest.to_owned(),
+                });
+            }
+            "--config-settings" => {
+                let (key, value) = value.split_once('=').unwrap_or((value, ""));
+                entry
+                    .config_settings
+                    .push((key.to_owned(), value.to_owned()));
+            }
+            _ => return Err(format!("unsupported option '{option}'")),
+        }
+    }
+
+    Ok(entry)
+}
+
+/// Parses a requirement. In addition to PEP 508 requirements this also accepts urls and paths to
+/// archives, with or without a name, e.g. `./dist/foo-1.0-py3-none-any.whl`.
+fn parse_requirement(text: &str, dir: &Path) -> Result<Requirement, String> {
+    let pep508_error = match Requirement::from_str(text) {
+        Ok(requirement) => return Ok(requirement),
+        Err(err) => err,
+    };
+
+    let (location, marker) = match text.split_once(';') {
+        Some((location, marker)) => (location.trim(), Some(marker.trim())),
+        None => (text, None),
+    };
+    let (name, location) = match location.split_once('@') {
+        Some((name, location)) if !name.contains(['/', ':']) => {
+            (Some(name.trim().to_owned()), location.trim())
+        }
+        _ => (None, location),
+    };
+    if !is_location(location) {
+        return Err(format!("invalid requirement: {pep508_error}"));
+    }
+
+    let url = resolve_location(location, dir)?;
+    let name = name
+        .or_else(|| name_from_url(&url))
+        .ok_or_else(|| format!("cannot determine the name of the package at '{location}'"))?;
+    let requirement = match marker {
+        Some(marker) => format!("{name} @ {url} ; {marker}"),
+        None => format!("{name} @ {url}"),
+    };
+    Requirement::from_str(&requirement).map_err(|err| format!("invalid requirement: {err}"))
+}
+
+/// Parses the value of `-e`, e.g. `./project[extra]` or `git+https://host/repo#egg=name`.
+fn parse_editable(value: &str, dir: &Path) -> Result<EditableRequirement, String> {
+    let (location, extras) = match value.strip_suffix(']').and_then(|v| v.rsplit_once('[')) {
+        Some((location, extras)) if !location.contains("://") => (location, extras),
+        _ => (value, ""),
+    };
+    let extras = extras
+        .split(',')
+        .map(str::trim)
+        .filter(|extra| !extra.is_empty())
+        .map(|extra| Extra::from_str(extra).map_err(|err| err.to_string()))
+        .collect::<Result<_, _>>()?;
+
+    Ok(EditableRequirement {
+        url: resolve_location(location, dir)?,
+        extras,
+    })
+}
+
+/// Returns true if the text refers to a url or a path instead of a package name.
+fn is_location(text: &str) -> bool {
+    text.contains("://")
+        || text.starts_with(['.', '/', '~'])
+        || [".whl", ".tar.gz", ".zip"]
+            .iter()
+            .any(|extension| text.ends_with(extension))
+}
+
+/// Converts a url or a path relative to `dir` to a url.
+fn resolve_location(location: &str, dir: &Path) -> Result<Url, String> {
+    if location.contains("://") {
+        return Url::parse(location).map_err(|err| format!("invalid url '{location}': {err}"));
+    }
+    Url::from_file_path(dir.join(location))
+        .map_err(|_| format!("cannot convert '{location}' to a url"))
+}
+
+/// Determines the name of the package from the `#egg=` fragment of the url or from the filename
+/// of the archive it refers to.
+fn name_from_url(url: &Url) -> Option<String> {
+    if let Some(egg) = url
+        .fragment()
+        .into_iter()
+        .flat_map(|fragment| fragment.split('&'))
+        .find_map(|part| part.strip_prefix("egg="))
+    {
+        return Some(egg.to_owned());
+    }
+
+    let filename = url.path_segments()?.last()?;
+    if filename.ends_with(".whl") {
+        return filename.split('-').next().map(str::to_owned);
+    }
+    let stem = filename
+        .strip_suffix(".tar.gz")
+        .or_else(|| filename.strip_suffix(".zip"))?;
+    stem.rsplit_once('-').map(|(name, _)| name.to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write(dir: &Path, path: &str, contents: &str) -> PathBuf {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_requirements_txt() {
+        let dir = tempfile::tempdir().unwrap();
+        let hash = |c: char| c.to_string().repeat(64);
+        let path = write(
+            dir.path(),
+            "requirements.txt",
+            &format!(
+                r#"# A comment
+--index-url https://example.com/simple
+--extra-index-url=https://extra.example.com/simple
+--pre
+
+-r nested/more.txt
+-c constraints.txt
+flask[async]>=3.0 ; python_version >= "3.8" \
+    --hash=sha256:{} \
+    --hash sha256:{}
+requests==2.31.0  # A trailing comment
+./wheels/foo-1.0-py3-none-any.whl
+bar @ https://example.com/bar-2.0.tar.gz#sha256={}
+-e ./local_project[dev, test]
+"#,
+                hash('a'),
+                hash('b'),
+                hash('c'),
+            ),
+        );
+        write(
+            dir.path(),
+            "nested/more.txt",
+            "six==1.16.0\n-r ../other.txt\n",
+        );
+        write(dir.path(), "other.txt", "click\n");
+        write(dir.path(), "constraints.txt", "werkzeug<3.1\n");
+
+        let requirements = RequirementsTxt::from_path(&path).unwrap();
+        assert_eq!(
+            requirements.index_url.as_ref().map(Url::as_str),
+            Some("https://example.com/simple")
+        );
+        assert_eq!(
+            requirements
+                .extra_index_urls
+                .iter()
+                .map(Url::as_str)
+                .collect::<Vec<_>>(),
+            ["https://extra.example.com/simple"]
+        );
+        assert!(requirements.pre);
+
+        // Includes are expanded in place
+        let names = requirements
+            .requirements()
+            .map(|requirement| requirement.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["six", "click", "flask", "requests", "foo", "bar"]);
+
+        let flask = &requirements.requirements[2];
+        assert_eq!(
+            flask.requirement,
+            Requirement::from_str(r#"flask[async]>=3.0 ; python_version >= "3.8""#).unwrap()
+        );
+        assert_eq!(
+            flask
+                .hashes
+                .iter()
+                .map(|hash| hash.sha256().unwrap())
+                .collect::<Vec<_>>(),
+            [
+                parse_digest_from_hex::<Sha256>(&hash('a')).unwrap(),
+                parse_digest_from_hex::<Sha256>(&hash('b')).unwrap()
+            ]
+        );
+
+        // Relative paths are resolved relative to the requirements file
+        let foo = &requirements.requirements[4].requirement;
+        assert_eq!(
+            foo.to_string(),
+            format!(
+                "foo @ {}",
+                Url::from_file_path(dir.path().join("wheels/foo-1.0-py3-none-any.whl")).unwrap()
+            )
+        );
+
+        // Constraints are kept separately
+        assert_eq!(
+            requirements
+                .constraints
+                .iter()
+                .map(|entry| &entry.requirement)
+                .collect::<Vec<_>>(),
+            [&Requirement::from_str("werkzeug<3.1").unwrap()]
+        );
+
+        assert_eq!(
+            requirements.editables,
+            [EditableRequirement {
+                url: Url::from_file_path(dir.path().join("local_project")).unwrap(),
+                extras: vec!["dev".parse().unwrap(), "test".parse().unwrap()],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_recursive_include() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(dir.path(), "a.txt", "-r b.txt\n");
+        write(dir.path(), "b.txt", "-r a.txt\n");
+
+        let err = RequirementsTxt::from_path(&path).unwrap_err();
+        assert!(matches!(err, RequirementsTxtError::RecursiveInclude(p) if p == path));
+    }
+
+    #[test]
+    fn test_recursive_include_through_different_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let path = write(dir.path(), "a.txt", "-r sub/../b.txt\n");
+        write(dir.path(), "b.txt", "-r ./a.txt\n");
+
+        let err = RequirementsTxt::from_path(&path).unwrap_err();
+        assert!(matches!(err, RequirementsTxtError::RecursiveInclude(_)));
+    }
+
+    #[test]
+    fn test_errors() {
+        let path = Path::new("/project/requirements.txt");
+        let err = RequirementsTxt::parse("flask\n--unknown-option\n", path).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/project/requirements.txt:2: unsupported option '--unknown-option'"
+        );
+
+        let err = RequirementsTxt::parse("flask --hash=md5\n", path).unwrap_err();
+        assert!(err.to_string().contains("invalid hash 'md5'"));
+
+        let err = RequirementsTxt::parse("-c c.txt\n", path).unwrap_err();
+        assert!(matches!(err, RequirementsTxtError::Io(..)));
+    }
+}
@@ -1,58 +1,90 @@
 use super::{
+    error::ResolveError,
+    prefetch::Prefetched,
     pypi_version_types::PypiPackageName,
-    solve_options::{PreReleaseResolution, ResolveOptions, SDistResolution},
+    solve_options::{
+        PreReleaseResolution, ResolutionStrategy, ResolveOptions, SDistResolution,
+        UnknownExtraResolution,
+    },
+    solve_types::ExclusionReason,
     PypiVersion, PypiVersionSet,
 };
 use crate::{
     artifacts::{SDist, Wheel},
-    index::{ArtifactRequest, PackageDb},
+    index::{ArtifactRequest, HttpRequestError, PackageDb},
     python_env::WheelTags,
     types::{
         ArtifactFromBytes, ArtifactInfo, ArtifactName, Extra, NormalizedPackageName, PackageName,
+        WheelCoreMetadata,
     },
     wheel_builder::WheelBuilder,
 };
 use elsa::FrozenMap;
 use itertools::Itertools;
-use miette::{Diagnostic, MietteDiagnostic};
+use miette::MietteDiagnostic;
 use parking_lot::Mutex;
-use pep440_rs::{Operator, VersionSpecifier, VersionSpecifiers};
+use pep440_rs::{Operator, Version, VersionSpecifier, VersionSpecifiers};
 use pep508_rs::{MarkerEnvironment, Requirement, VersionOrUrl};
 use resolvo::{
     Candidates, Dependencies, DependencyProvider, KnownDependencies, NameId, Pool, SolvableId,
     SolverCache,
 };
-use std::{any::Any, borrow::Borrow, cmp::Ordering, rc::Rc, str::FromStr, sync::Arc};
-use thiserror::Error;
+use std::{
+    any::Any,
+    borrow::Borrow,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    str::FromStr,
+    sync::Arc,
+};
 use url::Url;
 
+/// A version of a package from the index and the reason it was excluded before solving, if any.
+type ConsideredVersion = (Version, Option<ExclusionReason>);
+
 /// This is a [`DependencyProvider`] for PyPI packages
 pub(crate) struct PypiDependencyProvider {
     pub pool: Rc<Pool<PypiVersionSet, PypiPackageName>>,
     pub cached_artifacts: FrozenMap<SolvableId, Vec<Arc<ArtifactInfo>>>,
     pub name_to_url: FrozenMap<NormalizedPackageName, String>,
+    pub dependency_edges: Mutex<HashMap<SolvableId, Vec<Requirement>>>,
+    pub retained_metadata: Mutex<HashMap<SolvableId, Arc<WheelCoreMetadata>>>,
+    /// The versions from the index that were considered for each package, together with the
+    /// reason they were excluded before solving (if any).
+    pub considered_versions: Mutex<HashMap<NormalizedPackageName, Vec<ConsideredVersion>>>,
+    pinned_versions: HashMap<NormalizedPackageName, Version>,
+    /// The packages that are directly required, see [`ResolutionStrategy::LowestDirect`].
+    direct_dependencies: HashSet<NormalizedPackageName>,
     package_db: Arc<PackageDb>,
     wheel_builder: Arc<WheelBuilder>,
     markers: Arc<MarkerEnvironment>,
     compatible_tags: Option<Arc<WheelTags>>,
 
     options: ResolveOptions,
-    should_cancel_with_value: Mutex<Option<MetadataError>>,
+    should_cancel_with_value: Mutex<Option<ResolveError>>,
+
+    /// When set, artifacts and metadata are only read from this cache instead of being fetched.
+    prefetched: Option<Arc<Prefetched>>,
 }
 
 impl PypiDependencyProvider {
     /// Creates a new PypiDependencyProvider
     /// for use with the [`resolvo`] crate
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         pool: Pool<PypiVersionSet, PypiPackageName>,
         package_db: Arc<PackageDb>,
         markers: Arc<MarkerEnvironment>,
         compatible_tags: Option<Arc<WheelTags>>,
         name_to_url: FrozenMap<NormalizedPackageName, String>,
+        pinned_versions: HashMap<NormalizedPackageName, Version>,
+        direct_dependencies: HashSet<NormalizedPackageName>,
         wheel_builder: Arc<WheelBuilder>,
         options: ResolveOptions,
-    ) -> miette::Result<Self> {
-        Ok(Self {
+        prefetched: Option<Arc<Prefetched>>,
+    ) -> Self {
+        Self {
             pool: Rc::new(pool),
             package_db,
             wheel_builder,
@@ -60,28 +92,36 @@ impl PypiDependencyProvider {
             compatible_tags,
             cached_artifacts: Default::default(),
             name_to_url,
+            dependency_edges: Default::default(),
+            retained_metadata: Default::default(),
+            considered_versions: Default::default(),
+            pinned_versions,
+            direct_dependencies,
             options,
             should_cancel_with_value: Default::default(),
-        })
+            prefetched,
+        }
     }
 
     fn filter_candidates<'a, A: Borrow<ArtifactInfo>>(
         &self,
         artifacts: &'a [A],
-    ) -> Result<Vec<&'a A>, &'static str> {
+        allow_yanked: bool,
+    ) -> Result<Vec<&'a A>, ExclusionReason> {
         // Filter only artifacts we can work with
         if artifacts.is_empty() {
             // If there are no wheel artifacts, we're just gonna skip it
-            return Err("there are no packages available");
+            return Err(ExclusionReason::IncompatibleArtifacts(
+                "there are no packages available".into(),
+            ));
         }
 
-        let mut artifacts = artifacts.iter().collect::<Vec<_>>();
         // Filter yanked artifacts
-        artifacts.retain(|a| !(*a).borrow().yanked.yanked);
+        let artifacts = filter_yanked(artifacts.iter().collect(), allow_yanked)?;
 
-        if artifacts.is_empty() {
-            return Err("it is yanked");
-        }
+        // Filter artifacts that are incompatible with the python version of the target environment
+        let artifacts =
+            filter_requires_python(artifacts, Some(&self.markers.python_full_version.version))?;
 
         // This should keep only the wheels
         let mut wheels = if self.options.sdist_resolution.allow_wheels() {
@@ -92,7 +132,9 @@ impl PypiDependencyProvider {
                 .collect::<Vec<_>>();
 
             if !self.options.sdist_resolution.allow_sdists() && wheels.is_empty() {
-                return Err("there are no wheels available");
+                return Err(ExclusionReason::IncompatibleArtifacts(
+                    "there are no wheels available".into(),
+                ));
             }
 
             wheels
@@ -112,9 +154,13 @@ impl PypiDependencyProvider {
 
             if wheels.is_empty() && sdists.is_empty() {
                 if self.options.sdist_resolution.allow_wheels() {
-                    return Err("there are no wheels or sdists");
+                    return Err(ExclusionReason::IncompatibleArtifacts(
+                        "there are no wheels or sdists".into(),
+                    ));
                 } else {
-                    return Err("there are no sdists");
+                    return Err(ExclusionReason::IncompatibleArtifacts(
+                        "there are no sdists".into(),
+                    ));
                 }
             }
 
@@ -127,7 +173,9 @@ impl PypiDependencyProvider {
             });
 
             if wheels.is_empty() && sdists.is_empty() {
-                return Err("none of the sdists formats are supported");
+                return Err(ExclusionReason::IncompatibleArtifacts(
+                    "none of the sdists formats are supported".into(),
+                ));
             }
 
             sdists
@@ -150,26 +198,25 @@ impl PypiDependencyProvider {
                 // check the most compatible artifacts for dependencies first.
                 // this only needs to be done for wheels
                 wheels.sort_by_cached_key(|a| {
-                    -(*a)
-                        .borrow()
-                        .filename
-                        .as_wheel()
-                        .expect("only wheels are considered")
-                        .all_tags_iter()
-                        .filter_map(|tag| compatible_tags.compatibility(&tag))
-                        .max()
+                    -compatible_tags
+                        .wheel_compatibility(
+                            (*a).borrow()
+                                .filename
+                                .as_wheel()
+                                .expect("only wheels are considered"),
+                        )
                         .unwrap_or(0)
                 });
             }
 
             if !self.options.sdist_resolution.allow_sdists() && wheels.is_empty() {
-                return Err(
-                    "none of the artifacts are compatible with the Python interpreter or glibc version",
-                );
+                return Err(ExclusionReason::IncompatibleArtifacts(
+                    "none of the artifacts are compatible with the Python interpreter or glibc version".into(),
+                ));
             }
 
             if wheels.is_empty() && sdists.is_empty() {
-                return Err("none of the artifacts are compatible with the Python interpreter or glibc version and there are no supported sdists");
+                return Err(ExclusionReason::IncompatibleArtifacts("none of the artifacts are compatible with the Python interpreter or glibc version and there are no supported sdists".into()));
             }
         }
 
@@ -178,7 +225,9 @@ impl PypiDependencyProvider {
         let artifacts = wheels;
 
         if artifacts.is_empty() {
-            return Err("there are no supported artifacts");
+            return Err(ExclusionReason::IncompatibleArtifacts(
+                "there are no supported artifacts".into(),
+            ));
         }
 
         Ok(artifacts)
@@ -204,17 +253,136 @@ impl PypiDependencyProvider {
     }
 }
 
-#[derive(Debug, Error, Diagnostic, Clone)]
-pub(crate) enum MetadataError {
-    #[error("Extraction of metadata in case of wheels or building in case of sdists returned no results for following artifacts:\n{0}")]
-    NoMetadata(String),
+/// Fetches the metadata of a package version from one of its `artifacts`, building an sdist if
+/// required.
+pub(crate) async fn fetch_metadata(
+    package_db: &PackageDb,
+    wheel_builder: &Arc<WheelBuilder>,
+    artifacts: &[Arc<ArtifactInfo>],
+) -> Result<WheelCoreMetadata, ResolveError> {
+    let formatted_artifacts = || {
+        artifacts
+            .iter()
+            .format_with("\n", |a, f| f(&format_args!("\t- {}", a.filename)))
+            .to_string()
+    };
 
-    #[error("No metadata could be extracted for the following available artifacts:\n{artifacts}")]
-    ExtractionFailure {
-        artifacts: String,
-        #[related]
-        errors: Vec<MietteDiagnostic>,
-    },
+    match package_db
+        .get_metadata(artifacts, Some(wheel_builder))
+        .await
+    {
+        // We have retrieved a value without error
+        Ok(Some((_, metadata))) => Ok(metadata),
+        // No results have been found with the methods we tried
+        Ok(None) => Err(ResolveError::MetadataFetchFailed {
+            artifacts: formatted_artifacts(),
+        }),
+        // Errors have occurred during metadata extraction
+        // This is almost always an sdist build failure
+        Err(e) => {
+            if e.downcast_ref::<HttpRequestError>().is_some() {
+                Err(ResolveError::Network {
+                    artifacts: formatted_artifacts(),
+                    errors: vec![MietteDiagnostic::new(e.to_string())],
+                })
+            } else {
+                Err(ResolveError::BuildFailed {
+                    artifacts: formatted_artifacts(),
+                    errors: vec![MietteDiagnostic::new(e.to_string()).with_help("Probably an error during processing of source distributions. Please check the error message above.")],
+                })
+            }
+        }
+    }
+}
+
+/// Removes yanked artifacts unless `allow_yanked` is set. Returns an error that includes the reason
+/// the artifacts were yanked (if any) when all artifacts are yanked.
+fn filter_yanked<A: Borrow<ArtifactInfo>>(
+    artifacts: Vec<&A>,
+    allow_yanked: bool,
+) -> Result<Vec<&A>, ExclusionReason> {
+    if allow_yanked {
+        for artifact in &artifacts {
+            let artifact: &ArtifactInfo = (*artifact).borrow();
+            if artifact.yanked.yanked {
+                tracing::warn!(
+                    "'{}' is yanked but is considered because it was explicitly pinned{}",
+                    artifact.filename,
+                    artifact
+                        .yanked
+                        .reason
+                        .as_ref()
+                        .map(|reason| format!(" (yanked: {reason})"))
+                        .unwrap_or_default()
+                );
+            }
+        }
+        return Ok(artifacts);
+    }
+
+    let reason = artifacts
+        .iter()
+        .find_map(|a| (*a).borrow().yanked.reason.clone());
+
+    let artifacts = artifacts
+        .into_iter()
+        .filter(|a| !(*a).borrow().yanked.yanked)
+        .collect::<Vec<_>>();
+
+    if artifacts.is_empty() {
+        return Err(ExclusionReason::Yanked(reason));
+    }
+
+    Ok(artifacts)
+}
+
+/// Removes artifacts whose `requires_python` does not contain the given python version. Artifacts
+/// that do not specify `requires_python` are always kept. Returns an error that includes the
+/// required python versions when all artifacts are incompatible.
+fn filter_requires_python<'a, A: Borrow<ArtifactInfo>>(
+    artifacts: Vec<&'a A>,
+    python_version: Option<&Version>,
+) -> Result<Vec<&'a A>, ExclusionReason> {
+    let Some(python_version) = python_version else {
+        return Ok(artifacts);
+    };
+
+    let (compatible, incompatible): (Vec<_>, Vec<_>) = artifacts.into_iter().partition(|a| {
+        (*a).borrow()
+            .requires_python
+            .as_ref()
+            .map_or(true, |specifiers| specifiers.contains(python_version))
+    });
+
+    if compatible.is_empty() && !incompatible.is_empty() {
+        let requires_python = incompatible
+            .iter()
+            .filter_map(|a| (*a).borrow().requires_python.as_ref())
+            .map(ToString::to_string)
+            .unique()
+            .join(", ");
+        return Err(ExclusionReason::RequiresPython {
+            requires_python,
+            python_version: python_version.to_string(),
+        });
+    }
+
+    Ok(compatible)
+}
+
+/// Returns the artifacts to read the metadata of a version from. When `skip_sdist_fallback` is set,
+/// only the wheels are used if there are any. This ensures that we never try to build an sdist for
+/// a version that has wheels.
+fn metadata_artifacts<A: Borrow<ArtifactInfo> + Clone>(
+    artifacts: &[A],
+    skip_sdist_fallback: bool,
+) -> Vec<A> {
+    let is_wheel = |a: &&A| Borrow::<ArtifactInfo>::borrow(*a).is::<Wheel>();
+    if skip_sdist_fallback && artifacts.iter().any(|a| is_wheel(&a)) {
+        artifacts.iter().filter(is_wheel).cloned().collect()
+    } else {
+        artifacts.to_vec()
+    }
 }
 
 impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDependencyProvider {
@@ -235,6 +403,18 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
         _: &SolverCache<PypiVersionSet, PypiPackageName, Self>,
         solvables: &mut [SolvableId],
     ) {
+        // Determine whether the lowest versions of this package should be preferred.
+        let prefer_lowest = solvables.first().is_some_and(|&solvable_id| {
+            let name_id = self.pool.resolve_solvable(solvable_id).name_id();
+            match self.options.resolution_strategy {
+                ResolutionStrategy::Highest => false,
+                ResolutionStrategy::Lowest => true,
+                ResolutionStrategy::LowestDirect => self
+                    .direct_dependencies
+                    .contains(self.pool.resolve_package_name(name_id).base()),
+            }
+        });
+
         solvables.sort_by(|&a, &b| {
             // First sort the solvables based on the artifact types we have available for them and
             // whether some of them are preferred. If one artifact type is preferred over another
@@ -269,7 +449,12 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
                 (PypiVersion::Url(_), PypiVersion::Version { .. }) => Ordering::Greater,
                 (PypiVersion::Version { .. }, PypiVersion::Url(_)) => Ordering::Less,
 
-                // Sort versions from highest to lowest
+                // Sort versions from highest to lowest, or the other way around if the lowest
+                // versions are preferred
+                (
+                    PypiVersion::Version { version: a, .. },
+                    PypiVersion::Version { version: b, .. },
+                ) if prefer_lowest => a.cmp(b),
                 (
                     PypiVersion::Version { version: a, .. },
                     PypiVersion::Version { version: b, .. },
@@ -295,26 +480,43 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
             ArtifactRequest::FromIndex(package_name.base().clone())
         };
 
-        let lease = self.aquire_lease_to_run().await;
-        let result: Result<_, miette::Report> = tokio::spawn({
-            let package_db = self.package_db.clone();
-            async move {
-                let result = package_db.available_artifacts(request).await?.clone();
-                drop(lease);
-                Ok(result)
-            }
-        })
-        .await
-        .expect("cancelled");
-
-        let artifacts = match result {
-            Ok(artifacts) => artifacts,
-            Err(err) => {
-                tracing::error!(
-                    "failed to fetch artifacts of '{package_name}': {err:?}, skipping.."
-                );
+        let artifacts = if self.options.frozen {
+            // Only the locked version is a candidate, which is added below
+            if !self
+                .options
+                .locked_packages
+                .contains_key(package_name.base())
+            {
+                *self.should_cancel_with_value.lock() = Some(ResolveError::NotFrozen {
+                    package: package_name.base().to_string(),
+                });
                 return None;
             }
+            Default::default()
+        } else if let Some(prefetched) = &self.prefetched {
+            prefetched.available_artifacts(&request)?
+        } else {
+            let lease = self.aquire_lease_to_run().await;
+            let result: Result<_, miette::Report> = tokio::spawn({
+                let package_db = self.package_db.clone();
+                async move {
+                    let result = package_db.available_artifacts(request).await?.clone();
+                    drop(lease);
+                    Ok(result)
+                }
+            })
+            .await
+            .expect("cancelled");
+
+            match result {
+                Ok(artifacts) => artifacts,
+                Err(err) => {
+                    tracing::error!(
+                        "failed to fetch artifacts of '{package_name}': {err:?}, skipping.."
+                    );
+                    return None;
+                }
+            }
         };
         let mut candidates = Candidates::default();
         let locked_package = self.options.locked_packages.get(package_name.base());
@@ -335,6 +537,7 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
             PreReleaseResolution::Allow => true,
         };
 
+        let mut considered_versions = Vec::new();
         for (artifact_version, artifacts) in artifacts.iter() {
             // Skip this version if a locked or favored version exists for this version. It will be
             // added below.
@@ -370,20 +573,43 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
             let solvable_id = self.pool.intern_solvable(name, internable_version);
             candidates.candidates.push(solvable_id);
 
+            // Yanked artifacts are only considered if the user explicitly pinned this version
+            let allow_yanked = self.options.allow_yanked_if_pinned
+                && matches!(artifact_version, PypiVersion::Version { version, .. } if self.pinned_versions.get(package_name.base()) == Some(version));
+
             // Determine the candidates
-            match self.filter_candidates(artifacts) {
+            let exclusion_reason = match self.filter_candidates(artifacts, allow_yanked) {
                 Ok(artifacts) => {
                     self.cached_artifacts
                         .insert(solvable_id, artifacts.into_iter().cloned().collect());
+                    None
                 }
                 Err(reason) => {
                     candidates
                         .excluded
-                        .push((solvable_id, self.pool.intern_string(reason)));
+                        .push((solvable_id, self.pool.intern_string(reason.to_string())));
+                    Some(reason)
                 }
+            };
+
+            // Remember why this version might not be selected, to be able to explain the solution
+            if let (PypiPackageName::Base(_), PypiVersion::Version { version, .. }) =
+                (package_name, artifact_version)
+            {
+                let exclusion_reason = exclusion_reason.or_else(|| {
+                    (version.any_prerelease() && !should_package_allow_prerelease)
+                        .then_some(ExclusionReason::PreRelease)
+                });
+                considered_versions.push((version.clone(), exclusion_reason));
             }
         }
 
+        if !considered_versions.is_empty() {
+            self.considered_versions
+                .lock()
+                .insert(package_name.base().clone(), considered_versions);
+        }
+
         // Add a locked dependency
         if let Some(locked) = self.options.locked_packages.get(package_name.base()) {
             let version = if let Some(url) = &locked.url {
@@ -401,8 +627,13 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
                 .insert(solvable_id, locked.artifacts.clone());
         }
 
-        // Add a favored dependency
-        if let Some(favored) = self.options.favored_packages.get(package_name.base()) {
+        // Add a favored dependency, in frozen mode only the locked version is a candidate
+        if let Some(favored) = self
+            .options
+            .favored_packages
+            .get(package_name.base())
+            .filter(|_| !self.options.frozen)
+        {
             let version = if let Some(url) = &favored.url {
                 PypiVersion::Url(url.clone())
             } else {
@@ -434,6 +665,17 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
 
         let mut dependencies = KnownDependencies::default();
 
+        // The requirement on exactly this version of the package
+        let same_version = match package_version {
+            PypiVersion::Version { version, .. } => {
+                VersionOrUrl::VersionSpecifier(VersionSpecifiers::from_iter([
+                    VersionSpecifier::new(Operator::ExactEqual, version.clone(), false)
+                        .expect("failed to construct equality version specifier"),
+                ]))
+            }
+            PypiVersion::Url(url_version) => VersionOrUrl::Url(url_version.clone()),
+        };
+
         // Add a dependency to the base dependency when we have an extra
         // So that we have a connection to the base package
         if let PypiPackageName::Extra(package_name, _) = package_name {
@@ -441,19 +683,12 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
                 .pool
                 .lookup_package_name(&PypiPackageName::Base(package_name.clone()))
                 .expect("base package not found while resolving extra");
-            let specifiers = match package_version {
-                PypiVersion::Version { version, .. } => {
-                    VersionOrUrl::VersionSpecifier(VersionSpecifiers::from_iter([
-                        VersionSpecifier::new(Operator::ExactEqual, version.clone(), false)
-                            .expect("failed to construct equality version specifier"),
-                    ]))
-                }
-                PypiVersion::Url(url_version) => VersionOrUrl::Url(url_version.clone()),
-            };
-
             let version_set_id = self.pool.intern_version_set(
                 base_name_id,
-                PypiVersionSet::from_spec(Some(specifiers), &self.options.pre_release_resolution),
+                PypiVersionSet::from_spec(
+                    Some(same_version.clone()),
+                    &self.options.pre_release_resolution,
+                ),
             );
             dependencies.requirements.push(version_set_id);
         }
@@ -464,24 +699,31 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
             .get(&solvable_id)
             .expect("the artifacts must already have been cached");
 
-        // If there are no artifacts we can have two cases
-        if artifacts.is_empty() {
+        // Check if this is the locked version of the package
+        let locked_package = self
+            .options
+            .locked_packages
+            .get(package_name.base())
+            .filter(|locked| match package_version {
+                PypiVersion::Url(url) => locked.url.as_ref() == Some(url),
+                PypiVersion::Version { version, .. } => &locked.version == version,
+            });
+
+        let result = if let Some(metadata) = locked_package.and_then(|p| p.metadata.as_ref()) {
+            // The metadata of a locked package is used as is
+            Ok(metadata.as_ref().clone())
+        } else if self.options.frozen {
+            // Nothing is fetched or built in frozen mode, the cached metadata of the locked
+            // packages is read before solving.
+            Err(ResolveError::FrozenMetadataNotCached {
+                package: package_name.base().to_string(),
+            })
+        } else if artifacts.is_empty() {
             // TODO: rework this so it makes more sense from an API perspective later, I think we should add the concept of installed_and_locked or something
             // It is locked the package data may be available externally
             // So it's fine if there are no artifacts, we can just assume this has been taken care of
-            let locked_package = self.options.locked_packages.get(package_name.base());
-            match package_version {
-                PypiVersion::Url(url) => {
-                    if locked_package.map(|p| &p.url) == Some(&Some(url.clone())) {
-                        return Dependencies::Known(dependencies);
-                    }
-                }
-
-                PypiVersion::Version { version, .. } => {
-                    if locked_package.map(|p| &p.version) == Some(version) {
-                        return Dependencies::Known(dependencies);
-                    }
-                }
+            if locked_package.is_some() {
+                return Dependencies::Known(dependencies);
             }
 
             // Otherwise, we do expect data, and it's not fine if there are no artifacts
@@ -490,65 +732,66 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
                 package_name, package_version
             ));
             return Dependencies::Unknown(error);
-        }
-
-        let result: miette::Result<_> = tokio::spawn({
-            let package_db = self.package_db.clone();
-            let wheel_builder = self.wheel_builder.clone();
-            let artifacts = artifacts.to_vec();
-            let lease = self.aquire_lease_to_run().await;
-            async move {
-                if let Some((ai, metadata)) = package_db
-                    .get_metadata(&artifacts, Some(&wheel_builder))
-                    .await?
-                {
-                    drop(lease);
-                    Ok(Some((ai.clone(), metadata)))
-                } else {
-                    drop(lease);
-                    Ok(None)
+        } else {
+            let artifacts = metadata_artifacts(artifacts, self.options.skip_sdist_fallback);
+            if let Some(prefetched) = &self.prefetched {
+                match prefetched.metadata(package_name.base(), package_version, &artifacts) {
+                    Some(result) => result,
+                    // The metadata is fetched before the solver is restarted, until then assume the
+                    // package has no dependencies.
+                    None => return Dependencies::Known(dependencies),
                 }
+            } else {
+                tokio::spawn({
+                    let package_db = self.package_db.clone();
+                    let wheel_builder = self.wheel_builder.clone();
+                    let lease = self.aquire_lease_to_run().await;
+                    async move {
+                        let result = fetch_metadata(&package_db, &wheel_builder, &artifacts).await;
+                        drop(lease);
+                        result
+                    }
+                })
+                .await
+                .expect("cancelled")
             }
-        })
-        .await
-        .expect("cancelled");
+        };
 
         let metadata = match result {
-            // We have retrieved a value without error
-            Ok(value) => {
-                if let Some((_, metadata)) = value {
-                    // Return the metadata
-                    metadata
-                } else {
-                    let formatted_artifacts = artifacts
-                        .iter()
-                        .format_with("\n", |a, f| f(&format_args!("\t- {}", a.filename)))
-                        .to_string();
-                    // No results have been found with the methods we tried
-                    *self.should_cancel_with_value.lock() =
-                        Some(MetadataError::NoMetadata(formatted_artifacts));
-                    return Dependencies::Unknown(self.pool.intern_string("".to_string()));
-                }
-            }
-            // Errors have occurred during metadata extraction
-            // This is almost always an sdist build failure
-            Err(e) => {
-                let formatted_artifacts = artifacts
-                    .iter()
-                    .format_with("\n", |a, f| f(&format_args!("\t- {}", a.filename)))
-                    .to_string();
-                *self.should_cancel_with_value.lock() = Some(MetadataError::ExtractionFailure {
-                    artifacts: formatted_artifacts,
-                    errors: vec![MietteDiagnostic::new(e.to_string()).with_help("Probably an error during processing of source distributions. Please check the error message above.")],
-                });
+            Ok(metadata) => metadata,
+            Err(error) => {
+                *self.should_cancel_with_value.lock() = Some(error);
                 return Dependencies::Unknown(self.pool.intern_string("".to_string()));
             }
         };
 
+        // Check that the requested extra is actually provided by the package
+        if let PypiPackageName::Extra(name, extra) = package_name {
+            if !metadata.extras.contains(extra) {
+                let message = format!(
+                    "{name}=={} does not provide the extra '{}'",
+                    metadata.version,
+                    extra.as_str()
+                );
+                match self.options.unknown_extras {
+                    UnknownExtraResolution::Warn => tracing::warn!("{message}"),
+                    UnknownExtraResolution::Error => {
+                        return Dependencies::Unknown(self.pool.intern_string(message));
+                    }
+                }
+            }
+        }
+
+        if self.options.retain_metadata {
+            self.retained_metadata
+                .lock()
+                .insert(solvable_id, Arc::new(metadata.clone()));
+        }
+
         // Add constraints that restrict that the extra packages are set to the same version.
         if let PypiPackageName::Base(package_name) = package_name {
             // Add constraints on the extras of a package
-            for extra in metadata.extras {
+            for extra in metadata.extras.iter().cloned() {
                 let extra_name_id = self
                     .pool
                     .intern_package_name(PypiPackageName::Extra(package_name.clone(), extra));
@@ -573,17 +816,36 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
             }
         }
 
+        // Skip the dependencies of the package itself
+        if self.options.no_deps {
+            return Dependencies::Known(dependencies);
+        }
+
         let extras = package_name
             .extra()
-            .into_iter()
-            .map(|e| e.as_str())
-            .collect::<Vec<_>>();
-        for requirement in metadata.requires_dist {
-            // Evaluate environment markers
-            if let Some(markers) = requirement.marker.as_ref() {
-                if !markers.evaluate(&self.markers, &extras) {
-                    continue;
-                }
+            .map(std::slice::from_ref)
+            .unwrap_or_default();
+        let mut edges = Vec::new();
+        for requirement in metadata.runtime_requirements(&self.markers, extras) {
+            // Requirements on excluded packages are dropped as if they did not exist
+            if PackageName::from_str(&requirement.name).map_or(false, |name| {
+                self.options
+                    .exclude
+                    .contains(&NormalizedPackageName::from(name))
+            }) {
+                continue;
+            }
+
+            // Record the edge in the dependency graph. For an extra only the dependencies that are
+            // conditional on the extra are recorded, the others are already part of the base
+            // package.
+            if package_name.extra().is_none()
+                || requirement
+                    .marker
+                    .as_ref()
+                    .is_some_and(|markers| !markers.evaluate(&self.markers, &[]))
+            {
+                edges.push(requirement.clone());
             }
 
             // Add the dependency to the pool
@@ -592,8 +854,19 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
                 version_or_url,
                 extras,
                 ..
-            } = requirement;
+            } = requirement.clone();
             let name = PackageName::from_str(&name).expect("invalid package name");
+
+            // A package that refers to its own extras, e.g. `pkg[all]` requiring `pkg[a]`, refers
+            // to the extras of the same version. Pin it explicitly so the solver does not have to
+            // derive that from the base package.
+            let version_or_url =
+                if version_or_url.is_none() && name.as_str() == package_name.base().as_str() {
+                    Some(same_version.clone())
+                } else {
+                    version_or_url
+                };
+
             let dependency_name_id = self
                 .pool
                 .intern_package_name(PypiPackageName::Base(name.clone().into()));
@@ -630,6 +903,134 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
             }
         }
 
+        self.dependency_edges.lock().insert(solvable_id, edges);
+
         Dependencies::Known(dependencies)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{DistInfoMetadata, Yanked};
+
+    fn artifact(filename: &str, yanked: Yanked) -> ArtifactInfo {
+        artifact_requiring_python(filename, yanked, None)
+    }
+
+    fn artifact_requiring_python(
+        filename: &str,
+        yanked: Yanked,
+        requires_python: Option<&str>,
+    ) -> ArtifactInfo {
+        let name: NormalizedPackageName = "foo".parse().unwrap();
+        ArtifactInfo {
+            filename: ArtifactName::from_filename(filename, None, &name).unwrap(),
+            url: Url::parse(&format!("https://example.com/{filename}")).unwrap(),
+            is_direct_url: false,
+            hashes: None,
+            requires_python: requires_python.map(|spec| spec.parse().unwrap()),
+            dist_info_metadata: DistInfoMetadata::default(),
+            yanked,
+        }
+    }
+
+    fn yanked(reason: Option<&str>) -> Yanked {
+        Yanked {
+            yanked: true,
+            reason: reason.map(ToString::to_string),
+        }
+    }
+
+    #[test]
+    fn test_filter_yanked() {
+        let artifacts = [
+            artifact("foo-1.0-py3-none-any.whl", yanked(None)),
+            artifact("foo-1.0.tar.gz", Yanked::default()),
+        ];
+        let filtered = filter_yanked(artifacts.iter().collect(), false).unwrap();
+        assert_eq!(filtered, vec![&artifacts[1]]);
+    }
+
+    #[test]
+    fn test_filter_yanked_reason() {
+        let artifacts = [artifact(
+            "foo-1.0-py3-none-any.whl",
+            yanked(Some("dependency confusion")),
+        )];
+        assert_eq!(
+            filter_yanked(artifacts.iter().collect(), false)
+                .unwrap_err()
+                .to_string(),
+            "it is yanked: dependency confusion"
+        );
+
+        let artifacts = [artifact("foo-1.0-py3-none-any.whl", yanked(None))];
+        assert_eq!(
+            filter_yanked(artifacts.iter().collect(), false)
+                .unwrap_err()
+                .to_string(),
+            "it is yanked"
+        );
+    }
+
+    #[test]
+    fn test_filter_yanked_allowed_when_pinned() {
+        let artifacts = [artifact(
+            "foo-1.0-py3-none-any.whl",
+            yanked(Some("dependency confusion")),
+        )];
+        let filtered = filter_yanked(artifacts.iter().collect(), true).unwrap();
+        assert_eq!(filtered, vec![&artifacts[0]]);
+    }
+
+    #[test]
+    fn test_filter_requires_python() {
+        let artifacts = [
+            artifact_requiring_python(
+                "foo-1.0-py3-none-any.whl",
+                Yanked::default(),
+                Some(">=3.12"),
+            ),
+            artifact_requiring_python("foo-1.0.tar.gz", Yanked::default(), None),
+        ];
+        let python_version = Version::from_str("3.8.10").unwrap();
+        let filtered =
+            filter_requires_python(artifacts.iter().collect(), Some(&python_version)).unwrap();
+        assert_eq!(filtered, vec![&artifacts[1]]);
+
+        // Without a python version nothing is filtered
+        let filtered = filter_requires_python(artifacts.iter().collect(), None).unwrap();
+        assert_eq!(filtered.len(), 2);
+
+        // All artifacts are incompatible
+        let artifacts = [artifact_requiring_python(
+            "foo-1.0-py3-none-any.whl",
+            Yanked::default(),
+            Some(">=3.12"),
+        )];
+        assert_eq!(
+            filter_requires_python(artifacts.iter().collect(), Some(&python_version))
+                .unwrap_err()
+                .to_string(),
+            "it requires python >=3.12 but the python version is 3.8.10"
+        );
+    }
+
+    #[test]
+    fn test_metadata_artifacts() {
+        let artifacts = [
+            artifact("foo-1.0-py3-none-any.whl", Yanked::default()),
+            artifact("foo-1.0.tar.gz", Yanked::default()),
+        ];
+        assert_eq!(metadata_artifacts(&artifacts, false), artifacts.to_vec());
+        assert_eq!(
+            metadata_artifacts(&artifacts, true),
+            vec![artifacts[0].clone()]
+        );
+
+        // Versions without wheels still use their sdists
+        let artifacts = [artifact("foo-1.0.tar.gz", Yanked::default())];
+        assert_eq!(metadata_artifacts(&artifacts, true), artifacts.to_vec());
+    }
+}
@@ -0,0 +1,52 @@
+use miette::{Diagnostic, MietteDiagnostic};
+use thiserror::Error;
+
+/// An error that can occur when resolving an environment with [`super::resolve`].
+#[derive(Debug, Error, Diagnostic, Clone)]
+#[allow(missing_docs)]
+pub enum ResolveError {
+    /// There is no set of packages that satisfies the requirements. The message explains why the
+    /// requirements conflict.
+    #[error("{0}")]
+    NoSolution(String),
+
+    /// None of the artifacts of a package returned any metadata.
+    #[error("Extraction of metadata in case of wheels or building in case of sdists returned no results for following artifacts:\n{artifacts}")]
+    MetadataFetchFailed { artifacts: String },
+
+    /// Extracting the metadata of a package failed, this is almost always caused by a failure to
+    /// build a source distribution.
+    #[error("No metadata could be extracted for the following available artifacts:\n{artifacts}")]
+    BuildFailed {
+        artifacts: String,
+        #[related]
+        errors: Vec<MietteDiagnostic>,
+    },
+
+    /// The metadata of a package could not be downloaded.
+    #[error("Could not download the metadata of the following available artifacts:\n{artifacts}")]
+    Network {
+        artifacts: String,
+        #[related]
+        errors: Vec<MietteDiagnostic>,
+    },
+
+    /// A package is required that is not part of the locked packages while resolving with
+    /// [`super::solve_options::ResolveOptions::frozen`].
+    #[error("'{package}' is required but it is not one of the frozen packages")]
+    NotFrozen { package: String },
+
+    /// The metadata of a locked package is not available without fetching it while resolving with
+    /// [`super::solve_options::ResolveOptions::frozen`].
+    #[error("the metadata of the frozen package '{package}' is not cached")]
+    FrozenMetadataNotCached { package: String },
+
+    /// A package was requested directly that is also excluded with
+    /// [`super::solve_options::ResolveOptions::exclude`].
+    #[error("'{package}' is requested but it is also excluded from the resolution")]
+    ExcludedPackageRequested { package: String },
+
+    /// The resolution was cancelled before it completed.
+    #[error("the operation was cancelled")]
+    Cancelled,
+}
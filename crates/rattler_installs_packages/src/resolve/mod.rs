@@ -9,11 +9,20 @@
 //!
 
 mod dependency_provider;
+mod error;
+mod prefetch;
 mod pypi_version_types;
 mod solve;
 pub mod solve_options;
 mod solve_types;
 
+pub use error::ResolveError;
 pub use pypi_version_types::PypiVersion;
 pub use pypi_version_types::PypiVersionSet;
-pub use solve::{resolve, PinnedPackage};
+pub use solve::{
+    favored_from_venv, pinned_packages_from_requirements, pinned_packages_from_versions, resolve,
+    resolve_with_explanation, resolve_with_graph, PinnedPackage,
+};
+pub use solve_types::{
+    DependencyEdge, ExcludedVersion, ExclusionReason, ResolutionExplanation, ResolvedGraph,
+};
@@ -0,0 +1,211 @@
+//! Caches the information the solver needs so it can run without awaiting any io. See
+//! [`super::solve_options::ResolveOptions::prefetch`].
+
+use super::{
+    dependency_provider::fetch_metadata, error::ResolveError, solve_options::ResolveOptions,
+    PypiVersion,
+};
+use crate::{
+    index::{ArtifactRequest, PackageDb},
+    types::{ArtifactInfo, NormalizedPackageName, PackageName, WheelCoreMetadata},
+    utils::redact_url,
+    wheel_builder::WheelBuilder,
+};
+use async_recursion::async_recursion;
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use pep508_rs::VersionOrUrl;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    sync::Arc,
+};
+use url::Url;
+
+type VersionArtifacts = IndexMap<PypiVersion, Vec<Arc<ArtifactInfo>>>;
+
+/// Identifies an [`ArtifactRequest`] without the wheel builder.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ArtifactsKey {
+    FromIndex(NormalizedPackageName),
+    DirectUrl(NormalizedPackageName, Url),
+}
+
+impl ArtifactsKey {
+    fn from_request(request: &ArtifactRequest) -> Self {
+        match request {
+            ArtifactRequest::FromIndex(name) => Self::FromIndex(name.clone()),
+            ArtifactRequest::DirectUrl { name, url, .. } => {
+                Self::DirectUrl(name.clone(), url.clone())
+            }
+        }
+    }
+
+    fn into_request(self, wheel_builder: &Arc<WheelBuilder>) -> ArtifactRequest {
+        match self {
+            Self::FromIndex(name) => ArtifactRequest::FromIndex(name),
+            Self::DirectUrl(name, url) => ArtifactRequest::DirectUrl {
+                name,
+                url,
+                wheel_builder: wheel_builder.clone(),
+            },
+        }
+    }
+}
+
+/// Displays the package and its direct url, without the credentials in the url.
+impl Display for ArtifactsKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FromIndex(name) => write!(f, "{}", name.as_str()),
+            Self::DirectUrl(name, url) => write!(f, "{} @ {}", name.as_str(), redact_url(url)),
+        }
+    }
+}
+
+/// The information the solver asked for that has not been fetched yet.
+#[derive(Default)]
+struct Missing {
+    artifacts: Vec<ArtifactsKey>,
+    metadata: Vec<(NormalizedPackageName, PypiVersion, Vec<Arc<ArtifactInfo>>)>,
+}
+
+/// The available artifacts and metadata of packages that have been fetched ahead of time. The
+/// solver only reads from this cache and records what is missing, which is then fetched with
+/// [`Prefetched::fetch_missing`] before the solver is restarted.
+#[derive(Default)]
+pub(crate) struct Prefetched {
+    /// The available artifacts per request, `None` if fetching them failed.
+    artifacts: Mutex<HashMap<ArtifactsKey, Option<VersionArtifacts>>>,
+    metadata: Mutex<
+        HashMap<(NormalizedPackageName, PypiVersion), Result<WheelCoreMetadata, ResolveError>>,
+    >,
+    missing: Mutex<Missing>,
+}
+
+impl Prefetched {
+    /// Returns the available artifacts for the request. Returns `None` if they could not be fetched
+    /// or if they have not been fetched yet, in which case the request is recorded.
+    pub fn available_artifacts(&self, request: &ArtifactRequest) -> Option<VersionArtifacts> {
+        let key = ArtifactsKey::from_request(request);
+        match self.artifacts.lock().get(&key) {
+            Some(artifacts) => artifacts.clone(),
+            None => {
+                self.missing.lock().artifacts.push(key);
+                None
+            }
+        }
+    }
+
+    /// Returns the metadata of a package version. Returns `None` if it has not been fetched yet, in
+    /// which case it is recorded that the metadata should be fetched from `artifacts`.
+    pub fn metadata(
+        &self,
+        name: &NormalizedPackageName,
+        version: &PypiVersion,
+        artifacts: &[Arc<ArtifactInfo>],
+    ) -> Option<Result<WheelCoreMetadata, ResolveError>> {
+        let key = (name.clone(), version.clone());
+        if let Some(result) = self.metadata.lock().get(&key) {
+            return Some(result.clone());
+        }
+        self.missing
+            .lock()
+            .metadata
+            .push((key.0, key.1, artifacts.to_vec()));
+        None
+    }
+
+    /// Fetches everything the solver asked for but was missing. The index pages of the
+    /// dependencies of the fetched metadata are fetched as well, because the solver will most
+    /// likely ask for them next. Returns `false` if nothing was missing.
+    #[async_recursion]
+    pub async fn fetch_missing(
+        &self,
+        package_db: &Arc<PackageDb>,
+        wheel_builder: &Arc<WheelBuilder>,
+        options: &ResolveOptions,
+    ) -> bool {
+        let Missing {
+            mut artifacts,
+            metadata,
+        } = std::mem::take(&mut *self.missing.lock());
+        if artifacts.is_empty() && metadata.is_empty() {
+            return false;
+        }
+
+        let fetched_metadata = futures::future::join_all(metadata.into_iter().map(
+            |(name, version, artifacts)| async move {
+                let _lease = acquire_lease(options).await;
+                let result = fetch_metadata(package_db, wheel_builder, &artifacts).await;
+                ((name, version), result)
+            },
+        ))
+        .await;
+
+        for (key, result) in fetched_metadata {
+            if let Ok(metadata) = &result {
+                artifacts.extend(metadata.requires_dist.iter().filter_map(|requirement| {
+                    let name: NormalizedPackageName =
+                        PackageName::from_str(&requirement.name).ok()?.into();
+                    if options.exclude.contains(&name) {
+                        return None;
+                    }
+                    Some(match &requirement.version_or_url {
+                        Some(VersionOrUrl::Url(url)) => ArtifactsKey::DirectUrl(name, url.clone()),
+                        _ => ArtifactsKey::FromIndex(name),
+                    })
+                }));
+            }
+            self.metadata.lock().insert(key, result);
+        }
+
+        // Only fetch the artifacts that have not been fetched before
+        let artifacts = {
+            let fetched = self.artifacts.lock();
+            let mut artifacts = artifacts
+                .into_iter()
+                .filter(|key| !fetched.contains_key(key))
+                .collect::<Vec<_>>();
+            artifacts.sort_by_key(|key| format!("{key:?}"));
+            artifacts.dedup();
+            artifacts
+        };
+
+        let fetched_artifacts =
+            futures::future::join_all(artifacts.into_iter().map(|key| async move {
+                let _lease = acquire_lease(options).await;
+                let result = package_db
+                    .available_artifacts(key.clone().into_request(wheel_builder))
+                    .await
+                    .map(Clone::clone);
+                (key, result)
+            }))
+            .await;
+
+        let mut fetched = self.artifacts.lock();
+        for (key, result) in fetched_artifacts {
+            let artifacts = match result {
+                Ok(artifacts) => Some(artifacts),
+                Err(err) => {
+                    tracing::error!("failed to fetch artifacts of '{key}': {err:?}, skipping..");
+                    None
+                }
+            };
+            fetched.insert(key, artifacts);
+        }
+
+        true
+    }
+}
+
+/// Acquires a lease to limit the number of concurrent requests
+async fn acquire_lease(options: &ResolveOptions) -> tokio::sync::OwnedSemaphorePermit {
+    options
+        .max_concurrent_tasks
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("could not acquire semaphore")
+}
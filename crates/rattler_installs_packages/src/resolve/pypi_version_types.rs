@@ -176,3 +176,40 @@ impl Display for PypiPackageName {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn contains(spec: &str, version: &str, prerelease_option: &PreReleaseResolution) -> bool {
+        let spec = VersionOrUrl::VersionSpecifier(spec.parse().unwrap());
+        let version = PypiVersion::Version {
+            version: Version::from_str(version).unwrap(),
+            package_allows_prerelease: false,
+        };
+        PypiVersionSet::from_spec(Some(spec), prerelease_option).contains(&version)
+    }
+
+    #[test]
+    fn test_allow_pre_releases() {
+        let allow = PreReleaseResolution::Allow;
+        let default = PreReleaseResolution::default();
+        let disallow = PreReleaseResolution::Disallow;
+
+        // Stable versions are always selectable
+        for option in [&allow, &default, &disallow] {
+            assert!(contains(">=1.0", "2.0", option));
+        }
+
+        // Pre-releases are only selectable by default if the spec mentions a pre-release
+        assert!(!contains(">=1.0", "2.0b1", &default));
+        assert!(contains(">=1.0b0", "2.0b1", &default));
+        assert!(!contains(">=1.0b0", "2.0b1", &disallow));
+
+        // Allowing all pre-releases is strictly more permissive
+        assert!(contains(">=1.0", "2.0b1", &allow));
+        assert!(contains(">=1.0b0", "2.0b1", &allow));
+        assert!(!contains(">=3.0", "2.0b1", &allow));
+    }
+}
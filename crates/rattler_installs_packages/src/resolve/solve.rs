@@ -1,20 +1,32 @@
-use crate::index::PackageDb;
-use crate::python_env::WheelTags;
+use crate::index::{ArtifactRequest, PackageDb};
+use crate::python_env::{find_distributions_in_venv, VEnv, WheelTags};
 use crate::resolve::dependency_provider::PypiDependencyProvider;
+use crate::resolve::error::ResolveError;
+use crate::resolve::prefetch::Prefetched;
 use crate::resolve::pypi_version_types::PypiVersion;
 use crate::types::PackageName;
 use crate::wheel_builder::WheelBuilder;
-use crate::{types::ArtifactInfo, types::Extra, types::NormalizedPackageName};
+use crate::{
+    types::ArtifactInfo, types::BuildTag, types::Extra, types::NormalizedPackageName,
+    types::WheelCoreMetadata,
+};
 use elsa::FrozenMap;
-use pep440_rs::Version;
+use miette::IntoDiagnostic;
+use pep440_rs::{Operator, Version};
 use pep508_rs::{MarkerEnvironment, Requirement, VersionOrUrl};
-use resolvo::{DefaultSolvableDisplay, Pool, Solver, UnsolvableOrCancelled};
+use resolvo::{
+    runtime::{AsyncRuntime, NowOrNeverRuntime},
+    DefaultSolvableDisplay, Pool, SolvableId, Solver, UnsolvableOrCancelled, VersionSetId,
+};
 use std::collections::HashMap;
 use std::str::FromStr;
 use url::Url;
 
 use crate::resolve::pypi_version_types::{PypiPackageName, PypiVersionSet};
 use crate::resolve::solve_options::ResolveOptions;
+use crate::resolve::solve_types::{
+    DependencyEdge, ExcludedVersion, ExclusionReason, ResolutionExplanation, ResolvedGraph,
+};
 use std::collections::HashSet;
 use std::convert::identity;
 use std::ops::Deref;
@@ -40,10 +52,185 @@ pub struct PinnedPackage {
     ///
     /// This list may be empty if the package was locked or favored.
     pub artifacts: Vec<Arc<ArtifactInfo>>,
+
+    /// The metadata of the selected version. This is only available if
+    /// [`ResolveOptions::retain_metadata`] was enabled during resolution and the metadata was
+    /// read during resolution (which is not the case for locked packages without artifacts).
+    pub metadata: Option<Arc<WheelCoreMetadata>>,
+}
+
+impl PinnedPackage {
+    /// Returns the license of the selected version, see [`WheelCoreMetadata::effective_license`].
+    /// This requires the metadata to be retained, see [`ResolveOptions::retain_metadata`].
+    pub fn license(&self) -> Option<&str> {
+        self.metadata
+            .as_deref()
+            .and_then(WheelCoreMetadata::effective_license)
+    }
+
+    /// Returns the build tag of the most compatible artifact if that artifact is a wheel with a
+    /// build tag in its filename. Wheels of the same version with a different build tag can
+    /// differ in their contents.
+    pub fn build_tag(&self) -> Option<&BuildTag> {
+        self.artifacts
+            .first()?
+            .filename
+            .as_wheel()?
+            .build_tag
+            .as_ref()
+    }
+}
+
+/// Constructs [`PinnedPackage`]s for the given package names and versions by looking up the
+/// available artifacts in the `package_db`. The result can be passed to
+/// [`ResolveOptions::with_favored_packages`] or [`ResolveOptions::with_locked_packages`] to bias
+/// the resolution towards a previous solution.
+///
+/// Returns an error if a package or version cannot be found in the index.
+pub async fn pinned_packages_from_versions(
+    package_db: &PackageDb,
+    packages: impl IntoIterator<Item = (NormalizedPackageName, Version)>,
+) -> miette::Result<HashMap<NormalizedPackageName, PinnedPackage>> {
+    let mut result = HashMap::new();
+    for (name, version) in packages {
+        let pinned = pin_from_index(package_db, name.clone(), version, HashSet::new()).await?;
+        result.insert(name, pinned);
+    }
+    Ok(result)
+}
+
+/// Constructs [`PinnedPackage`]s from requirements of the form `name==version` (e.g. read from an
+/// existing lock file) by looking up the available artifacts in the `package_db`. Extras that are
+/// specified on the requirement are added to the pinned package.
+///
+/// Returns an error if a requirement does not pin an exact version or if the version cannot be
+/// found in the index.
+pub async fn pinned_packages_from_requirements(
+    package_db: &PackageDb,
+    requirements: impl IntoIterator<Item = &Requirement>,
+) -> miette::Result<HashMap<NormalizedPackageName, PinnedPackage>> {
+    let mut result = HashMap::new();
+    for requirement in requirements {
+        let name: NormalizedPackageName = PackageName::from_str(&requirement.name)
+            .map_err(|e| miette::miette!("invalid package name '{}': {e}", requirement.name))?
+            .into();
+
+        let version = match &requirement.version_or_url {
+            Some(VersionOrUrl::VersionSpecifier(specifiers)) => match &specifiers[..] {
+                [specifier]
+                    if matches!(specifier.operator(), Operator::Equal | Operator::ExactEqual) =>
+                {
+                    Some(specifier.version().clone())
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+        .ok_or_else(|| miette::miette!("'{requirement}' does not pin an exact version"))?;
+
+        let extras = requirement
+            .extras
+            .iter()
+            .flatten()
+            .map(|extra| extra.parse())
+            .collect::<Result<HashSet<Extra>, _>>()
+            .map_err(|e| miette::miette!("invalid extra in '{requirement}': {e}"))?;
+
+        let pinned = pin_from_index(package_db, name.clone(), version, extras).await?;
+        result.insert(name, pinned);
+    }
+    Ok(result)
+}
+
+/// Constructs [`PinnedPackage`]s for the distributions that are installed in `venv`. Pass them to
+/// [`ResolveOptions::with_favored_packages`] to prefer the installed versions over upgrading them
+/// when resolving, which minimizes the changes to an existing environment. This is similar to the
+/// behavior of pip without `--upgrade`.
+///
+/// Favoring a version only biases the solver. If a requirement, either one that is passed to
+/// [`resolve`] or a dependency, does not allow the installed version a different version is
+/// selected, so explicit version requirements always win.
+///
+/// Distributions whose version cannot be found in the index, e.g. because they were installed
+/// from a direct url or a local path, are skipped.
+pub async fn favored_from_venv(
+    venv: &VEnv,
+    package_db: &PackageDb,
+) -> miette::Result<HashMap<NormalizedPackageName, PinnedPackage>> {
+    let distributions =
+        find_distributions_in_venv(venv.root(), venv.install_paths()).into_diagnostic()?;
+
+    let mut result = HashMap::new();
+    for distribution in distributions {
+        let available = package_db
+            .available_artifacts(ArtifactRequest::FromIndex(distribution.name.clone()))
+            .await?;
+        let artifacts = available
+            .iter()
+            .find_map(|(version, artifacts)| match version {
+                PypiVersion::Version { version, .. } if version == &distribution.version => {
+                    Some(artifacts.clone())
+                }
+                _ => None,
+            });
+        let Some(artifacts) = artifacts else {
+            tracing::debug!(
+                "installed version {} of '{}' is not available in the index, it is not favored",
+                distribution.version,
+                distribution.name.as_str()
+            );
+            continue;
+        };
+
+        result.insert(
+            distribution.name.clone(),
+            PinnedPackage {
+                name: distribution.name,
+                version: distribution.version,
+                url: None,
+                extras: HashSet::new(),
+                artifacts,
+                metadata: None,
+            },
+        );
+    }
+    Ok(result)
+}
+
+/// Looks up the artifacts of a specific version of a package in the index.
+async fn pin_from_index(
+    package_db: &PackageDb,
+    name: NormalizedPackageName,
+    version: Version,
+    extras: HashSet<Extra>,
+) -> miette::Result<PinnedPackage> {
+    let artifacts = package_db
+        .available_artifacts(ArtifactRequest::FromIndex(name.clone()))
+        .await?
+        .iter()
+        .find_map(|(v, artifacts)| match v {
+            PypiVersion::Version { version: v, .. } if v == &version => Some(artifacts.clone()),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            miette::miette!(
+                "version {version} of package '{}' could not be found in the index",
+                name.as_str()
+            )
+        })?;
+
+    Ok(PinnedPackage {
+        name,
+        version,
+        url: None,
+        extras,
+        artifacts,
+        metadata: None,
+    })
 }
 
 /// Resolves an environment that contains the given requirements and all dependencies of those
-/// requirements.
+/// requirements. The returned packages are sorted by name so the output is reproducible.
 ///
 /// `requirements` defines the requirements of packages that must be present in the solved
 /// environment.
@@ -52,6 +239,10 @@ pub struct PinnedPackage {
 /// If `compatible_tags` is defined then the available artifacts of a distribution are filtered to
 /// include only artifacts that are compatible with the specified tags. If `None` is passed, the
 /// artifacts are not filtered at all
+///
+/// If the resolution cache of the `package_db` is enabled (see
+/// [`PackageDb::with_resolution_cache`]) a previous result for the same inputs is returned without
+/// solving again.
 pub async fn resolve(
     package_db: Arc<PackageDb>,
     requirements: impl IntoIterator<Item = &Requirement>,
@@ -59,8 +250,138 @@ pub async fn resolve(
     compatible_tags: Option<Arc<WheelTags>>,
     wheel_builder: Arc<WheelBuilder>,
     options: ResolveOptions,
-) -> miette::Result<Vec<PinnedPackage>> {
+) -> Result<Vec<PinnedPackage>, ResolveError> {
+    let requirements: Vec<_> = requirements.into_iter().collect();
+    let cache_key = package_db.resolution_cache_key(
+        &requirements,
+        &env_markers,
+        compatible_tags.as_deref(),
+        &options,
+    );
+    if let Some(key) = &cache_key {
+        if let Some(packages) = package_db.cached_resolution(key).await {
+            return Ok(packages);
+        }
+    }
+
+    let (packages, _) = resolve_with_graph(
+        package_db.clone(),
+        requirements,
+        env_markers,
+        compatible_tags,
+        wheel_builder,
+        options,
+    )
+    .await?;
+
+    if let Some(key) = &cache_key {
+        package_db.cache_resolution(key, &packages).await;
+    }
+
+    Ok(packages)
+}
+
+/// Resolves an environment like [`resolve`] but also returns the [`ResolvedGraph`] of the
+/// solution, which describes which requirement of which package introduced each of the pinned
+/// packages.
+pub async fn resolve_with_graph(
+    package_db: Arc<PackageDb>,
+    requirements: impl IntoIterator<Item = &Requirement>,
+    env_markers: Arc<MarkerEnvironment>,
+    compatible_tags: Option<Arc<WheelTags>>,
+    wheel_builder: Arc<WheelBuilder>,
+    options: ResolveOptions,
+) -> Result<(Vec<PinnedPackage>, ResolvedGraph), ResolveError> {
+    let (packages, graph, _) = resolve_with_details(
+        package_db,
+        requirements,
+        env_markers,
+        compatible_tags,
+        wheel_builder,
+        options,
+    )
+    .await?;
+    Ok((packages, graph))
+}
+
+/// Resolves an environment like [`resolve`] but also returns a [`ResolutionExplanation`] that
+/// describes, for every pinned package, which higher versions were available and why they were not
+/// selected. This can be used to answer questions like "why was 2.1.0 selected and not 2.2.0?".
+pub async fn resolve_with_explanation(
+    package_db: Arc<PackageDb>,
+    requirements: impl IntoIterator<Item = &Requirement>,
+    env_markers: Arc<MarkerEnvironment>,
+    compatible_tags: Option<Arc<WheelTags>>,
+    wheel_builder: Arc<WheelBuilder>,
+    options: ResolveOptions,
+) -> Result<(Vec<PinnedPackage>, ResolutionExplanation), ResolveError> {
+    let (packages, _, explanation) = resolve_with_details(
+        package_db,
+        requirements,
+        env_markers,
+        compatible_tags,
+        wheel_builder,
+        options,
+    )
+    .await?;
+    Ok((packages, explanation))
+}
+
+/// The pinned packages of a solution together with its graph and explanation.
+type ResolutionDetails = (Vec<PinnedPackage>, ResolvedGraph, ResolutionExplanation);
+
+async fn resolve_with_details(
+    package_db: Arc<PackageDb>,
+    requirements: impl IntoIterator<Item = &Requirement>,
+    env_markers: Arc<MarkerEnvironment>,
+    compatible_tags: Option<Arc<WheelTags>>,
+    wheel_builder: Arc<WheelBuilder>,
+    mut options: ResolveOptions,
+) -> Result<ResolutionDetails, ResolveError> {
     let requirements: Vec<_> = requirements.into_iter().cloned().collect();
+
+    if options.frozen {
+        // The solver never fetches anything in frozen mode, so read the cached metadata of the
+        // locked packages up front.
+        for locked in options.locked_packages.values_mut() {
+            if locked.metadata.is_some() {
+                continue;
+            }
+            match package_db.get_cached_metadata(&locked.artifacts).await {
+                Ok(metadata) => locked.metadata = metadata.map(|(_, m)| Arc::new(m)),
+                Err(err) => {
+                    tracing::debug!(
+                        "failed to read the cached metadata of {}: {err}",
+                        locked.name
+                    )
+                }
+            }
+        }
+    }
+
+    if options.prefetch {
+        // Run the solver against the prefetched data and fetch whatever it was missing, until
+        // the solver no longer asks for anything new.
+        let prefetched = Arc::new(Prefetched::default());
+        loop {
+            let result = resolve_inner(
+                package_db.clone(),
+                &requirements,
+                env_markers.clone(),
+                compatible_tags.clone(),
+                wheel_builder.clone(),
+                options.clone(),
+                Some(prefetched.clone()),
+            );
+            if !prefetched
+                .fetch_missing(&package_db, &wheel_builder, &options)
+                .await
+            {
+                return result;
+            }
+        }
+    }
+
     tokio::task::spawn_blocking(move || {
         resolve_inner(
             package_db,
@@ -69,13 +390,14 @@ pub async fn resolve(
             compatible_tags,
             wheel_builder,
             options,
+            None,
         )
     })
     .await
     .map_or_else(
         |e| match e.try_into_panic() {
             Ok(panic) => std::panic::resume_unwind(panic),
-            Err(_) => Err(miette::miette!("the operation was cancelled")),
+            Err(_) => Err(ResolveError::Cancelled),
         },
         identity,
     )
@@ -88,27 +410,52 @@ fn resolve_inner<'r>(
     compatible_tags: Option<Arc<WheelTags>>,
     wheel_buider: Arc<WheelBuilder>,
     options: ResolveOptions,
-) -> miette::Result<Vec<PinnedPackage>> {
+    prefetched: Option<Arc<Prefetched>>,
+) -> Result<ResolutionDetails, ResolveError> {
     // Construct the pool
     let pool = Pool::new();
 
     // Construct HashMap of Name to URL
     let name_to_url: FrozenMap<NormalizedPackageName, String> = FrozenMap::default();
 
+    // Keep track of the versions that are explicitly pinned by the user
+    let mut pinned_versions = HashMap::new();
+
+    // Keep track of the packages that are directly required by the user
+    let mut direct_dependencies = HashSet::new();
+
     // Construct the root requirements from the requirements requested by the user.
     let requirements = requirements.into_iter();
     let requirement_count = requirements.size_hint();
     let mut root_requirements =
         Vec::with_capacity(requirement_count.1.unwrap_or(requirement_count.0));
 
-    for Requirement {
-        name,
-        version_or_url,
-        extras,
-        ..
-    } in requirements
-    {
+    // The requirements of the user are the edges from the root of the graph.
+    let mut graph = ResolvedGraph::default();
+
+    for requirement in requirements {
+        let Requirement {
+            name,
+            version_or_url,
+            extras,
+            ..
+        } = requirement;
         let name = PackageName::from_str(name).expect("invalid package name");
+        if options
+            .exclude
+            .contains(&NormalizedPackageName::from(name.clone()))
+        {
+            return Err(ResolveError::ExcludedPackageRequested {
+                package: name.as_str().to_owned(),
+            });
+        }
+        direct_dependencies.insert(name.clone().into());
+        graph.root.push(DependencyEdge {
+            dependency: name.clone().into(),
+            requirement: requirement.clone(),
+            via_extra: None,
+        });
+
         let pypi_name = PypiPackageName::Base(name.clone().into());
         let dependency_package_name = pool.intern_package_name(pypi_name.clone());
         let version_set_id = pool.intern_version_set(
@@ -121,6 +468,14 @@ fn resolve_inner<'r>(
             name_to_url.insert(pypi_name.base().clone(), url.clone().as_str().to_owned());
         }
 
+        if let Some(VersionOrUrl::VersionSpecifier(specifiers)) = version_or_url {
+            if let [specifier] = &specifiers[..] {
+                if matches!(specifier.operator(), Operator::Equal | Operator::ExactEqual) {
+                    pinned_versions.insert(pypi_name.base().clone(), specifier.version().clone());
+                }
+            }
+        }
+
         for extra in extras.iter().flatten() {
             let extra: Extra = extra.parse().expect("invalid extra");
             let dependency_package_name = pool
@@ -134,45 +489,35 @@ fn resolve_inner<'r>(
     }
 
     // Construct the provider
+    let is_prefetched = prefetched.is_some();
     let provider = PypiDependencyProvider::new(
         pool,
         package_db,
         env_markers,
         compatible_tags,
         name_to_url,
+        pinned_versions,
+        direct_dependencies,
         wheel_buider,
         options,
-    )?;
-
-    // Invoke the solver to get a solution to the requirements
-    let mut solver = Solver::new(&provider).with_runtime(tokio::runtime::Handle::current());
-    let solvables = match solver.solve(root_requirements) {
-        Ok(solvables) => solvables,
-        Err(e) => {
-            return match e {
-                UnsolvableOrCancelled::Unsolvable(problem) => Err(miette::miette!(
-                    "{}",
-                    problem
-                        .display_user_friendly(
-                            &solver,
-                            solver.pool.clone(),
-                            &DefaultSolvableDisplay
-                        )
-                        .to_string()
-                        .trim()
-                )),
-                UnsolvableOrCancelled::Cancelled(e) => {
-                    let e = e.downcast::<crate::resolve::dependency_provider::MetadataError>().expect("invalid cancellation error message, expected a MetadataError, this indicates an error in the code");
-                    let report = e.deref().clone().into();
-                    Err(report)
-                }
-            };
-        }
+        prefetched,
+    );
+
+    // Invoke the solver to get a solution to the requirements. The prefetched data is available
+    // without awaiting anything, so the solver does not need an async runtime.
+    let solvables = if is_prefetched {
+        solve(&provider, root_requirements, NowOrNeverRuntime)?
+    } else {
+        solve(
+            &provider,
+            root_requirements,
+            tokio::runtime::Handle::current(),
+        )?
     };
     let mut result: HashMap<NormalizedPackageName, PinnedPackage> = HashMap::new();
     for solvable_id in solvables {
-        let solvable = solver.pool.resolve_solvable(solvable_id);
-        let name = solver.pool.resolve_package_name(solvable.name_id());
+        let solvable = provider.pool.resolve_solvable(solvable_id);
+        let name = provider.pool.resolve_package_name(solvable.name_id());
         let version = solvable.inner();
 
         let artifacts: Vec<_> = provider
@@ -203,16 +548,647 @@ fn resolve_inner<'r>(
                 url,
                 artifacts,
                 extras: Default::default(),
+                metadata: None,
             });
 
+        // Attach the retained metadata
+        if entry.metadata.is_none() {
+            entry.metadata = provider.retained_metadata.lock().remove(&solvable_id);
+        }
+
         // Add the extra if selected
         if let PypiPackageName::Extra(_, extra) = name {
             entry.extras.insert(extra.clone());
         }
+
+        // Add the dependencies of this solvable to the graph
+        let edges = provider
+            .dependency_edges
+            .lock()
+            .remove(&solvable_id)
+            .unwrap_or_default();
+        graph
+            .edges
+            .entry(name.base().clone())
+            .or_default()
+            .extend(edges.into_iter().map(|requirement| {
+                DependencyEdge {
+                    dependency: PackageName::from_str(&requirement.name)
+                        .expect("invalid package name")
+                        .into(),
+                    requirement,
+                    via_extra: name.extra().cloned(),
+                }
+            }));
     }
 
-    Ok(result.into_values().collect())
+    // Explain why the versions higher than the selected versions were not selected
+    let mut considered_versions = std::mem::take(&mut *provider.considered_versions.lock());
+    let mut explanation = ResolutionExplanation::default();
+    for package in result.values().filter(|package| package.url.is_none()) {
+        let Some(versions) = considered_versions.remove(&package.name) else {
+            continue;
+        };
+        let mut excluded = versions
+            .into_iter()
+            .filter(|(version, _)| version > &package.version)
+            .map(|(version, reason)| ExcludedVersion {
+                version,
+                reason: reason.unwrap_or(ExclusionReason::Conflict),
+            })
+            .collect::<Vec<_>>();
+        if excluded.is_empty() {
+            continue;
+        }
+        excluded.sort_by(|a, b| b.version.cmp(&a.version));
+        explanation.excluded.insert(package.name.clone(), excluded);
+    }
+
+    // Sort the packages so the output is the same for every run, a `HashMap` does not have a
+    // deterministic order.
+    let mut packages: Vec<_> = result.into_values().collect();
+    packages.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.version.cmp(&b.version)));
+
+    Ok((packages, graph, explanation))
+}
+
+/// Solves the `root_requirements` using the given async `runtime` to drive the provider.
+fn solve<RT: AsyncRuntime>(
+    provider: &PypiDependencyProvider,
+    root_requirements: Vec<VersionSetId>,
+    runtime: RT,
+) -> Result<Vec<SolvableId>, ResolveError> {
+    let mut solver = Solver::new(provider).with_runtime(runtime);
+    solver.solve(root_requirements).map_err(|e| match e {
+        UnsolvableOrCancelled::Unsolvable(problem) => ResolveError::NoSolution(
+            problem
+                .display_user_friendly(&solver, solver.pool.clone(), &DefaultSolvableDisplay)
+                .to_string()
+                .trim()
+                .to_string(),
+        ),
+        UnsolvableOrCancelled::Cancelled(e) => {
+            let e = e.downcast::<ResolveError>().expect("invalid cancellation error message, expected a ResolveError, this indicates an error in the code");
+            e.deref().clone()
+        }
+    })
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+    use crate::index::PackageSourcesBuilder;
+    use crate::python_env::Pep508EnvMakers;
+    use crate::resolve::solve_options::{
+        ResolutionStrategy, SDistResolution, UnknownExtraResolution,
+    };
+    use crate::utils::get_package_db;
+    use itertools::Itertools;
+    use reqwest::Client;
+    use reqwest_middleware::ClientWithMiddleware;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn test_pinned_packages_from_requirements() {
+        let (package_db, _tempdir) = get_package_db();
+        let requirements = [Requirement::from_str("flask[async]==3.0.0").unwrap()];
+
+        let pinned = pinned_packages_from_requirements(&package_db, &requirements)
+            .await
+            .unwrap();
+
+        let flask = pinned
+            .get(&"flask".parse::<NormalizedPackageName>().unwrap())
+            .unwrap();
+        assert_eq!(flask.version, Version::from_str("3.0.0").unwrap());
+        assert!(flask.extras.contains(&"async".parse::<Extra>().unwrap()));
+        assert!(!flask.artifacts.is_empty());
+    }
+
+    async fn resolve_requirement(
+        requirement: &str,
+        options: ResolveOptions,
+    ) -> Result<(Vec<PinnedPackage>, ResolvedGraph), ResolveError> {
+        let (package_db, _tempdir) = get_package_db();
+        let env_markers = Arc::new(Pep508EnvMakers::from_env().await.unwrap().0);
+        let wheel_builder = WheelBuilder::new(
+            package_db.clone(),
+            env_markers.clone(),
+            None,
+            options.clone(),
+        )
+        .unwrap();
+        let requirements = [Requirement::from_str(requirement).unwrap()];
+
+        resolve_with_graph(
+            package_db,
+            &requirements,
+            env_markers,
+            None,
+            wheel_builder,
+            options,
+        )
+        .await
+    }
+
+    async fn resolve_flask(options: ResolveOptions) -> (Vec<PinnedPackage>, ResolvedGraph) {
+        resolve_requirement("flask[async]==3.0.0", options)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_deterministic_order() {
+        let names = |packages: Vec<PinnedPackage>| {
+            packages
+                .into_iter()
+                .map(|p| p.name.as_str().to_owned())
+                .collect_vec()
+        };
+
+        let (first, _) = resolve_flask(ResolveOptions::default()).await;
+        let first = names(first);
+        assert!(first.windows(2).all(|w| w[0] < w[1]), "{first:?}");
+
+        for _ in 0..3 {
+            let (packages, _) = resolve_flask(ResolveOptions::default()).await;
+            assert_eq!(names(packages), first);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_no_solution() {
+        let err = resolve_requirement("flask==0.0.0", ResolveOptions::default())
+            .await
+            .unwrap_err();
+        let ResolveError::NoSolution(message) = err else {
+            panic!("expected no solution, got {err:?}");
+        };
+        assert!(message.contains("flask"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_unknown_extra() {
+        // By default an unknown extra is ignored
+        let (packages, _) = resolve_requirement("six[nonexistent]", ResolveOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].name.as_str(), "six");
+
+        // But it can also be rejected
+        let err = resolve_requirement(
+            "six[nonexistent]",
+            ResolveOptions::default().with_unknown_extras(UnknownExtraResolution::Error),
+        )
+        .await
+        .unwrap_err();
+        let ResolveError::NoSolution(message) = err else {
+            panic!("expected no solution, got {err:?}");
+        };
+        assert!(message.contains("does not provide the extra 'nonexistent'"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolution_strategy() {
+        let resolve = |resolution_strategy| async move {
+            let options = ResolveOptions {
+                sdist_resolution: SDistResolution::OnlyWheels,
+                ..ResolveOptions::default()
+            }
+            .with_resolution_strategy(resolution_strategy);
+            let (packages, _) = resolve_requirement("python-dateutil>=2.8.0", options)
+                .await
+                .unwrap();
+            let version = |name: &str| {
+                packages
+                    .iter()
+                    .find(|p| p.name.as_str() == name)
+                    .unwrap()
+                    .version
+                    .clone()
+            };
+            (version("python-dateutil"), version("six"))
+        };
+
+        let lowest_dateutil = Version::from_str("2.8.0").unwrap();
+        let (highest_dateutil, highest_six) = resolve(ResolutionStrategy::Highest).await;
+        assert!(highest_dateutil > lowest_dateutil);
+
+        // The lowest version of every package is selected
+        let (dateutil, six) = resolve(ResolutionStrategy::Lowest).await;
+        assert_eq!(dateutil, lowest_dateutil);
+        assert!(six < highest_six);
+
+        // Only the direct requirement is resolved to its lowest version
+        let (dateutil, six) = resolve(ResolutionStrategy::LowestDirect).await;
+        assert_eq!(dateutil, lowest_dateutil);
+        assert_eq!(six, highest_six);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_with_graph() {
+        let requirements = [Requirement::from_str("flask[async]==3.0.0").unwrap()];
+        let (packages, graph) = resolve_flask(ResolveOptions::default()).await;
+
+        // The root requirement is an edge from the root
+        let flask: NormalizedPackageName = "flask".parse().unwrap();
+        assert_eq!(graph.root.len(), 1);
+        assert_eq!(graph.root[0].dependency, flask);
+        assert_eq!(graph.root[0].requirement, requirements[0]);
+
+        // Every edge should point to a package in the solution
+        for edges in graph.edges.values() {
+            for edge in edges {
+                assert!(packages.iter().any(|p| p.name == edge.dependency));
+            }
+        }
+
+        // asgiref is only required through the `async` extra of flask
+        let asgiref = graph
+            .dependencies(&flask)
+            .iter()
+            .find(|edge| edge.dependency.as_str() == "asgiref")
+            .unwrap();
+        assert_eq!(asgiref.via_extra, Some("async".parse().unwrap()));
+
+        // werkzeug is an unconditional dependency of flask
+        let werkzeug: NormalizedPackageName = "werkzeug".parse().unwrap();
+        let (dependent, werkzeug) = graph
+            .dependents(&werkzeug)
+            .find(|(dependent, _)| *dependent == Some(&flask))
+            .unwrap();
+        assert_eq!(dependent, Some(&flask));
+        assert_eq!(werkzeug.via_extra, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_retain_metadata() {
+        let (packages, _) = resolve_flask(ResolveOptions::default()).await;
+        assert!(packages.iter().all(|p| p.metadata.is_none()));
+
+        let (packages, _) =
+            resolve_flask(ResolveOptions::default().with_retain_metadata(true)).await;
+        let flask = packages
+            .iter()
+            .find(|p| p.name.as_str() == "flask")
+            .unwrap();
+        let metadata = flask.metadata.as_ref().unwrap();
+        assert_eq!(metadata.name.as_str(), "flask");
+        assert_eq!(metadata.version, flask.version);
+        assert!(metadata.extras.contains(&"async".parse::<Extra>().unwrap()));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_no_deps() {
+        let (packages, graph) = resolve_flask(ResolveOptions::default().with_no_deps(true)).await;
+
+        // Only flask is selected, the extra resolves to the same version as flask itself
+        assert_eq!(packages.len(), 1);
+        let flask = &packages[0];
+        assert_eq!(flask.name.as_str(), "flask");
+        assert_eq!(flask.version, Version::from_str("3.0.0").unwrap());
+        assert!(flask.extras.contains(&"async".parse::<Extra>().unwrap()));
+        assert!(graph.edges.values().all(Vec::is_empty));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_exclude() {
+        let exclude = HashSet::from(["werkzeug".parse().unwrap()]);
+        let (packages, graph) =
+            resolve_flask(ResolveOptions::default().with_exclude(exclude.clone())).await;
+        assert!(packages.iter().any(|p| p.name.as_str() == "flask"));
+        assert!(packages.iter().all(|p| p.name.as_str() != "werkzeug"));
+        assert!(graph
+            .edges
+            .values()
+            .flatten()
+            .all(|edge| edge.dependency.as_str() != "werkzeug"));
+
+        // Requesting an excluded package directly is an error
+        let err = resolve_requirement("werkzeug", ResolveOptions::default().with_exclude(exclude))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, ResolveError::ExcludedPackageRequested { ref package } if package == "werkzeug"),
+            "unexpected error: {err:?}"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_frozen() {
+        let (packages, _) =
+            resolve_flask(ResolveOptions::default().with_retain_metadata(true)).await;
+        let locked: HashMap<_, _> = packages
+            .iter()
+            .map(|p| (p.name.clone(), p.clone()))
+            .collect();
+
+        // The locked packages satisfy the requirements
+        let (frozen, _) = resolve_flask(ResolveOptions {
+            locked_packages: locked.clone(),
+            frozen: true,
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(frozen.len(), packages.len());
+        for package in frozen {
+            assert_eq!(package.version, locked[&package.name].version);
+        }
+
+        // The dependencies are read from the locked metadata, which does not require the artifacts
+        // and also works when prefetching
+        let without_artifacts: HashMap<_, _> = locked
+            .iter()
+            .map(|(name, p)| {
+                let package = PinnedPackage {
+                    artifacts: Vec::new(),
+                    ..p.clone()
+                };
+                (name.clone(), package)
+            })
+            .collect();
+        let (frozen, _) = resolve_flask(ResolveOptions {
+            locked_packages: without_artifacts,
+            frozen: true,
+            prefetch: true,
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(frozen.len(), packages.len());
+
+        // Favored packages are not considered
+        let favored = locked
+            .iter()
+            .map(|(name, p)| {
+                let package = PinnedPackage {
+                    version: "0.0.1".parse().unwrap(),
+                    ..p.clone()
+                };
+                (name.clone(), package)
+            })
+            .collect();
+        let (frozen, _) = resolve_flask(ResolveOptions {
+            locked_packages: locked.clone(),
+            favored_packages: favored,
+            frozen: true,
+            ..Default::default()
+        })
+        .await;
+        for package in frozen {
+            assert_eq!(package.version, locked[&package.name].version);
+        }
+
+        // A different version of flask is not locked
+        let err = resolve_requirement(
+            "flask==2.3.3",
+            ResolveOptions {
+                locked_packages: locked.clone(),
+                frozen: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, ResolveError::NoSolution(_)), "{err:?}");
+
+        // A dependency is missing from the locked packages
+        let mut incomplete = locked;
+        incomplete.remove(&"werkzeug".parse::<NormalizedPackageName>().unwrap());
+        let err = resolve_requirement(
+            "flask==3.0.0",
+            ResolveOptions {
+                locked_packages: incomplete,
+                frozen: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, ResolveError::NotFrozen { package } if package == "werkzeug"));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_resolve_with_prefetch() {
+        let pinned = |packages: Vec<PinnedPackage>| {
+            let mut pinned = packages
+                .into_iter()
+                .map(|p| {
+                    let extras = p.extras.iter().map(|e| e.as_str().to_owned()).sorted();
+                    (p.name, p.version, extras.collect_vec())
+                })
+                .collect_vec();
+            pinned.sort();
+            pinned
+        };
+
+        let (packages, graph) = resolve_flask(ResolveOptions::default().with_prefetch(true)).await;
+        for edges in graph.edges.values() {
+            for edge in edges {
+                assert!(packages.iter().any(|p| p.name == edge.dependency));
+            }
+        }
+
+        let (expected, _) = resolve_flask(ResolveOptions::default()).await;
+        assert_eq!(pinned(packages), pinned(expected));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_self_referential_extras() {
+        // The `all` extra of this package requires its own `a` and `b` extras, which require
+        // `six` and `packaging` respectively.
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/wheels/self_referential_extras-1.0.0-py3-none-any.whl")
+            .canonicalize()
+            .unwrap();
+        let url = Url::from_file_path(path).unwrap();
+        let (packages, graph) = resolve_requirement(
+            &format!("self_referential_extras[all] @ {url}"),
+            ResolveOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let name: NormalizedPackageName = "self_referential_extras".parse().unwrap();
+        let package = packages.iter().find(|p| p.name == name).unwrap();
+        assert_eq!(package.url.as_ref(), Some(&url));
+        assert_eq!(
+            package
+                .extras
+                .iter()
+                .map(Extra::as_str)
+                .sorted()
+                .collect_vec(),
+            ["a", "all", "b"]
+        );
+        assert!(packages.iter().any(|p| p.name.as_str() == "six"));
+        assert!(packages.iter().any(|p| p.name.as_str() == "packaging"));
+
+        // The extras are reached through the `all` extra of the package itself
+        let self_edges = graph
+            .dependencies(&name)
+            .iter()
+            .filter(|edge| edge.dependency == name)
+            .collect_vec();
+        assert_eq!(self_edges.len(), 2);
+        assert!(self_edges
+            .iter()
+            .all(|edge| edge.via_extra == Some("all".parse().unwrap())));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_favored_from_venv() {
+        let (package_db, _tempdir) = get_package_db();
+        let name: NormalizedPackageName = "six".parse().unwrap();
+
+        // Install an older version of six into a venv
+        let venv_dir = tempfile::tempdir().unwrap();
+        let venv =
+            VEnv::create(venv_dir.path(), crate::python_env::PythonLocation::System).unwrap();
+        let six = pinned_packages_from_requirements(
+            &package_db,
+            &[Requirement::from_str("six==1.15.0").unwrap()],
+        )
+        .await
+        .unwrap();
+        let artifact_info = six[&name]
+            .artifacts
+            .iter()
+            .find(|a| a.is::<crate::artifacts::Wheel>())
+            .unwrap();
+        let (wheel, _) = package_db.get_wheel(artifact_info, None).await.unwrap();
+        venv.install_wheel(&wheel, &Default::default()).unwrap();
+
+        let favored = favored_from_venv(&venv, &package_db).await.unwrap();
+        let six = &favored[&name];
+        assert_eq!(six.version, Version::from_str("1.15.0").unwrap());
+        assert!(!six.artifacts.is_empty());
+
+        // The installed version is preferred over the latest version
+        let options = ResolveOptions::default().with_favored_packages(favored);
+        let (packages, _) = resolve_requirement("six", options.clone()).await.unwrap();
+        assert_eq!(packages[0].version, Version::from_str("1.15.0").unwrap());
+
+        // Unless a requirement does not allow it
+        let (packages, _) = resolve_requirement("six>=1.16", options).await.unwrap();
+        assert!(packages[0].version >= Version::from_str("1.16").unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolution_cache() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let make_package_db = |generation: &str| {
+            let sources = PackageSourcesBuilder::new("https://pypi.org/simple/".parse().unwrap())
+                .build()
+                .unwrap();
+            Arc::new(
+                PackageDb::new(
+                    sources,
+                    ClientWithMiddleware::from(Client::new()),
+                    tempdir.path(),
+                    Default::default(),
+                )
+                .unwrap()
+                .with_resolution_cache(generation)
+                .unwrap(),
+            )
+        };
+
+        let requirements = [Requirement::from_str("flask[async]==3.0.0").unwrap()];
+        let requirement_refs = requirements.iter().collect::<Vec<_>>();
+        let env_markers = Arc::new(Pep508EnvMakers::from_env().await.unwrap().0);
+        let options = ResolveOptions::default();
+
+        let package_db = make_package_db("1");
+        let key = package_db
+            .resolution_cache_key(&requirement_refs, &env_markers, None, &options)
+            .unwrap();
+        assert!(package_db.cached_resolution(&key).await.is_none());
+
+        let wheel_builder = WheelBuilder::new(
+            package_db.clone(),
+            env_markers.clone(),
+            None,
+            options.clone(),
+        )
+        .unwrap();
+        let packages = resolve(
+            package_db.clone(),
+            &requirements,
+            env_markers.clone(),
+            None,
+            wheel_builder,
+            options.clone(),
+        )
+        .await
+        .unwrap();
+
+        // The resolution is now cached
+        let mut cached = package_db.cached_resolution(&key).await.unwrap();
+        let mut packages = packages;
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        cached.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(cached, packages);
+
+        // A different index generation results in a different key
+        let other_key = make_package_db("2")
+            .resolution_cache_key(&requirement_refs, &env_markers, None, &options)
+            .unwrap();
+        assert_ne!(key, other_key);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_packages_from_requirements_requires_exact_version() {
+        let (package_db, _tempdir) = get_package_db();
+        let requirements = [Requirement::from_str("flask>=3.0.0").unwrap()];
+
+        assert!(
+            pinned_packages_from_requirements(&package_db, &requirements)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_with_explanation() {
+        let (package_db, _tempdir) = get_package_db();
+        let env_markers = Arc::new(Pep508EnvMakers::from_env().await.unwrap().0);
+        let options = ResolveOptions::default();
+        let wheel_builder = WheelBuilder::new(
+            package_db.clone(),
+            env_markers.clone(),
+            None,
+            options.clone(),
+        )
+        .unwrap();
+        let requirements = [Requirement::from_str("flask==2.3.0").unwrap()];
+
+        let (packages, explanation) = resolve_with_explanation(
+            package_db,
+            &requirements,
+            env_markers,
+            None,
+            wheel_builder,
+            options,
+        )
+        .await
+        .unwrap();
+
+        // Only versions higher than the selected version are explained
+        let flask: NormalizedPackageName = "flask".parse().unwrap();
+        let selected = &packages.iter().find(|p| p.name == flask).unwrap().version;
+        let excluded = explanation.excluded_versions(&flask);
+        assert!(!excluded.is_empty());
+        assert!(excluded.iter().all(|excluded| &excluded.version > selected));
+        assert!(excluded
+            .windows(2)
+            .all(|pair| pair[0].version > pair[1].version));
+
+        // A newer version of flask does not satisfy the requirement
+        assert_eq!(
+            explanation.reason(&flask, &Version::from_str("3.0.0").unwrap()),
+            Some(&ExclusionReason::Conflict)
+        );
+        assert_eq!(explanation.reason(&flask, selected), None);
+    }
+}
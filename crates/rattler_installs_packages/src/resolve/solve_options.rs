@@ -2,8 +2,13 @@
 
 use crate::{python_env::PythonLocation, types::NormalizedPackageName};
 use pep508_rs::{Requirement, VersionOrUrl};
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::{collections::HashMap, str::FromStr};
+use std::time::Duration;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 use tokio::sync::Semaphore;
 
 use crate::types::PackageName;
@@ -136,7 +141,12 @@ pub enum PreReleaseResolution {
         allow_names: Vec<String>,
     },
 
-    /// Allow any pre-releases to be selected during resolution
+    /// Allow any pre-releases to be selected during resolution, for all packages. This is the
+    /// equivalent of `pip install --pre`.
+    ///
+    /// This is strictly more permissive than the other variants: every version that would be
+    /// selectable with [`PreReleaseResolution::AllowIfNoOtherVersionsOrEnabled`] is also
+    /// selectable with this variant, so there is no need to additionally list package names.
     Allow,
 }
 
@@ -179,6 +189,35 @@ impl SDistResolution {
     }
 }
 
+/// Defines which of the compatible versions of a package the solver prefers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResolutionStrategy {
+    /// Select the highest compatible version of every package.
+    #[default]
+    Highest,
+
+    /// Select the lowest compatible version of every package. This is useful to verify that the
+    /// lower bounds of the declared requirements are actually correct.
+    Lowest,
+
+    /// Select the lowest compatible version of the packages that are directly required and the
+    /// highest compatible version of their transitive dependencies.
+    LowestDirect,
+}
+
+/// Defines what happens when a requirement requests an extra that is not provided by a package
+/// (e.g. `pandas[performance]` when pandas does not list `performance` in its `Provides-Extra`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownExtraResolution {
+    /// Log a warning and ignore the extra, like pip does.
+    #[default]
+    Warn,
+
+    /// Versions that do not provide the requested extra cannot be selected. If none of the
+    /// versions provide it resolution fails with an error that mentions the unknown extra.
+    Error,
+}
+
 /// Specifies what to do with failed build environments
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum OnWheelBuildFailure {
@@ -189,6 +228,19 @@ pub enum OnWheelBuildFailure {
     DeleteBuildEnv,
 }
 
+/// Specifies which build frontend is used to invoke the build backend of an sdist
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BuildFrontend {
+    /// Use the build frontend that is bundled with this crate. It calls the hooks of the build
+    /// backend directly.
+    #[default]
+    Builtin,
+
+    /// Use [pypa/build](https://github.com/pypa/build) (`python -m build`). The `build` package is
+    /// installed into the build environment in addition to the build requirements of the sdist.
+    PypaBuild,
+}
+
 /// Additional options that may influence the solver. In general passing [`Default::default`] to
 /// the [`super::resolve`] function should provide sane defaults, however if you want to fine tune the
 /// resolver you can do so via this struct.
@@ -205,6 +257,11 @@ pub struct ResolveOptions {
     /// Defines if we should inherit env variables during build process of wheel files
     pub clean_env: bool,
 
+    /// The names of env variables that are passed through from the parent process even if
+    /// [`ResolveOptions::clean_env`] is set, e.g. `SYSTEMROOT` on windows. Variables in
+    /// [`ResolveOptions::env_variables`] take precedence. Ignored if `clean_env` is not set.
+    pub clean_env_allowlist: Vec<String>,
+
     /// Defines what to do with failed build environments
     /// by default these are deleted but can also be saved for debugging purposes
     pub on_wheel_build_failure: OnWheelBuildFailure,
@@ -224,6 +281,89 @@ pub struct ResolveOptions {
 
     /// Defines env variables that can be used during resolving
     pub env_variables: HashMap<String, String>,
+
+    /// Allow yanked versions to be selected if they are explicitly pinned in the requirements
+    /// (e.g. `foo==1.0.0`). This matches the behavior of pip. By default yanked versions are never
+    /// selected.
+    pub allow_yanked_if_pinned: bool,
+
+    /// Never fall back to sdists to obtain the metadata of a version that has compatible wheels,
+    /// not even if reading the metadata from the wheels fails. This avoids spinning up build
+    /// environments for packages that ship wheels, which speeds up resolution. This trades
+    /// completeness for speed: a version whose wheel metadata cannot be read will not be
+    /// considered. By default sdists are used as a fallback.
+    pub skip_sdist_fallback: bool,
+
+    /// Retain the metadata of the selected packages and attach it to the resolved
+    /// [`PinnedPackage`]s. This avoids having to fetch the metadata again after resolution at the
+    /// cost of keeping it in memory. By default the metadata is not retained.
+    pub retain_metadata: bool,
+
+    /// The maximum duration of a single invocation of the build backend when building a wheel or
+    /// its metadata from an sdist. If the deadline passes the build process, and every process it
+    /// spawned, is killed and the build fails with [`crate::wheel_builder::WheelBuildError::Timeout`].
+    /// By default builds never time out.
+    pub build_timeout: Option<Duration>,
+
+    /// The directory in which build environments and other scratch space used while building
+    /// wheels from sdists are created. The directory is created if it does not exist. Saved build
+    /// environments (see [`ResolveOptions::on_wheel_build_failure`]) are also persisted in this
+    /// directory. This is useful on systems where the default temporary directory is small or
+    /// mounted `noexec`. By default the system temporary directory is used.
+    pub build_dir: Option<PathBuf>,
+
+    /// The build frontend that is used to build wheels and their metadata from sdists. By default
+    /// the builtin frontend is used.
+    pub build_frontend: BuildFrontend,
+
+    /// Retain the stdout and stderr of the commands that are run to build sdists, regardless of
+    /// whether the build succeeded, up to the given number of bytes per sdist. The logs can be
+    /// retrieved with [`crate::wheel_builder::WheelBuilder::build_logs`]. By default no output is
+    /// retained.
+    pub build_log_limit: Option<usize>,
+
+    /// Fetch the available artifacts and metadata of packages asynchronously before running the
+    /// solver synchronously against the fetched data, instead of running the solver on a blocking
+    /// thread that awaits every request as it goes. Whenever the solver encounters data that has
+    /// not been fetched yet it is restarted once that data has been fetched. This makes
+    /// [`super::resolve`] usable from contexts that cannot block, e.g. a single threaded runtime.
+    ///
+    /// The trade-off is that the solver may run multiple times and that the index pages of all
+    /// dependencies of fetched packages are fetched ahead of time, including packages the solver
+    /// would never have looked at, which can result in more network requests. The solver itself
+    /// still runs on the calling task. By default this is disabled.
+    pub prefetch: bool,
+
+    /// Only resolve the requested packages without any of their dependencies, like pip's
+    /// `--no-deps`. The extras of a requested package are still resolved to the same version as
+    /// the package itself, but the dependencies they introduce are ignored. The build
+    /// environments of sdists are always resolved with their dependencies. By default all
+    /// dependencies are resolved.
+    pub no_deps: bool,
+
+    /// Only select the versions in [`ResolveOptions::locked_packages`] and never look for
+    /// alternatives in the index. The metadata of the locked packages is taken from
+    /// [`PinnedPackage::metadata`] or from the cache, it is never downloaded or built. Resolution
+    /// fails if a package is required that is not locked, if its metadata is not available, or if
+    /// the locked versions do not satisfy the requirements. [`ResolveOptions::favored_packages`]
+    /// are ignored. This can be used to verify that a lock file is still consistent. By default
+    /// this is disabled.
+    pub frozen: bool,
+
+    /// Defines what happens when an extra is requested that a package does not provide. By
+    /// default a warning is logged and the extra is ignored.
+    pub unknown_extras: UnknownExtraResolution,
+
+    /// Defines whether the highest or the lowest compatible versions are selected. By default the
+    /// highest versions are selected.
+    pub resolution_strategy: ResolutionStrategy,
+
+    /// Packages that are never part of the resolution, e.g. because they are provided by the
+    /// environment. Requirements on these packages are dropped, which also leaves out the
+    /// dependencies they would have introduced. Directly requesting an excluded package is an
+    /// error, see [`super::ResolveError::ExcludedPackageRequested`]. The build environments of
+    /// sdists are resolved without exclusions. By default no packages are excluded.
+    pub exclude: HashSet<NormalizedPackageName>,
 }
 
 impl Default for ResolveOptions {
@@ -232,12 +372,26 @@ impl Default for ResolveOptions {
             sdist_resolution: SDistResolution::default(),
             python_location: PythonLocation::default(),
             clean_env: false,
+            clean_env_allowlist: Vec::new(),
             on_wheel_build_failure: OnWheelBuildFailure::default(),
             pre_release_resolution: PreReleaseResolution::default(),
             max_concurrent_tasks: Arc::new(Semaphore::new(30)),
             locked_packages: HashMap::default(),
             favored_packages: HashMap::default(),
             env_variables: HashMap::default(),
+            allow_yanked_if_pinned: false,
+            skip_sdist_fallback: false,
+            retain_metadata: false,
+            build_timeout: None,
+            build_dir: None,
+            build_frontend: BuildFrontend::default(),
+            build_log_limit: None,
+            prefetch: false,
+            no_deps: false,
+            frozen: false,
+            unknown_extras: UnknownExtraResolution::default(),
+            resolution_strategy: ResolutionStrategy::default(),
+            exclude: HashSet::default(),
         }
     }
 }
@@ -280,4 +434,116 @@ impl ResolveOptions {
             ..self
         }
     }
+
+    /// Create a new instance of `ResolveOptions` with the given `clean_env_allowlist`. See
+    /// [`ResolveOptions::clean_env_allowlist`].
+    pub fn with_clean_env_allowlist(self, clean_env_allowlist: Vec<String>) -> Self {
+        Self {
+            clean_env_allowlist,
+            ..self
+        }
+    }
+
+    /// Create a new instance of `ResolveOptions` that allows (or disallows) the selection of
+    /// yanked versions that are explicitly pinned.
+    pub fn with_allow_yanked_if_pinned(self, allow_yanked_if_pinned: bool) -> Self {
+        Self {
+            allow_yanked_if_pinned,
+            ..self
+        }
+    }
+
+    /// Create a new instance of `ResolveOptions` that never falls back to sdists to obtain the
+    /// metadata of a version that has compatible wheels. See
+    /// [`ResolveOptions::skip_sdist_fallback`].
+    pub fn with_skip_sdist_fallback(self, skip_sdist_fallback: bool) -> Self {
+        Self {
+            skip_sdist_fallback,
+            ..self
+        }
+    }
+
+    /// Create a new instance of `ResolveOptions` that retains (or does not retain) the metadata of
+    /// the resolved packages. See [`ResolveOptions::retain_metadata`].
+    pub fn with_retain_metadata(self, retain_metadata: bool) -> Self {
+        Self {
+            retain_metadata,
+            ..self
+        }
+    }
+
+    /// Create a new instance of `ResolveOptions` with the given `build_timeout`. See
+    /// [`ResolveOptions::build_timeout`].
+    pub fn with_build_timeout(self, build_timeout: Option<Duration>) -> Self {
+        Self {
+            build_timeout,
+            ..self
+        }
+    }
+
+    /// Create a new instance of `ResolveOptions` with the given `build_dir`. See
+    /// [`ResolveOptions::build_dir`].
+    pub fn with_build_dir(self, build_dir: Option<PathBuf>) -> Self {
+        Self { build_dir, ..self }
+    }
+
+    /// Create a new instance of `ResolveOptions` with the given `build_frontend`. See
+    /// [`ResolveOptions::build_frontend`].
+    pub fn with_build_frontend(self, build_frontend: BuildFrontend) -> Self {
+        Self {
+            build_frontend,
+            ..self
+        }
+    }
+
+    /// Create a new instance of `ResolveOptions` with the given `build_log_limit`. See
+    /// [`ResolveOptions::build_log_limit`].
+    pub fn with_build_log_limit(self, build_log_limit: Option<usize>) -> Self {
+        Self {
+            build_log_limit,
+            ..self
+        }
+    }
+
+    /// Create a new instance of `ResolveOptions` with the given `prefetch`. See
+    /// [`ResolveOptions::prefetch`].
+    pub fn with_prefetch(self, prefetch: bool) -> Self {
+        Self { prefetch, ..self }
+    }
+
+    /// Create a new instance of `ResolveOptions` with the given `no_deps`. See
+    /// [`ResolveOptions::no_deps`].
+    pub fn with_no_deps(self, no_deps: bool) -> Self {
+        Self { no_deps, ..self }
+    }
+
+    /// Create a new instance of `ResolveOptions` with the given `frozen`. See
+    /// [`ResolveOptions::frozen`].
+    pub fn with_frozen(self, frozen: bool) -> Self {
+        Self { frozen, ..self }
+    }
+
+    /// Create a new instance of `ResolveOptions` with the given `unknown_extras`. See
+    /// [`ResolveOptions::unknown_extras`].
+    pub fn with_unknown_extras(self, unknown_extras: UnknownExtraResolution) -> Self {
+        Self {
+            unknown_extras,
+            ..self
+        }
+    }
+
+    /// Create a new instance of `ResolveOptions` with the given `resolution_strategy`. See
+    /// [`ResolveOptions::resolution_strategy`].
+    pub fn with_resolution_strategy(self, resolution_strategy: ResolutionStrategy) -> Self {
+        Self {
+            resolution_strategy,
+            ..self
+        }
+    }
+
+    /// Create a new instance of `ResolveOptions` with the given `exclude`. See
+    /// [`ResolveOptions::exclude`].
+    pub fn with_exclude(self, exclude: HashSet<NormalizedPackageName>) -> Self {
+        Self { exclude, ..self }
+    }
 }
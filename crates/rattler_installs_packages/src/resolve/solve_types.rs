@@ -1 +1,153 @@
+use crate::types::{Extra, NormalizedPackageName};
+use pep440_rs::Version;
+use pep508_rs::Requirement;
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
 
+/// A single edge in the [`ResolvedGraph`], describing why a package was required.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyEdge {
+    /// The name of the package that is required.
+    pub dependency: NormalizedPackageName,
+
+    /// The requirement that introduced the dependency, as specified by the user for root edges or
+    /// as specified in the metadata of the dependent package.
+    pub requirement: Requirement,
+
+    /// The extra of the dependent package through which this dependency was activated. `None` if
+    /// the dependency is not conditional on an extra.
+    pub via_extra: Option<Extra>,
+}
+
+/// The dependency graph of a resolution, see [`super::resolve_with_graph`].
+///
+/// The requirements passed to the solver are represented as edges from a synthetic root node.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolvedGraph {
+    /// The edges from the synthetic root node, one for each requirement passed to the solver.
+    pub root: Vec<DependencyEdge>,
+
+    /// The dependency edges of each pinned package.
+    pub edges: HashMap<NormalizedPackageName, Vec<DependencyEdge>>,
+}
+
+impl ResolvedGraph {
+    /// Returns the dependency edges of the given package, or an empty slice if the package has no
+    /// dependencies or is not part of the resolution.
+    pub fn dependencies(&self, name: &NormalizedPackageName) -> &[DependencyEdge] {
+        self.edges.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the edges that point to the given package, together with the name of the dependent
+    /// package. `None` is used for the synthetic root node.
+    pub fn dependents<'a>(
+        &'a self,
+        name: &'a NormalizedPackageName,
+    ) -> impl Iterator<Item = (Option<&'a NormalizedPackageName>, &'a DependencyEdge)> + 'a {
+        let root = self.root.iter().map(|edge| (None, edge));
+        let edges = self
+            .edges
+            .iter()
+            .flat_map(|(from, edges)| edges.iter().map(move |edge| (Some(from), edge)));
+        root.chain(edges)
+            .filter(move |(_, edge)| &edge.dependency == name)
+    }
+}
+
+/// The reason a version of a package was not selected by the solver, see
+/// [`ResolutionExplanation`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExclusionReason {
+    /// All artifacts of the version are yanked, with the reason given by the index if any.
+    Yanked(Option<String>),
+
+    /// None of the artifacts of the version support the python interpreter.
+    RequiresPython {
+        /// The `requires_python` specifiers of the artifacts.
+        requires_python: String,
+        /// The version of the python interpreter.
+        python_version: String,
+    },
+
+    /// None of the artifacts of the version can be used, e.g. because their tags are not
+    /// compatible with the python interpreter or only sdists are available while sdists are not
+    /// allowed. Contains a description of the problem.
+    IncompatibleArtifacts(String),
+
+    /// The version is a pre-release and pre-releases are not allowed for the package.
+    PreRelease,
+
+    /// The version is available but could not be selected because it does not satisfy the
+    /// requirements of the resolution, or because its own dependencies conflict with them.
+    Conflict,
+}
+
+impl Display for ExclusionReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExclusionReason::Yanked(Some(reason)) => write!(f, "it is yanked: {reason}"),
+            ExclusionReason::Yanked(None) => write!(f, "it is yanked"),
+            ExclusionReason::RequiresPython {
+                requires_python,
+                python_version,
+            } => write!(
+                f,
+                "it requires python {requires_python} but the python version is {python_version}"
+            ),
+            ExclusionReason::IncompatibleArtifacts(reason) => write!(f, "{reason}"),
+            ExclusionReason::PreRelease => {
+                write!(f, "it is a pre-release and pre-releases are not allowed")
+            }
+            ExclusionReason::Conflict => {
+                write!(f, "it conflicts with the requirements of the resolution")
+            }
+        }
+    }
+}
+
+/// A version of a package that is higher than the selected version, together with the reason it
+/// was not selected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExcludedVersion {
+    /// The version that was not selected.
+    pub version: Version,
+
+    /// Why the version was not selected.
+    pub reason: ExclusionReason,
+}
+
+/// Explains why the solver did not select higher versions of the packages in a resolution, see
+/// [`super::resolve_with_explanation`].
+///
+/// Only packages from an index are explained, packages that were requested by direct url have a
+/// single candidate.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolutionExplanation {
+    /// The versions higher than the selected version of each pinned package, ordered from highest
+    /// to lowest.
+    pub excluded: HashMap<NormalizedPackageName, Vec<ExcludedVersion>>,
+}
+
+impl ResolutionExplanation {
+    /// Returns the versions of the given package that are higher than the selected version, or an
+    /// empty slice if the selected version is the highest available version or the package is not
+    /// part of the resolution.
+    pub fn excluded_versions(&self, name: &NormalizedPackageName) -> &[ExcludedVersion] {
+        self.excluded.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the reason the given version of a package was not selected, if it is higher than
+    /// the selected version.
+    pub fn reason(
+        &self,
+        name: &NormalizedPackageName,
+        version: &Version,
+    ) -> Option<&ExclusionReason> {
+        self.excluded_versions(name)
+            .iter()
+            .find(|excluded| &excluded.version == version)
+            .map(|excluded| &excluded.reason)
+    }
+}
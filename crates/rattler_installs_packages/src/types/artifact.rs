@@ -1,7 +1,8 @@
 use super::artifact_name::InnerAsArtifactName;
 use crate::resolve::PypiVersion;
-use crate::types::SourceArtifactName;
+use crate::types::{SourceArtifactName, WheelCoreMetadata};
 use crate::utils::ReadAndSeek;
+use rattler_digest::{Sha256, Sha256Hash};
 use std::path::Path;
 
 /// Trait to implement if it is a type that has an [`super::artifact_name::ArtifactName`]
@@ -47,6 +48,14 @@ pub trait ArtifactFromSource: HasArtifactName + Sync {
     /// that will we be used for hashing
     fn try_get_bytes(&self) -> Result<Vec<u8>, std::io::Error>;
 
+    /// Returns the sha256 hash of the bytes returned by [`ArtifactFromSource::try_get_bytes`].
+    /// Implementations can override this to hash the artifact without reading it into memory.
+    fn try_get_hash(&self) -> Result<Sha256Hash, std::io::Error> {
+        Ok(rattler_digest::compute_bytes_digest::<Sha256>(
+            self.try_get_bytes()?,
+        ))
+    }
+
     /// Distribution Name
     fn distribution_name(&self) -> String;
 
@@ -59,6 +68,24 @@ pub trait ArtifactFromSource: HasArtifactName + Sync {
     /// Read the build system info from the pyproject.toml
     fn read_pyproject_toml(&self) -> Result<pyproject_toml::PyProjectToml, ReadPyProjectError>;
 
+    /// Reads the metadata from the `[project]` table of the pyproject.toml without building the
+    /// artifact. Returns `None` if the metadata is not static.
+    ///
+    /// See [`WheelCoreMetadata::from_pyproject_toml`].
+    fn pep621_metadata(&self) -> Result<Option<(Vec<u8>, WheelCoreMetadata)>, ReadPyProjectError> {
+        let pyproject_toml = match self.read_pyproject_toml() {
+            Ok(pyproject_toml) => pyproject_toml,
+            Err(ReadPyProjectError::NoPyProjectTomlFound) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        WheelCoreMetadata::from_pyproject_toml(&pyproject_toml).map_err(|e| {
+            ReadPyProjectError::PyProjectTomlParseError(format!(
+                "invalid [project] table in pyproject.toml: {}",
+                e
+            ))
+        })
+    }
+
     /// extract to a specific location
     /// for sdist we unpack it
     /// for stree we move it
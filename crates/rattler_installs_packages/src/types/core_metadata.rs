@@ -6,12 +6,24 @@ use crate::{
     types::Extra, types::PackageName, types::ParsePackageNameError, types::RFC822ish,
     types::Version, types::VersionSpecifiers,
 };
+use indexmap::IndexMap;
 use once_cell::sync::Lazy;
 use pep440_rs::Pep440Error;
-use pep508_rs::Requirement;
-use std::{collections::HashSet, str::FromStr};
+use pep508_rs::{MarkerEnvironment, Requirement};
+use std::{collections::HashSet, fmt::Write, str::FromStr};
 use thiserror::Error;
 
+/// The metadata fields we use to resolve, as the name of the core metadata field and the name of
+/// the corresponding field in the `[project]` table of a `pyproject.toml`. If any of these are
+/// marked as dynamic, in a PKG-INFO or a `pyproject.toml`, the artifact has to be built to know
+/// their values.
+const REQUIRED_STATIC_FIELDS: [(&str, &str); 4] = [
+    ("Version", "version"),
+    ("Requires-Python", "requires-python"),
+    ("Requires-Dist", "dependencies"),
+    ("Provides-Extra", "optional-dependencies"),
+];
+
 /// Holds the parsed PKG-INFO file.
 pub struct PackageInfo {
     /// The parsed PKG-INFO file.
@@ -31,9 +43,43 @@ impl PackageInfo {
     pub fn new(parsed: RFC822ish) -> Self {
         Self { parsed }
     }
+
+    /// Returns the fields that are marked as `Dynamic` (PEP 643). The values of these fields in
+    /// the PKG-INFO of an sdist are not guaranteed to match the metadata of the built wheel.
+    pub fn dynamic_fields(&self) -> impl Iterator<Item = &str> {
+        self.parsed
+            .fields
+            .get("dynamic")
+            .into_iter()
+            .flatten()
+            .map(|field| field.trim())
+    }
+
+    /// Converts the PKG-INFO of an sdist into the metadata of the package. The values in a
+    /// PKG-INFO are only reliable if it implements PEP 643 and none of the fields we use to
+    /// resolve are marked as `Dynamic`, otherwise an error is returned and the sdist has to be
+    /// built to get its metadata. The same applies to the PEP 658 `.metadata` file of an sdist,
+    /// which holds the contents of its PKG-INFO.
+    pub fn into_sdist_metadata(self) -> Result<WheelCoreMetadata, WheelCoreMetaDataError> {
+        if let Some(field) = self.dynamic_fields().find(|field| {
+            REQUIRED_STATIC_FIELDS
+                .iter()
+                .any(|(required, _)| required.eq_ignore_ascii_case(field))
+        }) {
+            return Err(WheelCoreMetaDataError::DynamicField(field.to_owned()));
+        }
+
+        let metadata = WheelCoreMetadata::try_from(self)?;
+        if !metadata.metadata_version.implements_pep643() {
+            return Err(WheelCoreMetaDataError::PredatesPep643(
+                metadata.metadata_version.0,
+            ));
+        }
+        Ok(metadata)
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 
 /// The core metadata of a wheel.
 pub struct WheelCoreMetadata {
@@ -50,6 +96,57 @@ pub struct WheelCoreMetadata {
     pub requires_python: Option<VersionSpecifiers>,
     /// Extras provided by this distribution
     pub extras: HashSet<Extra>,
+    /// Other distributions that are contained in this distribution, e.g. `OtherProject (3.4)` or
+    /// `virtual_package; python_version >= "3.4"`. Matches the `Provides-Dist` field. The entries
+    /// are stored verbatim because they do not have to be valid PEP 508 requirements.
+    pub provides_dist: Vec<String>,
+    /// Distributions that are made obsolete by this distribution, e.g. `OtherProject (<3.0)`.
+    /// Matches the `Obsoletes-Dist` field. The entries are stored verbatim.
+    pub obsoletes_dist: Vec<String>,
+    /// The free-form license text of the `License` field. Superseded by `License-Expression` in
+    /// metadata version 2.4.
+    pub license: Option<String>,
+    /// The SPDX license expression of the `License-Expression` field (PEP 639), e.g.
+    /// `MIT AND Apache-2.0`.
+    pub license_expression: Option<String>,
+    /// The paths of the license files relative to the `.dist-info/licenses` directory. Matches the
+    /// `License-File` field (PEP 639).
+    pub license_files: Vec<String>,
+    /// A one-line summary of what the distribution does. Matches the `Summary` field.
+    pub summary: Option<String>,
+    /// The long description of the distribution. This is the body of the metadata file, or the
+    /// `Description` field for older metadata.
+    pub description: Option<String>,
+    /// The markup of the description, e.g. `text/markdown`. Matches the
+    /// `Description-Content-Type` field.
+    pub description_content_type: Option<String>,
+    /// The keywords of the distribution. Matches the `Keywords` field, which is split on commas,
+    /// or on whitespace if it does not contain any.
+    pub keywords: Vec<String>,
+    /// The URL of the homepage of the distribution. Matches the `Home-page` field.
+    pub home_page: Option<String>,
+    /// The URL from which the distribution can be downloaded. Matches the `Download-URL` field.
+    pub download_url: Option<String>,
+    /// The name of the author. Matches the `Author` field.
+    pub author: Option<String>,
+    /// The email address of the author, possibly including a name, e.g.
+    /// `Jane Doe <jane@example.com>`. Matches the `Author-email` field.
+    pub author_email: Option<String>,
+    /// The name of the maintainer. Matches the `Maintainer` field.
+    pub maintainer: Option<String>,
+    /// The email address of the maintainer. Matches the `Maintainer-email` field.
+    pub maintainer_email: Option<String>,
+    /// The trove classifiers of the distribution. Matches the `Classifier` field.
+    pub classifiers: Vec<String>,
+    /// The platforms the distribution supports. Matches the `Platform` field.
+    pub platforms: Vec<String>,
+    /// Additional URLs of the distribution by their label, e.g. `Documentation` or `Changelog`.
+    /// Matches the `Project-URL` field, in the order in which they occur.
+    pub project_urls: IndexMap<String, String>,
+    /// The fields that are present even though they were introduced in a later version than the
+    /// declared `Metadata-Version`, e.g. `License-Expression` in version 2.1 metadata. These
+    /// fields are still used, many packages declare an outdated metadata version.
+    pub unsupported_fields: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -70,6 +167,68 @@ impl MetadataVersion {
         }
         true
     }
+
+    /// Returns true if this metadata version is at least `major.minor`.
+    fn is_at_least(&self, major: u64, minor: u64) -> bool {
+        let release = &self.0.release;
+        let version = (
+            release.first().copied().unwrap_or(0),
+            release.get(1).copied().unwrap_or(0),
+        );
+        version >= (major, minor)
+    }
+
+    /// The `Requires-Dist` field was introduced in metadata version 1.2.
+    pub fn supports_requires_dist(&self) -> bool {
+        self.is_at_least(1, 2)
+    }
+
+    /// The `Provides-Extra` field was introduced in metadata version 2.1.
+    pub fn supports_provides_extra(&self) -> bool {
+        self.is_at_least(2, 1)
+    }
+
+    /// The `Dynamic` field was introduced in metadata version 2.2 (PEP 643).
+    pub fn supports_dynamic(&self) -> bool {
+        self.is_at_least(2, 2)
+    }
+
+    /// The `License-Expression` field was introduced in metadata version 2.4 (PEP 639).
+    pub fn supports_license_expression(&self) -> bool {
+        self.is_at_least(2, 4)
+    }
+}
+
+/// Checks whether a metadata version supports a field.
+type SupportsField = fn(&MetadataVersion) -> bool;
+
+/// The fields that were introduced after metadata version 1.0, together with the predicate that
+/// checks whether a metadata version supports them.
+const VERSIONED_FIELDS: [(&str, SupportsField); 8] = [
+    ("Requires-Dist", MetadataVersion::supports_requires_dist),
+    ("Requires-Python", MetadataVersion::supports_requires_dist),
+    ("Provides-Dist", MetadataVersion::supports_requires_dist),
+    ("Obsoletes-Dist", MetadataVersion::supports_requires_dist),
+    ("Provides-Extra", MetadataVersion::supports_provides_extra),
+    ("Dynamic", MetadataVersion::supports_dynamic),
+    (
+        "License-Expression",
+        MetadataVersion::supports_license_expression,
+    ),
+    ("License-File", MetadataVersion::supports_license_expression),
+];
+
+/// Returns the fields that are present in the metadata even though they were introduced in a
+/// later version than the declared `Metadata-Version`.
+fn unsupported_fields(metadata_version: &MetadataVersion, parsed: &RFC822ish) -> Vec<String> {
+    VERSIONED_FIELDS
+        .iter()
+        .filter(|(field, is_supported)| {
+            parsed.fields.contains_key(&field.to_ascii_lowercase())
+                && !is_supported(metadata_version)
+        })
+        .map(|(field, _)| field.to_string())
+        .collect()
 }
 
 #[derive(Debug, Error)]
@@ -96,6 +255,12 @@ pub enum WheelCoreMetaDataError {
     #[error("unsupported METADATA version {0}")]
     UnsupportedVersion(Version),
 
+    #[error("{0} is marked as dynamic, the sdist must be built")]
+    DynamicField(String),
+
+    #[error("Metadata-Version {0} predates PEP 643, the sdist must be built")]
+    PredatesPep643(Version),
+
     #[error(transparent)]
     InvalidPackageName(#[from] ParsePackageNameError),
 
@@ -106,6 +271,97 @@ pub enum WheelCoreMetaDataError {
     FailedToParse(String),
 }
 
+impl WheelCoreMetadata {
+    /// Returns the license of the distribution. The SPDX `License-Expression` is preferred, the
+    /// free-form `License` field is used for older metadata. Returns `None` if neither is set.
+    pub fn effective_license(&self) -> Option<&str> {
+        self.license_expression
+            .as_deref()
+            .or(self.license.as_deref())
+    }
+
+    /// Returns the requirements from `Requires-Dist` that are active in the given environment with
+    /// the given extras enabled. Requirements without a marker are always active.
+    ///
+    /// This is the same evaluation the resolver uses to determine the dependencies of a package.
+    pub fn runtime_requirements<'a>(
+        &'a self,
+        markers: &'a MarkerEnvironment,
+        extras: &'a [Extra],
+    ) -> impl Iterator<Item = &'a Requirement> + 'a {
+        let extras = extras.iter().map(Extra::as_str).collect::<Vec<_>>();
+        self.requires_dist.iter().filter(move |requirement| {
+            requirement
+                .marker
+                .as_ref()
+                .map_or(true, |marker| marker.evaluate(markers, &extras))
+        })
+    }
+
+    /// Constructs the metadata from the `[project]` table of a `pyproject.toml` as described in
+    /// [PEP 621](https://peps.python.org/pep-0621/), this allows reading the metadata of a source
+    /// artifact without invoking its build backend.
+    ///
+    /// Returns `None` if there is no `[project]` table or if any of the fields we rely on is marked
+    /// as dynamic, in that case the artifact has to be built to get the metadata. Otherwise
+    /// returns the constructed `METADATA` file together with its parsed form.
+    pub fn from_pyproject_toml(
+        pyproject_toml: &pyproject_toml::PyProjectToml,
+    ) -> Result<Option<(Vec<u8>, Self)>, WheelCoreMetaDataError> {
+        let Some(project) = &pyproject_toml.project else {
+            return Ok(None);
+        };
+
+        if let Some(field) = project.dynamic.iter().flatten().find(|field| {
+            REQUIRED_STATIC_FIELDS
+                .iter()
+                .any(|(_, required)| required.eq_ignore_ascii_case(field))
+        }) {
+            tracing::debug!(
+                "{field} is marked as dynamic in the pyproject.toml of {}, the metadata must be built",
+                project.name
+            );
+            return Ok(None);
+        }
+        let Some(version) = &project.version else {
+            return Ok(None);
+        };
+
+        // Construct the metadata as a build backend would
+        let mut metadata = String::new();
+        writeln!(metadata, "Metadata-Version: 2.1").unwrap();
+        writeln!(metadata, "Name: {}", project.name).unwrap();
+        writeln!(metadata, "Version: {version}").unwrap();
+        if let Some(requires_python) = &project.requires_python {
+            writeln!(metadata, "Requires-Python: {requires_python}").unwrap();
+        }
+        for requirement in project.dependencies.iter().flatten() {
+            writeln!(metadata, "Requires-Dist: {requirement}").unwrap();
+        }
+        for (extra, requirements) in project.optional_dependencies.iter().flatten() {
+            writeln!(metadata, "Provides-Extra: {extra}").unwrap();
+            for requirement in requirements {
+                let mut requirement = requirement.clone();
+                match requirement.marker.take() {
+                    Some(marker) => writeln!(
+                        metadata,
+                        "Requires-Dist: {requirement} ; ({marker}) and extra == \"{extra}\""
+                    ),
+                    None => writeln!(
+                        metadata,
+                        "Requires-Dist: {requirement} ; extra == \"{extra}\""
+                    ),
+                }
+                .unwrap();
+            }
+        }
+
+        let bytes = metadata.into_bytes();
+        let metadata = WheelCoreMetadata::try_from(bytes.as_slice())?;
+        Ok(Some((bytes, metadata)))
+    }
+}
+
 impl TryFrom<&[u8]> for WheelCoreMetadata {
     type Error = WheelCoreMetaDataError;
 
@@ -119,6 +375,7 @@ impl TryFrom<PackageInfo> for WheelCoreMetadata {
 
     fn try_from(value: PackageInfo) -> Result<Self, Self::Error> {
         let (name, version, metadata_version, mut parsed) = parse_common(value)?;
+        let unsupported_fields = unsupported_fields(&metadata_version, &parsed);
 
         let mut requires_dist = Vec::new();
         for req_str in parsed.take_all("Requires-Dist").into_iter() {
@@ -147,6 +404,30 @@ impl TryFrom<PackageInfo> for WheelCoreMetadata {
             );
         }
 
+        let provides_dist = parsed.take_all("Provides-Dist");
+        let obsoletes_dist = parsed.take_all("Obsoletes-Dist");
+
+        let license = take_first(&mut parsed, &name, "License");
+        let license_expression = take_first(&mut parsed, &name, "License-Expression");
+        let license_files = parsed.take_all("License-File");
+
+        let summary = take_first(&mut parsed, &name, "Summary");
+        let description = match parsed.body.take() {
+            Some(body) if !body.trim().is_empty() => Some(body),
+            _ => take_first(&mut parsed, &name, "Description"),
+        };
+        let description_content_type = take_first(&mut parsed, &name, "Description-Content-Type");
+        let keywords = parse_keywords(parsed.take_all("Keywords"));
+        let home_page = take_first(&mut parsed, &name, "Home-page");
+        let download_url = take_first(&mut parsed, &name, "Download-URL");
+        let author = take_first(&mut parsed, &name, "Author");
+        let author_email = take_first(&mut parsed, &name, "Author-email");
+        let maintainer = take_first(&mut parsed, &name, "Maintainer");
+        let maintainer_email = take_first(&mut parsed, &name, "Maintainer-email");
+        let classifiers = parsed.take_all("Classifier");
+        let platforms = parsed.take_all("Platform");
+        let project_urls = parse_project_urls(&name, parsed.take_all("Project-URL"));
+
         Ok(WheelCoreMetadata {
             name,
             version,
@@ -154,8 +435,83 @@ impl TryFrom<PackageInfo> for WheelCoreMetadata {
             requires_dist,
             requires_python,
             extras,
+            provides_dist,
+            obsoletes_dist,
+            license,
+            license_expression,
+            license_files,
+            summary,
+            description,
+            description_content_type,
+            keywords,
+            home_page,
+            download_url,
+            author,
+            author_email,
+            maintainer,
+            maintainer_email,
+            classifiers,
+            platforms,
+            project_urls,
+            unsupported_fields,
+        })
+    }
+}
+
+/// Splits the values of the `Keywords` field into the individual keywords. The spec prescribes a
+/// comma separated list, but older metadata often separates the keywords with whitespace.
+fn parse_keywords(values: Vec<String>) -> Vec<String> {
+    values
+        .iter()
+        .flat_map(|value| {
+            if value.contains(',') {
+                value.split(',').collect::<Vec<_>>()
+            } else {
+                value.split_whitespace().collect()
+            }
         })
+        .map(str::trim)
+        .filter(|keyword| !keyword.is_empty())
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// Parses the `Project-URL` fields, which have the form `label, url`. Entries without a label are
+/// ignored, if a label occurs multiple times the first URL is used.
+fn parse_project_urls(name: &PackageName, values: Vec<String>) -> IndexMap<String, String> {
+    let mut project_urls = IndexMap::new();
+    for value in values {
+        let Some((label, url)) = value.split_once(',') else {
+            tracing::warn!(
+                "ignoring Project-URL of {}: {value}, expected 'label, url'",
+                name.as_source_str()
+            );
+            continue;
+        };
+        project_urls
+            .entry(label.trim().to_owned())
+            .or_insert_with(|| url.trim().to_owned());
+    }
+    project_urls
+}
+
+/// Takes the value of a field that should only occur once. Unlike [`RFC822ish::maybe_take`] this
+/// does not fail if the field occurs multiple times, the first value is used instead. Use this for
+/// informational fields that do not affect installation. Empty values are ignored.
+fn take_first(parsed: &mut RFC822ish, name: &PackageName, key: &str) -> Option<String> {
+    let mut values = parsed.take_all(key);
+    if values.len() > 1 {
+        tracing::warn!(
+            "the metadata of {} contains multiple {key} fields, using the first one",
+            name.as_source_str()
+        );
+    }
+    if values.is_empty() {
+        return None;
     }
+    let value = values.swap_remove(0);
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_owned())
 }
 
 fn parse_common(
@@ -209,3 +565,300 @@ fn parse_common(
         parsed,
     ))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{artifacts::Wheel, python_env::Pep508EnvMakers};
+
+    #[test]
+    fn test_runtime_requirements() {
+        let metadata = WheelCoreMetadata::try_from(
+            b"Metadata-Version: 2.1\n\
+            Name: foo\n\
+            Version: 1.0\n\
+            Provides-Extra: socks\n\
+            Requires-Dist: idna\n\
+            Requires-Dist: colorama ; sys_platform == \"win32\"\n\
+            Requires-Dist: PySocks ; extra == \"socks\"\n\
+            \n"
+            .as_slice(),
+        )
+        .unwrap();
+
+        let names = |target: &str, extras: &[Extra]| {
+            let markers = Pep508EnvMakers::from_target(target).unwrap();
+            metadata
+                .runtime_requirements(&markers, extras)
+                .map(|requirement| requirement.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names("linux-x86_64-cpython-3.11", &[]), ["idna"]);
+        assert_eq!(
+            names("windows-x86_64-cpython-3.11", &[]),
+            ["idna", "colorama"]
+        );
+        assert_eq!(
+            names("linux-x86_64-cpython-3.11", &["socks".parse().unwrap()]),
+            ["idna", "PySocks"]
+        );
+    }
+
+    #[test]
+    fn test_metadata_version_capabilities() {
+        let version = |version: &str| MetadataVersion(Version::from_str(version).unwrap());
+
+        assert!(!version("1.0").supports_requires_dist());
+        assert!(!version("1.1").supports_requires_dist());
+        assert!(version("1.2").supports_requires_dist());
+        assert!(!version("2.0").supports_provides_extra());
+        assert!(version("2.1").supports_provides_extra());
+        assert!(!version("2.1").supports_dynamic());
+        assert!(version("2.2").supports_dynamic());
+        assert!(!version("2.3").supports_license_expression());
+        assert!(version("2.4").supports_license_expression());
+        assert!(version("2").supports_requires_dist());
+        assert!(!version("2").supports_provides_extra());
+
+        // Fields that the declared metadata version does not support are still parsed
+        let metadata = WheelCoreMetadata::try_from(
+            b"Metadata-Version: 1.0\n\
+            Name: foo\n\
+            Version: 1.0\n\
+            Requires-Dist: idna\n\
+            \n"
+            .as_slice(),
+        )
+        .unwrap();
+        assert_eq!(metadata.requires_dist[0].name, "idna");
+        assert_eq!(
+            metadata.unsupported_fields,
+            vec!["Requires-Dist".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_license_expression() {
+        let metadata = WheelCoreMetadata::try_from(
+            b"Metadata-Version: 2.4\n\
+            Name: foo\n\
+            Version: 1.0\n\
+            License-Expression: MIT AND Apache-2.0\n\
+            License-File: LICENSE-MIT\n\
+            License-File: licenses/LICENSE-APACHE\n\
+            \n"
+            .as_slice(),
+        )
+        .unwrap();
+        assert_eq!(
+            metadata.license_expression.as_deref(),
+            Some("MIT AND Apache-2.0")
+        );
+        assert_eq!(
+            metadata.license_files,
+            ["LICENSE-MIT", "licenses/LICENSE-APACHE"]
+        );
+        assert_eq!(metadata.license, None);
+        assert_eq!(metadata.effective_license(), Some("MIT AND Apache-2.0"));
+
+        // Older metadata only has the free-form license
+        let metadata = WheelCoreMetadata::try_from(
+            b"Metadata-Version: 2.1\n\
+            Name: foo\n\
+            Version: 1.0\n\
+            License: BSD\n\
+            \n"
+            .as_slice(),
+        )
+        .unwrap();
+        assert_eq!(metadata.license_expression, None);
+        assert!(metadata.license_files.is_empty());
+        assert_eq!(metadata.effective_license(), Some("BSD"));
+    }
+
+    #[test]
+    fn test_provides_and_obsoletes_dist() {
+        let metadata = WheelCoreMetadata::try_from(
+            b"Metadata-Version: 1.2\n\
+            Name: foo\n\
+            Version: 1.0\n\
+            Provides-Dist: OtherProject\n\
+            Provides-Dist: AnotherProject (3.4)\n\
+            Provides-Dist: virtual_package; python_version >= \"3.4\"\n\
+            Obsoletes-Dist: Gorgon\n\
+            Obsoletes-Dist: OtherProject (<3.0)\n\
+            \n"
+            .as_slice(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            metadata.provides_dist,
+            [
+                "OtherProject",
+                "AnotherProject (3.4)",
+                "virtual_package; python_version >= \"3.4\""
+            ]
+        );
+        assert_eq!(metadata.obsoletes_dist, ["Gorgon", "OtherProject (<3.0)"]);
+
+        // Both fields are optional
+        let metadata = WheelCoreMetadata::try_from(
+            b"Metadata-Version: 2.1\nName: foo\nVersion: 1.0\n\n".as_slice(),
+        )
+        .unwrap();
+        assert!(metadata.provides_dist.is_empty());
+        assert!(metadata.obsoletes_dist.is_empty());
+    }
+
+    #[test]
+    fn test_informational_fields() {
+        let wheel = Wheel::from_path(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../../test-data/wheels/miniblack-23.1.0-py3-none-any.whl"),
+            &"miniblack".parse().unwrap(),
+        )
+        .unwrap();
+        let (_, metadata) = wheel.metadata().unwrap();
+
+        assert_eq!(
+            metadata.summary.as_deref(),
+            Some("The uncompromising code formatter.")
+        );
+        assert_eq!(
+            metadata.author_email.as_deref(),
+            Some("Łukasz Langa <lukasz@langa.pl>")
+        );
+        assert_eq!(metadata.author, None);
+        assert_eq!(metadata.maintainer, None);
+        assert_eq!(metadata.home_page, None);
+        assert_eq!(
+            metadata.description_content_type.as_deref(),
+            Some("text/markdown")
+        );
+        assert!(metadata.description.is_some());
+        assert_eq!(
+            metadata.keywords,
+            [
+                "automation",
+                "autopep8",
+                "formatter",
+                "gofmt",
+                "pyfmt",
+                "rustfmt",
+                "yapf"
+            ]
+        );
+        assert!(metadata
+            .classifiers
+            .contains(&String::from("Environment :: Console")));
+        assert_eq!(
+            metadata.project_urls.iter().collect::<Vec<_>>(),
+            [
+                (
+                    &String::from("Changelog"),
+                    &String::from("https://github.com/psf/black/blob/main/CHANGES.md")
+                ),
+                (
+                    &String::from("Homepage"),
+                    &String::from("https://github.com/psf/black")
+                ),
+            ]
+        );
+
+        // Older metadata has the description in a field and separates keywords with whitespace
+        let metadata = WheelCoreMetadata::try_from(
+            b"Metadata-Version: 1.1\n\
+            Name: foo\n\
+            Version: 1.0\n\
+            Home-page: https://example.com\n\
+            Author: Jane Doe\n\
+            Maintainer: John Doe\n\
+            Maintainer-email: john@example.com\n\
+            Keywords: kalman  filters\n\
+            Platform: UNKNOWN\n\
+            Project-URL: no label\n\
+            Description: A description\n\
+            \n"
+            .as_slice(),
+        )
+        .unwrap();
+        assert_eq!(metadata.home_page.as_deref(), Some("https://example.com"));
+        assert_eq!(metadata.author.as_deref(), Some("Jane Doe"));
+        assert_eq!(metadata.maintainer.as_deref(), Some("John Doe"));
+        assert_eq!(
+            metadata.maintainer_email.as_deref(),
+            Some("john@example.com")
+        );
+        assert_eq!(metadata.keywords, ["kalman", "filters"]);
+        assert_eq!(metadata.platforms, ["UNKNOWN"]);
+        assert!(metadata.project_urls.is_empty());
+        assert_eq!(metadata.description.as_deref(), Some("A description"));
+    }
+
+    fn pyproject_toml(path: &str) -> pyproject_toml::PyProjectToml {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data")
+            .join(path);
+        pyproject_toml::PyProjectToml::new(&fs_err::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_from_pyproject_toml() {
+        let (_, metadata) = WheelCoreMetadata::from_pyproject_toml(&pyproject_toml(
+            "stree/static_pyproject/pyproject.toml",
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(metadata.name.as_str(), "static-pyproject");
+        assert_eq!(metadata.version.to_string(), "1.2.3");
+        assert_eq!(metadata.requires_python.unwrap().to_string(), ">=3.8");
+        assert_eq!(
+            metadata
+                .extras
+                .iter()
+                .map(Extra::as_str)
+                .collect::<Vec<_>>(),
+            ["test"]
+        );
+        assert_eq!(
+            metadata
+                .requires_dist
+                .iter()
+                .map(|req| req.name.as_str())
+                .collect::<Vec<_>>(),
+            ["requests", "tomli", "pytest", "pytest-asyncio"]
+        );
+        assert_eq!(
+            metadata.requires_dist[3],
+            Requirement::from_str(
+                r#"pytest-asyncio ; (sys_platform == 'linux') and extra == "test""#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_from_pyproject_toml_dynamic() {
+        let dynamic = pyproject_toml::PyProjectToml::new(
+            r#"
+            [project]
+            name = "foo"
+            version = "1.0"
+            dynamic = ["dependencies"]
+            "#,
+        )
+        .unwrap();
+        assert!(WheelCoreMetadata::from_pyproject_toml(&dynamic)
+            .unwrap()
+            .is_none());
+
+        // Poetry projects don't have a `[project]` table
+        assert!(WheelCoreMetadata::from_pyproject_toml(&pyproject_toml(
+            "stree/dev_folder_with_rich/pyproject.toml"
+        ))
+        .unwrap()
+        .is_none());
+    }
+}
@@ -4,7 +4,7 @@ use url::Url;
 /// Specifies the PyPa `direct_url.json` format.
 /// See: <https://packaging.python.org/en/latest/specifications/direct-url-data-structure/>
 ///
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde_with::skip_serializing_none]
 pub struct DirectUrlJson {
     /// Url to the source.
@@ -17,7 +17,7 @@ pub struct DirectUrlJson {
 /// Specifies the source of a direct url.
 ///
 /// currently we do not support the deprecated `hash` field
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DirectUrlSource {
     #[serde(rename = "archive_info")]
     /// Information about the archive file.
@@ -46,14 +46,14 @@ pub enum DirectUrlSource {
 
 /// Hashes for internal archive files.
 /// multiple hashes can be included but per recommendation only sha256 should be used.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DirectUrlHashes {
     /// Sha256 hash of the archive file.
     pub sha256: String,
 }
 
 /// Name of the VCS in a DirectUrlSource
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(missing_docs)]
 pub enum DirectUrlVcs {
     #[serde(rename = "git")]
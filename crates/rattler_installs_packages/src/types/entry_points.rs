@@ -2,8 +2,13 @@
 
 use super::extra::ParseExtraError;
 use crate::types::Extra;
+use configparser::ini::Ini;
 use regex::Regex;
-use std::{collections::HashSet, str::FromStr, sync::OnceLock};
+use std::{
+    collections::{BTreeMap, HashSet},
+    str::FromStr,
+    sync::OnceLock,
+};
 use thiserror::Error;
 
 /// Entry points are a mechanism for an installed python package to declare functions that can be
@@ -32,6 +37,20 @@ pub enum ParseEntryPointError {
     ParseExtraError(#[from] ParseExtraError),
 }
 
+/// An error that might be raised when parsing an `entry_points.txt` file into [`EntryPoints`].
+#[derive(Debug, Error)]
+#[allow(missing_docs)]
+pub enum ParseEntryPointsError {
+    #[error("failed to parse entry_points.txt contents: {0}")]
+    InvalidIni(String),
+
+    #[error("missing entry point for {0}")]
+    MissingEntryPoint(String),
+
+    #[error("failed to parse entry point for {0}: {1}")]
+    InvalidEntryPoint(String, ParseEntryPointError),
+}
+
 impl EntryPoint {
     /// Parses an entry point from a string.
     ///
@@ -103,6 +122,33 @@ impl EntryPoint {
 
     /// Returns a script to launch the entry-point.
     pub fn launch_script(&self) -> String {
+        self.make_launch_script(false)
+    }
+
+    /// Returns a script to launch the entry-point that first detaches from the controlling
+    /// terminal. Before the entry-point is imported the script forks, the child starts a new
+    /// session and redirects its standard streams to `/dev/null`, and the parent exits
+    /// immediately. This only works on unix.
+    pub fn detached_launch_script(&self) -> String {
+        self.make_launch_script(true)
+    }
+
+    fn make_launch_script(&self, detach: bool) -> String {
+        // Detach before the entry point is imported, importing a GUI toolkit can already start
+        // threads or connect to the display server, which does not survive a fork.
+        let detach = if detach {
+            r##"if __name__ == "__main__":
+    import os
+    if os.fork() != 0:
+        sys.exit(0)
+    os.setsid()
+    devnull = os.open(os.devnull, os.O_RDWR)
+    for fd in range(3):
+        os.dup2(devnull, fd)
+"##
+        } else {
+            ""
+        };
         let (module, import_name) = match self.function.as_deref() {
             Some(func) => (self.module.as_str(), func),
             None => match self.module.split_once('.') {
@@ -115,13 +161,103 @@ impl EntryPoint {
             r##"# -*- coding: utf-8 -*-
 import re
 import sys
-from {module} import {import_name}
+{detach}from {module} import {import_name}
 if __name__ == "__main__":
     sys.argv[0] = re.sub(r"(-script\.pyw|\.exe)?$", "", sys.argv[0])
     sys.exit({import_name}())
 "##,
             module = module,
-            import_name = import_name
+            import_name = import_name,
+            detach = detach,
         )
     }
 }
+
+/// The entry points declared by a distribution in its `entry_points.txt` file, grouped by the
+/// section they are declared in.
+///
+/// Next to the `console_scripts` and `gui_scripts` groups, from which scripts are generated during
+/// installation, packages can declare arbitrary groups. These are commonly used to register
+/// plugins, e.g. `pytest11` for pytest plugins. For entry points in these groups
+/// [`EntryPoint::script_name`] holds the name of the entry point.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EntryPoints {
+    /// All the groups and their entry points, sorted by name.
+    pub groups: BTreeMap<String, Vec<EntryPoint>>,
+}
+
+impl EntryPoints {
+    /// Parses the contents of an `entry_points.txt` file.
+    ///
+    /// Entry points that require extras are only included if all of them are part of `extras`.
+    /// If `extras` is `None` no filtering takes place.
+    ///
+    /// Only the entry points in the `console_scripts` and `gui_scripts` groups are validated, an
+    /// invalid entry point in any of the other groups is skipped. Their format is defined by the
+    /// package that consumes them, so a malformed plugin registration should not prevent
+    /// installing the package.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use rattler_installs_packages::types::EntryPoints;
+    /// let entry_points = EntryPoints::from_ini(
+    ///     "[console_scripts]\nblack = black:patched_main\n\n[pytest11]\ncov = pytest_cov.plugin\nbroken = not an entry point\n",
+    ///     None,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(entry_points.console_scripts()[0].script_name, "black");
+    /// assert_eq!(entry_points.group("pytest11").len(), 1);
+    /// assert_eq!(entry_points.group("pytest11")[0].module, "pytest_cov.plugin");
+    ///
+    /// assert!(EntryPoints::from_ini("[console_scripts]\nbroken = not an entry point\n", None).is_err());
+    /// ```
+    pub fn from_ini(
+        contents: &str,
+        extras: Option<&HashSet<Extra>>,
+    ) -> Result<Self, ParseEntryPointsError> {
+        let sections = Ini::new_cs()
+            .read(contents.to_string())
+            .map_err(ParseEntryPointsError::InvalidIni)?;
+
+        let mut groups = BTreeMap::new();
+        for (group, entries) in sections {
+            let is_script_group = group == "console_scripts" || group == "gui_scripts";
+            let mut entry_points = Vec::new();
+            for (name, entry_point) in entries {
+                let result = match entry_point {
+                    Some(entry_point) => EntryPoint::parse(name.clone(), &entry_point, extras)
+                        .map_err(|err| ParseEntryPointsError::InvalidEntryPoint(name, err)),
+                    None => Err(ParseEntryPointsError::MissingEntryPoint(name)),
+                };
+                match result {
+                    Ok(None) => {}
+                    Ok(Some(entry_point)) => entry_points.push(entry_point),
+                    Err(err) if is_script_group => return Err(err),
+                    Err(err) => {
+                        tracing::warn!("skipping entry point in group '{group}': {err}");
+                    }
+                }
+            }
+            entry_points.sort_by(|a, b| a.script_name.cmp(&b.script_name));
+            groups.insert(group, entry_points);
+        }
+
+        Ok(Self { groups })
+    }
+
+    /// Returns the entry points of the given group, or an empty slice if the group does not exist.
+    pub fn group(&self, name: &str) -> &[EntryPoint] {
+        self.groups.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the entry points from which console scripts are generated.
+    pub fn console_scripts(&self) -> &[EntryPoint] {
+        self.group("console_scripts")
+    }
+
+    /// Returns the entry points from which GUI scripts are generated.
+    pub fn gui_scripts(&self) -> &[EntryPoint] {
+        self.group("gui_scripts")
+    }
+}
@@ -30,13 +30,14 @@ pub use direct_url_json::{DirectUrlHashes, DirectUrlJson, DirectUrlSource, Direc
 
 pub use core_metadata::{MetadataVersion, PackageInfo, WheelCoreMetaDataError, WheelCoreMetadata};
 
-pub use record::{Record, RecordEntry};
+pub use record::{Record, RecordEntry, RecordHashAlgorithm};
+pub(crate) use record::{RecordHasher, RecordHashes, RecordHashingReader};
 
 pub use package_name::{NormalizedPackageName, PackageName, ParsePackageNameError};
 
 pub use extra::Extra;
 
-pub use entry_points::{EntryPoint, ParseEntryPointError};
+pub use entry_points::{EntryPoint, EntryPoints, ParseEntryPointError, ParseEntryPointsError};
 
 pub use project_info::{ArtifactHashes, ArtifactInfo, DistInfoMetadata, Meta, ProjectInfo, Yanked};
 
@@ -22,7 +22,7 @@ pub struct ProjectInfo {
 /// Describes a single artifact that is available for download.
 #[serde_as]
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
-#[serde(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case", from = "RawArtifactInfo")]
 pub struct ArtifactInfo {
     /// Artifact name
     pub filename: ArtifactName,
@@ -45,6 +45,41 @@ pub struct ArtifactInfo {
     pub yanked: Yanked,
 }
 
+/// The raw form of an [`ArtifactInfo`] as it is sent by an index. PEP 714 renamed the
+/// `dist-info-metadata` key to `core-metadata`, some indexes send both keys. The new key is
+/// preferred if both are present.
+#[serde_as]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct RawArtifactInfo {
+    filename: ArtifactName,
+    url: url::Url,
+    is_direct_url: bool,
+    hashes: Option<ArtifactHashes>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    requires_python: Option<VersionSpecifiers>,
+    #[serde(default)]
+    dist_info_metadata: DistInfoMetadata,
+    #[serde(default)]
+    core_metadata: Option<DistInfoMetadata>,
+    #[serde(default)]
+    yanked: Yanked,
+}
+
+impl From<RawArtifactInfo> for ArtifactInfo {
+    fn from(raw: RawArtifactInfo) -> Self {
+        Self {
+            filename: raw.filename,
+            url: raw.url,
+            is_direct_url: raw.is_direct_url,
+            hashes: raw.hashes,
+            requires_python: raw.requires_python,
+            dist_info_metadata: raw.core_metadata.unwrap_or(raw.dist_info_metadata),
+            yanked: raw.yanked,
+        }
+    }
+}
+
 impl ArtifactInfo {
     /// Returns true if this artifact describes an instance of `T`.
     pub fn is<T: HasArtifactName>(&self) -> bool {
@@ -158,3 +193,59 @@ impl From<RawYanked> for Yanked {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_core_metadata_key() {
+        let artifact = ArtifactInfo {
+            filename: ArtifactName::from_filename(
+                "foo-1.0-py3-none-any.whl",
+                None,
+                &"foo".parse().unwrap(),
+            )
+            .unwrap(),
+            url: "https://example.com/foo-1.0-py3-none-any.whl"
+                .parse()
+                .unwrap(),
+            is_direct_url: false,
+            hashes: None,
+            requires_python: None,
+            dist_info_metadata: Default::default(),
+            yanked: Default::default(),
+        };
+        let parse = |keys: &[(&str, bool)]| {
+            let Value::Object(mut value) = serde_json::to_value(&artifact).unwrap() else {
+                unreachable!()
+            };
+            // Only send the metadata keys of the test, like an index would
+            value.remove("dist-info-metadata");
+            value.remove("yanked");
+            value.insert("is-direct-url".into(), json!(false));
+            for (key, available) in keys {
+                value.insert(key.to_string(), json!(available));
+            }
+            serde_json::from_value::<ArtifactInfo>(Value::Object(value))
+                .unwrap()
+                .dist_info_metadata
+                .available
+        };
+
+        assert!(!parse(&[]));
+        assert!(parse(&[("dist-info-metadata", true)]));
+        assert!(parse(&[("core-metadata", true)]));
+
+        // Indexes may send both keys, the new key takes precedence
+        assert!(parse(&[
+            ("dist-info-metadata", false),
+            ("core-metadata", true)
+        ]));
+        assert!(!parse(&[
+            ("dist-info-metadata", true),
+            ("core-metadata", false)
+        ]));
+    }
+}
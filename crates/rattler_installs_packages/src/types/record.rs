@@ -1,9 +1,12 @@
 //! Defines the [`Record`] struct which holds the information stored in a `RECORD` file which is
 //! found in a wheel archive or installation.
 
+use data_encoding::BASE64URL_NOPAD;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use std::io::Read;
+use sha2::digest::DynDigest;
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
 use std::path::Path;
 
 /// Represents the RECORD file found in a wheels .dist-info folder.
@@ -20,7 +23,8 @@ pub struct RecordEntry {
     /// The path relative to the root of the environment or archive
     pub path: String,
 
-    /// The hash of the file. Usually this is a Sha256 hash.
+    /// The hash of the file prefixed with the algorithm, e.g. `sha256=<digest>`. Usually this is a
+    /// Sha256 hash, see [`RecordHashAlgorithm`].
     pub hash: Option<String>,
 
     /// The size of the file in bytes.
@@ -79,3 +83,183 @@ impl FromIterator<RecordEntry> for Record {
         }
     }
 }
+
+/// A hash algorithm that can be used for the entries of a `RECORD` file.
+///
+/// The hash of an entry is prefixed with the name of the algorithm followed by the urlsafe base64
+/// encoded digest without padding, e.g. `sha256=<digest>`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum RecordHashAlgorithm {
+    /// SHA-256, this is what virtually every tool uses.
+    #[default]
+    Sha256,
+
+    /// SHA-384
+    Sha384,
+
+    /// SHA-512
+    Sha512,
+}
+
+impl RecordHashAlgorithm {
+    /// All supported algorithms.
+    pub const ALL: [RecordHashAlgorithm; 3] = [Self::Sha256, Self::Sha384, Self::Sha512];
+
+    /// The name of the algorithm as used in the prefix of a hash.
+    pub fn name(self) -> &'static str {
+        match self {
+            RecordHashAlgorithm::Sha256 => "sha256",
+            RecordHashAlgorithm::Sha384 => "sha384",
+            RecordHashAlgorithm::Sha512 => "sha512",
+        }
+    }
+
+    /// Determines the algorithm of a hash as found in a `RECORD` file, e.g. `sha256=<digest>`.
+    /// Returns `None` if the algorithm is not supported.
+    pub fn from_record_hash(hash: &str) -> Option<Self> {
+        let (name, _) = hash.split_once('=')?;
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(name))
+    }
+
+    fn hasher(self) -> Box<dyn DynDigest + Send> {
+        match self {
+            RecordHashAlgorithm::Sha256 => Box::<sha2::Sha256>::default(),
+            RecordHashAlgorithm::Sha384 => Box::<sha2::Sha384>::default(),
+            RecordHashAlgorithm::Sha512 => Box::<sha2::Sha512>::default(),
+        }
+    }
+}
+
+impl Display for RecordHashAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Computes the hashes of data for one or more [`RecordHashAlgorithm`]s at the same time. This is
+/// used to verify a file against the hash of an incoming `RECORD` while also computing the hash
+/// for the `RECORD` that is written, which might use a different algorithm.
+pub(crate) struct RecordHasher {
+    hashers: Vec<(RecordHashAlgorithm, Box<dyn DynDigest + Send>)>,
+}
+
+impl RecordHasher {
+    /// Constructs a new instance that computes the hashes for the given algorithms.
+    pub fn new(algorithms: impl IntoIterator<Item = RecordHashAlgorithm>) -> Self {
+        let mut hashers: Vec<(RecordHashAlgorithm, Box<dyn DynDigest + Send>)> = Vec::new();
+        for algorithm in algorithms {
+            if !hashers.iter().any(|(other, _)| *other == algorithm) {
+                hashers.push((algorithm, algorithm.hasher()));
+            }
+        }
+        Self { hashers }
+    }
+
+    /// Returns the hashes of all the data that was written.
+    pub fn finalize(self) -> RecordHashes {
+        RecordHashes(
+            self.hashers
+                .into_iter()
+                .map(|(algorithm, hasher)| {
+                    let digest = hasher.finalize();
+                    (
+                        algorithm,
+                        format!("{algorithm}={}", BASE64URL_NOPAD.encode(&digest)),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Write for RecordHasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for (_, hasher) in self.hashers.iter_mut() {
+            hasher.update(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The hashes computed by a [`RecordHasher`] in the format used by `RECORD` files.
+#[derive(Debug, Clone)]
+pub(crate) struct RecordHashes(Vec<(RecordHashAlgorithm, String)>);
+
+impl RecordHashes {
+    /// Returns the hash for the given algorithm.
+    ///
+    /// Panics if the hash was not computed, the algorithm must have been passed to
+    /// [`RecordHasher::new`].
+    pub fn get(&self, algorithm: RecordHashAlgorithm) -> &str {
+        self.0
+            .iter()
+            .find(|(other, _)| *other == algorithm)
+            .map(|(_, hash)| hash.as_str())
+            .expect("the hash for this algorithm was not computed")
+    }
+}
+
+/// A reader that hashes all the data that is read through it.
+pub(crate) struct RecordHashingReader<R> {
+    reader: R,
+    hasher: RecordHasher,
+}
+
+impl<R: Read> RecordHashingReader<R> {
+    /// Constructs a new reader that computes the hashes for the given algorithms.
+    pub fn new(reader: R, algorithms: impl IntoIterator<Item = RecordHashAlgorithm>) -> Self {
+        Self {
+            reader,
+            hasher: RecordHasher::new(algorithms),
+        }
+    }
+
+    /// Returns the hashes of all the data that was read.
+    pub fn finalize(self) -> RecordHashes {
+        self.hasher.finalize()
+    }
+}
+
+impl<R: Read> Read for RecordHashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.hasher.write_all(&buf[..read])?;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record_hasher() {
+        let mut hasher =
+            RecordHasher::new([RecordHashAlgorithm::Sha256, RecordHashAlgorithm::Sha512]);
+        hasher.write_all(b"hello world").unwrap();
+        let hashes = hasher.finalize();
+        assert_eq!(
+            hashes.get(RecordHashAlgorithm::Sha256),
+            "sha256=uU0nuZNNPgilLlLX2n2r-sSE7-N6U4DukIj3rOLvzek"
+        );
+        assert!(hashes
+            .get(RecordHashAlgorithm::Sha512)
+            .starts_with("sha512="));
+
+        for hash in [
+            hashes.get(RecordHashAlgorithm::Sha256),
+            hashes.get(RecordHashAlgorithm::Sha512),
+        ] {
+            let algorithm = RecordHashAlgorithm::from_record_hash(hash).unwrap();
+            assert_eq!(hashes.get(algorithm), hash);
+        }
+        assert_eq!(RecordHashAlgorithm::from_record_hash("md5=abc"), None);
+        assert_eq!(RecordHashAlgorithm::from_record_hash("sha256"), None);
+    }
+}
@@ -1,13 +1,17 @@
+use std::io::{Cursor, Write};
 use std::sync::Arc;
 
 use reqwest::Client;
 use reqwest_middleware::ClientWithMiddleware;
 use tempfile::TempDir;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
 
 use crate::{
+    artifacts::Wheel,
     index::{PackageDb, PackageSourcesBuilder},
     python_env::Pep508EnvMakers,
     resolve::solve_options::ResolveOptions,
+    types::{ArtifactFromBytes, WheelFilename},
     wheel_builder::WheelBuilder,
 };
 
@@ -40,3 +44,26 @@ pub async fn setup(resolve_options: ResolveOptions) -> (Arc<WheelBuilder>, TempD
         tempdir,
     )
 }
+
+/// Creates an in-memory zip archive that contains the given files, stored without compression.
+pub fn wheel_archive(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    for (path, content) in files {
+        writer.start_file(*path, options).unwrap();
+        writer.write_all(content.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+/// Creates a wheel with the given filename from an in-memory archive.
+pub fn wheel_from_archive(filename: &str, archive: Vec<u8>) -> Wheel {
+    let (name, _) = filename.split_once('-').unwrap();
+    let name = WheelFilename::from_filename(filename, &name.parse().unwrap()).unwrap();
+    Wheel::from_bytes(name, Box::new(Cursor::new(archive))).unwrap()
+}
+
+/// Creates an in-memory wheel with the given filename that contains the given files.
+pub fn make_wheel(filename: &str, files: &[(&str, &str)]) -> Wheel {
+    wheel_from_archive(filename, wheel_archive(files))
+}
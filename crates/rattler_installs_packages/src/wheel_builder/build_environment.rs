@@ -1,10 +1,11 @@
 use crate::install::InstallWheelOptions;
-use crate::types::ArtifactFromSource;
+use crate::types::{ArtifactFromSource, SourceArtifactName};
 
 use crate::python_env::{PythonLocation, VEnv};
+use crate::resolve::solve_options::BuildFrontend;
 use crate::resolve::{resolve, PinnedPackage};
 use crate::utils::normalize_path;
-use crate::wheel_builder::{WheelBuildError, WheelBuilder};
+use crate::wheel_builder::{BuildRequirements, ExtractedSDistCache, WheelBuildError, WheelBuilder};
 use fs_err as fs;
 use fs_err::read_dir;
 use parking_lot::RwLock;
@@ -12,11 +13,14 @@ use pep508_rs::Requirement;
 use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
 
+use std::io::Read;
 use std::ops::DerefMut;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Output};
+use std::process::{Child, Command, Output, Stdio};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 enum DeleteOrPersist {
@@ -70,6 +74,14 @@ impl TempBuildEnvironment {
         *delete_or_persist = Some(new_value);
     }
 
+    /// Returns true if the temporary build environment is not deleted when it is dropped
+    fn is_persisted(&self) -> bool {
+        matches!(
+            self.delete_or_persist.read().as_ref(),
+            Some(DeleteOrPersist::Persist(_))
+        )
+    }
+
     /// Path to the temporary build environment
     fn path(&self) -> PathBuf {
         self.delete_or_persist
@@ -84,11 +96,38 @@ impl TempBuildEnvironment {
 // include static build_frontend.py string
 const BUILD_FRONTEND_PY: &str = include_str!("./wheel_builder_frontend.py");
 
+// include the build_frontend.py that uses pypa/build
+const PYPA_BUILD_FRONTEND_PY: &str = include_str!("./pypa_build_frontend.py");
+
+impl BuildFrontend {
+    /// The script that is invoked to run the build backend
+    fn script(self) -> &'static str {
+        match self {
+            BuildFrontend::Builtin => BUILD_FRONTEND_PY,
+            BuildFrontend::PypaBuild => PYPA_BUILD_FRONTEND_PY,
+        }
+    }
+
+    /// The requirements that need to be installed into the build environment for the frontend
+    fn requirements(self) -> Vec<Requirement> {
+        match self {
+            BuildFrontend::Builtin => Vec::new(),
+            BuildFrontend::PypaBuild => vec![Requirement {
+                name: "build".into(),
+                extras: None,
+                marker: None,
+                version_or_url: None,
+            }],
+        }
+    }
+}
+
 /// A build environment for building wheels
 /// This struct contains the virtualenv and everything that is needed
 /// to execute the PEP517 build backend hools
 #[derive(Debug)]
 pub(crate) struct BuildEnvironment {
+    artifact_name: SourceArtifactName,
     work_dir: TempBuildEnvironment,
     package_dir: PathBuf,
     #[allow(dead_code)]
@@ -99,6 +138,9 @@ pub(crate) struct BuildEnvironment {
     venv: VEnv,
     env_variables: HashMap<String, String>,
     clean_env: bool,
+    clean_env_allowlist: Vec<String>,
+    build_timeout: Option<Duration>,
+    build_frontend: BuildFrontend,
     #[allow(dead_code)]
     python_location: PythonLocation,
 }
@@ -134,12 +176,20 @@ impl BuildEnvironment {
     pub(crate) fn install_build_files(
         &mut self,
         sdist: &(impl ArtifactFromSource + ?Sized),
+        extracted_sdist_cache: &ExtractedSDistCache,
     ) -> std::io::Result<()> {
         // Extract the sdist to the work folder
         // extract to a specific package dir
         let work_dir = self.work_dir.path();
 
-        sdist.extract_to(work_dir.as_path())?;
+        // Sdist archives are extracted once into the cache and cloned from there, source trees are
+        // copied directly
+        match sdist.artifact_name() {
+            SourceArtifactName::SDist(_) => {
+                extracted_sdist_cache.clone_into(sdist, work_dir.as_path())?
+            }
+            SourceArtifactName::STree(_) => sdist.extract_to(work_dir.as_path())?,
+        }
 
         // when sdists are downloaded from pypi - they have correct name
         // name - version
@@ -171,7 +221,10 @@ impl BuildEnvironment {
         }
 
         // Write the python frontend to the work folder
-        fs::write(work_dir.join("build_frontend.py"), BUILD_FRONTEND_PY)
+        fs::write(
+            work_dir.join("build_frontend.py"),
+            self.build_frontend.script(),
+        )
     }
 
     /// Get the path to the work directory
@@ -188,8 +241,10 @@ impl BuildEnvironment {
     fn get_extra_requirements(
         &self,
         output_dir: &Path,
+        wheel_builder: &WheelBuilder,
     ) -> Result<HashSet<Requirement>, WheelBuildError> {
         let output = self.run_command("GetRequiresForBuildWheel", output_dir)?;
+        wheel_builder.record_build_output(&self.artifact_name, "GetRequiresForBuildWheel", &output);
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             return Err(WheelBuildError::Error(stderr.to_string()));
@@ -214,18 +269,23 @@ impl BuildEnvironment {
         self.work_dir.path()
     }
 
+    /// Returns true if the build environment has been persisted with [`BuildEnvironment::persist`]
+    pub(crate) fn is_persisted(&self) -> bool {
+        self.work_dir.is_persisted()
+    }
+
     /// Install extra requirements into the venv, if any extra were found
     /// If the extra requirements are already installed, this will do nothing
     /// for that requirement.
     pub(crate) async fn install_extra_requirements(
-        &self,
+        &mut self,
         wheel_builder: &Arc<WheelBuilder>,
     ) -> Result<(), WheelBuildError> {
         // Get extra requirements if any
         // Because we are using the build environment to get the extra requirements
         // and we should only do this once
         // its fine to use the work_dir as the output_dir
-        let extra_requirements = self.get_extra_requirements(&self.work_dir())?;
+        let extra_requirements = self.get_extra_requirements(&self.work_dir(), wheel_builder)?;
 
         // Combine previous requirements with extra requirements
         let combined_requirements = HashSet::from_iter(self.build_requirements.iter().cloned())
@@ -246,19 +306,24 @@ impl BuildEnvironment {
                 .clone()
                 .with_favored_packages(favored_packages)
                 .with_locked_packages(locked_packages)
-                .with_env_variables(self.env_variables.clone());
+                .with_env_variables(self.env_variables.clone())
+                .with_exclude(HashSet::default())
+                .with_no_deps(false);
 
             let all_requirements = combined_requirements.to_vec();
+            let frontend_requirements = self.build_frontend.requirements();
             let extra_resolved_wheels = resolve(
                 wheel_builder.package_db.clone(),
-                all_requirements.iter(),
+                all_requirements.iter().chain(frontend_requirements.iter()),
                 wheel_builder.env_markers.clone(),
                 wheel_builder.wheel_tags.clone(),
                 wheel_builder.clone(),
                 options,
             )
             .await
-            .map_err(|e| WheelBuildError::CouldNotResolveEnvironment(all_requirements, e))?;
+            .map_err(|e| {
+                WheelBuildError::CouldNotResolveEnvironment(all_requirements.clone(), e.into())
+            })?;
 
             // install extra wheels
             for package_info in extra_resolved_wheels {
@@ -289,11 +354,26 @@ impl BuildEnvironment {
                         panic!("could not get artifact: {}", e)
                     }
                 }
+                self.resolved_wheels.push(package_info);
             }
+            self.build_requirements = all_requirements;
         }
         Ok(())
     }
 
+    /// Returns the requirements of the build system and the packages that were installed into
+    /// the build environment to satisfy them.
+    pub(crate) fn build_requirements(&self) -> BuildRequirements {
+        BuildRequirements {
+            requirements: self.build_requirements.clone(),
+            installed: self
+                .resolved_wheels
+                .iter()
+                .map(|package| (package.name.clone(), package.version.clone()))
+                .collect(),
+        }
+    }
+
     /// Run a command in the build environment
     pub(crate) fn run_command(
         &self,
@@ -332,6 +412,12 @@ impl BuildEnvironment {
         let mut base_command = Command::new(self.venv.python_executable());
         if self.clean_env {
             base_command.env_clear();
+            // pass through the allowed env variables from the parent process
+            for name in &self.clean_env_allowlist {
+                if let Some(value) = std::env::var_os(name) {
+                    base_command.env(name, value);
+                }
+            }
         }
         base_command
             .current_dir(&self.package_dir)
@@ -348,9 +434,14 @@ impl BuildEnvironment {
             // Build system entry point
             .arg(&self.entry_point)
             // Building Wheel or Metadata
-            .arg(stage)
-            .output()
-            .map_err(|e| WheelBuildError::CouldNotRunCommand(stage.into(), e))
+            .arg(stage);
+
+        match self.build_timeout {
+            Some(timeout) => output_with_timeout(&mut base_command, stage, timeout),
+            None => base_command
+                .output()
+                .map_err(|e| WheelBuildError::CouldNotRunCommand(stage.into(), e)),
+        }
     }
 
     fn default_build_system() -> pyproject_toml::BuildSystem {
@@ -380,7 +471,7 @@ impl BuildEnvironment {
         wheel_builder: Arc<WheelBuilder>,
     ) -> Result<BuildEnvironment, WheelBuildError> {
         // Setup a work directory and a new env dir
-        let work_dir = tempfile::tempdir()?;
+        let work_dir = wheel_builder.tempdir()?;
         let venv = VEnv::create(
             &work_dir.path().join("venv"),
             wheel_builder.resolve_options.python_location.clone(),
@@ -414,17 +505,26 @@ impl BuildEnvironment {
                 .collect::<Vec<_>>()
         );
 
+        // The excluded packages are provided by the target environment, not by the isolated build
+        // environment, so build requirements on them still have to be resolved. The build
+        // environment also always needs the dependencies of the build backend.
         let options = wheel_builder
             .resolve_options
             .clone()
             .with_favored_packages(HashMap::default())
             .with_locked_packages(HashMap::default())
-            .with_env_variables(HashMap::default());
+            .with_env_variables(HashMap::default())
+            .with_exclude(HashSet::default())
+            .with_no_deps(false);
 
-        // Resolve the build environment
+        // Resolve the build environment, including the requirements of the build frontend
+        let build_frontend = wheel_builder.resolve_options.build_frontend;
+        let frontend_requirements = build_frontend.requirements();
         let resolved_wheels = resolve(
             wheel_builder.package_db.clone(),
-            build_requirements.iter(),
+            build_requirements
+                .iter()
+                .chain(frontend_requirements.iter()),
             wheel_builder.env_markers.clone(),
             wheel_builder.wheel_tags.clone(),
             wheel_builder.clone(),
@@ -436,7 +536,7 @@ impl BuildEnvironment {
                 "could not resolve build requirements when trying to build a wheel for : {}",
                 sdist.artifact_name()
             );
-            WheelBuildError::CouldNotResolveEnvironment(build_requirements.to_vec(), e)
+            WheelBuildError::CouldNotResolveEnvironment(build_requirements.to_vec(), e.into())
         })?;
 
         // Install into venv
@@ -479,6 +579,7 @@ impl BuildEnvironment {
         };
 
         Ok(BuildEnvironment {
+            artifact_name: sdist.artifact_name(),
             work_dir: TempBuildEnvironment::new(work_dir),
             package_dir,
             build_system,
@@ -488,11 +589,105 @@ impl BuildEnvironment {
             venv,
             env_variables,
             clean_env: wheel_builder.resolve_options.clean_env,
+            clean_env_allowlist: wheel_builder.resolve_options.clean_env_allowlist.clone(),
+            build_timeout: wheel_builder.resolve_options.build_timeout,
+            build_frontend,
             python_location: wheel_builder.resolve_options.python_location.clone(),
         })
     }
 }
 
+/// Runs the command and collects its output like [`Command::output`], but kills the process and
+/// all processes it spawned if it does not finish within `timeout`.
+fn output_with_timeout(
+    command: &mut Command,
+    stage: &str,
+    timeout: Duration,
+) -> Result<Output, WheelBuildError> {
+    // Spawn the process in a new process group so that the whole group can be killed, build
+    // backends often spawn compilers or other subprocesses.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| WheelBuildError::CouldNotRunCommand(stage.into(), e))?;
+
+    // Read the output on separate threads, otherwise the child blocks once a pipe is full
+    let stdout = read_to_end_in_thread(child.stdout.take());
+    let stderr = read_to_end_in_thread(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|e| WheelBuildError::CouldNotRunCommand(stage.into(), e))?
+        {
+            break status;
+        }
+
+        if Instant::now() >= deadline {
+            tracing::warn!("{stage} did not finish within {timeout:?}, killing the build process");
+            kill_process_tree(&mut child);
+            let _ = child.wait();
+            // The pipes are closed once all processes in the tree have exited
+            let _ = stdout.join();
+            let _ = stderr.join();
+            return Err(WheelBuildError::Timeout(stage.into(), timeout));
+        }
+
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+fn read_to_end_in_thread(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+/// Kills the child and the processes in its process group, see [`output_with_timeout`].
+#[cfg(unix)]
+fn kill_process_tree(child: &mut Child) {
+    // SAFETY: killpg has no memory safety requirements, the child is the leader of its own
+    // process group.
+    let result = unsafe { libc::killpg(child.id() as libc::pid_t, libc::SIGKILL) };
+    if result != 0 {
+        let _ = child.kill();
+    }
+}
+
+/// Kills the child and all of its descendants.
+#[cfg(windows)]
+fn kill_process_tree(child: &mut Child) {
+    let killed = Command::new("taskkill")
+        .args(["/F", "/T", "/PID", &child.id().to_string()])
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if !killed {
+        let _ = child.kill();
+    }
+}
+
+/// Kills the child, descendants are not tracked on this platform.
+#[cfg(not(any(unix, windows)))]
+fn kill_process_tree(child: &mut Child) {
+    let _ = child.kill();
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
@@ -525,4 +720,32 @@ mod tests {
         let backend_path = vec!["/no_absolute_allowed".to_string()];
         super::normalize_backend_path(&backend_path, &package_dir).unwrap_err();
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_with_timeout() {
+        use super::{output_with_timeout, WheelBuildError};
+        use std::process::Command;
+        use std::time::{Duration, Instant};
+
+        let output = output_with_timeout(
+            Command::new("sh").args(["-c", "echo hello"]),
+            "Wheel",
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hello\n");
+
+        // The grandchild keeps the stdout pipe open, so this only returns in time if the whole
+        // process group is killed.
+        let start = Instant::now();
+        let result = output_with_timeout(
+            Command::new("sh").args(["-c", "sleep 30 & sleep 30"]),
+            "Wheel",
+            Duration::from_millis(200),
+        );
+        assert!(matches!(result, Err(WheelBuildError::Timeout(..))));
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
 }
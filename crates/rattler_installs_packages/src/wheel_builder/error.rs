@@ -4,6 +4,7 @@ use crate::types::{ParseArtifactNameError, WheelCoreMetaDataError};
 use crate::wheel_builder::wheel_cache;
 use pep508_rs::Requirement;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// An error that can occur while building a wheel
 #[allow(missing_docs)]
@@ -21,6 +22,9 @@ pub enum WheelBuildError {
     #[error("could not run command {0} to build wheel: {1}")]
     CouldNotRunCommand(String, std::io::Error),
 
+    #[error("command {0} to build wheel did not finish within {1:?}")]
+    Timeout(String, Duration),
+
     #[error("could not resolve environment for wheel building: {1:?}")]
     CouldNotResolveEnvironment(Vec<Requirement>, miette::Report),
 
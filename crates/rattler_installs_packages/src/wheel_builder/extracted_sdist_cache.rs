@@ -0,0 +1,156 @@
+use crate::types::ArtifactFromSource;
+use fs_err as fs;
+use std::path::{Path, PathBuf};
+
+/// The name of the file in an extracted sdist directory that is written once the sdist has been
+/// fully extracted.
+const EXTRACTED_MARKER_FILE_NAME: &str = ".rip-extracted";
+
+/// A cache that extracts sdists once into a content-addressed store, so building the same sdist
+/// again does not require extracting the archive from scratch. The extracted tree is cloned into
+/// the work directory of a build environment with [`ExtractedSDistCache::clone_into`], using
+/// copy-on-write if the filesystem supports it.
+///
+/// Sdists are stored in `<cache_dir>/<sha256 of the sdist>/`.
+#[derive(Debug, Clone)]
+pub struct ExtractedSDistCache {
+    root: PathBuf,
+}
+
+impl ExtractedSDistCache {
+    /// Constructs a new cache that stores extracted sdists in `root`.
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Returns the directory that contains the extracted `sdist`, extracting it into the cache if
+    /// it is not already present.
+    ///
+    /// The sdist is first extracted into a temporary directory which is moved into place once all
+    /// files have been written, so a partially extracted sdist is never observed.
+    pub fn get_or_extract(
+        &self,
+        sdist: &(impl ArtifactFromSource + ?Sized),
+    ) -> std::io::Result<PathBuf> {
+        let hash = sdist.try_get_hash()?;
+        let path = self.root.join(format!("{hash:x}"));
+        let marker_path = path.join(EXTRACTED_MARKER_FILE_NAME);
+        if marker_path.is_file() {
+            return Ok(path);
+        }
+
+        fs::create_dir_all(&self.root)?;
+        let temp_dir = tempfile::tempdir_in(&self.root)?;
+        sdist.extract_to(temp_dir.path())?;
+        fs::write(temp_dir.path().join(EXTRACTED_MARKER_FILE_NAME), "")?;
+
+        // Move the extracted sdist into place. If this fails another process might have extracted
+        // the same sdist concurrently, in which case we use that one.
+        let temp_path = temp_dir.into_path();
+        if let Err(err) = fs::rename(&temp_path, &path) {
+            let _ = fs::remove_dir_all(&temp_path);
+            if !marker_path.is_file() {
+                return Err(err);
+            }
+        }
+
+        Ok(path)
+    }
+
+    /// Extracts the `sdist` into the cache if needed and clones the extracted files into
+    /// `destination`. Files are reflinked if the filesystem supports it and copied otherwise, so
+    /// the build can never modify the files in the cache.
+    pub fn clone_into(
+        &self,
+        sdist: &(impl ArtifactFromSource + ?Sized),
+        destination: &Path,
+    ) -> std::io::Result<()> {
+        let source = self.get_or_extract(sdist)?;
+        clone_dir(&source, destination, true)
+    }
+}
+
+/// Recursively clones the contents of `source` into `destination`.
+fn clone_dir(source: &Path, destination: &Path, is_root: bool) -> std::io::Result<()> {
+    fs::create_dir_all(destination)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        if is_root && entry.file_name() == EXTRACTED_MARKER_FILE_NAME {
+            continue;
+        }
+
+        let source = entry.path();
+        let destination = destination.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            clone_dir(&source, &destination, false)?;
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(fs::read_link(&source)?, &destination)?;
+            #[cfg(not(unix))]
+            fs::copy(&source, &destination).map(|_| ())?;
+        } else {
+            reflink_copy::reflink_or_copy(&source, &destination)?;
+            // A reflink does not carry over the permissions, e.g. the executable bit.
+            fs::set_permissions(&destination, entry.metadata()?.permissions())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::artifacts::SDist;
+
+    #[test]
+    fn test_extracted_sdist_cache() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/sdists/env_package-0.1.tar.gz");
+        let sdist = SDist::from_path(&path, &"env_package".parse().unwrap()).unwrap();
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = ExtractedSDistCache::new(cache_dir.path().join("extracted_sdists"));
+
+        // The sdist is only extracted once, into a directory named after the hash of the archive
+        let extracted = cache.get_or_extract(&sdist).unwrap();
+        let hash = rattler_digest::compute_file_digest::<rattler_digest::Sha256>(&path).unwrap();
+        assert_eq!(
+            extracted,
+            cache_dir
+                .path()
+                .join("extracted_sdists")
+                .join(format!("{hash:x}"))
+        );
+        assert!(extracted.join(EXTRACTED_MARKER_FILE_NAME).is_file());
+        assert_eq!(cache.get_or_extract(&sdist).unwrap(), extracted);
+
+        // Cloning the extracted sdist results in the same files as extracting it directly
+        let cloned = tempfile::tempdir().unwrap();
+        cache.clone_into(&sdist, cloned.path()).unwrap();
+        let extracted_directly = tempfile::tempdir().unwrap();
+        sdist.extract_to(extracted_directly.path()).unwrap();
+
+        let list_files = |root: &Path| {
+            let mut files = Vec::new();
+            let mut stack = vec![root.to_path_buf()];
+            while let Some(dir) = stack.pop() {
+                for entry in fs::read_dir(&dir).unwrap() {
+                    let path = entry.unwrap().path();
+                    if path.is_dir() {
+                        stack.push(path);
+                    } else {
+                        let content = fs::read(&path).unwrap();
+                        files.push((path.strip_prefix(root).unwrap().to_path_buf(), content));
+                    }
+                }
+            }
+            files.sort();
+            files
+        };
+        assert_eq!(
+            list_files(cloned.path()),
+            list_files(extracted_directly.path())
+        );
+    }
+}
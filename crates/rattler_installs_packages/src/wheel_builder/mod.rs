@@ -2,24 +2,30 @@
 
 mod build_environment;
 mod error;
+mod extracted_sdist_cache;
 mod wheel_cache;
 
 use fs_err as fs;
 
 use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::process::Output;
 use std::str::FromStr;
 
-use std::sync::{Arc, Weak};
+use std::sync::{Arc, Once, Weak};
 use std::{collections::HashMap, path::PathBuf};
 
 use parking_lot::Mutex;
-use pep508_rs::MarkerEnvironment;
+use pep508_rs::{MarkerEnvironment, Requirement};
 
 use crate::python_env::{ParsePythonInterpreterVersionError, PythonInterpreterVersion};
 use crate::resolve::solve_options::{OnWheelBuildFailure, ResolveOptions};
 use crate::types::ArtifactFromSource;
-use crate::types::{NormalizedPackageName, PackageName, SourceArtifactName, WheelFilename};
+use crate::types::{
+    NormalizedPackageName, PackageName, SourceArtifactName, Version, WheelFilename,
+};
 use crate::wheel_builder::build_environment::BuildEnvironment;
+pub use crate::wheel_builder::extracted_sdist_cache::ExtractedSDistCache;
 pub use crate::wheel_builder::wheel_cache::{WheelCache, WheelCacheKey};
 use crate::{artifacts::Wheel, index::PackageDb, python_env::WheelTags, types::WheelCoreMetadata};
 pub use error::WheelBuildError;
@@ -30,6 +36,91 @@ type OptionalBuildEnv = Option<Arc<BuildEnvironment>>;
 type BuildEnvironmentSender = broadcast::Sender<OptionalBuildEnv>;
 type BuildEnvironmentReceiver = broadcast::Receiver<OptionalBuildEnv>;
 
+/// The build dependencies of an sdist. These are resolved separately from the runtime dependencies
+/// and only installed into the isolated build environment of the sdist.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildRequirements {
+    /// The requirements of the build system, including the extra requirements reported by the
+    /// `get_requires_for_build_wheel` hook of the build backend.
+    pub requirements: Vec<Requirement>,
+
+    /// The packages that were installed into the build environment.
+    pub installed: Vec<(NormalizedPackageName, Version)>,
+}
+
+impl Display for BuildRequirements {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (idx, (name, version)) in self.installed.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}=={}", name.as_str(), version)?;
+        }
+        Ok(())
+    }
+}
+
+/// The output of a single command that was run in the build environment of an sdist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildLogEntry {
+    /// The stage of the build, e.g. `WheelMetadata` or `Wheel`.
+    pub stage: String,
+
+    /// Whether the command exited successfully.
+    pub success: bool,
+
+    /// The captured stdout of the command.
+    pub stdout: String,
+
+    /// The captured stderr of the command.
+    pub stderr: String,
+}
+
+/// The output of the commands that were run to build an sdist. See
+/// [`ResolveOptions::build_log_limit`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildLog {
+    /// The output of the commands in the order in which they were run.
+    pub entries: Vec<BuildLogEntry>,
+
+    /// True if output was dropped because the log exceeded [`ResolveOptions::build_log_limit`].
+    pub truncated: bool,
+}
+
+impl BuildLog {
+    /// The number of bytes of output retained in the log
+    fn len(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|entry| entry.stdout.len() + entry.stderr.len())
+            .sum()
+    }
+
+    /// Appends the output of a command. If the log would exceed `limit` bytes only the end of the
+    /// output is retained, stderr takes precedence over stdout.
+    fn push(&mut self, stage: &str, output: &Output, limit: usize) {
+        let mut remaining = limit.saturating_sub(self.len());
+        let stderr = self.tail(&output.stderr, &mut remaining);
+        let stdout = self.tail(&output.stdout, &mut remaining);
+        self.entries.push(BuildLogEntry {
+            stage: stage.to_string(),
+            success: output.status.success(),
+            stdout,
+            stderr,
+        });
+    }
+
+    /// Returns at most `remaining` bytes from the end of `bytes`
+    fn tail(&mut self, bytes: &[u8], remaining: &mut usize) -> String {
+        let start = bytes.len().saturating_sub(*remaining);
+        if start > 0 {
+            self.truncated = true;
+        }
+        *remaining -= bytes.len() - start;
+        String::from_utf8_lossy(&bytes[start..]).into_owned()
+    }
+}
+
 /// A builder for wheels
 pub struct WheelBuilder {
     /// A cache for virtualenvs that might be reused later in the process
@@ -57,8 +148,16 @@ pub struct WheelBuilder {
     /// only if the `save_on_failure` option is set in resolve options
     saved_build_envs: Mutex<HashSet<PathBuf>>,
 
+    /// The output of the build commands per sdist, only retained if
+    /// [`ResolveOptions::build_log_limit`] is set
+    build_logs: Mutex<HashMap<SourceArtifactName, BuildLog>>,
+
     /// Python interpreter version
     python_version: PythonInterpreterVersion,
+
+    /// Ensures that a mismatch between the environment markers and the build interpreter is only
+    /// reported once
+    python_version_mismatch_reported: Once,
 }
 
 impl WheelBuilder {
@@ -81,10 +180,35 @@ impl WheelBuilder {
             wheel_tags,
             resolve_options,
             saved_build_envs: Mutex::new(HashSet::new()),
+            build_logs: Mutex::new(HashMap::new()),
             python_version,
+            python_version_mismatch_reported: Once::new(),
         }))
     }
 
+    /// Warns if the environment markers are for a different python version than the interpreter
+    /// that builds wheels. This is expected when resolving for another target, so it is only
+    /// reported once an sdist actually has to be built.
+    fn report_python_version_mismatch(&self) {
+        if self
+            .python_version
+            .matches_marker_environment(&self.env_markers)
+        {
+            return;
+        }
+        self.python_version_mismatch_reported.call_once(|| {
+            tracing::warn!(
+                "the environment markers are for python {} but wheels are built with python {}.{}.{}, \
+                 sdists might be built for a different python version than they are resolved for. \
+                 Use `Pep508EnvMakers::from_python_location` to derive the markers from the build interpreter.",
+                self.env_markers.python_version.string,
+                self.python_version.major,
+                self.python_version.minor,
+                self.python_version.patch
+            );
+        });
+    }
+
     /// Get the python interpreter version
     pub fn python_version(&self) -> &PythonInterpreterVersion {
         &self.python_version
@@ -173,11 +297,13 @@ impl WheelBuilder {
 
         // Otherwise we need to do the work
         tracing::debug!("creating virtual env for: {:?}", sdist.distribution_name());
+        self.report_python_version_mismatch();
 
         // Wrap this in a future to capture the result
         let future = || async {
             let mut build_environment = BuildEnvironment::setup(sdist, self.clone()).await?;
-            build_environment.install_build_files(sdist)?;
+            build_environment
+                .install_build_files(sdist, self.package_db.extracted_sdist_cache())?;
             // Install extra requirements if any
             build_environment.install_extra_requirements(self).await?;
             Ok(build_environment)
@@ -205,11 +331,86 @@ impl WheelBuilder {
         }
     }
 
+    /// Create a new temporary directory in [`ResolveOptions::build_dir`], or in the system
+    /// temporary directory if no build directory was configured.
+    pub(crate) fn tempdir(&self) -> std::io::Result<tempfile::TempDir> {
+        match &self.resolve_options.build_dir {
+            Some(build_dir) => {
+                fs::create_dir_all(build_dir)?;
+                tempfile::tempdir_in(build_dir)
+            }
+            None => tempfile::tempdir(),
+        }
+    }
+
     /// Get the paths to the saved build environments
     pub fn saved_build_envs(&self) -> HashSet<PathBuf> {
         self.saved_build_envs.lock().clone()
     }
 
+    /// Returns the work directories of the build environments that are currently cached by this
+    /// builder and will be deleted once they are no longer used. Persisted build environments, see
+    /// [`WheelBuilder::saved_build_envs`], are not included.
+    pub fn active_build_envs(&self) -> HashSet<PathBuf> {
+        self.venv_cache
+            .lock()
+            .values()
+            .filter(|build_environment| !build_environment.is_persisted())
+            .map(|build_environment| build_environment.work_dir())
+            .collect()
+    }
+
+    /// Removes all cached build environments. The work directory of a build environment is deleted
+    /// as soon as it is no longer used, so environments that are still in use by an in-flight
+    /// build are deleted when that build finishes. Persisted build environments, see
+    /// [`WheelBuilder::saved_build_envs`], are never deleted.
+    ///
+    /// Dropping the builder has the same effect, this function is useful for long running
+    /// processes that keep a builder around but want to reclaim disk space. Build environments
+    /// that are needed again later are recreated.
+    pub fn cleanup(&self) {
+        let build_environments = std::mem::take(&mut *self.venv_cache.lock());
+        tracing::debug!(
+            "removing {} cached build environments",
+            build_environments.len()
+        );
+        drop(build_environments);
+    }
+
+    /// Returns the build requirements of every sdist for which a build environment was set up by
+    /// this builder. Sdists whose wheel or metadata was taken from the local wheel cache have not
+    /// been built and are therefore not included.
+    pub fn build_requirements(&self) -> HashMap<SourceArtifactName, BuildRequirements> {
+        self.venv_cache
+            .lock()
+            .iter()
+            .map(|(name, build_environment)| (name.clone(), build_environment.build_requirements()))
+            .collect()
+    }
+
+    /// Returns the output of the commands that were run to build every sdist, regardless of whether
+    /// the build succeeded. This is empty unless [`ResolveOptions::build_log_limit`] is set.
+    pub fn build_logs(&self) -> HashMap<SourceArtifactName, BuildLog> {
+        self.build_logs.lock().clone()
+    }
+
+    /// Records the output of a command that was run in the build environment of an sdist, if
+    /// build logs are retained.
+    pub(crate) fn record_build_output(
+        &self,
+        name: &SourceArtifactName,
+        stage: &str,
+        output: &Output,
+    ) {
+        if let Some(limit) = self.resolve_options.build_log_limit {
+            self.build_logs
+                .lock()
+                .entry(name.clone())
+                .or_default()
+                .push(stage, output, limit);
+        }
+    }
+
     /// Handle's a build failure by either saving the build environment or deleting it
     fn handle_build_failure<T>(
         self: &Arc<WheelBuilder>,
@@ -251,6 +452,16 @@ impl WheelBuilder {
             });
         }
 
+        // Source artifacts with static PEP 621 metadata don't have to be built at all
+        match sdist.pep621_metadata() {
+            Ok(Some(metadata)) => return Ok(metadata),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(
+                "failed to read static metadata of {}: {e}",
+                sdist.distribution_name()
+            ),
+        }
+
         let build_environment = self.setup_build_venv(sdist).await?;
 
         // Capture the result of the build
@@ -266,8 +477,9 @@ impl WheelBuilder {
         build_environment: &BuildEnvironment,
         sdist: &S,
     ) -> Result<(Vec<u8>, WheelCoreMetadata), WheelBuildError> {
-        let output_dir = tempfile::tempdir()?;
+        let output_dir = self.tempdir()?;
         let output = build_environment.run_command("WheelMetadata", output_dir.path())?;
+        self.record_build_output(&sdist.artifact_name(), "WheelMetadata", &output);
         if !output.status.success() {
             if output.status.code() == Some(50) {
                 tracing::warn!("SDist build backend does not support metadata generation");
@@ -322,9 +534,10 @@ impl WheelBuilder {
         build_environment: &BuildEnvironment,
         sdist: &S,
     ) -> Result<Wheel, WheelBuildError> {
-        let output_dir = tempfile::tempdir()?;
+        let output_dir = self.tempdir()?;
         // Run the wheel stage
         let output = build_environment.run_command("Wheel", output_dir.path())?;
+        self.record_build_output(&sdist.artifact_name(), "Wheel", &output);
 
         // Check for success
         if !output.status.success() {
@@ -377,13 +590,15 @@ mod tests {
     use crate::artifacts::SDist;
     use crate::index::{PackageDb, PackageSourcesBuilder};
     use crate::python_env::{Pep508EnvMakers, PythonInterpreterVersion};
-    use crate::resolve::solve_options::{OnWheelBuildFailure, ResolveOptions};
+    use crate::resolve::solve_options::{BuildFrontend, OnWheelBuildFailure, ResolveOptions};
+    use crate::types::ArtifactFromSource;
     use crate::wheel_builder::wheel_cache::WheelCacheKey;
-    use crate::wheel_builder::WheelBuilder;
+    use crate::wheel_builder::{BuildLog, WheelBuilder};
     use futures::future::TryJoinAll;
     use reqwest::Client;
     use reqwest_middleware::ClientWithMiddleware;
     use std::path::Path;
+    use std::process::Output;
     use std::sync::Arc;
     use tempfile::TempDir;
     use tokio_util::either::Either;
@@ -450,6 +665,103 @@ mod tests {
             .is_none());
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn build_wheel_reports_build_requirements() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/sdists/rich-13.6.0.tar.gz");
+
+        let sdist = SDist::from_path(&path, &"rich".parse().unwrap()).unwrap();
+
+        let (wheel_builder, _temp) = setup(ResolveOptions::default()).await;
+        wheel_builder.build_wheel(&sdist).await.unwrap();
+
+        // rich is built with poetry-core, which should only show up in the build requirements
+        let build_requirements = wheel_builder.build_requirements();
+        let report = build_requirements.get(&sdist.artifact_name()).unwrap();
+        assert_eq!(report.requirements[0].name, "poetry-core");
+        assert!(report
+            .installed
+            .iter()
+            .any(|(name, _)| name.as_str() == "poetry-core"));
+        assert!(report.to_string().starts_with("poetry-core=="));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn build_wheel_with_pypa_build() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/sdists/rich-13.6.0.tar.gz");
+
+        let sdist = SDist::from_path(&path, &"rich".parse().unwrap()).unwrap();
+
+        let (wheel_builder, _temp) =
+            setup(ResolveOptions::default().with_build_frontend(BuildFrontend::PypaBuild)).await;
+
+        let (_, metadata) = wheel_builder.get_sdist_metadata(&sdist).await.unwrap();
+        assert_eq!(metadata.name.as_source_str(), "rich");
+
+        let wheel = wheel_builder.build_wheel(&sdist).await.unwrap();
+        assert_eq!(wheel.name.distribution.as_source_str(), "rich");
+
+        // `build` is installed into the build environment but is not a build requirement
+        let build_requirements = wheel_builder.build_requirements();
+        let report = build_requirements.get(&sdist.artifact_name()).unwrap();
+        assert!(report.requirements.iter().all(|r| r.name != "build"));
+        assert!(report
+            .installed
+            .iter()
+            .any(|(name, _)| name.as_str() == "build"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn build_wheel_retains_build_logs() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/sdists/rich-13.6.0.tar.gz");
+
+        let sdist = SDist::from_path(&path, &"rich".parse().unwrap()).unwrap();
+
+        // Logs are not retained by default
+        let (wheel_builder, _temp) = setup(ResolveOptions::default()).await;
+        wheel_builder.build_wheel(&sdist).await.unwrap();
+        assert!(wheel_builder.build_logs().is_empty());
+
+        let (wheel_builder, _temp) =
+            setup(ResolveOptions::default().with_build_log_limit(Some(1024 * 1024))).await;
+        wheel_builder.build_wheel(&sdist).await.unwrap();
+
+        let build_logs = wheel_builder.build_logs();
+        let log = build_logs.get(&sdist.artifact_name()).unwrap();
+        let stages = log
+            .entries
+            .iter()
+            .map(|entry| entry.stage.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(stages, ["GetRequiresForBuildWheel", "Wheel"]);
+        assert!(log.entries.iter().all(|entry| entry.success));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn build_log_is_truncated() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let output = Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: b"0123456789".to_vec(),
+            stderr: b"abcdef".to_vec(),
+        };
+
+        let mut log = BuildLog::default();
+        log.push("GetRequiresForBuildWheel", &output, 20);
+        assert!(!log.truncated);
+
+        // Only the end of the output fits, stderr takes precedence
+        log.push("Wheel", &output, 20);
+        assert!(log.truncated);
+        assert_eq!(log.entries[1].stderr, "cdef");
+        assert_eq!(log.entries[1].stdout, "");
+        assert_eq!(log.len(), 20);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     pub async fn build_wheel_and_save_env() {
         let path = Path::new(env!("CARGO_MANIFEST_DIR"))
@@ -474,6 +786,58 @@ mod tests {
 
         // Check if the build env is there
         assert!(path.exists());
+
+        // Saved build environments are not removed by a cleanup
+        assert!(wheel_builder.active_build_envs().is_empty());
+        wheel_builder.cleanup();
+        assert!(path.exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn cleanup_build_envs() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/sdists/rich-13.6.0.tar.gz");
+        let sdist = SDist::from_path(&path, &"rich".parse().unwrap()).unwrap();
+        let (wheel_builder, _temp) = setup(ResolveOptions::default()).await;
+
+        wheel_builder.build_wheel(&sdist).await.unwrap();
+        let active_build_envs = wheel_builder.active_build_envs();
+        assert_eq!(active_build_envs.len(), 1);
+        let path = active_build_envs.iter().next().unwrap();
+        assert!(path.is_dir());
+
+        wheel_builder.cleanup();
+        assert!(wheel_builder.active_build_envs().is_empty());
+        assert!(!path.exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn build_wheel_and_save_env_in_build_dir() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/sdists/tampered-rich-13.6.0.tar.gz");
+
+        let sdist = SDist::from_path(&path, &"tampered-rich".parse().unwrap()).unwrap();
+        let build_dir = tempfile::tempdir().unwrap();
+        let build_dir = build_dir.path().join("builds");
+        let (wheel_builder, _temp) = setup(
+            ResolveOptions {
+                on_wheel_build_failure: OnWheelBuildFailure::SaveBuildEnv,
+                ..Default::default()
+            }
+            .with_build_dir(Some(build_dir.clone())),
+        )
+        .await;
+
+        let result = wheel_builder.clone().build_wheel(&sdist).await;
+        assert!(result.is_err());
+
+        // The build environment should have been created (and saved) in the build dir
+        let saved_build_envs = wheel_builder.saved_build_envs();
+        assert_eq!(saved_build_envs.len(), 1);
+        let path = saved_build_envs.iter().next().unwrap();
+        assert!(path.exists());
+        assert!(path.starts_with(&build_dir));
+        assert!(path.join("venv").exists());
     }
 
     // Enable this if you need to know what's going on
@@ -0,0 +1,75 @@
+# Build frontend that uses pypa/build (https://github.com/pypa/build) to invoke the build backend.
+# It follows the same contract as wheel_builder_frontend.py: the results are written to files in
+# the work directory passed as the first argument.
+import json
+import subprocess
+import sys
+from pathlib import Path
+from sys import exit
+
+from build import ProjectBuilder
+
+
+def get_requires_for_build_wheel(builder: ProjectBuilder, work_dir: Path):
+    result = sorted(builder.get_requires_for_build("wheel"))
+
+    j = json.dumps(result)
+    out_json_file = work_dir / "extra_requirements.json"
+    out_json_file.write_text(j)
+    print(j)
+
+
+def prepare_metadata_for_build_wheel(builder: ProjectBuilder, work_dir: Path):
+    result_file = work_dir / "metadata_result"
+
+    d = work_dir / "metadata"
+    d.mkdir()
+    dist_info = builder.prepare("wheel", str(d))
+    if dist_info is None:
+        # The backend does not support metadata generation
+        exit(50)
+    result_file.write_text(str(d / dist_info))
+
+
+def build_wheel(work_dir: Path):
+    wheel_dir = work_dir / "wheel"
+    result_file = work_dir / "wheel_result"
+
+    # The build requirements have already been installed into this environment
+    subprocess.run(
+        [
+            sys.executable,
+            "-m",
+            "build",
+            "--wheel",
+            "--no-isolation",
+            "--skip-dependency-check",
+            "--outdir",
+            str(wheel_dir),
+            ".",
+        ],
+        check=True,
+    )
+
+    wheels = list(wheel_dir.glob("*.whl"))
+    if len(wheels) != 1:
+        print(f"expected a single wheel in {wheel_dir}, found {len(wheels)}", file=sys.stderr)
+        exit(1)
+    result_file.write_text(str(wheels[0]))
+
+
+if __name__ == "__main__":
+    # The entry point of the backend is read from the pyproject.toml by pypa/build
+    work_dir, _entry_point, goal = sys.argv[1:]
+
+    work_dir = Path(work_dir)
+    builder = ProjectBuilder(".", python_executable=sys.executable)
+
+    if goal == "GetRequiresForBuildWheel":
+        get_requires_for_build_wheel(builder, work_dir)
+    if goal == "WheelMetadata":
+        prepare_metadata_for_build_wheel(builder, work_dir)
+    elif goal == "Wheel":
+        build_wheel(work_dir)
+
+    exit(0)
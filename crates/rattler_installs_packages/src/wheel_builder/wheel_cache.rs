@@ -80,8 +80,7 @@ impl WheelCacheKey {
         sdist: &impl ArtifactFromSource,
         python_interpreter_version: &PythonInterpreterVersion,
     ) -> Result<WheelCacheKey, std::io::Error> {
-        let hash = sdist.try_get_bytes()?;
-        let hash = rattler_digest::compute_bytes_digest::<Sha256>(&hash);
+        let hash = sdist.try_get_hash()?;
 
         // Hash python version
         Ok(WheelCacheKey::new(
@@ -0,0 +1,56 @@
+use clap::{Parser, Subcommand};
+use fs_err as fs;
+use itertools::Itertools;
+use miette::IntoDiagnostic;
+use rattler_installs_packages::python_env::{PythonLocation, WheelTags};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+#[command(propagate_version = true)]
+pub struct Args {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Print the wheel tags that are compatible with a python interpreter, most specific first
+    Tags(TagsArgs),
+}
+
+#[derive(Parser)]
+pub struct TagsArgs {
+    /// Path to the python interpreter to determine the tags for
+    #[clap(long, short)]
+    python_interpreter: Option<PathBuf>,
+
+    /// Print the tags in their compressed form
+    #[clap(long)]
+    compressed: bool,
+}
+
+pub async fn debug(args: Args) -> miette::Result<()> {
+    match args.command {
+        Commands::Tags(args) => print_tags(args).await,
+    }
+}
+
+async fn print_tags(args: TagsArgs) -> miette::Result<()> {
+    let python_location = match args.python_interpreter {
+        Some(python) => PythonLocation::Custom(fs::canonicalize(python).into_diagnostic()?),
+        None => PythonLocation::System,
+    };
+
+    let tags = WheelTags::from_python(python_location.executable().into_diagnostic()?.as_path())
+        .await
+        .into_diagnostic()?;
+
+    if args.compressed {
+        println!("{}", tags.to_compound_strings().iter().format("\n"));
+    } else {
+        println!("{}", tags.to_newline_separated());
+    }
+
+    Ok(())
+}
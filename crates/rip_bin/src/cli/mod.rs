@@ -1,3 +1,4 @@
+pub mod debug;
 pub mod resolve;
 
 pub mod wheels;
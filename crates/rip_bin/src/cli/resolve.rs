@@ -1,7 +1,7 @@
-use clap::{Parser, Subcommand};
-use fs_err as fs;
+use clap::{Parser, Subcommand, ValueEnum};
 use itertools::Itertools;
 use miette::{Context, IntoDiagnostic};
+use rattler_installs_packages::config::PipConfig;
 use rattler_installs_packages::index::PackageDb;
 use rattler_installs_packages::install::InstallWheelOptions;
 use rattler_installs_packages::python_env::{Pep508EnvMakers, PythonLocation, WheelTags};
@@ -9,21 +9,46 @@ use rattler_installs_packages::resolve::solve_options::{
     OnWheelBuildFailure, PreReleaseResolution, ResolveOptions, SDistResolution,
 };
 use rattler_installs_packages::resolve::PinnedPackage;
-use rattler_installs_packages::types::Requirement;
+use rattler_installs_packages::types::{PackageName, Requirement};
 use rattler_installs_packages::wheel_builder::WheelBuilder;
 use serde::Serialize;
 use std::collections::HashMap;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+/// The maximum number of bytes of build output that is retained per sdist with `--verbose-build`.
+const BUILD_LOG_LIMIT: usize = 1024 * 1024;
+
 #[derive(Serialize, Debug)]
 struct Solution {
     resolved: bool,
     packages: HashMap<String, String>,
+    artifacts: HashMap<String, SolutionArtifact>,
     error: Option<String>,
 }
 
+/// The artifact that was selected for a package in the [`Solution`].
+#[derive(Serialize, Debug)]
+struct SolutionArtifact {
+    url: String,
+    sha256: Option<String>,
+}
+
+/// How to output the result of a resolve.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// A human readable table of the selected versions
+    #[default]
+    Table,
+
+    /// The selected versions and artifacts as json
+    Json,
+
+    /// A pip compatible requirements file with pinned versions and hashes
+    Requirements,
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
     /// Resolve a set of requirements and output the resolved versions
@@ -33,6 +58,9 @@ pub enum Commands {
     /// Resolve and install a set of requirements
     #[clap(alias = "i")]
     Install(InstallArgs),
+
+    /// Install a single local wheel without consulting the index
+    InstallWheel(InstallWheelArgs),
 }
 
 #[derive(Parser)]
@@ -54,17 +82,47 @@ pub struct ResolveArgs {
     #[arg(short = 'c', long)]
     clean_env: bool,
 
+    /// Env variables that are still inherited when `--clean-env` is used.
+    #[arg(long, requires = "clean_env", value_delimiter = ',')]
+    clean_env_allowlist: Vec<String>,
+
     /// Save failed wheel build environments
     #[arg(long)]
     save_on_failure: bool,
 
-    /// Prefer pre-releases to normal releases
+    /// Directory in which build environments are created instead of the system temp directory
+    #[arg(long)]
+    build_dir: Option<PathBuf>,
+
+    /// Print the output of the build backend for every sdist that was built, also if resolving or
+    /// installing fails
+    #[arg(long)]
+    verbose_build: bool,
+
+    /// Allow pre-releases to be selected for all packages (like `pip install --pre`)
     #[clap(long)]
     pre: bool,
 
-    /// Output the result as json
+    /// Never build sdists to get the metadata of a version that has compatible wheels
+    #[clap(long)]
+    skip_sdist_fallback: bool,
+
+    /// Only resolve the requested packages, without their dependencies
     #[clap(long)]
+    no_deps: bool,
+
+    /// Packages that are left out of the resolution, e.g. because they are provided by the
+    /// environment
+    #[clap(long, value_delimiter = ',')]
+    exclude: Vec<PackageName>,
+
+    /// Output the result as json, this is the same as `--format json`
+    #[clap(long, conflicts_with = "format")]
     json: bool,
+
+    /// How to output the resolved packages
+    #[clap(long, value_enum, default_value_t)]
+    format: OutputFormat,
 }
 
 #[derive(Parser)]
@@ -77,6 +135,20 @@ pub struct InstallArgs {
     target: PathBuf,
 }
 
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+pub struct InstallWheelArgs {
+    /// Path or `file://` url of the wheel to install
+    wheel: String,
+
+    /// The target directory to install into
+    target: PathBuf,
+
+    /// Path to the python interpreter to use for creating the venv
+    #[clap(long, short)]
+    python_interpreter: Option<PathBuf>,
+}
+
 #[derive(Parser)]
 #[group(multiple = false)]
 pub struct SDistResolutionArgs {
@@ -97,55 +169,67 @@ pub struct SDistResolutionArgs {
     only_sdists: bool,
 }
 
-impl From<SDistResolutionArgs> for SDistResolution {
-    fn from(value: SDistResolutionArgs) -> Self {
-        if value.only_sdists {
+impl SDistResolutionArgs {
+    /// Returns the resolution that is selected by the flags, or `default` if none of the flags
+    /// was given.
+    fn resolution_or(self, default: SDistResolution) -> SDistResolution {
+        if self.only_sdists {
             SDistResolution::OnlySDists
-        } else if value.only_wheels {
+        } else if self.only_wheels {
             SDistResolution::OnlyWheels
-        } else if value.prefer_sdists {
+        } else if self.prefer_sdists {
             SDistResolution::PreferSDists
-        } else if value.prefer_wheels {
+        } else if self.prefer_wheels {
             SDistResolution::PreferWheels
         } else {
-            SDistResolution::Normal
+            default
         }
     }
 }
 
-pub async fn execute(package_db: Arc<PackageDb>, commands: Commands) -> miette::Result<()> {
+pub async fn execute(
+    package_db: Arc<PackageDb>,
+    pip_config: &PipConfig,
+    commands: Commands,
+) -> miette::Result<()> {
     let (args, target) = match commands {
         Commands::Resolve(args) => (args, None),
         Commands::Install(args) => (args.resolve_args, Some(args.target)),
+        Commands::InstallWheel(args) => return install_local_wheel(package_db, args).await,
+    };
+    let format = if args.json {
+        OutputFormat::Json
+    } else {
+        args.format
     };
 
-    // Determine the environment markers for the current machine
-    let env_markers = Arc::new(match args.python_interpreter {
-        Some(ref python) => {
-            let python = fs::canonicalize(python).into_diagnostic()?;
-            Pep508EnvMakers::from_python(&python).await.into_diagnostic()
-                .wrap_err_with(|| {
-                    format!(
-                        "failed to determine environment markers for the current machine (could not run Python in path: {:?})"
-                        , python
-                    )
-                })?
-        }
-        None => Pep508EnvMakers::from_env().await.into_diagnostic()
+    let python_location = match args.python_interpreter {
+        Some(python_interpreter) => PythonLocation::Custom(
+            canonicalize_interpreter(&python_interpreter)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("could not find {}", python_interpreter.display()))?,
+        ),
+        None => PythonLocation::System,
+    };
+
+    // Determine the environment markers from the same interpreter that is used to build wheels
+    let env_markers = Arc::new(
+        Pep508EnvMakers::from_python_location(&python_location)
+            .await
+            .into_diagnostic()
             .wrap_err_with(|| {
-                "failed to determine environment markers for the current machine (could not run Python)"
-            })?,
-    }.0);
+                format!(
+                    "failed to determine environment markers for the current machine (could not run Python: {:?})",
+                    python_location
+                )
+            })?
+            .0,
+    );
     tracing::debug!(
         "extracted the following environment markers from the system python interpreter:\n{:#?}",
         env_markers
     );
 
-    let python_location = match args.python_interpreter {
-        Some(python_interpreter) => PythonLocation::Custom(python_interpreter),
-        None => PythonLocation::System,
-    };
-
     let compatible_tags =
         WheelTags::from_python(python_location.executable().into_diagnostic()?.as_path())
             .await
@@ -162,19 +246,32 @@ pub async fn execute(package_db: Arc<PackageDb>, commands: Commands) -> miette::
         OnWheelBuildFailure::DeleteBuildEnv
     };
 
+    // The options from the pip configuration are the defaults, the flags take precedence
+    let defaults = pip_config.apply_to_resolve_options(ResolveOptions {
+        pre_release_resolution: PreReleaseResolution::from_specs(&args.specs),
+        ..Default::default()
+    });
     let pre_release_resolution = if args.pre {
         PreReleaseResolution::Allow
     } else {
-        PreReleaseResolution::from_specs(&args.specs)
+        defaults.pre_release_resolution.clone()
     };
 
     let resolve_opts = ResolveOptions {
-        sdist_resolution: args.sdist_resolution.into(),
+        sdist_resolution: args
+            .sdist_resolution
+            .resolution_or(defaults.sdist_resolution),
         python_location: python_location.clone(),
         clean_env: args.clean_env,
+        clean_env_allowlist: args.clean_env_allowlist,
         on_wheel_build_failure,
         pre_release_resolution,
-        ..Default::default()
+        skip_sdist_fallback: args.skip_sdist_fallback,
+        no_deps: args.no_deps,
+        exclude: args.exclude.into_iter().map(Into::into).collect(),
+        build_dir: args.build_dir,
+        build_log_limit: args.verbose_build.then_some(BUILD_LOG_LIMIT),
+        ..defaults
     };
 
     let wheel_builder = WheelBuilder::new(
@@ -185,6 +282,15 @@ pub async fn execute(package_db: Arc<PackageDb>, commands: Commands) -> miette::
     )
     .into_diagnostic()?;
 
+    // Warm the metadata cache for the requested packages before solving
+    let requested_names = args
+        .specs
+        .iter()
+        .filter_map(|spec| spec.name.parse::<PackageName>().ok())
+        .map(Into::into)
+        .collect_vec();
+    package_db.prefetch_metadata(&requested_names).await;
+
     // Solve the environment
     let blueprint = match rattler_installs_packages::resolve::resolve(
         package_db.clone(),
@@ -198,26 +304,127 @@ pub async fn execute(package_db: Arc<PackageDb>, commands: Commands) -> miette::
     {
         Ok(blueprint) => blueprint,
         Err(err) => {
-            return if args.json {
+            // The output of the build backend often explains why the resolution failed
+            if args.verbose_build {
+                print_build_logs(&wheel_builder);
+            }
+            return if format == OutputFormat::Json {
                 let solution = Solution {
                     resolved: false,
                     packages: HashMap::default(),
+                    artifacts: HashMap::default(),
                     error: Some(format!("{}", err)),
                 };
                 println!("{}", serde_json::to_string_pretty(&solution).unwrap());
                 return Ok(());
             } else {
-                Err(err.wrap_err("Could not solve for requested requirements"))
-            }
+                Err(miette::Report::new(err).wrap_err("Could not solve for requested requirements"))
+            };
         }
     };
 
     // Output the selected versions
+    match format {
+        OutputFormat::Table => print_table(&args.specs, &blueprint)?,
+        OutputFormat::Json => {
+            let solution = Solution {
+                resolved: true,
+                packages: blueprint
+                    .iter()
+                    .map(|p| (p.name.to_string(), p.version.to_string()))
+                    .collect(),
+                artifacts: blueprint
+                    .iter()
+                    .filter_map(|p| {
+                        let artifact = p.artifacts.first()?;
+                        Some((
+                            p.name.to_string(),
+                            SolutionArtifact {
+                                url: artifact.url.to_string(),
+                                sha256: artifact
+                                    .hashes
+                                    .as_ref()
+                                    .and_then(|hashes| hashes.sha256)
+                                    .map(|sha256| format!("{sha256:x}")),
+                            },
+                        ))
+                    })
+                    .collect(),
+                error: None,
+            };
+            println!("{}", serde_json::to_string_pretty(&solution).unwrap());
+        }
+        OutputFormat::Requirements => {
+            write_requirements(&mut std::io::stdout().lock(), &blueprint).into_diagnostic()?
+        }
+    }
+
+    // Install if requested
+    let result = match target {
+        Some(target) => {
+            install_packages(
+                package_db,
+                wheel_builder.clone(),
+                blueprint,
+                python_location,
+                target,
+            )
+            .await
+        }
+        None => Ok(()),
+    };
+
+    // Also show the build logs if the installation failed, they likely explain why
+    if args.verbose_build {
+        print_build_logs(&wheel_builder);
+    }
+
+    result
+}
+
+/// Makes the path of a python interpreter absolute. Only the directory that contains the
+/// interpreter is canonicalized, the interpreter of a virtual environment is usually a symlink to
+/// the base interpreter and resolving it would ignore the virtual environment.
+fn canonicalize_interpreter(python: &Path) -> std::io::Result<PathBuf> {
+    let file_name = python
+        .file_name()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "not a file"))?;
+    let dir = match python.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    Ok(fs_err::canonicalize(dir)?.join(file_name))
+}
+
+/// Prints the retained output of the build backend for every sdist that was built to stderr.
+fn print_build_logs(wheel_builder: &WheelBuilder) {
+    for (name, log) in wheel_builder
+        .build_logs()
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.to_string().cmp(&b.to_string()))
+    {
+        for entry in log.entries {
+            eprintln!(
+                "{} {} ({})",
+                console::style("build output of").bold(),
+                console::style(&name).bold().green(),
+                entry.stage
+            );
+            eprint!("{}{}", entry.stdout, entry.stderr);
+        }
+        if log.truncated {
+            eprintln!("(output of {name} was truncated)");
+        }
+    }
+}
+
+/// Prints the requested specs and a table of the selected versions.
+fn print_table(specs: &[Requirement], blueprint: &[PinnedPackage]) -> miette::Result<()> {
     println!(
         "{}:",
         console::style("Successfully resolved environment").bold()
     );
-    for spec in args.specs.iter() {
+    for spec in specs.iter() {
         println!("- {}", spec);
     }
 
@@ -241,38 +448,95 @@ pub async fn execute(package_db: Arc<PackageDb>, commands: Commands) -> miette::
             )
             .into_diagnostic()?;
         }
-        writeln!(
+        write!(
             tabbed_stdout,
             "\t{version}",
             version = pinned_package.version
         )
         .into_diagnostic()?;
+        if let Some(build_tag) = pinned_package.build_tag() {
+            write!(tabbed_stdout, " (build {build_tag})").into_diagnostic()?;
+        }
+        writeln!(tabbed_stdout).into_diagnostic()?;
     }
-    tabbed_stdout.flush().into_diagnostic()?;
+    tabbed_stdout.flush().into_diagnostic()
+}
 
-    if args.json {
-        let solution = Solution {
-            resolved: true,
-            packages: blueprint
-                .iter()
-                .map(|p| (p.name.to_string(), p.version.to_string()))
-                .collect(),
-            error: None,
-        };
-        println!("{}", serde_json::to_string_pretty(&solution).unwrap());
+/// Writes the pinned packages as a requirements file that can be installed with
+/// `pip install --require-hashes -r`. Every package lists the hashes of all its selected artifacts
+/// so pip is free to pick any of them.
+fn write_requirements(writer: &mut impl Write, blueprint: &[PinnedPackage]) -> std::io::Result<()> {
+    for pinned_package in blueprint.iter().sorted_by(|a, b| a.name.cmp(&b.name)) {
+        write!(writer, "{}", pinned_package.name.as_str())?;
+        if !pinned_package.extras.is_empty() {
+            write!(
+                writer,
+                "[{}]",
+                pinned_package
+                    .extras
+                    .iter()
+                    .map(|e| e.as_str())
+                    .sorted()
+                    .join(",")
+            )?;
+        }
+        match &pinned_package.url {
+            Some(url) => write!(writer, " @ {url}")?,
+            None => write!(writer, "=={}", pinned_package.version)?,
+        }
+
+        let hashes = pinned_package
+            .artifacts
+            .iter()
+            .filter_map(|artifact| artifact.hashes.as_ref()?.sha256)
+            .map(|sha256| format!("{sha256:x}"))
+            .unique()
+            .collect_vec();
+        for hash in hashes {
+            write!(writer, " \\\n    --hash=sha256:{hash}")?;
+        }
+        writeln!(writer)?;
     }
+    Ok(())
+}
 
-    // Install if requested
-    if let Some(target) = target {
-        install_packages(
-            package_db,
-            wheel_builder,
-            blueprint,
-            python_location,
-            target,
-        )
-        .await?
+/// Install a single local wheel into a virtual environment
+async fn install_local_wheel(
+    package_db: Arc<PackageDb>,
+    args: InstallWheelArgs,
+) -> miette::Result<()> {
+    let python_location = match args.python_interpreter {
+        Some(python_interpreter) => PythonLocation::Custom(
+            canonicalize_interpreter(&python_interpreter)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("could not find {}", python_interpreter.display()))?,
+        ),
+        None => PythonLocation::System,
+    };
+
+    let local_wheel = package_db.get_local_wheel(&args.wheel).await?;
+    if !args.target.exists() {
+        std::fs::create_dir_all(&args.target).into_diagnostic()?;
     }
+    let venv = rattler_installs_packages::python_env::VEnv::create(&args.target, python_location)
+        .into_diagnostic()?;
+    venv.install_wheel(
+        &local_wheel.wheel,
+        &InstallWheelOptions {
+            direct_url_json: Some(local_wheel.direct_url_json),
+            ..Default::default()
+        },
+    )
+    .into_diagnostic()?;
+
+    println!(
+        "Successfully installed {} {} into {}",
+        console::style(local_wheel.metadata.name.as_str())
+            .bold()
+            .green(),
+        console::style(local_wheel.metadata.version).italic(),
+        console::style(args.target.display()).bold()
+    );
 
     Ok(())
 }
@@ -335,6 +599,19 @@ pub async fn install_packages(
         .into_diagnostic()?;
     }
 
+    // Report which build dependencies were used for the packages that were built from source
+    for (name, build_requirements) in wheel_builder
+        .build_requirements()
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.to_string().cmp(&b.to_string()))
+    {
+        println!(
+            "built {} using {}",
+            console::style(name).bold(),
+            build_requirements
+        );
+    }
+
     println!(
         "\n{}",
         console::style("Successfully installed environment!").bold()
@@ -342,3 +619,82 @@ pub async fn install_packages(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rattler_installs_packages::types::{
+        ArtifactHashes, ArtifactInfo, ArtifactName, DistInfoMetadata, Extra, NormalizedPackageName,
+        Yanked,
+    };
+    use std::str::FromStr;
+    use url::Url;
+
+    fn pinned_package(
+        name: &str,
+        version: &str,
+        extras: &[&str],
+        artifacts: &[(&str, Option<&str>)],
+    ) -> PinnedPackage {
+        let name: NormalizedPackageName = name.parse().unwrap();
+        PinnedPackage {
+            version: version.parse().unwrap(),
+            url: None,
+            extras: extras.iter().map(|e| Extra::from_str(e).unwrap()).collect(),
+            artifacts: artifacts
+                .iter()
+                .map(|(filename, sha256)| {
+                    Arc::new(ArtifactInfo {
+                        filename: ArtifactName::from_filename(filename, None, &name).unwrap(),
+                        url: Url::parse(&format!("https://example.com/{filename}")).unwrap(),
+                        is_direct_url: false,
+                        hashes: sha256.map(|sha256| {
+                            serde_json::from_value::<ArtifactHashes>(
+                                serde_json::json!({ "sha256": sha256 }),
+                            )
+                            .unwrap()
+                        }),
+                        requires_python: None,
+                        dist_info_metadata: DistInfoMetadata::default(),
+                        yanked: Yanked::default(),
+                    })
+                })
+                .collect(),
+            metadata: None,
+            name,
+        }
+    }
+
+    #[test]
+    fn test_write_requirements() {
+        let wheel_hash = "a".repeat(64);
+        let sdist_hash = "b".repeat(64);
+        let blueprint = [
+            pinned_package(
+                "requests",
+                "2.31.0",
+                &["socks", "security"],
+                &[
+                    (
+                        "requests-2.31.0-py3-none-any.whl",
+                        Some(wheel_hash.as_str()),
+                    ),
+                    ("requests-2.31.0.tar.gz", Some(sdist_hash.as_str())),
+                ],
+            ),
+            pinned_package("idna", "3.6", &[], &[("idna-3.6-py3-none-any.whl", None)]),
+        ];
+
+        let mut output = Vec::new();
+        write_requirements(&mut output, &blueprint).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "idna==3.6\n\
+                 requests[security,socks]==2.31.0 \\\n    \
+                 --hash=sha256:{wheel_hash} \\\n    \
+                 --hash=sha256:{sdist_hash}\n"
+            )
+        );
+    }
+}
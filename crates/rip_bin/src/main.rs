@@ -4,13 +4,14 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use clap::{Parser, Subcommand};
-use miette::Context;
+use miette::{Context, IntoDiagnostic};
 use tracing_subscriber::filter::Directive;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
-use rattler_installs_packages::index::{CheckAvailablePackages, PackageSourcesBuilder};
+use rattler_installs_packages::config::PipConfig;
+use rattler_installs_packages::index::{CheckAvailablePackages, ProxyConfig, DEFAULT_CONCURRENCY};
 
-use rattler_installs_packages::normalize_index_url;
+use rattler_installs_packages::{normalize_index_url, redact_url};
 use reqwest::Client;
 use reqwest_middleware::ClientWithMiddleware;
 use rip_bin::cli::wheels::wheels;
@@ -29,16 +30,31 @@ struct Cli {
     #[command(flatten)]
     verbose: clap_verbosity_flag::Verbosity,
 
-    /// Base URL of the Python Package Index (default <https://pypi.org/simple>). This should point
-    /// to a repository compliant with PEP 503 (the simple repository API).
-    #[clap(default_value = "https://pypi.org/simple/", long, global = true)]
-    index_url: Url,
+    /// Base URL of the Python Package Index (default from pip's configuration or
+    /// <https://pypi.org/simple>). This should point to a repository compliant with PEP 503 (the
+    /// simple repository API).
+    #[clap(long, global = true)]
+    index_url: Option<Url>,
+
+    /// Ignore pip's configuration files and `PIP_*` environment variables
+    #[clap(long, global = true)]
+    isolated: bool,
 
     /// Species whether to always check for new package versions
     /// or if we have a server response for a package, use the
     /// age provided by the server to determine should send a request
     #[clap(long, global = true)]
     use_server_timeout: bool,
+
+    /// The maximum number of concurrent requests made to the package index
+    #[clap(long, global = true, default_value_t = DEFAULT_CONCURRENCY)]
+    concurrency: usize,
+
+    /// The proxy to use for all requests, e.g. `http://proxy.example.com:3128` or
+    /// `socks5h://proxy.example.com:1080` (default from the `HTTP_PROXY`, `HTTPS_PROXY` and
+    /// `ALL_PROXY` environment variables)
+    #[clap(long, global = true)]
+    proxy: Option<Url>,
 }
 
 #[derive(Subcommand)]
@@ -46,6 +62,9 @@ enum Commands {
     /// Options w.r.t locally built wheels
     Wheels(cli::wheels::Args),
 
+    /// Print information that is useful for debugging
+    Debug(cli::debug::Args),
+
     #[command(flatten)]
     InstallOrResolve(cli::resolve::Commands),
 }
@@ -68,9 +87,40 @@ async fn actual_main() -> miette::Result<()> {
         .join("rattler/pypi");
     tracing::info!("cache directory: {}", cache_dir.display());
 
+    // Read the indices from the configuration of pip, the index from the command line takes
+    // precedence. An invalid configuration is an error, like in pip, falling back to the default
+    // index could install packages from the wrong index.
+    let mut pip_config = if args.isolated {
+        PipConfig::default()
+    } else {
+        PipConfig::load()
+            .into_diagnostic()
+            .wrap_err("invalid pip configuration, use --isolated to ignore it")?
+    };
+    if let Some(index_url) = &args.index_url {
+        pip_config.index_url = Some(normalize_index_url(index_url.clone()));
+    }
+
+    // Determine the proxies, the proxy from the command line replaces the ones from the
+    // environment but the hosts in `NO_PROXY` are still connected to directly
+    let mut proxy = ProxyConfig::from_env()?;
+    if let Some(proxy_url) = &args.proxy {
+        proxy.http = Some(proxy_url.clone());
+        proxy.https = Some(proxy_url.clone());
+    }
+    let client = proxy
+        .apply(Client::builder())
+        .and_then(|builder| builder.build())
+        .into_diagnostic()
+        .wrap_err("failed to configure the http client")?;
+
     // Construct a package database
-    let index_url = normalize_index_url(args.index_url.clone());
-    let sources = PackageSourcesBuilder::new(index_url).build()?;
+    let sources = pip_config
+        .package_sources_builder()
+        .with_concurrency(args.concurrency)
+        .with_proxy(proxy)
+        .build()?;
+    let default_index_url = sources.default_index_url();
 
     let check_available_packages = if args.use_server_timeout {
         CheckAvailablePackages::UseServerTime
@@ -78,7 +128,7 @@ async fn actual_main() -> miette::Result<()> {
         CheckAvailablePackages::Always
     };
 
-    let client = ClientWithMiddleware::from(Client::new());
+    let client = ClientWithMiddleware::from(client);
     let package_db = Arc::new(
         rattler_installs_packages::index::PackageDb::new(
             sources,
@@ -89,14 +139,17 @@ async fn actual_main() -> miette::Result<()> {
         .wrap_err_with(|| {
             format!(
                 "failed to construct package database for index {}",
-                args.index_url
+                redact_url(&default_index_url)
             )
         })?,
     );
 
     match args.command {
-        Commands::InstallOrResolve(cmds) => cli::resolve::execute(package_db.clone(), cmds).await,
+        Commands::InstallOrResolve(cmds) => {
+            cli::resolve::execute(package_db.clone(), &pip_config, cmds).await
+        }
         Commands::Wheels(args) => wheels(package_db.clone(), args),
+        Commands::Debug(args) => cli::debug::debug(args).await,
     }
 }
 